@@ -0,0 +1,803 @@
+use super::generate::{
+    begin_update, process_references, stage_cache, stage_registry, ReferenceProcessor,
+    MAX_REFERENCE_ID, START_REFERENCE_ID,
+};
+use super::hashed_ids::HashedIdAllocator;
+use super::transaction::{StagedFile, Transaction};
+use super::CodeFinder;
+use crate::config::context::Cache;
+use crate::config::context::IdStrategy;
+use crate::config::Context;
+use crate::parser;
+use crate::vcs;
+use async_trait::async_trait;
+use log::error;
+use log::info;
+use log::warn;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// A reference ID found in a code file.
+#[derive(Clone, Debug)]
+struct FoundReference
+{
+    /// The path to the file containing the reference.
+    path: String,
+
+    /// The reference ID.
+    reference_id: u32,
+
+    /// The 1-based line number of the log message.
+    line: usize,
+
+    /// The range of characters in the file occupied by the reference ID.
+    span: Range<usize>,
+
+    /// The line of code containing the reference, without leading or trailing whitespace.
+    line_text: String,
+
+    /// The log message, without the reference. Empty if the log statement has no message
+    /// string literal.
+    message: String,
+}
+
+/// A reference processor for collecting every reference ID in a code base. As with all
+/// reference processors, the map and reduce operations are orchestrated by `process_references()`,
+/// so references are collected in canonical file order, then by position within each file.
+struct CollectReferencesProcessor {}
+
+#[async_trait]
+impl ReferenceProcessor<(), Vec<FoundReference>, Vec<FoundReference>> for CollectReferencesProcessor
+{
+    async fn map(
+        path: &str,
+        file_contents: &str,
+        _params: &Option<()>,
+        entries: &[parser::LogRefEntry],
+    ) -> Option<Vec<FoundReference>>
+    {
+        let mut result = Vec::new();
+
+        for entry in entries.iter()
+        {
+            if let (Some(reference_id), Some(span)) = (entry.reference(), entry.reference_span())
+            {
+                let line_start = file_contents[..span.start].rfind('\n').map_or(0, |i| i + 1);
+                let line_end = file_contents[span.end..]
+                    .find('\n')
+                    .map_or(file_contents.len(), |i| span.end + i);
+
+                result.push(FoundReference {
+                    path: path.to_string(),
+                    reference_id,
+                    line: entry.position().line(),
+                    span,
+                    line_text: file_contents[line_start..line_end].trim().to_string(),
+                    message: entry.message().unwrap_or_default().to_string(),
+                });
+            }
+        }
+
+        Some(result)
+    }
+
+    fn reduce(map_results: &[Vec<FoundReference>]) -> Option<Vec<FoundReference>>
+    {
+        Some(map_results.concat())
+    }
+}
+
+/// Decide which occurrences of duplicated reference IDs were brought in by a merge and need to
+/// be renumbered.
+///
+/// An occurrence is ours if its line of code appears, unchanged, in our side's version of the
+/// same file. Any other occurrence is incoming. If every occurrence of an ID is incoming, the
+/// first is kept.
+///
+/// # Arguments
+///
+/// * `references` - Every reference found in the code base, in canonical order.
+/// * `ours_lines` - Returns the lines of code in our side's version of a file.
+///
+/// # Returns
+///
+/// The indexes in `references` of the occurrences to renumber, in canonical order, otherwise an
+/// error message if our side's version of a file couldn't be read.
+fn find_incoming_duplicates<F>(
+    references: &[FoundReference],
+    mut ours_lines: F,
+) -> Result<Vec<usize>, String>
+where
+    F: FnMut(&str) -> Result<Vec<String>, String>,
+{
+    let mut occurrences: BTreeMap<u32, Vec<usize>> = BTreeMap::new();
+
+    for (index, reference) in references.iter().enumerate()
+    {
+        occurrences
+            .entry(reference.reference_id)
+            .or_default()
+            .push(index);
+    }
+
+    let mut unmatched_lines: HashMap<String, Vec<String>> = HashMap::new();
+    let mut result = Vec::new();
+
+    for (reference_id, indexes) in occurrences.iter().filter(|(_, i)| i.len() > 1)
+    {
+        let mut num_ours: usize = 0;
+        let mut incoming = Vec::new();
+
+        for index in indexes.iter()
+        {
+            let reference = &references[*index];
+
+            if !unmatched_lines.contains_key(&reference.path)
+            {
+                unmatched_lines.insert(reference.path.clone(), ours_lines(&reference.path)?);
+            }
+
+            let lines = unmatched_lines.get_mut(&reference.path).unwrap();
+
+            /*
+             * Each line of our version can only account for one occurrence, in case the same
+             * line appears more than once.
+             */
+            match lines.iter().position(|l| *l == reference.line_text)
+            {
+                Some(line_index) =>
+                {
+                    lines.swap_remove(line_index);
+                    num_ours += 1;
+                },
+                None => incoming.push(*index),
+            }
+        }
+
+        if num_ours == 0
+        {
+            incoming.remove(0);
+        }
+        else if incoming.is_empty()
+        {
+            warn!(
+                "[ref: 53] Reference ID {} is duplicated on our side of the merge - not renumbering it",
+                reference_id
+            );
+        }
+
+        result.extend(incoming);
+    }
+
+    result.sort_unstable();
+
+    Ok(result)
+}
+
+/// Replace reference IDs in a file's contents.
+///
+/// # Arguments
+///
+/// * `contents` - The contents of the file.
+/// * `replacements` - The references to replace and their new IDs, in order of position.
+///
+/// # Returns
+///
+/// The updated contents, or an error message if a reference isn't where it was expected.
+fn renumber_references(
+    contents: &str,
+    replacements: &[(&FoundReference, u32)],
+) -> Result<String, String>
+{
+    let mut result = String::with_capacity(contents.len());
+    let mut unwritten_content_start_pos: usize = 0;
+
+    for (reference, new_id) in replacements.iter()
+    {
+        if contents.get(reference.span.clone()) != Some(reference.reference_id.to_string().as_str())
+        {
+            return Err(format!(
+                "Reference ID {} not found at line {} of {}",
+                reference.reference_id, reference.line, reference.path
+            ));
+        }
+
+        result.push_str(&contents[unwritten_content_start_pos..reference.span.start]);
+        result.push_str(&new_id.to_string());
+        unwritten_content_start_pos = reference.span.end;
+    }
+
+    result.push_str(&contents[unwritten_content_start_pos..]);
+
+    Ok(result)
+}
+
+/// After a merge, renumber reference IDs that were allocated on both sides of the merge. The
+/// occurrences brought in by the merge are given new IDs, while ours keep theirs.
+///
+/// # Arguments
+///
+/// * `context` - Application context.
+/// * `ours_revision` - The revision on our side of the merge. If `None`, this is `HEAD` while a
+///   merge is in progress, otherwise `HEAD^1` (the first parent of a merge commit).
+///
+/// # Returns
+///
+/// Number of renumbered references, otherwise an error message on failure.
+pub fn resolve_conflicts(
+    context: &Context,
+    ours_revision: Option<&str>,
+) -> Result<u32, &'static str>
+{
+    let _run_lock = begin_update(context)?;
+
+    /*
+     * The source directories are expected to be in the same git repository.
+     */
+    let source_dirs = context.config.source_dirs();
+    let source_dir = source_dirs[0].as_str();
+
+    let ours_revision = match ours_revision
+    {
+        Some(r) => r,
+        None if vcs::merge_in_progress(source_dir) => "HEAD",
+        None => "HEAD^1",
+    };
+
+    if let Err(e) = vcs::verify_revision(source_dir, ours_revision)
+    {
+        error!("[ref: 54] Invalid revision {}: {}", ours_revision, e);
+        return Err("Invalid revision");
+    }
+
+    info!(
+        "[ref: 55] Comparing references with our side of the merge at {}",
+        ours_revision
+    );
+
+    let finder = match CodeFinder::new_unrestricted(context)
+    {
+        Some(f) => f,
+        None => return Err("Code discovery error"),
+    };
+
+    let references = match process_references::<
+        CollectReferencesProcessor,
+        (),
+        Vec<FoundReference>,
+        Vec<FoundReference>,
+    >(context, None, &finder)
+    {
+        Some(r) => r,
+        None => return Err("Failed to find references"),
+    };
+
+    let incoming = match find_incoming_duplicates(&references, |path| {
+        vcs::file_at_revision(path, ours_revision).map(|contents| {
+            contents.map_or(Vec::new(), |c| {
+                c.lines().map(|l| l.trim().to_string()).collect()
+            })
+        })
+    })
+    {
+        Ok(i) => i,
+        Err(e) =>
+        {
+            error!("[ref: 56] Failed to read our side of the merge: {}", e);
+            return Err("Failed to read our side of the merge");
+        },
+    };
+
+    if incoming.is_empty()
+    {
+        info!("[ref: 57] No duplicate references to renumber - nothing to do");
+        return Ok(0);
+    }
+
+    let registry = match context.read_registry()
+    {
+        Ok(r) => r,
+        Err(e) =>
+        {
+            error!("[ref: 98] Failed to read registry: {}", e);
+            return Err("Failed to read registry");
+        },
+    };
+
+    let max_reference_id = references.iter().map(|r| r.reference_id).max().unwrap_or(0);
+    let mut cache = context
+        .reload_cache()
+        .unwrap_or(Cache::new(START_REFERENCE_ID));
+    let mut next_reference_id = cache
+        .next_reference_id
+        .max(max_reference_id.saturating_add(1))
+        .max(
+            registry
+                .as_ref()
+                .and_then(|r| r.next_reference_id())
+                .unwrap_or(START_REFERENCE_ID),
+        );
+
+    /*
+     * With the hash ID strategy, renumbered references are given new hashed IDs instead, which
+     * avoid every ID in the code base.
+     */
+    let mut hashed_ids = match context.config.id_strategy
+    {
+        IdStrategy::Sequential => None,
+        IdStrategy::Hash => Some((
+            HashedIdAllocator::new(
+                references
+                    .iter()
+                    .map(|r| r.reference_id)
+                    .chain(registry.iter().flat_map(|r| r.issued_ids()))
+                    .collect(),
+                &context.config.id_salt,
+            ),
+            finder.source_roots(),
+        )),
+    };
+
+    let mut replacements: BTreeMap<&str, Vec<(&FoundReference, u32)>> = BTreeMap::new();
+
+    for index in incoming.iter()
+    {
+        let reference = &references[*index];
+
+        let new_reference_id = match hashed_ids.as_mut()
+        {
+            Some((allocator, source_roots)) =>
+            {
+                let path = source_roots
+                    .relative_path(std::path::Path::new(&reference.path))
+                    .unwrap_or(reference.path.clone());
+
+                match allocator.allocate(&path, &reference.message)
+                {
+                    Some(id) => id,
+                    None =>
+                    {
+                        error!("[ref: 88] No unused reference IDs are left");
+                        return Err("No unused reference IDs");
+                    },
+                }
+            },
+            None =>
+            {
+                let id = next_reference_id;
+
+                next_reference_id = match id.checked_add(1)
+                {
+                    Some(next) if id <= MAX_REFERENCE_ID => next,
+                    _ =>
+                    {
+                        error!(
+                            "[ref: 143] Can't renumber reference ID {} - no more reference IDs can be issued",
+                            reference.reference_id
+                        );
+                        return Err("Reference IDs exhausted");
+                    },
+                };
+
+                id
+            },
+        };
+
+        info!(
+            "[ref: 58] {}:{}: renumbered duplicate reference ID {} to {}",
+            reference.path, reference.line, reference.reference_id, new_reference_id
+        );
+
+        replacements
+            .entry(reference.path.as_str())
+            .or_default()
+            .push((reference, new_reference_id));
+    }
+
+    /*
+     * Commit all renumbered files and the lock file together, so that either all of them are
+     * updated or none of them are.
+     */
+    let mut transaction = Transaction::new(&context.journal_path());
+
+    for (path, file_replacements) in replacements.iter()
+    {
+        let staged_file = std::fs::canonicalize(path)
+            .map_err(|e| format!("Failed to resolve path {}: {}", path, e))
+            .and_then(|target_path| {
+                let contents = std::fs::read_to_string(&target_path)
+                    .map_err(|e| format!("Failed to read file {}: {}", path, e))?;
+
+                StagedFile::write(
+                    &target_path.to_string_lossy(),
+                    renumber_references(&contents, file_replacements)?.as_bytes(),
+                )
+            });
+
+        match staged_file
+        {
+            Ok(f) => transaction.add(f),
+            Err(e) =>
+            {
+                error!("[ref: 59] {}", e);
+                return Err("Failed to renumber references");
+            },
+        }
+    }
+
+    if let Err(e) = stage_registry(context, &registry, &mut transaction)
+    {
+        error!("[ref: 99] Failed to stage registry: {}", e);
+        return Err("Failed to renumber references");
+    }
+
+    if hashed_ids.is_none()
+    {
+        cache.next_reference_id = next_reference_id;
+
+        if let Err(e) = stage_cache(context, &cache, &mut transaction)
+        {
+            error!("[ref: 60] Failed to stage lock file: {}", e);
+            return Err("Failed to renumber references");
+        }
+    }
+
+    if let Err(e) = transaction.commit()
+    {
+        error!(
+            "[ref: 61] Failed to commit changes, no files were modified: {}",
+            e
+        );
+        return Err("Failed to renumber references");
+    }
+
+    info!("[ref: 62] Num. renumbered reference(s): {}", incoming.len());
+
+    Ok(incoming.len() as u32)
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use std::process::Command;
+    use tempdir::TempDir;
+
+    fn found(path: &str, reference_id: u32, line_text: &str) -> FoundReference
+    {
+        FoundReference {
+            path: path.to_string(),
+            reference_id,
+            line: 1,
+            span: 0..0,
+            line_text: line_text.to_string(),
+            message: String::new(),
+        }
+    }
+
+    fn git(repo_dir: &str, args: &[&str])
+    {
+        let status = Command::new("git")
+            .arg("-C")
+            .arg(repo_dir)
+            .args(["-c", "user.name=Test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .output()
+            .unwrap()
+            .status;
+
+        assert!(status.success());
+    }
+
+    fn create_test_context(source_dir: &str) -> Context
+    {
+        Context::new(
+            format!(
+                r#"
+source_dir: {}
+rust:
+  log_macros:
+    - module: test_module
+      name: test_macro
+"#,
+                source_dir
+            ),
+            source_dir,
+            false,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_find_incoming_duplicates()
+    {
+        let references = vec![
+            found("/a.rs", 1, "test_macro!(\"[ref: 1] A.\");"),
+            found("/a.rs", 2, "test_macro!(\"[ref: 2] B.\");"),
+            found("/b.rs", 1, "test_macro!(\"[ref: 1] C.\");"),
+            found("/b.rs", 2, "test_macro!(\"[ref: 2] B.\");"),
+            found("/b.rs", 3, "test_macro!(\"[ref: 3] D.\");"),
+        ];
+
+        /*
+         * Our side has a.rs but not b.rs, so both duplicates in b.rs are incoming.
+         */
+        let subject = find_incoming_duplicates(&references, |path| {
+            Ok(match path
+            {
+                "/a.rs" => vec![
+                    "test_macro!(\"[ref: 1] A.\");".to_string(),
+                    "test_macro!(\"[ref: 2] B.\");".to_string(),
+                ],
+                _ => Vec::new(),
+            })
+        });
+
+        assert_eq!(subject, Ok(vec![2, 3]));
+    }
+
+    #[test]
+    fn test_find_incoming_duplicates_same_file()
+    {
+        let references = vec![
+            found("/a.rs", 1, "test_macro!(\"[ref: 1] Theirs.\");"),
+            found("/a.rs", 1, "test_macro!(\"[ref: 1] Ours.\");"),
+        ];
+
+        let subject = find_incoming_duplicates(&references, |_| {
+            Ok(vec!["test_macro!(\"[ref: 1] Ours.\");".to_string()])
+        });
+
+        assert_eq!(subject, Ok(vec![0]));
+    }
+
+    #[test]
+    fn test_find_incoming_duplicates_identical_lines()
+    {
+        let references = vec![
+            found("/a.rs", 1, "test_macro!(\"[ref: 1] Same.\");"),
+            found("/a.rs", 1, "test_macro!(\"[ref: 1] Same.\");"),
+        ];
+
+        /*
+         * One line on our side can only account for one occurrence.
+         */
+        let subject = find_incoming_duplicates(&references, |_| {
+            Ok(vec!["test_macro!(\"[ref: 1] Same.\");".to_string()])
+        });
+
+        assert_eq!(subject, Ok(vec![1]));
+    }
+
+    #[test]
+    fn test_find_incoming_duplicates_no_ours()
+    {
+        let references = vec![
+            found("/a.rs", 5, "x"),
+            found("/b.rs", 5, "y"),
+            found("/c.rs", 5, "z"),
+        ];
+
+        let subject = find_incoming_duplicates(&references, |_| Ok(Vec::new()));
+
+        assert_eq!(subject, Ok(vec![1, 2]));
+    }
+
+    #[test]
+    fn test_find_incoming_duplicates_all_ours()
+    {
+        let references = vec![found("/a.rs", 5, "x"), found("/a.rs", 5, "y")];
+
+        let subject =
+            find_incoming_duplicates(&references, |_| Ok(vec!["x".to_string(), "y".to_string()]));
+
+        assert_eq!(subject, Ok(vec![]));
+    }
+
+    #[test]
+    fn test_find_incoming_duplicates_read_error()
+    {
+        let references = vec![found("/a.rs", 5, "x"), found("/a.rs", 5, "y")];
+
+        assert!(find_incoming_duplicates(&references, |_| Err("Failed".to_string())).is_err());
+    }
+
+    #[test]
+    fn test_renumber_references()
+    {
+        let contents = "a(\"[ref: 7] X\");\nb(ref = 7; \"Y\");\n";
+
+        let first = FoundReference {
+            path: "/a.rs".to_string(),
+            reference_id: 7,
+            line: 1,
+            span: 9..10,
+            line_text: String::new(),
+            message: String::new(),
+        };
+        let second = FoundReference {
+            path: "/a.rs".to_string(),
+            reference_id: 7,
+            line: 2,
+            span: 25..26,
+            line_text: String::new(),
+            message: String::new(),
+        };
+
+        assert_eq!(
+            renumber_references(contents, &[(&first, 100), (&second, 1001)]),
+            Ok("a(\"[ref: 100] X\");\nb(ref = 1001; \"Y\");\n".to_string())
+        );
+
+        let moved = FoundReference {
+            span: 0..1,
+            ..first.clone()
+        };
+
+        assert!(renumber_references(contents, &[(&moved, 100)]).is_err());
+    }
+
+    #[test]
+    fn test_resolve_conflicts_after_merge()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let repo_dir = temp_dir.path().canonicalize().unwrap();
+        let repo_dir_str = repo_dir.to_str().unwrap();
+        let test_context = create_test_context(repo_dir_str);
+
+        git(repo_dir_str, &["init", "-q", "-b", "main"]);
+
+        std::fs::write(
+            repo_dir.join("shared.rs"),
+            "fn a() {\n    test_macro!(\"[ref: 1] Base.\");\n}\n",
+        )
+        .unwrap();
+        test_context.cache_next_reference_id(2, repo_dir_str);
+        git(repo_dir_str, &["add", "-A"]);
+        git(repo_dir_str, &["commit", "-q", "-m", "Base"]);
+
+        git(repo_dir_str, &["checkout", "-q", "-b", "feature"]);
+        std::fs::write(
+            repo_dir.join("feature.rs"),
+            "fn f() {\n    test_macro!(\"[ref: 2] Feature.\");\n    test_macro!(\"[ref: 3] Feature again.\");\n}\n",
+        )
+        .unwrap();
+        test_context.cache_next_reference_id(4, repo_dir_str);
+        git(repo_dir_str, &["add", "-A"]);
+        git(repo_dir_str, &["commit", "-q", "-m", "Feature"]);
+
+        git(repo_dir_str, &["checkout", "-q", "main"]);
+        std::fs::write(
+            repo_dir.join("shared.rs"),
+            "fn a() {\n    test_macro!(\"[ref: 1] Base.\");\n    test_macro!(\"[ref: 2] Main.\");\n}\n",
+        )
+        .unwrap();
+        test_context.cache_next_reference_id(3, repo_dir_str);
+        git(repo_dir_str, &["add", "-A"]);
+        git(repo_dir_str, &["commit", "-q", "-m", "Main"]);
+
+        /*
+         * Resolve the lock file conflict in our favour, as if the merge driver weren't
+         * installed, so that renumbering has to take the IDs in the code into account.
+         */
+        let merge_status = Command::new("git")
+            .arg("-C")
+            .arg(repo_dir_str)
+            .args(["-c", "user.name=Test", "-c", "user.email=test@example.com"])
+            .args(["merge", "-q", "--no-ff", "--no-commit", "feature"])
+            .output()
+            .unwrap()
+            .status;
+        assert!(!merge_status.success());
+        git(repo_dir_str, &["checkout", "--ours", "Breadlog.lock"]);
+
+        assert_eq!(resolve_conflicts(&test_context, None), Ok(1));
+
+        assert_eq!(
+            std::fs::read_to_string(repo_dir.join("shared.rs")).unwrap(),
+            "fn a() {\n    test_macro!(\"[ref: 1] Base.\");\n    test_macro!(\"[ref: 2] Main.\");\n}\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(repo_dir.join("feature.rs")).unwrap(),
+            "fn f() {\n    test_macro!(\"[ref: 4] Feature.\");\n    test_macro!(\"[ref: 3] Feature again.\");\n}\n"
+        );
+        assert_eq!(test_context.reload_cached_next_reference_id(), Some(5));
+
+        /*
+         * Running again finds nothing to do.
+         */
+        assert_eq!(resolve_conflicts(&test_context, None), Ok(0));
+    }
+
+    #[test]
+    fn test_resolve_conflicts_hash_strategy()
+    {
+        use crate::codegen::hashed_ids::hash_reference_id;
+
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let repo_dir = temp_dir.path().canonicalize().unwrap();
+        let repo_dir_str = repo_dir.to_str().unwrap();
+        let mut test_context = create_test_context(repo_dir_str);
+        test_context.config.id_strategy = IdStrategy::Hash;
+
+        git(repo_dir_str, &["init", "-q", "-b", "main"]);
+
+        std::fs::write(
+            repo_dir.join("shared.rs"),
+            "fn a() {\n    test_macro!(\"[ref: 9] Ours.\");\n}\n",
+        )
+        .unwrap();
+        git(repo_dir_str, &["add", "-A"]);
+        git(repo_dir_str, &["commit", "-q", "-m", "Ours"]);
+
+        std::fs::write(
+            repo_dir.join("incoming.rs"),
+            "fn b() {\n    test_macro!(\"[ref: 9] Theirs.\");\n}\n",
+        )
+        .unwrap();
+
+        assert_eq!(resolve_conflicts(&test_context, Some("HEAD")), Ok(1));
+
+        assert_eq!(
+            std::fs::read_to_string(repo_dir.join("incoming.rs")).unwrap(),
+            format!(
+                "fn b() {{\n    test_macro!(\"[ref: {}] Theirs.\");\n}}\n",
+                hash_reference_id("incoming.rs", "Theirs.", "", 0)
+            )
+        );
+        assert!(test_context.reload_cache().is_none());
+    }
+
+    #[test]
+    fn test_resolve_conflicts_ids_exhausted()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let repo_dir = temp_dir.path().canonicalize().unwrap();
+        let repo_dir_str = repo_dir.to_str().unwrap();
+        let test_context = create_test_context(repo_dir_str);
+        let incoming = format!(
+            "fn b() {{\n    test_macro!(\"[ref: {}] Theirs.\");\n}}\n",
+            MAX_REFERENCE_ID
+        );
+
+        git(repo_dir_str, &["init", "-q", "-b", "main"]);
+
+        std::fs::write(
+            repo_dir.join("shared.rs"),
+            format!(
+                "fn a() {{\n    test_macro!(\"[ref: {}] Ours.\");\n}}\n",
+                MAX_REFERENCE_ID
+            ),
+        )
+        .unwrap();
+        git(repo_dir_str, &["add", "-A"]);
+        git(repo_dir_str, &["commit", "-q", "-m", "Ours"]);
+
+        std::fs::write(repo_dir.join("incoming.rs"), &incoming).unwrap();
+
+        assert_eq!(
+            resolve_conflicts(&test_context, Some("HEAD")),
+            Err("Reference IDs exhausted")
+        );
+        assert_eq!(
+            std::fs::read_to_string(repo_dir.join("incoming.rs")).unwrap(),
+            incoming
+        );
+    }
+
+    #[test]
+    fn test_resolve_conflicts_invalid_revision()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let repo_dir = temp_dir.path().to_str().unwrap();
+        let test_context = create_test_context(repo_dir);
+
+        git(repo_dir, &["init", "-q"]);
+
+        assert_eq!(
+            resolve_conflicts(&test_context, Some("does-not-exist")),
+            Err("Invalid revision")
+        );
+    }
+}
@@ -0,0 +1,412 @@
+use super::generate::scan_reference_records;
+use crate::config::registry::{ReferenceRecord, Registry};
+use crate::config::Context;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// The references in a version of a code base, keyed by reference ID.
+pub type Catalog = BTreeMap<u32, ReferenceRecord>;
+
+/// A change to one attribute of a reference that exists in both versions of a code base.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct AttributeChange
+{
+    /// The reference ID.
+    pub id: u32,
+
+    /// The attribute's value in the old version.
+    pub old: String,
+
+    /// The attribute's value in the new version.
+    pub new: String,
+}
+
+/// The differences between the references in two versions of a code base. A reference can be
+/// moved and have its level and message changed at the same time, in which case it appears in
+/// each of the corresponding lists.
+#[derive(Clone, Default, Debug, PartialEq, Serialize)]
+pub struct CatalogDiff
+{
+    /// References only in the new version.
+    pub added: Vec<ReferenceRecord>,
+
+    /// References only in the old version.
+    pub removed: Vec<ReferenceRecord>,
+
+    /// References whose file changed.
+    pub moved: Vec<AttributeChange>,
+
+    /// References whose log level (the name of the logging macro) changed.
+    pub level_changed: Vec<AttributeChange>,
+
+    /// References whose message template changed.
+    pub message_changed: Vec<AttributeChange>,
+}
+
+/// The output formats of a catalog diff.
+#[derive(Clone, Copy, Debug, PartialEq, clap::ValueEnum)]
+pub enum DiffFormat
+{
+    /// A human-readable report.
+    Text,
+
+    /// A JSON document.
+    Json,
+}
+
+impl CatalogDiff
+{
+    /// Compare two catalogs.
+    ///
+    /// # Arguments
+    ///
+    /// * `old` - The references in the old version of the code base.
+    /// * `new` - The references in the new version of the code base.
+    ///
+    /// # Returns
+    ///
+    /// The differences, each list ordered by reference ID.
+    pub fn new(old: &Catalog, new: &Catalog) -> CatalogDiff
+    {
+        let mut diff = CatalogDiff::default();
+
+        for (id, old_record) in old.iter()
+        {
+            let new_record = match new.get(id)
+            {
+                Some(r) => r,
+                None =>
+                {
+                    diff.removed.push(old_record.clone());
+                    continue;
+                },
+            };
+
+            let change = |old_value: &str, new_value: &str| {
+                (old_value != new_value).then(|| AttributeChange {
+                    id: *id,
+                    old: old_value.to_string(),
+                    new: new_value.to_string(),
+                })
+            };
+
+            diff.moved
+                .extend(change(&old_record.file, &new_record.file));
+            diff.level_changed
+                .extend(change(&old_record.level, &new_record.level));
+            diff.message_changed
+                .extend(change(&old_record.message, &new_record.message));
+        }
+
+        diff.added = new
+            .iter()
+            .filter(|(id, _)| !old.contains_key(id))
+            .map(|(_, r)| r.clone())
+            .collect();
+
+        diff
+    }
+
+    /// Returns whether or not the catalogs were the same.
+    pub fn is_empty(&self) -> bool
+    {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.moved.is_empty()
+            && self.level_changed.is_empty()
+            && self.message_changed.is_empty()
+    }
+
+    /// Format the differences.
+    ///
+    /// # Arguments
+    ///
+    /// * `format` - The output format.
+    ///
+    /// # Returns
+    ///
+    /// The formatted differences, otherwise an error message on failure.
+    pub fn format(&self, format: DiffFormat) -> Result<String, String>
+    {
+        match format
+        {
+            DiffFormat::Text => Ok(self.to_text()),
+            DiffFormat::Json => serde_json::to_string_pretty(self).map_err(|e| e.to_string()),
+        }
+    }
+
+    /// Format the differences as a human-readable report.
+    fn to_text(&self) -> String
+    {
+        if self.is_empty()
+        {
+            return String::from("No differences\n");
+        }
+
+        let mut text = String::new();
+
+        let mut section = |title: &str, lines: Vec<String>| {
+            if !lines.is_empty()
+            {
+                text.push_str(&format!("{} ({}):\n", title, lines.len()));
+                lines
+                    .iter()
+                    .for_each(|l| text.push_str(&format!("  {}\n", l)));
+            }
+        };
+
+        let describe_record =
+            |r: &ReferenceRecord| format!("{}: {} in {}: {:?}", r.id, r.level, r.file, r.message);
+        let describe_change = |c: &AttributeChange| format!("{}: {:?} -> {:?}", c.id, c.old, c.new);
+
+        section("Added", self.added.iter().map(describe_record).collect());
+        section(
+            "Removed",
+            self.removed.iter().map(describe_record).collect(),
+        );
+        section("Moved", self.moved.iter().map(describe_change).collect());
+        section(
+            "Level changed",
+            self.level_changed.iter().map(describe_change).collect(),
+        );
+        section(
+            "Message changed",
+            self.message_changed.iter().map(describe_change).collect(),
+        );
+
+        text
+    }
+}
+
+/// Build a catalog from the references found in a code base. If a reference ID appears more
+/// than once, only its first occurrence is kept.
+///
+/// # Arguments
+///
+/// * `records` - The references, in canonical order.
+///
+/// # Returns
+///
+/// The catalog.
+fn catalog_from_records(records: Vec<ReferenceRecord>) -> Catalog
+{
+    let mut catalog = Catalog::new();
+
+    for record in records.into_iter()
+    {
+        catalog.entry(record.id).or_insert(record);
+    }
+
+    catalog
+}
+
+/// Load the references in a version of a code base, either by scanning a source directory or
+/// by reading a catalog file. A catalog file is a Breadlog registry, of which only the
+/// references that haven't been retired are used.
+///
+/// # Arguments
+///
+/// * `context` - Application context, whose configuration is used to scan source directories.
+///   Required if `path` is a directory.
+/// * `path` - The source directory or catalog file.
+///
+/// # Returns
+///
+/// The catalog, otherwise an error message on failure.
+pub fn load_catalog(context: Option<&Context>, path: &str) -> Result<Catalog, String>
+{
+    if std::path::Path::new(path).is_dir()
+    {
+        let context = match context
+        {
+            Some(c) => c,
+            None => return Err(format!("A configuration is required to scan {}", path)),
+        };
+
+        return scan_reference_records(&context.for_source_dir(path), None)
+            .map(catalog_from_records);
+    }
+
+    let registry_yaml = match std::fs::read_to_string(path)
+    {
+        Ok(y) => y,
+        Err(e) => return Err(format!("Failed to read {}: {}", path, e)),
+    };
+
+    match serde_yaml::from_str::<Registry>(&registry_yaml)
+    {
+        Ok(registry) => Ok(registry
+            .references
+            .into_iter()
+            .filter(|(_, entry)| !entry.retired)
+            .map(|(id, entry)| {
+                (
+                    id,
+                    ReferenceRecord {
+                        id,
+                        message: entry.message,
+                        level: entry.level,
+                        file: entry.file,
+                    },
+                )
+            })
+            .collect()),
+        Err(e) => Err(format!("Failed to parse {}: {}", path, e)),
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use tempdir::TempDir;
+
+    fn record(id: u32, level: &str, file: &str, message: &str) -> ReferenceRecord
+    {
+        ReferenceRecord {
+            id,
+            message: message.to_string(),
+            level: level.to_string(),
+            file: file.to_string(),
+        }
+    }
+
+    fn create_test_context(source_dir: &str) -> Context
+    {
+        Context::new(
+            format!(
+                r#"
+source_dir: {}
+rust:
+  log_macros:
+    - module: test_module
+      name: info
+    - module: test_module
+      name: warn
+"#,
+                source_dir
+            ),
+            source_dir,
+            true,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_diff()
+    {
+        let old = catalog_from_records(vec![
+            record(1, "info", "a.rs", "Unchanged"),
+            record(2, "info", "a.rs", "Removed"),
+            record(3, "info", "a.rs", "Moved"),
+            record(4, "info", "a.rs", "Level"),
+            record(5, "info", "a.rs", "Old message"),
+        ]);
+        let new = catalog_from_records(vec![
+            record(1, "info", "a.rs", "Unchanged"),
+            record(3, "info", "b.rs", "Moved"),
+            record(4, "warn", "a.rs", "Level"),
+            record(5, "info", "a.rs", "New message"),
+            record(6, "info", "a.rs", "Added"),
+        ]);
+
+        let diff = CatalogDiff::new(&old, &new);
+
+        assert_eq!(diff.added, vec![record(6, "info", "a.rs", "Added")]);
+        assert_eq!(diff.removed, vec![record(2, "info", "a.rs", "Removed")]);
+        assert_eq!(
+            diff.moved,
+            vec![AttributeChange {
+                id: 3,
+                old: String::from("a.rs"),
+                new: String::from("b.rs")
+            }]
+        );
+        assert_eq!(
+            diff.level_changed,
+            vec![AttributeChange {
+                id: 4,
+                old: String::from("info"),
+                new: String::from("warn")
+            }]
+        );
+        assert_eq!(
+            diff.message_changed,
+            vec![AttributeChange {
+                id: 5,
+                old: String::from("Old message"),
+                new: String::from("New message")
+            }]
+        );
+
+        assert_eq!(
+            diff.format(DiffFormat::Text).unwrap(),
+            "Added (1):\n  6: info in a.rs: \"Added\"\nRemoved (1):\n  2: info in a.rs: \"Removed\"\nMoved (1):\n  3: \"a.rs\" -> \"b.rs\"\nLevel changed (1):\n  4: \"info\" -> \"warn\"\nMessage changed (1):\n  5: \"Old message\" -> \"New message\"\n"
+        );
+
+        let json: serde_json::Value =
+            serde_json::from_str(&diff.format(DiffFormat::Json).unwrap()).unwrap();
+        assert_eq!(json["added"][0]["id"], 6);
+        assert_eq!(json["moved"][0]["new"], "b.rs");
+
+        assert!(CatalogDiff::new(&old, &old).is_empty());
+        assert_eq!(
+            CatalogDiff::new(&old, &old)
+                .format(DiffFormat::Text)
+                .unwrap(),
+            "No differences\n"
+        );
+    }
+
+    #[test]
+    fn test_load_catalog()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let source_dir = temp_dir.path().join("src");
+        let nested_dir = source_dir.join("nested");
+
+        std::fs::create_dir_all(&nested_dir).unwrap();
+        std::fs::write(
+            nested_dir.join("test_file1.rs"),
+            "fn test1() {\n    info!(\"[ref: 1] Log test 1.\");\n    warn!(\"[ref: 1] Duplicate.\");\n    info!(\"Unreferenced.\");\n}\n",
+        )
+        .unwrap();
+
+        let test_context = create_test_context(temp_dir.path().to_str().unwrap());
+
+        let catalog = load_catalog(Some(&test_context), source_dir.to_str().unwrap()).unwrap();
+        assert_eq!(
+            catalog,
+            Catalog::from([(1, record(1, "info", "nested/test_file1.rs", "Log test 1."))])
+        );
+
+        assert!(load_catalog(None, source_dir.to_str().unwrap()).is_err());
+
+        /*
+         * Retired references aren't in the code base, so they're not in the catalog.
+         */
+        let mut registry = Registry::default();
+        registry.update(
+            &[
+                record(1, "info", "a.rs", "Log test 1."),
+                record(2, "info", "a.rs", "Log test 2."),
+            ],
+            "2024-01-01",
+        );
+        registry.update(&[record(1, "info", "a.rs", "Log test 1.")], "2024-01-02");
+
+        let registry_path = temp_dir.path().join("Breadlog.registry");
+        std::fs::write(
+            &registry_path,
+            Context::registry_contents(&registry).unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            load_catalog(None, registry_path.to_str().unwrap()).unwrap(),
+            Catalog::from([(1, record(1, "info", "a.rs", "Log test 1."))])
+        );
+
+        assert!(load_catalog(None, temp_dir.path().join("missing").to_str().unwrap()).is_err());
+    }
+}
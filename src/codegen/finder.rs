@@ -1,9 +1,15 @@
+use crate::config::discovery;
+use crate::config::sources::SourceRoots;
+use crate::config::Config;
 use crate::config::Context;
 
 use crate::parser::code_parser::CodeLanguage;
-use log::{error, warn};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use ignore::WalkBuilder;
+use log::{error, info, warn};
+use std::collections::BTreeSet;
 use std::fs::metadata;
-use walkdir::WalkDir;
+use std::path::{Path, PathBuf};
 
 /// Represents a single code file.
 pub struct CodeFile
@@ -29,6 +35,98 @@ impl CodeFile
     }
 }
 
+/// Decides which files in the source directory are searched, according to the configured glob
+/// patterns and file size limit.
+struct FileFilter
+{
+    /// The files to search, or `None` to search every file.
+    include: Option<GlobSet>,
+
+    /// The files and directories not to search.
+    exclude: GlobSet,
+
+    /// The size, in bytes, above which files aren't searched.
+    max_file_size: Option<u64>,
+}
+
+impl FileFilter
+{
+    /// Create a new `FileFilter` instance.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The configuration specifying the patterns and size limit.
+    ///
+    /// # Returns
+    ///
+    /// The filter, otherwise an error message if a pattern is invalid.
+    fn new(config: &Config) -> Result<FileFilter, String>
+    {
+        let build_set = |patterns: &[String]| -> Result<GlobSet, String> {
+            let mut builder = GlobSetBuilder::new();
+
+            for pattern in patterns
+            {
+                match GlobBuilder::new(pattern).literal_separator(true).build()
+                {
+                    Ok(glob) => builder.add(glob),
+                    Err(e) => return Err(format!("Invalid pattern {}: {}", pattern, e)),
+                };
+            }
+
+            builder.build().map_err(|e| e.to_string())
+        };
+
+        Ok(FileFilter {
+            include: match config.include.is_empty()
+            {
+                true => None,
+                false => Some(build_set(&config.include)?),
+            },
+            exclude: build_set(&config.exclude)?,
+            max_file_size: config.max_file_size,
+        })
+    }
+
+    /// Determine whether a file or directory is excluded. Everything in an excluded directory is
+    /// also excluded.
+    ///
+    /// # Arguments
+    ///
+    /// * `relative_path` - The path, relative to the source directory.
+    fn is_excluded(&self, relative_path: &Path) -> bool
+    {
+        self.exclude.is_match(relative_path)
+    }
+
+    /// Determine whether a file should be searched.
+    ///
+    /// # Arguments
+    ///
+    /// * `relative_path` - The path of the file, relative to the source directory.
+    /// * `size` - The size of the file, in bytes.
+    fn accepts(&self, relative_path: &Path, size: u64) -> bool
+    {
+        if self.is_excluded(relative_path)
+            || relative_path
+                .ancestors()
+                .skip(1)
+                .any(|p| !p.as_os_str().is_empty() && self.is_excluded(p))
+        {
+            return false;
+        }
+
+        if self.max_file_size.is_some_and(|max| size > max)
+        {
+            return false;
+        }
+
+        self.include
+            .as_ref()
+            .is_none_or(|include| include.is_match(relative_path))
+    }
+}
+
 /// Represents a collection of code files.
 pub struct CodeFinder<'ctx>
 {
@@ -37,20 +135,56 @@ pub struct CodeFinder<'ctx>
 
     /// A reference to the `Context` instance.
     context: &'ctx Context,
+
+    /// Whether or not to restrict the search to the context's selected files, if any.
+    use_selection: bool,
+
+    /// The configured source directories, resolved by the last search.
+    source_roots: SourceRoots,
+
+    /// The configuration, including the settings overridden by configuration files in the
+    /// directories containing the code files found by the last search.
+    config: Config,
 }
 
 impl<'ctx> CodeFinder<'ctx>
 {
-    /// Create a new `CodeFinder` instance.
+    /// Create a new `CodeFinder` instance. If the context specifies a selection of files, only
+    /// those files are found.
     ///
     /// # Arguments
     ///
     /// * `context` - A reference to the `Context` instance.
     pub fn new(context: &'ctx Context) -> Option<CodeFinder<'ctx>>
+    {
+        Self::create(context, true)
+    }
+
+    /// Create a new `CodeFinder` instance that finds all code files in the configured source
+    /// directory, regardless of any selection of files specified in the context.
+    ///
+    /// # Arguments
+    ///
+    /// * `context` - A reference to the `Context` instance.
+    pub fn new_unrestricted(context: &'ctx Context) -> Option<CodeFinder<'ctx>>
+    {
+        Self::create(context, false)
+    }
+
+    /// Create a new `CodeFinder` instance and perform the initial search.
+    ///
+    /// # Arguments
+    ///
+    /// * `context` - A reference to the `Context` instance.
+    /// * `use_selection` - Whether or not to restrict the search to the context's selected files.
+    fn create(context: &'ctx Context, use_selection: bool) -> Option<CodeFinder<'ctx>>
     {
         let mut result = Self {
             code_files: Vec::new(),
             context,
+            use_selection,
+            source_roots: SourceRoots::default(),
+            config: context.config.clone(),
         };
 
         if result.find()
@@ -63,7 +197,146 @@ impl<'ctx> CodeFinder<'ctx>
         }
     }
 
-    /// Find all code files in the configured source directory.
+    /// Returns the code files found in canonical order: sorted by path, comparing one path
+    /// component at a time. Unlike the order of `code_files`, this doesn't depend on the order
+    /// in which the file system lists directory entries.
+    pub fn ordered_code_files(&self) -> Vec<&CodeFile>
+    {
+        use std::path::Path;
+
+        let mut result: Vec<&CodeFile> = self.code_files.iter().collect();
+        result.sort_by(|a, b| Path::new(&a.path).cmp(Path::new(&b.path)));
+
+        result
+    }
+
+    /// Returns the source directories searched.
+    pub fn source_roots(&self) -> &SourceRoots
+    {
+        &self.source_roots
+    }
+
+    /// Returns the configuration to use when searching the code files found, including the
+    /// settings overridden by configuration files in the directories containing them.
+    pub fn config(&self) -> &Config
+    {
+        &self.config
+    }
+
+    /// Whether or not the files found are restricted to a selection of files.
+    pub fn is_restricted(&self) -> bool
+    {
+        self.use_selection && self.context.selected_files.is_some()
+    }
+
+    /// Determine whether a path has one of the configured code file extensions.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to check.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the path has a configured extension, `false` otherwise.
+    fn has_code_extension(&self, path: &std::path::Path) -> bool
+    {
+        if let Some(extension) = path.extension()
+        {
+            let extension_str = match extension
+                .to_str()
+                .ok_or("Failed to convert extension to string")
+            {
+                Ok(extension) => extension.to_string(),
+                Err(_e) =>
+                {
+                    warn!("[ref: 3] {}", _e);
+                    return false;
+                },
+            };
+
+            /*
+             * For now, we only support Rust so only have to check these extensions. In the
+             * future this may be extended to check for multiple configured languages.
+             */
+            return self.context.config.rust.extensions.contains(&extension_str);
+        }
+
+        false
+    }
+
+    /// Find the selected code files that are within the configured source directories.
+    ///
+    /// # Arguments
+    ///
+    /// * `selected_files` - The paths of the selected files.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the search was successful, `false` otherwise.
+    fn find_selected(&mut self, selected_files: &[String], filter: &FileFilter) -> bool
+    {
+        for selected_file in selected_files
+        {
+            use std::sync::atomic;
+
+            if self.context.stop_commanded.load(atomic::Ordering::Relaxed)
+            {
+                return false;
+            }
+
+            let path = match Path::new(selected_file).canonicalize()
+            {
+                Ok(p) => p,
+                Err(_e) =>
+                {
+                    warn!("[ref: 37] Selected file {} can't be found", selected_file);
+                    continue;
+                },
+            };
+
+            if !path.is_file()
+                || self.source_roots.root_of(&path).is_none()
+                || !self.has_code_extension(&path)
+            {
+                info!(
+                    "[ref: 38] Skipping selected file {} as it isn't a code file in the source directory",
+                    selected_file
+                );
+                continue;
+            }
+
+            let size = metadata(&path).map_or(0, |m| m.len());
+
+            if !self
+                .source_roots
+                .relative_path(&path)
+                .is_some_and(|p| filter.accepts(Path::new(&p), size))
+            {
+                info!(
+                    "[ref: 118] Skipping selected file {} as it's excluded by the configuration",
+                    selected_file
+                );
+                continue;
+            }
+
+            let path_str = match path.to_str()
+            {
+                Some(p) => p.to_string(),
+                None => continue,
+            };
+
+            if !self.code_files.iter().any(|f| f.path == path_str)
+            {
+                self.code_files
+                    .push(CodeFile::new(path_str, CodeLanguage::Rust));
+            }
+        }
+
+        true
+    }
+
+    /// Find all code files in the configured source directories, or the selected files within them
+    /// if the context specifies a selection.
     ///
     /// # Returns
     ///
@@ -81,26 +354,94 @@ impl<'ctx> CodeFinder<'ctx>
          * who's configured a path to a file instead of a directory.
          */
 
-        let source_dir_metadata = match metadata(&self.context.config.source_dir)
+        let config = &self.context.config;
+        let source_dirs = config.source_dirs();
+
+        for source_dir in source_dirs.iter()
         {
-            Ok(metadata) => metadata,
-            Err(_e) =>
+            let source_dir_metadata = match metadata(source_dir)
+            {
+                Ok(metadata) => metadata,
+                Err(_e) =>
+                {
+                    error!("[ref: 1] Failed to read source directory metadata");
+                    return false;
+                },
+            };
+
+            if !source_dir_metadata.is_dir()
             {
-                error!("[ref: 1] Failed to read source directory metadata");
+                error!("[ref: 2] Configured source path is not a directory");
+                return false;
+            }
+        }
+
+        self.source_roots = match SourceRoots::new(config)
+        {
+            Ok(r) => r,
+            Err(e) =>
+            {
+                error!("[ref: 36] Failed to resolve source directories: {}", e);
                 return false;
             },
         };
 
-        if !source_dir_metadata.is_dir()
+        let filter = match FileFilter::new(config)
         {
-            error!("[ref: 2] Configured source path is not a directory");
-            return false;
+            Ok(f) => f,
+            Err(e) =>
+            {
+                error!("[ref: 119] Failed to read file patterns: {}", e);
+                return false;
+            },
+        };
+
+        if self.use_selection
+        {
+            if let Some(selected_files) = &self.context.selected_files
+            {
+                return self.find_selected(selected_files, &filter)
+                    && self.load_directory_configs();
+            }
+        }
+
+        /*
+         * Hidden files are searched, as they always have been. Ignore files are only used if
+         * configured, and they're used outside git repositories too.
+         */
+        let mut walker = WalkBuilder::new(&source_dirs[0]);
+
+        for source_dir in source_dirs.iter().skip(1)
+        {
+            walker.add(source_dir);
         }
 
-        for entry in WalkDir::new(&self.context.config.source_dir)
-            .into_iter()
+        walker
+            .standard_filters(false)
+            .git_ignore(config.respect_gitignore)
+            .git_exclude(config.respect_gitignore)
+            .ignore(config.respect_gitignore)
+            .parents(config.respect_gitignore)
+            .require_git(false)
+            .follow_links(config.follow_symlinks)
+            .max_filesize(config.max_file_size);
+
+        let exclude = filter.exclude.clone();
+        let source_roots = self.source_roots.clone();
+        walker.filter_entry(move |entry| {
+            /*
+             * Prune excluded directories, rather than searching everything in them.
+             */
+            !entry.file_type().is_some_and(|t| t.is_dir())
+                || source_roots
+                    .relative_path(entry.path())
+                    .is_none_or(|p| p.is_empty() || !exclude.is_match(&p))
+        });
+
+        for entry in walker
+            .build()
             .filter_map(|e| e.ok())
-            .filter(|e| e.file_type().is_file())
+            .filter(|e| e.file_type().is_some_and(|t| t.is_file()))
         {
             use std::sync::atomic;
 
@@ -109,38 +450,103 @@ impl<'ctx> CodeFinder<'ctx>
                 return false;
             }
 
-            if let Some(extension) = entry.path().extension()
+            let size = entry.metadata().map_or(0, |m| m.len());
+
+            if self.has_code_extension(entry.path())
+                && self
+                    .source_roots
+                    .relative_path(entry.path())
+                    .is_some_and(|p| filter.accepts(Path::new(&p), size))
             {
-                let extension_str = match extension
-                    .to_str()
-                    .ok_or("Failed to convert extension to string")
+                let path_str = match entry.path().to_str()
                 {
-                    Ok(extension) => extension.to_string(),
-                    Err(_e) =>
-                    {
-                        warn!("[ref: 3] {}", _e);
-                        continue;
-                    },
+                    Some(path) => path.to_string(),
+                    None => continue,
                 };
 
+                self.code_files
+                    .push(CodeFile::new(path_str, CodeLanguage::Rust));
+            }
+        }
+
+        self.load_directory_configs()
+    }
+
+    /// Read the configuration files in the directories containing the code files found, which
+    /// override Rust settings for their subdirectories of the code base. A configuration file in
+    /// the configuration directory is the code base's own configuration, so it's skipped.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the configuration files were read successfully, `false` otherwise.
+    fn load_directory_configs(&mut self) -> bool
+    {
+        self.config = self.context.config.clone();
+
+        let config_dir = Path::new(&self.config.config_dir).canonicalize().ok();
+        let mut dirs: BTreeSet<PathBuf> = BTreeSet::new();
+
+        for code_file in self.code_files.iter()
+        {
+            let path = Path::new(&code_file.path);
+
+            let root = match self.source_roots.root_of(path)
+            {
+                Some(r) => r,
+                None => continue,
+            };
+
+            for dir in path.ancestors().skip(1)
+            {
                 /*
-                 * For now, we only support Rust so only have to check these extensions. In the
-                 * future this may be extended to check for multiple configured languages.
+                 * Stop at the source directory, or at a directory already visited, whose
+                 * ancestors have been too.
                  */
-                if self.context.config.rust.extensions.contains(&extension_str)
+                if !(dir.starts_with(&root.canonical_path) || dir.starts_with(&root.path))
+                    || !dirs.insert(dir.to_path_buf())
                 {
-                    let path_str = match entry.path().to_str()
-                    {
-                        Some(path) => path.to_string(),
-                        None => continue,
-                    };
-
-                    self.code_files
-                        .push(CodeFile::new(path_str, CodeLanguage::Rust));
+                    break;
                 }
             }
         }
 
+        for dir in dirs.iter()
+        {
+            let config_file = match discovery::config_file_in(dir)
+            {
+                Some(f) => f,
+                None => continue,
+            };
+
+            if dir.canonicalize().ok() == config_dir
+            {
+                continue;
+            }
+
+            let relative_dir = self.source_roots.relative_path(dir).unwrap_or_default();
+
+            match discovery::read_directory_config(&config_file, &relative_dir)
+            {
+                Ok(directory_config) =>
+                {
+                    info!(
+                        "[ref: 123] Using settings from {}",
+                        config_file.to_string_lossy()
+                    );
+                    self.config.rust.directories.push(directory_config);
+                },
+                Err(e) =>
+                {
+                    error!(
+                        "[ref: 122] Invalid configuration file {}: {}",
+                        config_file.to_string_lossy(),
+                        e
+                    );
+                    return false;
+                },
+            }
+        }
+
         true
     }
 }
@@ -592,4 +998,290 @@ rust:
             &finder.code_files
         ));
     }
+
+    #[test]
+    fn test_find_selected_files()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let source_dir = temp_dir.path().join("src");
+        create_dir(&source_dir).unwrap();
+
+        for file_name in ["test_file1.rs", "test_file2.rs", "test_file3.py"]
+        {
+            let mut source_file = File::create(source_dir.join(file_name)).unwrap();
+            source_file.write_all(b"Test file").unwrap();
+        }
+
+        {
+            let mut outside_file = File::create(temp_dir.path().join("outside.rs")).unwrap();
+            outside_file.write_all(b"Test file").unwrap();
+        }
+
+        let mut context = create_test_context(source_dir.to_str().unwrap().to_string());
+        context.selected_files = Some(vec![
+            source_dir
+                .join("test_file1.rs")
+                .to_str()
+                .unwrap()
+                .to_string(),
+            source_dir
+                .join("test_file3.py")
+                .to_str()
+                .unwrap()
+                .to_string(),
+            source_dir
+                .join("non_existent.rs")
+                .to_str()
+                .unwrap()
+                .to_string(),
+            temp_dir
+                .path()
+                .join("outside.rs")
+                .to_str()
+                .unwrap()
+                .to_string(),
+            source_dir
+                .join("test_file1.rs")
+                .to_str()
+                .unwrap()
+                .to_string(),
+        ]);
+
+        let finder = CodeFinder::new(&context).unwrap();
+
+        assert!(finder.is_restricted());
+        assert_eq!(finder.code_files.len(), 1);
+        assert!(search_codefile(
+            &source_dir
+                .canonicalize()
+                .unwrap()
+                .join("test_file1.rs")
+                .to_str()
+                .unwrap()
+                .to_string(),
+            &finder.code_files
+        ));
+
+        let unrestricted_finder = CodeFinder::new_unrestricted(&context).unwrap();
+
+        assert!(!unrestricted_finder.is_restricted());
+        assert_eq!(unrestricted_finder.code_files.len(), 2);
+    }
+
+    #[test]
+    fn test_find_selected_files_empty()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+
+        {
+            let mut source_file = File::create(temp_dir.path().join("test_file1.rs")).unwrap();
+            source_file.write_all(b"Test file").unwrap();
+        }
+
+        let mut context = create_test_context(temp_dir.path().to_str().unwrap().to_string());
+        context.selected_files = Some(Vec::new());
+
+        let finder = CodeFinder::new(&context).unwrap();
+
+        assert!(finder.is_restricted());
+        assert_eq!(finder.code_files.len(), 0);
+    }
+
+    /// Returns the paths of the files found, relative to a directory, in canonical order.
+    fn relative_code_files(finder: &CodeFinder, dir: &Path) -> Vec<String>
+    {
+        finder
+            .ordered_code_files()
+            .iter()
+            .map(|f| {
+                Path::new(&f.path)
+                    .strip_prefix(dir)
+                    .unwrap()
+                    .to_str()
+                    .unwrap()
+                    .to_string()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_include_exclude_patterns()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        create_dir(temp_dir.path().join("target")).unwrap();
+        create_dir(temp_dir.path().join("net")).unwrap();
+        create_dir(temp_dir.path().join("net").join("generated")).unwrap();
+
+        for file_name in [
+            "main.rs",
+            "target/build.rs",
+            "net/client.rs",
+            "net/generated/proto.rs",
+        ]
+        {
+            std::fs::write(temp_dir.path().join(file_name), "Test file").unwrap();
+        }
+
+        let mut context = create_test_context(temp_dir.path().to_str().unwrap().to_string());
+        context.config.exclude = vec![String::from("target"), String::from("**/generated")];
+
+        let finder = CodeFinder::new(&context).unwrap();
+        assert_eq!(
+            relative_code_files(&finder, temp_dir.path()),
+            vec!["main.rs", "net/client.rs"]
+        );
+
+        /*
+         * A single * doesn't match path separators.
+         */
+        context.config.include = vec![String::from("*.rs")];
+
+        let finder = CodeFinder::new(&context).unwrap();
+        assert_eq!(
+            relative_code_files(&finder, temp_dir.path()),
+            vec!["main.rs"]
+        );
+
+        /*
+         * Selected files are filtered in the same way.
+         */
+        context.selected_files = Some(vec![
+            temp_dir
+                .path()
+                .join("main.rs")
+                .to_str()
+                .unwrap()
+                .to_string(),
+            temp_dir
+                .path()
+                .join("net/client.rs")
+                .to_str()
+                .unwrap()
+                .to_string(),
+        ]);
+
+        let finder = CodeFinder::new(&context).unwrap();
+        assert_eq!(finder.code_files.len(), 1);
+
+        context.config.include = vec![String::from("[")];
+        assert!(CodeFinder::new(&context).is_none());
+    }
+
+    #[test]
+    fn test_respect_gitignore()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        create_dir(temp_dir.path().join("vendor")).unwrap();
+
+        std::fs::write(temp_dir.path().join(".gitignore"), "vendor/\n").unwrap();
+        std::fs::write(temp_dir.path().join(".ignore"), "scratch.rs\n").unwrap();
+
+        for file_name in ["main.rs", "scratch.rs", "vendor/lib.rs", ".hidden.rs"]
+        {
+            std::fs::write(temp_dir.path().join(file_name), "Test file").unwrap();
+        }
+
+        let mut context = create_test_context(temp_dir.path().to_str().unwrap().to_string());
+
+        let finder = CodeFinder::new(&context).unwrap();
+        assert_eq!(
+            relative_code_files(&finder, temp_dir.path()),
+            vec![".hidden.rs", "main.rs", "scratch.rs", "vendor/lib.rs"]
+        );
+
+        context.config.respect_gitignore = true;
+
+        let finder = CodeFinder::new(&context).unwrap();
+        assert_eq!(
+            relative_code_files(&finder, temp_dir.path()),
+            vec![".hidden.rs", "main.rs"]
+        );
+    }
+
+    #[test]
+    fn test_max_file_size()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+
+        std::fs::write(temp_dir.path().join("small.rs"), "1234").unwrap();
+        std::fs::write(temp_dir.path().join("large.rs"), "12345").unwrap();
+
+        let mut context = create_test_context(temp_dir.path().to_str().unwrap().to_string());
+        context.config.max_file_size = Some(4);
+
+        let finder = CodeFinder::new(&context).unwrap();
+        assert_eq!(
+            relative_code_files(&finder, temp_dir.path()),
+            vec!["small.rs"]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_follow_symlinks()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let source_dir = temp_dir.path().join("src");
+        let linked_dir = temp_dir.path().join("linked");
+        create_dir(&source_dir).unwrap();
+        create_dir(&linked_dir).unwrap();
+
+        std::fs::write(source_dir.join("main.rs"), "Test file").unwrap();
+        std::fs::write(linked_dir.join("lib.rs"), "Test file").unwrap();
+        std::os::unix::fs::symlink(&linked_dir, source_dir.join("linked")).unwrap();
+
+        let mut context = create_test_context(source_dir.to_str().unwrap().to_string());
+
+        let finder = CodeFinder::new(&context).unwrap();
+        assert_eq!(relative_code_files(&finder, &source_dir), vec!["main.rs"]);
+
+        context.config.follow_symlinks = true;
+
+        let finder = CodeFinder::new(&context).unwrap();
+        assert_eq!(
+            relative_code_files(&finder, &source_dir),
+            vec!["linked/lib.rs", "main.rs"]
+        );
+    }
+    #[test]
+    fn test_ordered_code_files()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        create_dir(temp_dir.path().join("a")).unwrap();
+        create_dir(temp_dir.path().join("a").join("b")).unwrap();
+
+        let file_paths = [
+            temp_dir.path().join("a.rs"),
+            temp_dir.path().join("a").join("b").join("c.rs"),
+            temp_dir.path().join("a").join("z.rs"),
+            temp_dir.path().join("b.rs"),
+        ];
+
+        for file_path in file_paths.iter().rev()
+        {
+            let mut source_file = File::create(file_path).unwrap();
+            source_file.write_all(b"Test file").unwrap();
+        }
+
+        let context = create_test_context(temp_dir.path().to_str().unwrap().to_string());
+
+        let mut finder = CodeFinder::new(&context).unwrap();
+        finder.code_files.reverse();
+
+        let ordered_paths: Vec<&str> = finder
+            .ordered_code_files()
+            .iter()
+            .map(|f| f.path.as_str())
+            .collect();
+
+        assert_eq!(
+            ordered_paths,
+            vec![
+                file_paths[1].to_str().unwrap(),
+                file_paths[2].to_str().unwrap(),
+                file_paths[0].to_str().unwrap(),
+                file_paths[3].to_str().unwrap(),
+            ]
+        );
+    }
 }
@@ -1,17 +1,34 @@
 use async_std::task;
 
+use super::hashed_ids::HashedIdAllocator;
+use super::report::{self, Finding, FindingKind};
+use super::run_lock::{RunLock, RunLockError};
+use super::transaction;
+use super::transaction::{sibling_path, StagedFile, Transaction};
 use super::CodeFinder;
+use crate::config::context::Cache;
+use crate::config::context::IdStrategy;
+use crate::config::registry;
+use crate::config::registry::{ReferenceRecord, Registry};
+use crate::config::sources::SourceRoots;
+use crate::config::Config;
 use crate::config::Context;
 use crate::parser;
 use async_trait::async_trait;
 use log::error;
 use log::info;
 use log::warn;
-use std::str::FromStr;
-use std::sync::atomic::AtomicU32;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::path::Path;
 use std::sync::Arc;
+use std::time::Duration;
 
-const START_REFERENCE_ID: u32 = 1;
+pub const START_REFERENCE_ID: u32 = 1;
+
+/// The largest reference ID that's issued. `u32::MAX` is never issued, so that the next reference
+/// ID can always be recorded after issuing any other.
+pub const MAX_REFERENCE_ID: u32 = u32::MAX - 1;
 
 /// Load the contents of the file at the given path.
 ///
@@ -41,6 +58,9 @@ async fn load_code(path: &String) -> Option<String>
 }
 
 /// Represents a temporary file which is deleted when dropped; compatible with `async_std::fs::File`.
+///
+/// The temporary file is created in the same directory as the file it will replace, so that it
+/// can be atomically renamed over that file without crossing file systems.
 struct AsyncTempFile
 {
     /// The path to the temporary file.
@@ -52,36 +72,25 @@ struct AsyncTempFile
 
 impl AsyncTempFile
 {
-    /// Create a new temporary file.
+    /// Create a new temporary file to replace the given target file.
+    ///
+    /// # Arguments
+    ///
+    /// * `target_path` - The path to the file that the temporary file will replace. This should
+    ///   already have had any symbolic links resolved.
     ///
     /// # Returns
     ///
     /// The temporary file, or an error message if the file could not be created.
-    pub async fn new() -> Result<AsyncTempFile, String>
+    pub async fn new(target_path: &str) -> Result<AsyncTempFile, String>
     {
-        use std::env::temp_dir;
-        use uuid::Uuid;
-
-        let mut file_path = temp_dir();
-        file_path.push(format!("breadlog-{}.tmp", Uuid::new_v4()));
-
-        let path_result = match file_path.to_str()
-        {
-            Some(p) => match String::from_str(p)
-            {
-                Ok(s) => s,
-                Err(e) =>
-                {
-                    return Err(format!(
-                        "Failed to convert temporary file path to string: {}",
-                        e
-                    ))
-                },
-            },
-            None => return Err("Failed to convert temporary file path to string".to_string()),
-        };
+        let path_result = sibling_path(target_path, "tmp")?;
 
-        match async_std::fs::File::create(file_path).await
+        match async_std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path_result)
+            .await
         {
             Ok(f) => Ok(AsyncTempFile {
                 path: path_result,
@@ -92,6 +101,7 @@ impl AsyncTempFile
     }
 
     /// Get the path to the temporary file.
+    #[allow(dead_code)]
     pub fn path(&self) -> &str
     {
         &self.path
@@ -102,6 +112,82 @@ impl AsyncTempFile
     {
         &mut self.file
     }
+
+    /// Stage the temporary file as the new contents of the target file. The temporary file's
+    /// contents are flushed to disk and it's given the target file's permissions and (where
+    /// possible) ownership. The target file is replaced when the returned staged file is
+    /// committed as part of a `Transaction`.
+    ///
+    /// The target file's modification time is deliberately not preserved: its contents have
+    /// changed, and build tools rely on the modification time to detect this.
+    ///
+    /// # Arguments
+    ///
+    /// * `target_path` - The path to the file to replace. This should already have had any
+    ///   symbolic links resolved, so that the link itself isn't replaced.
+    ///
+    /// # Returns
+    ///
+    /// The staged file, or an error message if the temporary file could not be staged.
+    pub async fn stage(mut self, target_path: &str) -> Result<StagedFile, String>
+    {
+        use async_std::io::WriteExt;
+
+        if let Err(e) = self.file.flush().await
+        {
+            return Err(format!("Failed to flush temporary file: {}", e));
+        }
+
+        if let Err(e) = self.file.sync_all().await
+        {
+            return Err(format!("Failed to sync temporary file: {}", e));
+        }
+
+        let target_metadata = match async_std::fs::metadata(target_path).await
+        {
+            Ok(m) => m,
+            Err(e) => return Err(format!("Failed to read metadata of {}: {}", target_path, e)),
+        };
+
+        if let Err(e) =
+            async_std::fs::set_permissions(&self.path, target_metadata.permissions()).await
+        {
+            return Err(format!(
+                "Failed to set permissions of temporary file: {}",
+                e
+            ));
+        }
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+
+            /*
+             * Only the superuser can give a file away, so failing to preserve ownership isn't
+             * fatal: the file is still updated, but will be owned by the current user.
+             */
+            if let Err(e) = std::os::unix::fs::chown(
+                &self.path,
+                Some(target_metadata.uid()),
+                Some(target_metadata.gid()),
+            )
+            {
+                let target_path_copy = target_path.to_string();
+                task::spawn(async move {
+                    warn!(
+                        "[ref: 43] Failed to preserve ownership of {}: {}",
+                        target_path_copy, e
+                    );
+                })
+                .await;
+            }
+        }
+
+        Ok(StagedFile::new(
+            std::mem::take(&mut self.path),
+            target_path.to_string(),
+        ))
+    }
 }
 
 /// Delete the temporary file when dropped.
@@ -112,6 +198,13 @@ impl Drop for AsyncTempFile
     {
         use std::fs::remove_file;
 
+        /* Once staged, the file is owned by the `StagedFile`.
+         */
+        if self.path.is_empty()
+        {
+            return;
+        }
+
         /* The client may have performed a file operation which means it can't be deleted, so
          * don't worry about errors.
          */
@@ -150,6 +243,27 @@ pub trait ReferenceProcessor<Params, MapResult, ReduceResult>
         entries: &[parser::LogRefEntry],
     ) -> Option<MapResult>;
 
+    /// Determine the parameters to pass to the map operation for a file. Unlike the map
+    /// operation, this is called for one file at a time in the order the files were found, so
+    /// it can be used to deterministically divide a shared resource (such as a range of
+    /// reference IDs) between files before they're processed concurrently. By default, every
+    /// file receives a copy of the processor's parameters.
+    ///
+    /// # Arguments
+    ///
+    /// * `params` - The processor's parameters, which may be updated for subsequent files.
+    /// * `entries` - The log reference entries found in the file.
+    ///
+    /// # Returns
+    ///
+    /// The parameters to pass to the map operation for the file.
+    fn file_params(params: &mut Option<Params>, _entries: &[parser::LogRefEntry]) -> Option<Params>
+    where
+        Params: Clone,
+    {
+        params.clone()
+    }
+
     /// Reduce the results of the map operation.
     ///
     /// # Arguments
@@ -188,16 +302,13 @@ impl ReferenceProcessor<u32, (u32, usize), (u32, usize)> for NextReferenceIdProc
 
         for reference in entries.iter()
         {
-            if reference.usable_reference_position()
+            if let Some(reference_id) = reference.reference()
             {
-                if let Some(reference_id) = reference.reference()
-                {
-                    max_file_ref = cmp::max(max_file_ref, reference_id);
-                }
-                else
-                {
-                    num_missing_refs += 1;
-                }
+                max_file_ref = cmp::max(max_file_ref, reference_id);
+            }
+            else if reference.needs_reference()
+            {
+                num_missing_refs += 1;
             }
         }
 
@@ -222,13 +333,41 @@ impl ReferenceProcessor<u32, (u32, usize), (u32, usize)> for NextReferenceIdProc
             return Some((START_REFERENCE_ID, missing_refs_result));
         }
 
-        Some((ref_id_result + 1, missing_refs_result))
+        match ref_id_result.checked_add(1)
+        {
+            Some(next_reference_id) => Some((next_reference_id, missing_refs_result)),
+            None =>
+            {
+                error!(
+                    "[ref: 139] Found reference ID {} - no more reference IDs can be issued",
+                    ref_id_result
+                );
+                None
+            },
+        }
     }
 }
 
-/// A reference processor for counting the number of missing references in a code base. As with
-/// all reference processors, the map and reduce operations are orchestrated by `process_references()`.
-struct CountMissingReferenceIdProcessor {}
+/// The parameters for scanning the references to record in the registry.
+#[derive(Clone)]
+struct RegistryScanParams
+{
+    /// The source directories.
+    source_roots: Arc<SourceRoots>,
+
+    /// The paths of files staged to replace code files, keyed by the canonical path of the code
+    /// file.
+    staged_paths: Arc<HashMap<String, String>>,
+
+    /// The configuration to use when searching staged files for log references.
+    config: Arc<Config>,
+}
+
+/// A reference processor for collecting the references to record in the registry. Files with
+/// staged replacements are scanned as they'll be once the replacements are committed. As with
+/// all reference processors, the map and reduce operations are orchestrated by
+/// `process_references()`.
+struct RegistryScanProcessor {}
 
 /// # Type Parameters
 ///
@@ -236,92 +375,81 @@ struct CountMissingReferenceIdProcessor {}
 /// * `MapResult` - The type of the result of the map operation.
 /// * `ReduceResult` - The type of the result of the reduce operation.
 #[async_trait]
-impl ReferenceProcessor<u32, u32, u32> for CountMissingReferenceIdProcessor
+impl ReferenceProcessor<RegistryScanParams, Vec<ReferenceRecord>, Vec<ReferenceRecord>>
+    for RegistryScanProcessor
 {
     async fn map(
         path: &str,
         _file_contents: &str,
-        _params: &Option<u32>,
+        params: &Option<RegistryScanParams>,
         entries: &[parser::LogRefEntry],
-    ) -> Option<u32>
+    ) -> Option<Vec<ReferenceRecord>>
     {
-        let mut missing_ref_count: u32 = 0;
-
-        for reference in entries.iter()
+        let params = params.as_ref()?;
+        let canonical_path = async_std::fs::canonicalize(path).await.ok();
+
+        let file = params
+            .source_roots
+            .relative_path(Path::new(path))
+            .unwrap_or(path.to_string());
+
+        let staged_entries;
+        let entries = match canonical_path.and_then(|p| {
+            params
+                .staged_paths
+                .get(p.to_string_lossy().as_ref())
+                .cloned()
+        })
         {
-            if !reference.exists()
+            Some(staged_path) =>
             {
-                let path_copy = path.to_string();
-                let line = reference.position().line();
-                let column = reference.position().column();
-
-                if !reference.usable_reference_position()
-                {
-                    task::spawn(async move {
-                        warn!(
-                            "[ref: 35] Unusable reference will be ignored in file {}, line {}, column {}",
-                            path_copy, line, column,
-                        );
-                    })
-                    .await;
-
-                    tracing::event!(
-                        tracing::Level::TRACE,
-                        "unusable_reference_{}_{}",
-                        line,
-                        column
-                    );
-
-                    continue;
-                }
+                let staged_contents = load_code(&staged_path).await?;
 
-                task::spawn(async move {
-                    warn!(
-                        "[ref: 5] Missing reference in file {}, line {}, column {}",
-                        path_copy, line, column,
-                    );
-                })
-                .await;
-
-                tracing::event!(
-                    tracing::Level::TRACE,
-                    "missing_reference_{}_{}",
-                    line,
-                    column
+                staged_entries = parser::code_parser::find_references(
+                    parser::code_parser::CodeLanguage::Rust,
+                    &staged_contents,
+                    &config_for_file(Path::new(path), &params.config, &params.source_roots),
                 );
 
-                missing_ref_count += 1;
-            }
-        }
-
-        let path_copy = path.to_string();
-        task::spawn(async move {
-            info!(
-                "[ref: 6] Total missing references in {}: {}",
-                path_copy, missing_ref_count
-            );
-        })
-        .await;
+                &staged_entries
+            },
+            None => entries,
+        };
 
-        Some(missing_ref_count)
+        Some(
+            entries
+                .iter()
+                .filter_map(|e| {
+                    e.reference().map(|id| ReferenceRecord {
+                        id,
+                        message: e.message().unwrap_or_default().to_string(),
+                        level: e.macro_name().to_string(),
+                        file: file.clone(),
+                    })
+                })
+                .collect(),
+        )
     }
 
-    fn reduce(map_results: &[u32]) -> Option<u32>
+    fn reduce(map_results: &[Vec<ReferenceRecord>]) -> Option<Vec<ReferenceRecord>>
     {
-        let mut reduce_result: u32 = 0;
+        Some(map_results.concat())
+    }
+}
 
-        for map_result in map_results.iter()
-        {
-            reduce_result += *map_result;
-        }
+/// The reference IDs to insert.
+#[derive(Clone, Debug, PartialEq)]
+enum ReferenceIds
+{
+    /// Contiguous IDs, starting from the given ID.
+    Sequential(u32),
 
-        info!(
-            "[ref: 7] Total missing references (all files): {}",
-            reduce_result
-        );
+    /// IDs chosen in advance, keyed by file path. Each file's IDs are in order of position
+    /// within the file.
+    Assigned(Arc<HashMap<String, Vec<u32>>>),
 
-        Some(reduce_result)
-    }
+    /// No IDs, because no references are missing.
+    Unneeded,
 }
 
 /// The result of inserting references into a file.
@@ -329,46 +457,134 @@ struct InsertReferencesResult
 {
     failure: bool,
     num_inserted_references: usize,
+
+    /// The number of malformed references put in canonical form.
+    num_fixed_references: usize,
+
+    /// The rewritten files, which replace the originals only when committed.
+    staged_files: Vec<StagedFile>,
 }
 
 /// A reference processor for inserting references into a code base. As with
 /// all reference processors, the map and reduce operations are orchestrated by `process_references()`.
+///
+/// The processor's parameter is the reference IDs to use. Sequential IDs are divided into a
+/// contiguous range for each file before any files are modified, so IDs are assigned in a stable
+/// order: first by file path (in the canonical order of `CodeFinder::ordered_code_files()`), then
+/// by position within the file.
 struct InsertReferencesProcessor {}
 
+impl InsertReferencesProcessor
+{
+    /// Count the entries in a file that need a reference to be inserted.
+    ///
+    /// # Arguments
+    ///
+    /// * `entries` - The log reference entries found in the file.
+    fn count_insertable(entries: &[parser::LogRefEntry]) -> usize
+    {
+        entries.iter().filter(|&e| e.needs_reference()).count()
+    }
+
+    /// Count the entries in a file with a malformed reference that can be put in canonical form.
+    ///
+    /// # Arguments
+    ///
+    /// * `entries` - The log reference entries found in the file.
+    fn count_fixable(entries: &[parser::LogRefEntry]) -> usize
+    {
+        entries
+            .iter()
+            .filter(|e| e.malformed_reference().and_then(|m| m.fix()).is_some())
+            .count()
+    }
+}
+
 /// # Type Parameters
 ///
 /// * `Params` - The type of the parameters to pass to the processor.
 /// * `MapResult` - The type of the result of the map operation.
 /// * `ReduceResult` - The type of the result of the reduce operation.
 #[async_trait]
-impl ReferenceProcessor<Arc<AtomicU32>, InsertReferencesResult, InsertReferencesResult>
+impl ReferenceProcessor<ReferenceIds, InsertReferencesResult, InsertReferencesResult>
     for InsertReferencesProcessor
 {
     async fn map(
         path: &str,
         file_contents: &str,
-        params: &Option<Arc<AtomicU32>>,
+        params: &Option<ReferenceIds>,
         entries: &[parser::LogRefEntry],
     ) -> Option<InsertReferencesResult>
     {
-        if entries
-            .iter()
-            .filter(|&e| !e.exists() && e.usable_reference_position())
-            .count()
-            == 0
+        let num_insertable = InsertReferencesProcessor::count_insertable(entries);
+        let num_fixable = InsertReferencesProcessor::count_fixable(entries);
+
+        if num_insertable == 0 && num_fixable == 0
         {
             return Some(InsertReferencesResult {
                 failure: false,
                 num_inserted_references: 0,
+                num_fixed_references: 0,
+                staged_files: Vec::new(),
             });
         }
 
-        let mut created_entries: usize = 0;
-
-        let next_reference_id = match params
+        let reference_ids: Vec<u32> = match params
         {
-            Some(next_id) => next_id,
-            None =>
+            _ if num_insertable == 0 => Vec::new(),
+            Some(ReferenceIds::Sequential(first_id)) =>
+            {
+                /*
+                 * The next reference ID after those inserted must be recordable too.
+                 */
+                if first_id.checked_add(num_insertable as u32).is_none()
+                {
+                    let path_copy = path.to_string();
+                    task::spawn(async move {
+                        error!(
+                            "[ref: 140] Ran out of reference IDs while inserting references in {}",
+                            path_copy
+                        );
+                    })
+                    .await;
+
+                    tracing::event!(tracing::Level::TRACE, "reference_ids_exhausted");
+
+                    return Some(InsertReferencesResult {
+                        failure: true,
+                        num_inserted_references: 0,
+                        num_fixed_references: 0,
+                        staged_files: Vec::new(),
+                    });
+                }
+
+                (*first_id..).take(num_insertable).collect()
+            },
+            Some(ReferenceIds::Assigned(assigned_ids)) => match assigned_ids.get(path)
+            {
+                Some(ids) if ids.len() == num_insertable => ids.clone(),
+                _ =>
+                {
+                    let path_copy = path.to_string();
+                    task::spawn(async move {
+                        error!(
+                            "[ref: 82] Unexpected missing reference IDs for {} during reference insert",
+                            path_copy
+                        );
+                    })
+                    .await;
+
+                    tracing::event!(tracing::Level::TRACE, "unexpected_assigned_reference_ids");
+
+                    return Some(InsertReferencesResult {
+                        failure: true,
+                        num_inserted_references: 0,
+                        num_fixed_references: 0,
+                        staged_files: Vec::new(),
+                    });
+                },
+            },
+            Some(ReferenceIds::Unneeded) | None =>
             {
                 task::spawn(async {
                     error!("[ref: 8] Unexpected missing next reference ID during reference insert");
@@ -380,16 +596,42 @@ impl ReferenceProcessor<Arc<AtomicU32>, InsertReferencesResult, InsertReferences
                 return Some(InsertReferencesResult {
                     failure: true,
                     num_inserted_references: 0,
+                    num_fixed_references: 0,
+                    staged_files: Vec::new(),
                 });
             },
         };
 
         use async_std::io::WriteExt;
 
+        /* If the file is a symbolic link, its target is updated and the link is left intact.
+         */
+        let target_path = match async_std::fs::canonicalize(path).await
+        {
+            Ok(p) => p.to_string_lossy().to_string(),
+            Err(e) =>
+            {
+                let path_copy = path.to_string();
+                task::spawn(async move {
+                    error!("[ref: 44] Failed to resolve path {}: {}", path_copy, e);
+                })
+                .await;
+
+                tracing::event!(tracing::Level::TRACE, "failed_to_resolve_path");
+
+                return Some(InsertReferencesResult {
+                    failure: true,
+                    num_inserted_references: 0,
+                    num_fixed_references: 0,
+                    staged_files: Vec::new(),
+                });
+            },
+        };
+
         /* Create a temporary file to write the new contents to. Once the file is written,
          * it will be moved to the original file.
          */
-        let mut scratch_file = match AsyncTempFile::new().await
+        let mut scratch_file = match AsyncTempFile::new(&target_path).await
         {
             Ok(f) => f,
             Err(e) =>
@@ -402,17 +644,47 @@ impl ReferenceProcessor<Arc<AtomicU32>, InsertReferencesResult, InsertReferences
                 return Some(InsertReferencesResult {
                     failure: true,
                     num_inserted_references: 0,
+                    num_fixed_references: 0,
+                    staged_files: Vec::new(),
                 });
             },
         };
 
+        /*
+         * Each edit replaces a range of the file with new text, in order of position. Inserted
+         * references replace an empty range.
+         */
+        let mut edits: Vec<(std::ops::Range<usize>, String)> = Vec::new();
+        let mut unused_reference_ids = reference_ids.into_iter();
+        let mut created_entries: usize = 0;
+
+        for entry in entries.iter()
+        {
+            if entry.needs_reference()
+            {
+                if let Some(reference_id) = unused_reference_ids.next()
+                {
+                    let insert_pos = entry.position().character();
+
+                    edits.push((
+                        insert_pos..insert_pos,
+                        entry.insertable_reference_string(reference_id),
+                    ));
+
+                    created_entries += 1;
+                }
+            }
+            else if let Some(fix) = entry.malformed_reference().and_then(|m| m.fix())
+            {
+                edits.extend(fix);
+            }
+        }
+
         let mut unwritten_content_start_pos: usize = 0;
 
-        for entry in entries
-            .iter()
-            .filter(|e| !e.exists() && e.usable_reference_position())
+        for (range, replacement) in edits
         {
-            let insert_pos = entry.position().character();
+            let insert_pos = range.start;
 
             if insert_pos < unwritten_content_start_pos
             {
@@ -429,6 +701,8 @@ impl ReferenceProcessor<Arc<AtomicU32>, InsertReferencesResult, InsertReferences
                 return Some(InsertReferencesResult {
                     failure: true,
                     num_inserted_references: 0,
+                    num_fixed_references: 0,
+                    staged_files: Vec::new(),
                 });
             }
 
@@ -448,19 +722,15 @@ impl ReferenceProcessor<Arc<AtomicU32>, InsertReferencesResult, InsertReferences
                     return Some(InsertReferencesResult {
                         failure: true,
                         num_inserted_references: 0,
+                        num_fixed_references: 0,
+                        staged_files: Vec::new(),
                     });
                 },
             }
 
-            unwritten_content_start_pos += insert_pos - unwritten_content_start_pos;
+            unwritten_content_start_pos = range.end;
 
-            let reference_id = next_reference_id.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-            let insertable_ref_id_string = entry.insertable_reference_string(reference_id);
-
-            match scratch_file
-                .file()
-                .write_all(insertable_ref_id_string.as_bytes())
-                .await
+            match scratch_file.file().write_all(replacement.as_bytes()).await
             {
                 Ok(_) => (),
                 Err(e) =>
@@ -473,11 +743,11 @@ impl ReferenceProcessor<Arc<AtomicU32>, InsertReferencesResult, InsertReferences
                     return Some(InsertReferencesResult {
                         failure: true,
                         num_inserted_references: 0,
+                        num_fixed_references: 0,
+                        staged_files: Vec::new(),
                     });
                 },
             }
-
-            created_entries += 1;
         }
 
         let end_of_file_index = file_contents.len();
@@ -501,373 +771,2016 @@ impl ReferenceProcessor<Arc<AtomicU32>, InsertReferencesResult, InsertReferences
                     return Some(InsertReferencesResult {
                         failure: true,
                         num_inserted_references: 0,
+                        num_fixed_references: 0,
+                        staged_files: Vec::new(),
                     });
                 },
             }
         }
 
-        match async_std::fs::rename(scratch_file.path(), path).await
+        match scratch_file.stage(&target_path).await
         {
-            Ok(_) =>
+            Ok(staged_file) =>
             {
                 return Some(InsertReferencesResult {
                     failure: false,
                     num_inserted_references: created_entries,
+                    num_fixed_references: num_fixable,
+                    staged_files: vec![staged_file],
                 });
             },
             Err(e) =>
             {
                 task::spawn(async move {
-                    error!("[ref: 14] Failed to rename temporary file: {}", e);
+                    error!("[ref: 14] Failed to replace file {}: {}", target_path, e);
                 })
                 .await;
 
-                tracing::event!(tracing::Level::TRACE, "failed_to_rename_temp_file");
+                tracing::event!(tracing::Level::TRACE, "failed_to_stage_temp_file");
 
                 return Some(InsertReferencesResult {
                     failure: true,
-                    num_inserted_references: created_entries,
+                    num_inserted_references: 0,
+                    num_fixed_references: 0,
+                    staged_files: Vec::new(),
                 });
             },
         }
     }
 
+    fn file_params(
+        params: &mut Option<ReferenceIds>,
+        entries: &[parser::LogRefEntry],
+    ) -> Option<ReferenceIds>
+    {
+        let file_reference_ids = params.clone();
+
+        if let Some(ReferenceIds::Sequential(next_reference_id)) = params
+        {
+            /*
+             * Running out of IDs is reported when the file is processed.
+             */
+            *next_reference_id = next_reference_id
+                .saturating_add(InsertReferencesProcessor::count_insertable(entries) as u32);
+        }
+
+        file_reference_ids
+    }
+
     fn reduce(map_results: &[InsertReferencesResult]) -> Option<InsertReferencesResult>
     {
         let mut insert_count: usize = 0;
+        let mut fix_count: usize = 0;
         let mut reduce_failure: bool = false;
+        let mut staged_files: Vec<StagedFile> = Vec::new();
 
         for map_result in map_results.iter()
         {
             insert_count += map_result.num_inserted_references;
+            fix_count += map_result.num_fixed_references;
             reduce_failure |= map_result.failure;
+            staged_files.extend(map_result.staged_files.iter().cloned());
         }
 
         Some(InsertReferencesResult {
             failure: reduce_failure,
             num_inserted_references: insert_count,
+            num_fixed_references: fix_count,
+            staged_files,
         })
     }
 }
 
-/// Process references in the given code base.
-///
-/// # Arguments
-///
-/// * `context` - The context to use.
-/// * `params` - Any parameters to pass to the processor.
-/// * `finder` - The code finder to use.
-///
-/// # Returns
-///
-/// The result of the relevant processor's reduce operation, or `None` if the operation failed.
-fn process_references<
-    'generator,
-    ProcessorType,
-    Param: Send + Clone + 'static,
-    MapResult: Send + 'static,
-    ReduceResult,
->(
-    context: &'generator Context,
-    params: Option<Param>,
-    finder: &'generator CodeFinder,
-) -> Option<ReduceResult>
-where
-    ProcessorType: ReferenceProcessor<Param, MapResult, ReduceResult>,
+/// The log messages in a file, as needed to derive hashed reference IDs.
+#[derive(Clone)]
+struct FileMessages
 {
-    let config_task_outer = context.config.clone();
-    let stop_flag = context.stop_commanded.clone();
+    /// The path to the file, as found by the code finder.
+    path: String,
 
-    task::block_on(async {
-        let mut all_map_results = Vec::new();
+    /// The path to the file used to derive hashed reference IDs (see
+    /// `SourceRoots::relative_path()`).
+    relative_path: String,
 
-        for file in finder.code_files.iter()
-        {
-            if stop_flag.load(std::sync::atomic::Ordering::Relaxed)
-            {
-                return None;
-            }
+    /// The references already in the file.
+    existing_references: Vec<u32>,
 
-            let path = file.path.clone();
-            let language = file.language;
-            let config_task_inner = config_task_outer.clone();
-            let params_task_inner = params.clone();
+    /// The messages of the log statements that need a reference to be inserted, in order of
+    /// position. Log statements without a message string literal have an empty message.
+    new_messages: Vec<String>,
+}
 
-            if let Some(file_contents) = load_code(&path).await
-            {
-                let references = parser::code_parser::find_references(
-                    language,
-                    &file_contents,
-                    &config_task_inner,
-                );
+/// A reference processor for collecting the log messages that need a reference to be inserted,
+/// and the references already in use. As with all reference processors, the map and reduce
+/// operations are orchestrated by `process_references()`.
+///
+/// The processor's parameter is the source directories.
+struct MessagesProcessor {}
 
-                if let Some(map_result) =
-                    ProcessorType::map(&path, &file_contents, &params_task_inner, &references).await
-                {
-                    all_map_results.push(map_result);
-                }
-            }
-        }
+/// # Type Parameters
+///
+/// * `Params` - The type of the parameters to pass to the processor.
+/// * `MapResult` - The type of the result of the map operation.
+/// * `ReduceResult` - The type of the result of the reduce operation.
+#[async_trait]
+impl ReferenceProcessor<Arc<SourceRoots>, FileMessages, Vec<FileMessages>> for MessagesProcessor
+{
+    async fn map(
+        path: &str,
+        _file_contents: &str,
+        params: &Option<Arc<SourceRoots>>,
+        entries: &[parser::LogRefEntry],
+    ) -> Option<FileMessages>
+    {
+        let relative_path = params
+            .as_ref()
+            .and_then(|r| r.relative_path(Path::new(path)));
+
+        Some(FileMessages {
+            path: path.to_string(),
+            relative_path: relative_path.unwrap_or(path.to_string()),
+            existing_references: entries.iter().filter_map(|e| e.reference()).collect(),
+            new_messages: entries
+                .iter()
+                .filter(|e| e.needs_reference())
+                .map(|e| e.message().unwrap_or_default().to_string())
+                .collect(),
+        })
+    }
+
+    fn reduce(map_results: &[FileMessages]) -> Option<Vec<FileMessages>>
+    {
+        Some(map_results.to_vec())
+    }
+}
+
+/// A code file that has been loaded and parsed, ready for the map operation.
+struct ParsedCodeFile
+{
+    /// The path to the file.
+    path: String,
+
+    /// The contents of the file.
+    contents: String,
 
-        if stop_flag.load(std::sync::atomic::Ordering::Relaxed)
+    /// The log reference entries found in the file.
+    references: Vec<parser::LogRefEntry>,
+}
+
+/// Determine the configuration to use when searching a file for log references, taking into
+/// account the settings overridden for the directories containing it.
+///
+/// # Arguments
+///
+/// * `path` - The path to the file.
+/// * `config` - The configuration for the whole code base.
+/// * `source_roots` - The source directories.
+///
+/// # Returns
+///
+/// The configuration for the file.
+fn config_for_file(path: &Path, config: &Arc<Config>, source_roots: &SourceRoots) -> Arc<Config>
+{
+    if config.rust.directories.is_empty()
+    {
+        return config.clone();
+    }
+
+    let relative_path = match source_roots.relative_path(path)
+    {
+        Some(p) => p,
+        None => return config.clone(),
+    };
+
+    match config.rust.for_path(Path::new(&relative_path))
+    {
+        Some(rust) if rust != config.rust =>
         {
-            return None;
-        }
+            let mut file_config = (**config).clone();
+            file_config.rust = rust;
 
-        ProcessorType::reduce(&all_map_results)
+            Arc::new(file_config)
+        },
+        _ => config.clone(),
+    }
+}
+
+/// Load and parse a code file.
+///
+/// # Arguments
+///
+/// * `path` - The path to the file.
+/// * `language` - The language contained in the file.
+/// * `config` - The configuration to use when searching for log references.
+/// * `source_roots` - The source directories.
+///
+/// # Returns
+///
+/// The parsed file, or `None` if the file could not be read.
+async fn parse_code_file(
+    path: String,
+    language: parser::code_parser::CodeLanguage,
+    config: Arc<Config>,
+    source_roots: Arc<SourceRoots>,
+) -> Option<ParsedCodeFile>
+{
+    let contents = load_code(&path).await?;
+
+    /*
+     * Parsing is CPU-bound, so it's run on a thread where it won't block other tasks.
+     */
+    task::spawn_blocking(move || {
+        let config = config_for_file(Path::new(&path), &config, &source_roots);
+        let references = parser::code_parser::find_references(language, &contents, &config);
+
+        Some(ParsedCodeFile {
+            path,
+            contents,
+            references,
+        })
     })
+    .await
 }
 
-/// Check the given code base for missing references.
+/// Process references in the given code base.
+///
+/// Files are loaded, parsed and mapped concurrently, with at most `context.max_jobs` files in
+/// each stage at once. The parameters for each file's map operation are determined in the
+/// finder's canonical file order (see `CodeFinder::ordered_code_files()`), and map results are
+/// reduced in that same order, so the outcome depends neither on the order in which files were
+/// discovered nor on the order in which concurrent work completes.
 ///
 /// # Arguments
 ///
-/// * `context` - Application context.
+/// * `context` - The context to use.
+/// * `params` - Any parameters to pass to the processor.
+/// * `finder` - The code finder to use.
 ///
 /// # Returns
 ///
-/// Number of missing references found, otherwise an error message on failure.
-pub fn check_references(context: &Context) -> Result<u32, &'static str>
+/// The result of the relevant processor's reduce operation, or `None` if the operation failed.
+pub fn process_references<
+    'generator,
+    ProcessorType,
+    Param: Send + Sync + Clone + 'static,
+    MapResult: Send + 'static,
+    ReduceResult,
+>(
+    context: &'generator Context,
+    params: Option<Param>,
+    finder: &'generator CodeFinder,
+) -> Option<ReduceResult>
+where
+    ProcessorType: ReferenceProcessor<Param, MapResult, ReduceResult> + 'static,
 {
-    if let Some(finder) = CodeFinder::new(context)
-    {
-        if finder.code_files.is_empty()
+    use futures::stream::{self, StreamExt};
+    use std::sync::atomic::Ordering;
+
+    let config = Arc::new(finder.config().clone());
+    let source_roots = Arc::new(finder.source_roots().clone());
+    let stop_flag = context.stop_commanded.clone();
+    let max_jobs = std::cmp::max(context.max_jobs, 1);
+    let mut remaining_params = params;
+
+    task::block_on(async {
+        let all_map_results: Vec<Option<MapResult>> = stream::iter(finder.ordered_code_files())
+            .map(|file| {
+                let path = file.path.clone();
+                let language = file.language;
+                let config_task = config.clone();
+                let source_roots_task = source_roots.clone();
+                let stop_flag_task = stop_flag.clone();
+
+                task::spawn(async move {
+                    if stop_flag_task.load(Ordering::Relaxed)
+                    {
+                        return None;
+                    }
+
+                    parse_code_file(path, language, config_task, source_roots_task).await
+                })
+            })
+            .buffered(max_jobs)
+            .map(|parsed| {
+                /*
+                 * This closure is called for each file in turn, in canonical file order,
+                 * regardless of the order in which parsing completes.
+                 */
+                let file_params = parsed
+                    .as_ref()
+                    .map(|p| ProcessorType::file_params(&mut remaining_params, &p.references));
+                let stop_flag_task = stop_flag.clone();
+
+                task::spawn(async move {
+                    let parsed = parsed?;
+
+                    if stop_flag_task.load(Ordering::Relaxed)
+                    {
+                        return None;
+                    }
+
+                    ProcessorType::map(
+                        &parsed.path,
+                        &parsed.contents,
+                        &file_params.flatten(),
+                        &parsed.references,
+                    )
+                    .await
+                })
+            })
+            .buffered(max_jobs)
+            .collect()
+            .await;
+
+        if stop_flag.load(Ordering::Relaxed)
         {
-            return Err("No files found");
+            return None;
         }
 
-        info!("[ref: 15] Found {} file(s)", finder.code_files.len());
+        let all_map_results: Vec<MapResult> = all_map_results.into_iter().flatten().collect();
 
-        let missing_reference_count =
-            process_references::<CountMissingReferenceIdProcessor, u32, u32, u32>(
-                context, None, &finder,
-            )
-            .map_or(0, |id| id);
+        ProcessorType::reduce(&all_map_results)
+    })
+}
 
-        if missing_reference_count > 0
+/// Log check mode's findings. Duplicate reference IDs only appear in the report, as they don't
+/// fail check mode - they're renumbered by the `resolve-conflicts` subcommand.
+///
+/// # Arguments
+///
+/// * `findings` - The findings.
+fn log_findings(findings: &[Finding])
+{
+    for finding in findings.iter()
+    {
+        let (file, line, column) = (&finding.file, finding.line, finding.column);
+
+        match finding.kind
         {
-            return Err("One or more missing references were found");
+            FindingKind::Missing => warn!(
+                "[ref: 5] Missing reference in file {}, line {}, column {}",
+                file, line, column,
+            ),
+            FindingKind::Unusable => warn!(
+                "[ref: 35] Unusable reference will be ignored in file {}, line {}, column {}",
+                file, line, column,
+            ),
+            FindingKind::Retired => warn!(
+                "[ref: 95] Retired reference ID {} reused in file {}, line {}, column {}",
+                finding.reference.unwrap_or_default(),
+                file,
+                line,
+                column,
+            ),
+            FindingKind::Malformed => warn!(
+                "[ref: 112] Malformed reference in file {}, line {}, column {}: {}",
+                file, line, column, finding.message,
+            ),
+            FindingKind::Forbidden => warn!(
+                "[ref: 116] Forbidden reference in file {}, line {}, column {} - {}",
+                file, line, column, finding.message,
+            ),
+            FindingKind::Duplicate => continue,
         }
-    }
-    else
-    {
-        return Err("Code discovery error");
-    }
 
-    Ok(0)
+        tracing::event!(
+            tracing::Level::TRACE,
+            "{}_{}_{}",
+            finding.kind.rule_id().replace('-', "_"),
+            line,
+            column
+        );
+    }
 }
 
-/// Insert log references in the given code base.
+/// Check the given code base for missing, malformed, forbidden and retired references, and that
+/// the registry is in sync with the code. Each file is parsed once to find every kind of problem.
 ///
 /// # Arguments
 ///
 /// * `context` - Application context.
+/// * `findings` - Updated with the problems found with log statements, in canonical file order,
+///   then by position within each file. These are found even if the check fails because of them.
 ///
 /// # Returns
 ///
-/// Number of inserted references, otherwise an error message on failure.
-pub fn generate_code(context: &Context) -> Result<u32, &'static str>
+/// Number of missing references found, otherwise an error message on failure.
+pub fn check_references(context: &Context, findings: &mut Vec<Finding>)
+    -> Result<u32, &'static str>
 {
+    /*
+     * Check mode never modifies the code base, so an interrupted update can't be rolled back
+     * here - and checking a partially updated code base would give misleading results.
+     */
+    if transaction::recovery_pending(&context.journal_path())
+    {
+        error!("[ref: 45] A previous update was interrupted - run Breadlog without --check to roll it back");
+        return Err("Interrupted update pending recovery");
+    }
+
     if let Some(finder) = CodeFinder::new(context)
     {
         if finder.code_files.is_empty()
         {
+            if finder.is_restricted()
+            {
+                info!("[ref: 39] No selected files to check - nothing to do");
+                return Ok(0);
+            }
+
             return Err("No files found");
         }
 
-        info!("[ref: 16] Found {} file(s)", finder.code_files.len());
+        info!("[ref: 15] Found {} file(s)", finder.code_files.len());
 
-        let calculated_next_reference_id = match context.cached_next_reference_id
+        let registry = match context.read_registry()
         {
-            Some(id) =>
+            Ok(r) => r,
+            Err(e) =>
             {
-                info!("[ref: 17] Using cached next reference ID");
-
-                id
+                error!("[ref: 89] Failed to read registry: {}", e);
+                return Err("Failed to read registry");
             },
-            None =>
-            {
-                info!("[ref: 18] Performing first pass to determine next reference ID");
-
-                let references_id_result = match process_references::<
-                    NextReferenceIdProcessor,
-                    u32,
-                    (u32, usize),
-                    (u32, usize),
-                >(context, None, &finder)
-                {
-                    Some(r) => r,
-                    None => return Err("Failed to determine next reference ID"),
-                };
-
-                if references_id_result.1 == 0
-                {
-                    info!("[ref: 19] No missing references - nothing to do");
-                    return Ok(0);
-                }
+        };
 
-                references_id_result.0
+        *findings = match report::find_findings(context, &finder, &registry)
+        {
+            Ok(f) => f,
+            Err(e) =>
+            {
+                error!("[ref: 142] Failed to check references: {}", e);
+                return Err("Failed to check references");
             },
         };
 
-        let next_reference_id = Arc::new(AtomicU32::new(calculated_next_reference_id));
-
-        info!(
-            "[ref: 20] Next reference ID: {}",
-            next_reference_id.load(std::sync::atomic::Ordering::Relaxed)
-        );
+        log_findings(findings);
 
-        let reference_updates = match process_references::<
-            InsertReferencesProcessor,
-            Arc<AtomicU32>,
-            InsertReferencesResult,
-            InsertReferencesResult,
-        >(context, Some(next_reference_id), &finder)
-        {
-            Some(r) => r,
-            None => return Err("Failed to insert references"),
-        };
+        let count = |kind: FindingKind| findings.iter().filter(|f| f.kind == kind).count();
 
-        let cachable_reference_id =
-            calculated_next_reference_id + (reference_updates.num_inserted_references as u32);
-        context.cache_next_reference_id(cachable_reference_id, context.config.config_dir.as_str());
+        let missing_reference_count = count(FindingKind::Missing);
 
         info!(
-            "[ref: 21] Num. inserted reference(s): {}",
-            reference_updates.num_inserted_references
+            "[ref: 7] Total missing references (all files): {}",
+            missing_reference_count
         );
-    }
-    else
-    {
-        return Err("Code discovery error");
-    }
 
-    Ok(0)
-}
+        let malformed_reference_count = count(FindingKind::Malformed);
 
-#[cfg(test)]
-mod tests
-{
-    use async_trait::async_trait;
-    use futures::AsyncWriteExt;
-    use regex::Regex;
-    use std::fs::File;
-    use std::io::Read;
-    use std::io::Write;
-    use std::str::FromStr;
-    use tempdir::TempDir;
-    extern crate testing_logger;
-    use tracing_test::traced_test;
+        if malformed_reference_count > 0
+        {
+            error!(
+                "[ref: 115] Found {} malformed reference(s) - run Breadlog without --check to fix them",
+                malformed_reference_count
+            );
+        }
 
-    use super::generate_code;
-    use super::process_references;
-    use super::CountMissingReferenceIdProcessor;
-    use super::InsertReferencesProcessor;
-    use super::InsertReferencesResult;
-    use super::NextReferenceIdProcessor;
-    use super::ReferenceProcessor;
-    use crate::codegen::CodeFinder;
-    use crate::config::Context;
-    use crate::parser;
-    use crate::parser::LogRefKind;
+        let forbidden_reference_count = count(FindingKind::Forbidden);
 
-    struct TestRefProcCount {}
+        if forbidden_reference_count > 0
+        {
+            error!(
+                "[ref: 117] Found {} reference(s) in log statements whose macro forbids them",
+                forbidden_reference_count
+            );
+        }
 
-    #[async_trait]
-    impl ReferenceProcessor<u32, u32, u32> for TestRefProcCount
-    {
-        async fn map(
-            path: &str,
-            _file_contents: &str,
-            _params: &Option<u32>,
-            entries: &[parser::LogRefEntry],
-        ) -> Option<u32>
+        let retired_reference_count = count(FindingKind::Retired);
+
+        if retired_reference_count > 0
         {
-            if path.ends_with("_none.rs")
-            {
-                return None;
-            }
+            error!(
+                "[ref: 96] Found {} retired reference ID(s) in use - retired IDs must not be reused",
+                retired_reference_count
+            );
+        }
 
-            Some(entries.len() as u32)
+        if missing_reference_count > 0
+        {
+            return Err("One or more missing references were found");
         }
 
-        fn reduce(map_results: &[u32]) -> Option<u32>
+        if malformed_reference_count > 0
         {
-            let mut reduce_result: u32 = 0;
+            return Err("One or more malformed references were found");
+        }
 
-            for map_result in map_results.iter()
-            {
-                reduce_result += *map_result;
-            }
+        if forbidden_reference_count > 0
+        {
+            return Err("One or more forbidden references were found");
+        }
 
-            if reduce_result == 0
+        if retired_reference_count > 0
+        {
+            return Err("One or more retired references were found");
+        }
+
+        if let Some(r) = registry
+        {
+            match check_registry(context, &r)
             {
-                return None;
+                Ok(true) => (),
+                Ok(false) =>
+                {
+                    error!("[ref: 104] The registry is out of sync with the code - run Breadlog without --check to update it");
+                    return Err("Registry out of sync");
+                },
+                Err(e) =>
+                {
+                    error!("[ref: 105] Failed to check registry: {}", e);
+                    return Err("Failed to check registry");
+                },
             }
-
-            Some(reduce_result)
         }
     }
-
-    fn create_test_context(source_dir: &String, check_mode: bool, structured_mode: bool)
-        -> Context
+    else
     {
-        let context = Context::new(
-            format!(
-                r#"
-source_dir: {}
-rust:
-  structured: {}
-  log_macros:
-    - module: test_module
-      name: test_macro
-"#,
-                source_dir, structured_mode
-            )
-            .to_string(),
-            &source_dir,
-            check_mode,
+        return Err("Code discovery error");
+    }
+
+    Ok(0)
+}
+
+/// Determine the next reference ID by scanning the whole code base, ignoring the lock file.
+///
+/// # Arguments
+///
+/// * `context` - Application context.
+///
+/// # Returns
+///
+/// The next reference ID, or `None` if the code base could not be scanned.
+pub fn scan_next_reference_id(context: &Context) -> Option<u32>
+{
+    let registry = match context.read_registry()
+    {
+        Ok(r) => r,
+        Err(e) =>
+        {
+            error!("[ref: 94] Failed to read registry: {}", e);
+            return None;
+        },
+    };
+
+    let finder = CodeFinder::new_unrestricted(context)?;
+
+    process_references::<NextReferenceIdProcessor, u32, (u32, usize), (u32, usize)>(
+        context, None, &finder,
+    )
+    .map(|r| skip_issued_reference_ids(r.0, &registry))
+}
+
+/// Move a next reference ID past every ID the registry records as issued, so that IDs removed
+/// from the code base are never issued again.
+///
+/// # Arguments
+///
+/// * `next_reference_id` - The next reference ID, determined from the code base or lock file.
+/// * `registry` - The registry, if it's enabled.
+///
+/// # Returns
+///
+/// The next reference ID that has never been issued.
+fn skip_issued_reference_ids(next_reference_id: u32, registry: &Option<Registry>) -> u32
+{
+    match registry.as_ref().and_then(|r| r.next_reference_id())
+    {
+        Some(registry_next_id) => next_reference_id.max(registry_next_id),
+        None => next_reference_id,
+    }
+}
+
+/// Scan the whole code base for references, recording the message, level and file of each.
+///
+/// # Arguments
+///
+/// * `context` - Application context.
+/// * `transaction` - A transaction that's yet to be committed. If given, files it updates are
+///   scanned as they'll be once it's committed.
+///
+/// # Returns
+///
+/// The references in canonical file order, then by position within each file, otherwise an
+/// error message on failure.
+pub fn scan_reference_records(
+    context: &Context,
+    transaction: Option<&Transaction>,
+) -> Result<Vec<ReferenceRecord>, String>
+{
+    let staged_paths: HashMap<String, String> = transaction.map_or(HashMap::new(), |t| {
+        t.staged_files()
+            .iter()
+            .map(|f| (f.target_path().to_string(), f.staged_path().to_string()))
+            .collect()
+    });
+
+    /*
+     * References are retired when they're removed from any file, so the whole code base must
+     * be scanned even if only a selection of files is being updated.
+     */
+    let finder = match CodeFinder::new_unrestricted(context)
+    {
+        Some(f) => f,
+        None => return Err("Code discovery error".to_string()),
+    };
+
+    match process_references::<
+        RegistryScanProcessor,
+        RegistryScanParams,
+        Vec<ReferenceRecord>,
+        Vec<ReferenceRecord>,
+    >(
+        context,
+        Some(RegistryScanParams {
+            source_roots: Arc::new(finder.source_roots().clone()),
+            staged_paths: Arc::new(staged_paths),
+            config: Arc::new(finder.config().clone()),
+        }),
+        &finder,
+    )
+    {
+        Some(r) => Ok(r),
+        None => Err("Failed to find references".to_string()),
+    }
+}
+
+/// Stage an update to the registry as part of a transaction, recording the references that will
+/// be in the code base once the transaction is committed. If the registry is disabled or
+/// unchanged, nothing is staged.
+///
+/// # Arguments
+///
+/// * `context` - Application context.
+/// * `registry` - The registry, as read at the start of the update.
+/// * `transaction` - The transaction to add the registry to, after every other file has been
+///   added to it.
+///
+/// # Returns
+///
+/// Nothing, or an error message if the registry could not be staged.
+pub fn stage_registry(
+    context: &Context,
+    registry: &Option<Registry>,
+    transaction: &mut Transaction,
+) -> Result<(), String>
+{
+    let registry = match registry
+    {
+        Some(r) => r,
+        None => return Ok(()),
+    };
+
+    let present = scan_reference_records(context, Some(transaction))?;
+
+    let mut updated_registry = registry.clone();
+    let changes = updated_registry.update(&present, &registry::today());
+
+    for retired_id in changes.retired.iter()
+    {
+        info!("[ref: 97] Retired reference ID {}", retired_id);
+    }
+
+    if changes.is_empty()
+    {
+        return Ok(());
+    }
+
+    info!(
+        "[ref: 100] Registry updated: {} added, {} changed, {} retired",
+        changes.added.len(),
+        changes.changed.len(),
+        changes.retired.len()
+    );
+
+    transaction.add(StagedFile::write(
+        &context.registry_path(),
+        Context::registry_contents(&updated_registry)?.as_bytes(),
+    )?);
+
+    Ok(())
+}
+
+/// Check that the registry records the references in the code base.
+///
+/// # Arguments
+///
+/// * `context` - Application context.
+/// * `registry` - The registry.
+///
+/// # Returns
+///
+/// Whether or not the registry is in sync, otherwise an error message on failure.
+fn check_registry(context: &Context, registry: &Registry) -> Result<bool, String>
+{
+    let present = scan_reference_records(context, None)?;
+
+    let changes = registry.clone().update(&present, &registry::today());
+
+    for id in changes.added.iter()
+    {
+        warn!(
+            "[ref: 101] Reference ID {} is missing from the registry",
+            id
+        );
+    }
+
+    for id in changes.changed.iter()
+    {
+        warn!(
+            "[ref: 102] The registry's record of reference ID {} is out of date",
+            id
+        );
+    }
+
+    for id in changes.retired.iter()
+    {
+        warn!(
+            "[ref: 103] Reference ID {} has been removed but isn't retired in the registry",
+            id
+        );
+    }
+
+    Ok(changes.is_empty())
+}
+
+/// Stage an update to the lock file as part of a transaction. If caching is disabled, nothing is
+/// staged.
+///
+/// # Arguments
+///
+/// * `context` - Application context.
+/// * `cache` - The new contents of the lock file.
+/// * `transaction` - The transaction to add the lock file to.
+///
+/// # Returns
+///
+/// Nothing, or an error message if the lock file could not be staged.
+pub fn stage_cache(
+    context: &Context,
+    cache: &Cache,
+    transaction: &mut Transaction,
+) -> Result<(), String>
+{
+    if let Some(cache_contents) = context.cache_contents(cache)
+    {
+        transaction.add(StagedFile::write(
+            &Context::cache_path(&context.config.config_dir),
+            cache_contents.as_bytes(),
+        )?);
+    }
+
+    Ok(())
+}
+
+/// Prepare to modify a code base: take the run lock, then roll back any update that was
+/// previously interrupted.
+///
+/// # Arguments
+///
+/// * `context` - Application context.
+///
+/// # Returns
+///
+/// The run lock, which must be held until all changes have been committed, otherwise an error
+/// message on failure.
+pub fn begin_update(context: &Context) -> Result<RunLock, &'static str>
+{
+    let run_lock = match RunLock::acquire(
+        &context.run_lock_path(),
+        Duration::from_secs(context.config.lock_timeout),
+        &context.stop_commanded,
+        || info!("[ref: 50] Waiting for another Breadlog run to finish"),
+    )
+    {
+        Ok(l) => l,
+        Err(RunLockError::Timeout) =>
+        {
+            error!(
+                "[ref: 51] Another Breadlog run still holds {} after {} second(s)",
+                context.run_lock_path(),
+                context.config.lock_timeout
+            );
+            return Err("Another Breadlog run is in progress");
+        },
+        Err(RunLockError::Stopped) =>
+        {
+            warn!("[ref: 138] Stopped while waiting for another Breadlog run to finish");
+            return Err("Stopped while waiting for run lock");
+        },
+        Err(RunLockError::Io(e)) =>
+        {
+            error!("[ref: 52] Failed to take run lock: {}", e);
+            return Err("Failed to take run lock");
+        },
+    };
+
+    match transaction::recover(&context.journal_path())
+    {
+        Ok(0) => (),
+        Ok(num_files) => warn!(
+            "[ref: 46] Rolled back an interrupted update of {} file(s)",
+            num_files
+        ),
+        Err(e) =>
+        {
+            error!("[ref: 47] Failed to roll back an interrupted update: {}", e);
+            return Err("Failed to recover from interrupted update");
+        },
+    }
+
+    Ok(run_lock)
+}
+
+/// Determine the first reference ID to allocate sequentially: from the block reserved for the
+/// ID owner if there is one, otherwise from the lock file or a scan of the whole code base.
+///
+/// # Arguments
+///
+/// * `context` - Application context.
+/// * `finder` - The code finder for the files being updated.
+/// * `cache` - The contents of the lock file, if it exists.
+/// * `registry` - The registry, if it's enabled.
+///
+/// # Returns
+///
+/// The first reference ID, `None` if there are no missing references, otherwise an error message
+/// on failure.
+fn next_sequential_reference_id(
+    context: &Context,
+    finder: &CodeFinder,
+    cache: &Option<Cache>,
+    registry: &Option<Registry>,
+) -> Result<Option<u32>, &'static str>
+{
+    let next_reference_id = match (&context.id_owner, cache)
+    {
+        (Some(owner), _) =>
+        {
+            let reservation = match cache.as_ref().and_then(|c| c.reservation(owner))
+            {
+                Some(r) => r,
+                None =>
+                {
+                    error!(
+                        "[ref: 68] No block of reference IDs is reserved for {}",
+                        owner
+                    );
+                    return Err("No reserved block of reference IDs");
+                },
+            };
+
+            let num_missing_references = match process_references::<
+                NextReferenceIdProcessor,
+                u32,
+                (u32, usize),
+                (u32, usize),
+            >(context, None, finder)
+            {
+                Some(r) => r.1,
+                None => return Err("Failed to count missing references"),
+            };
+
+            if num_missing_references == 0
+            {
+                info!("[ref: 81] No missing references - nothing to do");
+                return Ok(None);
+            }
+
+            if num_missing_references > reservation.remaining() as usize
+            {
+                error!(
+                    "[ref: 69] The block reserved for {} has {} unused reference ID(s), but {} are needed",
+                    owner,
+                    reservation.remaining(),
+                    num_missing_references
+                );
+                return Err("Not enough reserved reference IDs");
+            }
+
+            info!(
+                "[ref: 70] Using the block of reference IDs reserved for {}",
+                owner
+            );
+
+            reservation.next
+        },
+        (None, Some(c)) =>
+        {
+            info!("[ref: 17] Using cached next reference ID");
+
+            skip_issued_reference_ids(c.next_reference_id, registry)
+        },
+        (None, None) =>
+        {
+            info!("[ref: 18] Performing first pass to determine next reference ID");
+
+            /*
+             * Even if only a selection of files is being updated, the next reference ID
+             * must be determined from the whole code base to avoid reusing IDs.
+             */
+            let unrestricted_finder;
+            let id_finder = if finder.is_restricted()
+            {
+                unrestricted_finder = match CodeFinder::new_unrestricted(context)
+                {
+                    Some(f) => f,
+                    None => return Err("Code discovery error"),
+                };
+
+                &unrestricted_finder
+            }
+            else
+            {
+                finder
+            };
+
+            let references_id_result = match process_references::<
+                NextReferenceIdProcessor,
+                u32,
+                (u32, usize),
+                (u32, usize),
+            >(context, None, id_finder)
+            {
+                Some(r) => r,
+                None => return Err("Failed to determine next reference ID"),
+            };
+
+            if references_id_result.1 == 0
+            {
+                info!("[ref: 19] No missing references - nothing to do");
+                return Ok(None);
+            }
+
+            skip_issued_reference_ids(references_id_result.0, registry)
+        },
+    };
+
+    Ok(Some(next_reference_id))
+}
+
+/// Choose hashed reference IDs for the log statements with missing references. IDs are chosen in
+/// canonical file order, then by position within each file, so that collisions are resolved the
+/// same way every time.
+///
+/// # Arguments
+///
+/// * `context` - Application context.
+/// * `finder` - The code finder for the files being updated.
+/// * `registry` - The registry, if it's enabled.
+///
+/// # Returns
+///
+/// The chosen IDs, `None` if there are no missing references, otherwise an error message on
+/// failure.
+fn assign_hashed_reference_ids(
+    context: &Context,
+    finder: &CodeFinder,
+    registry: &Option<Registry>,
+) -> Result<Option<ReferenceIds>, &'static str>
+{
+    let files = match process_references::<
+        MessagesProcessor,
+        Arc<SourceRoots>,
+        FileMessages,
+        Vec<FileMessages>,
+    >(
+        context,
+        Some(Arc::new(finder.source_roots().clone())),
+        finder,
+    )
+    {
+        Some(f) => f,
+        None => return Err("Failed to find log messages"),
+    };
+
+    if files.iter().all(|f| f.new_messages.is_empty())
+    {
+        info!("[ref: 84] No missing references - nothing to do");
+        return Ok(None);
+    }
+
+    /*
+     * New IDs must not collide with any reference in the code base, even if only a selection
+     * of files is being updated.
+     */
+    let mut used_ids: HashSet<u32> = if finder.is_restricted()
+    {
+        let unrestricted_finder = match CodeFinder::new_unrestricted(context)
+        {
+            Some(f) => f,
+            None => return Err("Code discovery error"),
+        };
+
+        match process_references::<
+            MessagesProcessor,
+            Arc<SourceRoots>,
+            FileMessages,
+            Vec<FileMessages>,
+        >(
+            context,
+            Some(Arc::new(unrestricted_finder.source_roots().clone())),
+            &unrestricted_finder,
         )
-        .unwrap();
+        {
+            Some(all_files) => all_files
+                .iter()
+                .flat_map(|f| f.existing_references.iter().copied())
+                .collect(),
+            None => return Err("Failed to find log messages"),
+        }
+    }
+    else
+    {
+        files
+            .iter()
+            .flat_map(|f| f.existing_references.iter().copied())
+            .collect()
+    };
+
+    /*
+     * Nor must they reuse an ID that has been removed from the code base.
+     */
+    if let Some(r) = registry
+    {
+        used_ids.extend(r.issued_ids());
+    }
+
+    let mut allocator = HashedIdAllocator::new(used_ids, &context.config.id_salt);
+    let mut assigned_ids: HashMap<String, Vec<u32>> = HashMap::new();
+
+    for file in files.iter()
+    {
+        let mut file_ids: Vec<u32> = Vec::new();
+
+        for message in file.new_messages.iter()
+        {
+            match allocator.allocate(&file.relative_path, message)
+            {
+                Some(id) => file_ids.push(id),
+                None =>
+                {
+                    error!("[ref: 85] No unused reference IDs are left");
+                    return Err("No unused reference IDs");
+                },
+            }
+        }
+
+        assigned_ids.insert(file.path.clone(), file_ids);
+    }
+
+    info!("[ref: 87] Using hashed reference IDs");
+
+    Ok(Some(ReferenceIds::Assigned(Arc::new(assigned_ids))))
+}
+
+/// Insert log references in the given code base.
+///
+/// # Arguments
+///
+/// * `context` - Application context.
+///
+/// # Returns
+///
+/// Number of inserted references, otherwise an error message on failure.
+pub fn generate_code(context: &Context) -> Result<u32, &'static str>
+{
+    /*
+     * Hold the run lock until all changes are committed, so that concurrent runs can't
+     * allocate the same reference IDs.
+     */
+    let _run_lock = begin_update(context)?;
+
+    if let Some(finder) = CodeFinder::new(context)
+    {
+        if finder.code_files.is_empty()
+        {
+            if finder.is_restricted()
+            {
+                info!("[ref: 40] No selected files to update - nothing to do");
+                return Ok(0);
+            }
+
+            return Err("No files found");
+        }
+
+        info!("[ref: 16] Found {} file(s)", finder.code_files.len());
+
+        /*
+         * Another run may have updated the lock file while this one waited for the run lock.
+         */
+        let cache = context.reload_cache();
+
+        let registry = match context.read_registry()
+        {
+            Ok(r) => r,
+            Err(e) =>
+            {
+                error!("[ref: 93] Failed to read registry: {}", e);
+                return Err("Failed to read registry");
+            },
+        };
+
+        /*
+         * Even if there's nothing to insert, malformed references may need fixing and
+         * references may have been removed since the registry was last updated.
+         */
+        let reference_ids = match context.config.id_strategy
+        {
+            IdStrategy::Sequential =>
+            {
+                match next_sequential_reference_id(context, &finder, &cache, &registry)?
+                {
+                    Some(id) =>
+                    {
+                        info!("[ref: 20] Next reference ID: {}", id);
+
+                        ReferenceIds::Sequential(id)
+                    },
+                    None => ReferenceIds::Unneeded,
+                }
+            },
+            IdStrategy::Hash =>
+            {
+                if context.id_owner.is_some()
+                {
+                    error!("[ref: 86] Reserved blocks of reference IDs can't be used with the hash ID strategy");
+                    return Err("Reserved blocks of reference IDs unsupported");
+                }
+
+                match assign_hashed_reference_ids(context, &finder, &registry)?
+                {
+                    Some(ids) => ids,
+                    None => ReferenceIds::Unneeded,
+                }
+            },
+        };
+
+        let reference_updates = match process_references::<
+            InsertReferencesProcessor,
+            ReferenceIds,
+            InsertReferencesResult,
+            InsertReferencesResult,
+        >(context, Some(reference_ids.clone()), &finder)
+        {
+            Some(r) => r,
+            None => return Err("Failed to insert references"),
+        };
+
+        if reference_updates.failure
+        {
+            /*
+             * Dropping the staged files discards them, leaving every file untouched.
+             */
+            return Err("Failed to insert references");
+        }
+
+        /*
+         * Commit all rewritten files and the lock file together, so that either all of them
+         * are updated or none of them are.
+         */
+        let mut transaction = Transaction::new(&context.journal_path());
+
+        for staged_file in reference_updates.staged_files.iter()
+        {
+            transaction.add(staged_file.clone());
+        }
+
+        /*
+         * Hashed IDs don't need coordinating, so the lock file is only updated when IDs are
+         * allocated sequentially.
+         */
+        if let ReferenceIds::Sequential(first_reference_id) = reference_ids
+        {
+            let cachable_reference_id = match first_reference_id
+                .checked_add(reference_updates.num_inserted_references as u32)
+            {
+                Some(id) => id,
+                None =>
+                {
+                    error!("[ref: 141] Ran out of reference IDs");
+                    return Err("Reference IDs exhausted");
+                },
+            };
+
+            let mut updated_cache = cache.unwrap_or(Cache::new(cachable_reference_id));
+
+            match &context.id_owner
+            {
+                Some(owner) =>
+                {
+                    if let Some(reservation) = updated_cache.reservation_mut(owner)
+                    {
+                        reservation.next = cachable_reference_id;
+                    }
+                },
+                None => updated_cache.next_reference_id = cachable_reference_id,
+            }
+
+            if let Err(e) = stage_cache(context, &updated_cache, &mut transaction)
+            {
+                error!("[ref: 48] Failed to stage lock file: {}", e);
+                return Err("Failed to insert references");
+            }
+        }
+
+        if let Err(e) = stage_registry(context, &registry, &mut transaction)
+        {
+            error!("[ref: 92] Failed to stage registry: {}", e);
+            return Err("Failed to insert references");
+        }
+
+        if transaction.is_empty()
+        {
+            return Ok(0);
+        }
+
+        if let Err(e) = transaction.commit()
+        {
+            error!(
+                "[ref: 49] Failed to commit changes, no files were modified: {}",
+                e
+            );
+            return Err("Failed to insert references");
+        }
+
+        info!(
+            "[ref: 21] Num. inserted reference(s): {}",
+            reference_updates.num_inserted_references
+        );
+
+        if reference_updates.num_fixed_references > 0
+        {
+            info!(
+                "[ref: 111] Num. malformed reference(s) fixed: {}",
+                reference_updates.num_fixed_references
+            );
+        }
+    }
+    else
+    {
+        return Err("Code discovery error");
+    }
+
+    Ok(0)
+}
+
+#[cfg(test)]
+mod tests
+{
+    use async_trait::async_trait;
+    use futures::AsyncWriteExt;
+    use regex::Regex;
+    use std::fs::File;
+    use std::io::Read;
+    use std::io::Write;
+    use std::str::FromStr;
+    use tempdir::TempDir;
+    extern crate testing_logger;
+    use tracing_test::traced_test;
+
+    use super::check_references;
+    use super::generate_code;
+    use super::process_references;
+    use super::InsertReferencesProcessor;
+    use super::InsertReferencesResult;
+    use super::NextReferenceIdProcessor;
+    use super::ReferenceIds;
+    use super::ReferenceProcessor;
+    use super::RunLock;
+    use super::Transaction;
+    use super::MAX_REFERENCE_ID;
+    use crate::codegen::report::{Finding, FindingKind};
+    use crate::codegen::CodeFinder;
+    use crate::config::context::{Cache, Reservation, RustDirectoryConfig};
+    use crate::config::registry::{ReferenceRecord, Registry};
+    use crate::config::Context;
+    use crate::parser;
+    use crate::parser::LogRefKind;
+    use std::collections::HashMap;
+    use std::collections::HashSet;
+    use std::sync::Arc;
+
+    struct TestRefProcCount {}
+
+    /// Commit the files staged by the insert references processor.
+    fn commit_staged(insert_result: &InsertReferencesResult, journal_path: &str)
+    {
+        let mut transaction = Transaction::new(journal_path);
+
+        for staged_file in insert_result.staged_files.iter()
+        {
+            transaction.add(staged_file.clone());
+        }
+
+        transaction.commit().unwrap();
+    }
+
+    #[async_trait]
+    impl ReferenceProcessor<u32, u32, u32> for TestRefProcCount
+    {
+        async fn map(
+            path: &str,
+            _file_contents: &str,
+            _params: &Option<u32>,
+            entries: &[parser::LogRefEntry],
+        ) -> Option<u32>
+        {
+            if path.ends_with("_none.rs")
+            {
+                return None;
+            }
+
+            Some(entries.len() as u32)
+        }
+
+        fn reduce(map_results: &[u32]) -> Option<u32>
+        {
+            let mut reduce_result: u32 = 0;
+
+            for map_result in map_results.iter()
+            {
+                reduce_result += *map_result;
+            }
+
+            if reduce_result == 0
+            {
+                return None;
+            }
+
+            Some(reduce_result)
+        }
+    }
+
+    fn create_test_context(source_dir: &String, check_mode: bool, structured_mode: bool)
+        -> Context
+    {
+        let context = Context::new(
+            format!(
+                r#"
+source_dir: {}
+rust:
+  structured: {}
+  log_macros:
+    - module: test_module
+      name: test_macro
+"#,
+                source_dir, structured_mode
+            )
+            .to_string(),
+            &source_dir,
+            check_mode,
+        )
+        .unwrap();
+
+        context
+            .stop_commanded
+            .store(false, std::sync::atomic::Ordering::Relaxed);
+
+        context
+    }
+
+    #[test_log::test(async_std::test)]
+    async fn test_next_ref_id_map_no_entries()
+    {
+        const TEST_PATH: &str = "test.rs";
+        let test_contents = String::new();
+        let test_entries: Vec<parser::LogRefEntry> = Vec::new();
+
+        let map_result = NextReferenceIdProcessor::map(
+            &String::from(TEST_PATH),
+            &test_contents,
+            &None,
+            &test_entries,
+        )
+        .await;
+
+        assert_eq!(map_result, Some((0, 0)));
+    }
+
+    #[test_log::test(async_std::test)]
+    async fn test_next_ref_id_map_none_entries()
+    {
+        const TEST_PATH: &str = "test.rs";
+        let test_contents = String::new();
+        let mut test_entries: Vec<parser::LogRefEntry> = Vec::new();
+
+        {
+            let code_pos = parser::CodePosition::new(1, 1, 1);
+            let entry = parser::LogRefEntry::new(
+                code_pos,
+                None,
+                String::from_str("test").unwrap(),
+                LogRefKind::String,
+                None,
+                None,
+            );
+            test_entries.push(entry);
+        }
+
+        {
+            let code_pos = parser::CodePosition::new(1, 2, 1);
+            let entry = parser::LogRefEntry::new(
+                code_pos,
+                None,
+                String::from_str("test").unwrap(),
+                LogRefKind::String,
+                None,
+                None,
+            );
+            test_entries.push(entry);
+        }
+
+        let map_result = NextReferenceIdProcessor::map(
+            &String::from(TEST_PATH),
+            &test_contents,
+            &None,
+            &test_entries,
+        )
+        .await;
+
+        assert_eq!(map_result, Some((0, 2)));
+    }
+
+    #[test_log::test(async_std::test)]
+    async fn test_next_ref_id_map_max_calc()
+    {
+        const TEST_PATH: &str = "test.rs";
+        let test_contents = String::new();
+        let mut test_entries: Vec<parser::LogRefEntry> = Vec::new();
+
+        {
+            let code_pos = parser::CodePosition::new(1, 1, 1);
+            let entry = parser::LogRefEntry::new(
+                code_pos,
+                Some(1),
+                String::from_str("test").unwrap(),
+                LogRefKind::String,
+                None,
+                None,
+            );
+            test_entries.push(entry);
+        }
+
+        {
+            let code_pos = parser::CodePosition::new(1, 2, 1);
+            let entry = parser::LogRefEntry::new(
+                code_pos,
+                Some(3),
+                String::from_str("test").unwrap(),
+                LogRefKind::String,
+                None,
+                None,
+            );
+            test_entries.push(entry);
+        }
+
+        {
+            let code_pos = parser::CodePosition::new(1, 2, 1);
+            let entry = parser::LogRefEntry::new(
+                code_pos,
+                Some(8),
+                String::from_str("test").unwrap(),
+                LogRefKind::String,
+                None,
+                None,
+            );
+            test_entries.push(entry);
+        }
+
+        let map_result = NextReferenceIdProcessor::map(
+            &String::from(TEST_PATH),
+            &test_contents,
+            &None,
+            &test_entries,
+        )
+        .await;
+
+        assert_eq!(map_result, Some((8, 0)));
+    }
+
+    #[test_log::test(async_std::test)]
+    async fn test_next_ref_id_only_count_usable()
+    {
+        const TEST_PATH: &str = "test.rs";
+        let test_contents = String::new();
+        let mut test_entries: Vec<parser::LogRefEntry> = Vec::new();
+
+        {
+            let code_pos = parser::CodePosition::new(1, 1, 1);
+            let entry = parser::LogRefEntry::new(
+                code_pos,
+                None,
+                String::from_str("test").unwrap(),
+                LogRefKind::StructuredPreExisting,
+                None,
+                None,
+            );
+            test_entries.push(entry);
+        }
+
+        {
+            let code_pos = parser::CodePosition::new(1, 2, 1);
+            let entry = parser::LogRefEntry::new(
+                code_pos,
+                None,
+                String::from_str("test").unwrap(),
+                LogRefKind::String,
+                None,
+                None,
+            );
+            test_entries.push(entry);
+        }
+
+        let map_result = NextReferenceIdProcessor::map(
+            &String::from(TEST_PATH),
+            &test_contents,
+            &None,
+            &test_entries,
+        )
+        .await;
+
+        assert_eq!(map_result, Some((0, 1)));
+    }
+
+    #[test_log::test(async_std::test)]
+    async fn test_missing_refs_map_count()
+    {
+        const TEST_PATH: &str = "test.rs";
+        let test_contents = String::new();
+        let mut test_entries: Vec<parser::LogRefEntry> = Vec::new();
+
+        {
+            let code_pos = parser::CodePosition::new(1, 1, 1);
+            let entry = parser::LogRefEntry::new(
+                code_pos,
+                None,
+                String::from_str("test").unwrap(),
+                LogRefKind::String,
+                None,
+                None,
+            );
+            test_entries.push(entry);
+        }
+
+        {
+            let code_pos = parser::CodePosition::new(1, 2, 1);
+            let entry = parser::LogRefEntry::new(
+                code_pos,
+                Some(3),
+                String::from_str("test").unwrap(),
+                LogRefKind::String,
+                None,
+                None,
+            );
+            test_entries.push(entry);
+        }
+
+        {
+            let code_pos = parser::CodePosition::new(1, 2, 1);
+            let entry = parser::LogRefEntry::new(
+                code_pos,
+                None,
+                String::from_str("test").unwrap(),
+                LogRefKind::String,
+                None,
+                None,
+            );
+            test_entries.push(entry);
+        }
+
+        let map_result = NextReferenceIdProcessor::map(
+            &String::from(TEST_PATH),
+            &test_contents,
+            &None,
+            &test_entries,
+        )
+        .await;
+
+        assert_eq!(map_result, Some((3, 2)));
+    }
+
+    #[test]
+    fn test_next_ref_id_reduce_no_results()
+    {
+        let test_input: Vec<(u32, usize)> = Vec::new();
+        assert_eq!(NextReferenceIdProcessor::reduce(&test_input), Some((1, 0)));
+    }
+
+    #[test]
+    fn test_next_ref_id_reduce_default()
+    {
+        let mut test_input: Vec<(u32, usize)> = Vec::new();
+
+        test_input.push((0, 0));
+        test_input.push((0, 0));
+
+        assert_eq!(NextReferenceIdProcessor::reduce(&test_input), Some((1, 0)));
+    }
+
+    #[test]
+    fn test_next_ref_id_reduce_max()
+    {
+        let mut test_input: Vec<(u32, usize)> = Vec::new();
+
+        test_input.push((4, 0));
+        test_input.push((2, 0));
+        test_input.push((1, 0));
+
+        assert_eq!(NextReferenceIdProcessor::reduce(&test_input), Some((5, 0)));
+    }
+
+    #[test]
+    fn test_next_ref_id_missing_ref_reduce_count()
+    {
+        let mut test_input: Vec<(u32, usize)> = Vec::new();
+
+        test_input.push((4, 1));
+        test_input.push((2, 2));
+        test_input.push((1, 4));
+
+        assert_eq!(NextReferenceIdProcessor::reduce(&test_input), Some((5, 7)));
+    }
+
+    #[test]
+    fn test_next_ref_id_reduce_exhausted()
+    {
+        let test_input: Vec<(u32, usize)> = vec![(MAX_REFERENCE_ID, 0)];
+
+        assert_eq!(
+            NextReferenceIdProcessor::reduce(&test_input),
+            Some((u32::MAX, 0))
+        );
+
+        let test_input: Vec<(u32, usize)> = vec![(4, 1), (u32::MAX, 0)];
+
+        assert_eq!(NextReferenceIdProcessor::reduce(&test_input), None);
+    }
+
+    #[test]
+    #[traced_test]
+    fn test_log_findings()
+    {
+        let finding = |kind: FindingKind, line: usize| Finding {
+            kind,
+            file: String::from("test.rs"),
+            crate_name: None,
+            line,
+            column: 5,
+            reference: Some(line as u32),
+            message: String::from("Log test."),
+        };
+
+        super::log_findings(&[
+            finding(FindingKind::Missing, 1),
+            finding(FindingKind::Unusable, 2),
+            finding(FindingKind::Retired, 3),
+            finding(FindingKind::Malformed, 4),
+            finding(FindingKind::Forbidden, 5),
+            finding(FindingKind::Duplicate, 6),
+        ]);
+
+        assert!(logs_contain("missing_reference_1_5"));
+        assert!(logs_contain("unusable_reference_2_5"));
+        assert!(logs_contain("retired_reference_3_5"));
+        assert!(logs_contain("malformed_reference_4_5"));
+        assert!(logs_contain("forbidden_reference_5_5"));
+
+        /*
+         * Duplicates don't fail check mode, so they're only reported.
+         */
+        assert!(!logs_contain("duplicate_reference_6_5"));
+    }
+
+    #[test]
+    fn test_process_no_results()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let test_context =
+            create_test_context(&temp_dir.path().to_str().unwrap().to_string(), false, false);
+
+        {
+            let source_file_path = temp_dir
+                .path()
+                .join("test_file1_none.rs")
+                .to_str()
+                .unwrap()
+                .to_string();
+
+            let mut source_file = File::create(&source_file_path).unwrap();
+            source_file.write_all(b"fn test1() {}").unwrap();
+        }
+
+        {
+            let source_file_path = temp_dir
+                .path()
+                .join("test_file2_none.rs")
+                .to_str()
+                .unwrap()
+                .to_string();
+
+            let mut source_file = File::create(&source_file_path).unwrap();
+            source_file.write_all(b"fn test2() {}").unwrap();
+        }
+
+        let test_finder = CodeFinder::new(&test_context).unwrap();
+
+        assert_eq!(
+            process_references::<TestRefProcCount, u32, u32, u32>(
+                &test_context,
+                None,
+                &test_finder
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_process_results()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let test_context =
+            create_test_context(&temp_dir.path().to_str().unwrap().to_string(), false, false);
+
+        {
+            let source_file_path = temp_dir
+                .path()
+                .join("test_file1.rs")
+                .to_str()
+                .unwrap()
+                .to_string();
+
+            let mut source_file = File::create(&source_file_path).unwrap();
+            source_file
+                .write_all(
+                    br#"
+fn test1() {
+    test_macro!("Log test.");
+}
+            "#,
+                )
+                .unwrap();
+        }
+
+        {
+            let source_file_path = temp_dir
+                .path()
+                .join("test_file2.rs")
+                .to_str()
+                .unwrap()
+                .to_string();
+
+            let mut source_file = File::create(&source_file_path).unwrap();
+            source_file
+                .write_all(
+                    br#"
+fn test2() {
+    test_macro!("Log test.");
+}
+            "#,
+                )
+                .unwrap();
+        }
+
+        let test_finder = CodeFinder::new(&test_context).unwrap();
+
+        assert_eq!(
+            process_references::<TestRefProcCount, u32, u32, u32>(
+                &test_context,
+                None,
+                &test_finder
+            ),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn test_process_partial_results()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let test_context =
+            create_test_context(&temp_dir.path().to_str().unwrap().to_string(), false, false);
+
+        {
+            let source_file_path = temp_dir
+                .path()
+                .join("test_file1_none.rs")
+                .to_str()
+                .unwrap()
+                .to_string();
+
+            let mut source_file = File::create(&source_file_path).unwrap();
+            source_file
+                .write_all(
+                    br#"
+fn test1() {}
+            "#,
+                )
+                .unwrap();
+        }
+
+        {
+            let source_file_path = temp_dir
+                .path()
+                .join("test_file2.rs")
+                .to_str()
+                .unwrap()
+                .to_string();
+
+            let mut source_file = File::create(&source_file_path).unwrap();
+            source_file
+                .write_all(
+                    br#"
+fn test2() {
+    test_macro!("Log test.");
+}
+            "#,
+                )
+                .unwrap();
+        }
+
+        let test_finder = CodeFinder::new(&test_context).unwrap();
+
+        assert_eq!(
+            process_references::<TestRefProcCount, u32, u32, u32>(
+                &test_context,
+                None,
+                &test_finder
+            ),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn test_process_stop_command()
+    {
+        use std::sync::atomic::Ordering;
+
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let test_context =
+            create_test_context(&temp_dir.path().to_str().unwrap().to_string(), false, false);
+
+        {
+            let source_file_path = temp_dir
+                .path()
+                .join("test_file1.rs")
+                .to_str()
+                .unwrap()
+                .to_string();
+
+            let mut source_file = File::create(&source_file_path).unwrap();
+            source_file
+                .write_all(
+                    br#"
+fn test1() {
+    test_macro!("Log test.");
+}
+            "#,
+                )
+                .unwrap();
+        }
+
+        {
+            let source_file_path = temp_dir
+                .path()
+                .join("test_file2.rs")
+                .to_str()
+                .unwrap()
+                .to_string();
+
+            let mut source_file = File::create(&source_file_path).unwrap();
+            source_file
+                .write_all(
+                    br#"
+fn test2() {
+    test_macro!("Log test.");
+}
+            "#,
+                )
+                .unwrap();
+        }
+
+        let test_finder = CodeFinder::new(&test_context).unwrap();
+
+        test_context
+            .stop_commanded
+            .fetch_or(true, Ordering::Relaxed);
+
+        assert_eq!(
+            process_references::<TestRefProcCount, u32, u32, u32>(
+                &test_context,
+                None,
+                &test_finder
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_process_next_reference_id()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let test_context =
+            create_test_context(&temp_dir.path().to_str().unwrap().to_string(), false, false);
+
+        {
+            let source_file_path = temp_dir
+                .path()
+                .join("test_file1.rs")
+                .to_str()
+                .unwrap()
+                .to_string();
+
+            let mut source_file = File::create(&source_file_path).unwrap();
+            source_file
+                .write_all(
+                    br#"
+fn test1() {
+    test_macro!("[ref: 9] Log test.");
+}
+            "#,
+                )
+                .unwrap();
+        }
 
-        context
-            .stop_commanded
-            .store(false, std::sync::atomic::Ordering::Relaxed);
+        {
+            let source_file_path = temp_dir
+                .path()
+                .join("test_file2.rs")
+                .to_str()
+                .unwrap()
+                .to_string();
 
-        context
+            let mut source_file = File::create(&source_file_path).unwrap();
+            source_file
+                .write_all(
+                    br#"
+fn test2() {
+    test_macro!("[ref: 10] Log test.");
+}
+            "#,
+                )
+                .unwrap();
+        }
+
+        let test_finder = CodeFinder::new(&test_context).unwrap();
+
+        assert_eq!(
+            process_references::<NextReferenceIdProcessor, u32, (u32, usize), (u32, usize)>(
+                &test_context,
+                None,
+                &test_finder
+            ),
+            Some((11, 0))
+        );
     }
 
-    #[test_log::test(async_std::test)]
-    async fn test_next_ref_id_map_no_entries()
+    #[test]
+    fn test_process_insert_references_no_files()
     {
-        const TEST_PATH: &str = "test.rs";
-        let test_contents = String::new();
-        let test_entries: Vec<parser::LogRefEntry> = Vec::new();
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let test_context =
+            create_test_context(&temp_dir.path().to_str().unwrap().to_string(), false, false);
 
-        let map_result = NextReferenceIdProcessor::map(
-            &String::from(TEST_PATH),
-            &test_contents,
-            &None,
-            &test_entries,
-        )
-        .await;
+        let test_finder = CodeFinder::new(&test_context).unwrap();
 
-        assert_eq!(map_result, Some((0, 0)));
+        let insert_result = process_references::<
+            InsertReferencesProcessor,
+            ReferenceIds,
+            InsertReferencesResult,
+            InsertReferencesResult,
+        >(&test_context, None, &test_finder)
+        .unwrap();
+
+        /*
+         * Because there are no files, the map() function should not be called.
+         * As a result, the processor will not report failure despite the lack of the required parameter.
+         */
+
+        assert!(!insert_result.failure);
+        assert_eq!(insert_result.num_inserted_references, 0);
     }
 
     #[test_log::test(async_std::test)]
-    async fn test_next_ref_id_map_none_entries()
+    #[traced_test]
+    async fn test_process_insert_references_missing_next_id()
     {
-        const TEST_PATH: &str = "test.rs";
-        let test_contents = String::new();
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let source_file_path = temp_dir
+            .path()
+            .join("test_file2.rs")
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        File::create(&source_file_path).unwrap();
+        let file_contents = String::new();
         let mut test_entries: Vec<parser::LogRefEntry> = Vec::new();
 
         {
-            let code_pos = parser::CodePosition::new(1, 1, 1);
+            let code_pos = parser::CodePosition::new(30, 2, 18);
             let entry = parser::LogRefEntry::new(
                 code_pos,
                 None,
-                String::from_str("test").unwrap(),
+                String::from_str("test_macro").unwrap(),
                 LogRefKind::String,
                 None,
                 None,
@@ -875,43 +2788,51 @@ rust:
             test_entries.push(entry);
         }
 
-        {
-            let code_pos = parser::CodePosition::new(1, 2, 1);
-            let entry = parser::LogRefEntry::new(
-                code_pos,
-                None,
-                String::from_str("test").unwrap(),
-                LogRefKind::String,
-                None,
-                None,
-            );
-            test_entries.push(entry);
-        }
+        let insert_result =
+            InsertReferencesProcessor::map(&source_file_path, &file_contents, &None, &test_entries)
+                .await
+                .unwrap();
 
-        let map_result = NextReferenceIdProcessor::map(
-            &String::from(TEST_PATH),
-            &test_contents,
-            &None,
-            &test_entries,
-        )
-        .await;
+        assert!(insert_result.failure);
+        assert_eq!(insert_result.num_inserted_references, 0);
 
-        assert_eq!(map_result, Some((0, 2)));
+        assert!(logs_contain("unexpected_next_reference_id"));
     }
 
     #[test_log::test(async_std::test)]
-    async fn test_next_ref_id_map_max_calc()
+    #[traced_test]
+    async fn test_process_insert_references_out_of_order_insert_pos()
     {
-        const TEST_PATH: &str = "test.rs";
-        let test_contents = String::new();
+        let source_file_contents = String::from_str(
+            r#"
+fn test1() {
+    test_macro!("Log test.");
+}
+"#,
+        )
+        .unwrap();
+
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let source_file_path = temp_dir
+            .path()
+            .join("test_file.rs")
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        let mut source_file = File::create(&source_file_path).unwrap();
+        source_file
+            .write_all(source_file_contents.as_bytes())
+            .unwrap();
+
         let mut test_entries: Vec<parser::LogRefEntry> = Vec::new();
 
         {
-            let code_pos = parser::CodePosition::new(1, 1, 1);
+            let code_pos = parser::CodePosition::new(30, 2, 18);
             let entry = parser::LogRefEntry::new(
                 code_pos,
-                Some(1),
-                String::from_str("test").unwrap(),
+                None,
+                String::from_str("test_macro").unwrap(),
                 LogRefKind::String,
                 None,
                 None,
@@ -920,11 +2841,11 @@ rust:
         }
 
         {
-            let code_pos = parser::CodePosition::new(1, 2, 1);
+            let code_pos = parser::CodePosition::new(29, 2, 17);
             let entry = parser::LogRefEntry::new(
                 code_pos,
-                Some(3),
-                String::from_str("test").unwrap(),
+                None,
+                String::from_str("test_macro").unwrap(),
                 LogRefKind::String,
                 None,
                 None,
@@ -932,12 +2853,138 @@ rust:
             test_entries.push(entry);
         }
 
+        let insert_result = InsertReferencesProcessor::map(
+            &source_file_path,
+            &source_file_contents,
+            &Some(ReferenceIds::Sequential(10)),
+            &test_entries,
+        )
+        .await
+        .unwrap();
+
+        commit_staged(
+            &insert_result,
+            temp_dir.path().join("Breadlog.journal").to_str().unwrap(),
+        );
+
+        assert!(insert_result.failure);
+        assert_eq!(insert_result.num_inserted_references, 0);
+
+        logs_assert(|lines: &[&str]| {
+            match lines
+                .iter()
+                .filter(|line| line.contains("unexpected_reference_insert_pos"))
+                .count()
+            {
+                1 => Ok(()),
+                n => Err(format!("More than 1 matching event: {}", n)),
+            }
+        });
+    }
+
+    #[test_log::test(async_std::test)]
+    #[traced_test]
+    async fn test_process_insert_references_ids_exhausted()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let source_file_path = temp_dir
+            .path()
+            .join("test_file.rs")
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        let source_file_contents = "fn test1() {\n    test_macro!(\"Log test.\");\n}\n";
+        std::fs::write(&source_file_path, source_file_contents).unwrap();
+
+        let test_entries = vec![parser::LogRefEntry::new(
+            parser::CodePosition::new(17, 2, 17),
+            None,
+            String::from_str("test_macro").unwrap(),
+            LogRefKind::String,
+            None,
+            None,
+        )];
+
+        let insert_result = InsertReferencesProcessor::map(
+            &source_file_path,
+            source_file_contents,
+            &Some(ReferenceIds::Sequential(u32::MAX)),
+            &test_entries,
+        )
+        .await
+        .unwrap();
+
+        assert!(insert_result.failure);
+        assert_eq!(insert_result.num_inserted_references, 0);
+        assert!(insert_result.staged_files.is_empty());
+        assert_eq!(
+            std::fs::read_to_string(&source_file_path).unwrap(),
+            source_file_contents
+        );
+        assert!(logs_contain("reference_ids_exhausted"));
+    }
+
+    #[test_log::test(async_std::test)]
+    async fn test_process_insert_references_empty_file()
+    {
+        let source_file_contents = String::new();
+
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let source_file_path = temp_dir
+            .path()
+            .join("test_file.rs")
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        let _ = File::create(&source_file_path).unwrap();
+
+        let test_entries: Vec<parser::LogRefEntry> = Vec::new();
+
+        let insert_result = InsertReferencesProcessor::map(
+            &source_file_path,
+            &source_file_contents,
+            &Some(ReferenceIds::Sequential(1)),
+            &test_entries,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(insert_result.failure, false);
+        assert_eq!(insert_result.num_inserted_references, 0);
+    }
+
+    #[test_log::test(async_std::test)]
+    #[traced_test]
+    async fn test_process_insert_references_parent_dir_missing()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let source_file_path = temp_dir
+            .path()
+            .join("does_not_exist")
+            .join("test_file.rs")
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        let source_file_contents = String::from_str(
+            r#"
+fn test1() {
+    test_macro!("Log test.");
+}
+        "#,
+        )
+        .unwrap();
+
+        let mut test_entries: Vec<parser::LogRefEntry> = Vec::new();
+
         {
-            let code_pos = parser::CodePosition::new(1, 2, 1);
+            let code_pos = parser::CodePosition::new(30, 2, 18);
             let entry = parser::LogRefEntry::new(
                 code_pos,
-                Some(8),
-                String::from_str("test").unwrap(),
+                None,
+                String::from_str("test_macro").unwrap(),
                 LogRefKind::String,
                 None,
                 None,
@@ -945,505 +2992,776 @@ rust:
             test_entries.push(entry);
         }
 
-        let map_result = NextReferenceIdProcessor::map(
-            &String::from(TEST_PATH),
-            &test_contents,
-            &None,
+        let insert_result = InsertReferencesProcessor::map(
+            &source_file_path,
+            &source_file_contents,
+            &Some(ReferenceIds::Sequential(10)),
             &test_entries,
         )
-        .await;
+        .await
+        .unwrap();
 
-        assert_eq!(map_result, Some((8, 0)));
+        commit_staged(
+            &insert_result,
+            temp_dir.path().join("Breadlog.journal").to_str().unwrap(),
+        );
+
+        assert!(insert_result.failure);
+        assert_eq!(insert_result.num_inserted_references, 0);
+
+        logs_assert(|lines: &[&str]| {
+            match lines
+                .iter()
+                .filter(|line| line.contains("failed_to_resolve_path"))
+                .count()
+            {
+                1 => Ok(()),
+                n => Err(format!("More than 1 matching event: {}", n)),
+            }
+        });
     }
 
-    #[test_log::test(async_std::test)]
-    async fn test_next_ref_id_only_count_usable()
+    #[test]
+    fn test_process_insert_references_reduce_success()
     {
-        const TEST_PATH: &str = "test.rs";
-        let test_contents = String::new();
-        let mut test_entries: Vec<parser::LogRefEntry> = Vec::new();
+        let mut test_input: Vec<InsertReferencesResult> = Vec::new();
 
         {
-            let code_pos = parser::CodePosition::new(1, 1, 1);
-            let entry = parser::LogRefEntry::new(
-                code_pos,
-                None,
-                String::from_str("test").unwrap(),
-                LogRefKind::StructuredPreExisting,
-                None,
-                None,
-            );
-            test_entries.push(entry);
+            let result = InsertReferencesResult {
+                failure: false,
+                num_inserted_references: 2,
+                num_fixed_references: 0,
+                staged_files: Vec::new(),
+            };
+            test_input.push(result);
         }
 
         {
-            let code_pos = parser::CodePosition::new(1, 2, 1);
-            let entry = parser::LogRefEntry::new(
-                code_pos,
-                None,
-                String::from_str("test").unwrap(),
-                LogRefKind::String,
-                None,
-                None,
-            );
-            test_entries.push(entry);
+            let result = InsertReferencesResult {
+                failure: false,
+                num_inserted_references: 3,
+                num_fixed_references: 1,
+                staged_files: Vec::new(),
+            };
+            test_input.push(result);
         }
 
-        let map_result = NextReferenceIdProcessor::map(
-            &String::from(TEST_PATH),
-            &test_contents,
-            &None,
-            &test_entries,
-        )
-        .await;
+        let insert_result = InsertReferencesProcessor::reduce(&test_input).unwrap();
 
-        assert_eq!(map_result, Some((0, 1)));
+        assert_eq!(insert_result.failure, false);
+        assert_eq!(insert_result.num_inserted_references, 5);
+        assert_eq!(insert_result.num_fixed_references, 1);
     }
 
-    #[test_log::test(async_std::test)]
-    async fn test_missing_refs_map_count()
+    #[test]
+    fn test_process_insert_references_reduce_failure()
     {
-        const TEST_PATH: &str = "test.rs";
-        let test_contents = String::new();
-        let mut test_entries: Vec<parser::LogRefEntry> = Vec::new();
+        let mut test_input: Vec<InsertReferencesResult> = Vec::new();
 
         {
-            let code_pos = parser::CodePosition::new(1, 1, 1);
-            let entry = parser::LogRefEntry::new(
-                code_pos,
-                None,
-                String::from_str("test").unwrap(),
-                LogRefKind::String,
-                None,
-                None,
-            );
-            test_entries.push(entry);
+            let result = InsertReferencesResult {
+                failure: true,
+                num_inserted_references: 2,
+                num_fixed_references: 0,
+                staged_files: Vec::new(),
+            };
+            test_input.push(result);
         }
 
         {
-            let code_pos = parser::CodePosition::new(1, 2, 1);
-            let entry = parser::LogRefEntry::new(
-                code_pos,
-                Some(3),
-                String::from_str("test").unwrap(),
-                LogRefKind::String,
-                None,
-                None,
-            );
-            test_entries.push(entry);
+            let result = InsertReferencesResult {
+                failure: false,
+                num_inserted_references: 1,
+                num_fixed_references: 0,
+                staged_files: Vec::new(),
+            };
+            test_input.push(result);
+        }
+
+        let insert_result = InsertReferencesProcessor::reduce(&test_input).unwrap();
+
+        assert!(insert_result.failure);
+        assert_eq!(insert_result.num_inserted_references, 3);
+    }
+
+    #[test]
+    fn test_process_insert_references_files()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let test_context =
+            create_test_context(&temp_dir.path().to_str().unwrap().to_string(), false, false);
+
+        let source_file_path_1 = temp_dir
+            .path()
+            .join("test_file1.rs")
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        {
+            let mut source_file = File::create(&source_file_path_1).unwrap();
+            source_file
+                .write_all(
+                    br#"
+    fn test1_1() {
+        test_macro!("Log test 1_1.");
+    }
+
+    fn test1_2() {
+        test_macro!("Log test 1_2.");
+    }"#,
+                )
+                .unwrap();
+        }
+
+        let source_file_path_2 = temp_dir
+            .path()
+            .join("test_file2.rs")
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        {
+            let mut source_file = File::create(&source_file_path_2).unwrap();
+            source_file
+                .write_all(
+                    br#"
+    fn test2_1() {
+        test_macro!("Log test 2_1.");
+    }
+
+    fn test2_2() {
+        test_macro!("Log test 2_2.");
+    }"#,
+                )
+                .unwrap();
+        }
+
+        let test_finder = CodeFinder::new(&test_context).unwrap();
+
+        let insert_result = process_references::<
+            InsertReferencesProcessor,
+            ReferenceIds,
+            InsertReferencesResult,
+            InsertReferencesResult,
+        >(
+            &test_context,
+            Some(ReferenceIds::Sequential(2)),
+            &test_finder,
+        )
+        .unwrap();
+
+        commit_staged(&insert_result, &test_context.journal_path());
+
+        /* Load the source files after they have finished being processed. Note that we can't reuse the file objects from
+         * earlier because the descriptors will no longer be valid due to the rename operation performed by the processor.
+         */
+        let mut source_file_1 = File::open(&source_file_path_1).unwrap();
+        let mut post_file_contents_1 = String::new();
+        source_file_1
+            .read_to_string(&mut post_file_contents_1)
+            .unwrap();
+
+        let mut source_file_2 = File::open(&source_file_path_2).unwrap();
+        let mut post_file_contents_2 = String::new();
+        source_file_2
+            .read_to_string(&mut post_file_contents_2)
+            .unwrap();
+
+        assert_eq!(insert_result.failure, false);
+        assert_eq!(insert_result.num_inserted_references, 4);
+
+        /* Files are processed in path order, so the first file receives the first IDs.
+         */
+
+        assert_eq!(
+            post_file_contents_1,
+            r#"
+    fn test1_1() {
+        test_macro!("[ref: 2] Log test 1_1.");
+    }
+
+    fn test1_2() {
+        test_macro!("[ref: 3] Log test 1_2.");
+    }"#
+        );
+
+        assert_eq!(
+            post_file_contents_2,
+            r#"
+    fn test2_1() {
+        test_macro!("[ref: 4] Log test 2_1.");
+    }
+
+    fn test2_2() {
+        test_macro!("[ref: 5] Log test 2_2.");
+    }"#
+        );
+    }
+
+    #[test]
+    fn test_process_insert_references_empty_files()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let test_context =
+            create_test_context(&temp_dir.path().to_str().unwrap().to_string(), false, false);
+
+        let source_file_path_1 = temp_dir
+            .path()
+            .join("test_file1.rs")
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        {
+            let _ = File::create(&source_file_path_1).unwrap();
         }
 
+        let source_file_path_2 = temp_dir
+            .path()
+            .join("test_file2.rs")
+            .to_str()
+            .unwrap()
+            .to_string();
+
         {
-            let code_pos = parser::CodePosition::new(1, 2, 1);
-            let entry = parser::LogRefEntry::new(
-                code_pos,
-                None,
-                String::from_str("test").unwrap(),
-                LogRefKind::String,
-                None,
-                None,
-            );
-            test_entries.push(entry);
+            let _ = File::create(&source_file_path_2).unwrap();
         }
 
-        let map_result = NextReferenceIdProcessor::map(
-            &String::from(TEST_PATH),
-            &test_contents,
-            &None,
-            &test_entries,
-        )
-        .await;
+        let test_finder = CodeFinder::new(&test_context).unwrap();
 
-        assert_eq!(map_result, Some((3, 2)));
-    }
+        let insert_result = process_references::<
+            InsertReferencesProcessor,
+            ReferenceIds,
+            InsertReferencesResult,
+            InsertReferencesResult,
+        >(
+            &test_context,
+            Some(ReferenceIds::Sequential(2)),
+            &test_finder,
+        )
+        .unwrap();
 
-    #[test]
-    fn test_next_ref_id_reduce_no_results()
-    {
-        let test_input: Vec<(u32, usize)> = Vec::new();
-        assert_eq!(NextReferenceIdProcessor::reduce(&test_input), Some((1, 0)));
-    }
+        commit_staged(&insert_result, &test_context.journal_path());
 
-    #[test]
-    fn test_next_ref_id_reduce_default()
-    {
-        let mut test_input: Vec<(u32, usize)> = Vec::new();
+        /* Load the source files after they have finished being processed. Note that we can't reuse the file objects from
+         * earlier because the descriptors will no longer be valid due to the rename operation performed by the processor.
+         */
+        let mut source_file_1 = File::open(&source_file_path_1).unwrap();
+        let mut post_file_contents_1 = String::new();
+        source_file_1
+            .read_to_string(&mut post_file_contents_1)
+            .unwrap();
 
-        test_input.push((0, 0));
-        test_input.push((0, 0));
+        let mut source_file_2 = File::open(&source_file_path_2).unwrap();
+        let mut post_file_contents_2 = String::new();
+        source_file_2
+            .read_to_string(&mut post_file_contents_2)
+            .unwrap();
 
-        assert_eq!(NextReferenceIdProcessor::reduce(&test_input), Some((1, 0)));
+        assert_eq!(insert_result.failure, false);
+        assert_eq!(insert_result.num_inserted_references, 0);
     }
 
     #[test]
-    fn test_next_ref_id_reduce_max()
+    fn test_process_insert_references_structured_files()
     {
-        let mut test_input: Vec<(u32, usize)> = Vec::new();
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let test_context =
+            create_test_context(&temp_dir.path().to_str().unwrap().to_string(), false, true);
 
-        test_input.push((4, 0));
-        test_input.push((2, 0));
-        test_input.push((1, 0));
+        let source_file_path_1 = temp_dir
+            .path()
+            .join("test_file1.rs")
+            .to_str()
+            .unwrap()
+            .to_string();
 
-        assert_eq!(NextReferenceIdProcessor::reduce(&test_input), Some((5, 0)));
+        {
+            let mut source_file = File::create(&source_file_path_1).unwrap();
+            source_file
+                .write_all(
+                    br#"
+    fn test1_1() {
+        test_macro!("Log test 1_1.");
     }
 
-    #[test]
-    fn test_next_ref_id_missing_ref_reduce_count()
-    {
-        let mut test_input: Vec<(u32, usize)> = Vec::new();
+    fn test1_2() {
+        test_macro!("Log test 1_2.");
+    }"#,
+                )
+                .unwrap();
+        }
 
-        test_input.push((4, 1));
-        test_input.push((2, 2));
-        test_input.push((1, 4));
+        let source_file_path_2 = temp_dir
+            .path()
+            .join("test_file2.rs")
+            .to_str()
+            .unwrap()
+            .to_string();
 
-        assert_eq!(NextReferenceIdProcessor::reduce(&test_input), Some((5, 7)));
+        {
+            let mut source_file = File::create(&source_file_path_2).unwrap();
+            source_file
+                .write_all(
+                    br#"
+    fn test2_1() {
+        test_macro!("Log test 2_1.");
     }
 
-    #[test_log::test(async_std::test)]
-    async fn test_missing_ref_map_no_refs()
-    {
-        const TEST_PATH: &str = "test.rs";
-        let test_contents = String::new();
-        let test_entries: Vec<parser::LogRefEntry> = Vec::new();
+    fn test2_2() {
+        test_macro!("Log test 2_2.");
+    }"#,
+                )
+                .unwrap();
+        }
 
-        let map_result = CountMissingReferenceIdProcessor::map(
-            &String::from(TEST_PATH),
-            &test_contents,
-            &None,
-            &test_entries,
+        let test_finder = CodeFinder::new(&test_context).unwrap();
+
+        let insert_result = process_references::<
+            InsertReferencesProcessor,
+            ReferenceIds,
+            InsertReferencesResult,
+            InsertReferencesResult,
+        >(
+            &test_context,
+            Some(ReferenceIds::Sequential(2)),
+            &test_finder,
         )
-        .await;
+        .unwrap();
 
-        assert_eq!(map_result, Some(0));
-    }
+        commit_staged(&insert_result, &test_context.journal_path());
 
-    #[test_log::test(async_std::test)]
-    async fn test_missing_ref_map_no_missing_refs()
-    {
-        const TEST_PATH: &str = "test.rs";
-        let test_contents = String::new();
-        let mut test_entries: Vec<parser::LogRefEntry> = Vec::new();
+        /* Load the source files after they have finished being processed. Note that we can't reuse the file objects from
+         * earlier because the descriptors will no longer be valid due to the rename operation performed by the processor.
+         */
+        let mut source_file_1 = File::open(&source_file_path_1).unwrap();
+        let mut post_file_contents_1 = String::new();
+        source_file_1
+            .read_to_string(&mut post_file_contents_1)
+            .unwrap();
 
-        {
-            let code_pos = parser::CodePosition::new(1, 2, 3);
-            let entry = parser::LogRefEntry::new(
-                code_pos,
-                Some(1),
-                String::from_str("test").unwrap(),
-                LogRefKind::String,
-                None,
-                None,
-            );
-            test_entries.push(entry);
-        }
+        let mut source_file_2 = File::open(&source_file_path_2).unwrap();
+        let mut post_file_contents_2 = String::new();
+        source_file_2
+            .read_to_string(&mut post_file_contents_2)
+            .unwrap();
 
-        {
-            let code_pos = parser::CodePosition::new(4, 5, 6);
-            let entry = parser::LogRefEntry::new(
-                code_pos,
-                Some(2),
-                String::from_str("test").unwrap(),
-                LogRefKind::String,
-                None,
-                None,
-            );
-            test_entries.push(entry);
-        }
+        assert_eq!(insert_result.failure, false);
+        assert_eq!(insert_result.num_inserted_references, 4);
 
-        let map_result = CountMissingReferenceIdProcessor::map(
-            &String::from(TEST_PATH),
-            &test_contents,
-            &None,
-            &test_entries,
-        )
-        .await;
+        /* Files are processed in path order, so the first file receives the first IDs.
+         */
 
-        assert_eq!(map_result, Some(0));
+        assert_eq!(
+            post_file_contents_1,
+            r#"
+    fn test1_1() {
+        test_macro!(ref = 2; "Log test 1_1.");
     }
 
-    #[test_log::test(async_std::test)]
-    #[traced_test]
-    async fn test_missing_ref_map_missing_refs()
-    {
-        const TEST_PATH: &str = "test.rs";
-        let test_contents = String::new();
-        let mut test_entries: Vec<parser::LogRefEntry> = Vec::new();
+    fn test1_2() {
+        test_macro!(ref = 3; "Log test 1_2.");
+    }"#
+        );
 
-        {
-            let code_pos = parser::CodePosition::new(1, 2, 3);
-            let entry = parser::LogRefEntry::new(
-                code_pos,
-                Some(1),
-                String::from_str("test").unwrap(),
-                LogRefKind::String,
-                None,
-                None,
-            );
-            test_entries.push(entry);
-        }
+        assert_eq!(
+            post_file_contents_2,
+            r#"
+    fn test2_1() {
+        test_macro!(ref = 4; "Log test 2_1.");
+    }
 
-        {
-            let code_pos = parser::CodePosition::new(4, 5, 6);
-            let entry = parser::LogRefEntry::new(
-                code_pos,
-                None,
-                String::from_str("test").unwrap(),
-                LogRefKind::String,
-                None,
-                None,
-            );
-            test_entries.push(entry);
-        }
+    fn test2_2() {
+        test_macro!(ref = 5; "Log test 2_2.");
+    }"#
+        );
+    }
 
+    #[test]
+    fn test_insert_references_file_params()
+    {
+        let mut entries: Vec<parser::LogRefEntry> = Vec::new();
+
+        for (reference, kind) in [
+            (None, LogRefKind::String),
+            (Some(3), LogRefKind::String),
+            (None, LogRefKind::StructuredPreExisting),
+            (None, LogRefKind::StructuredNew),
+        ]
         {
-            let code_pos = parser::CodePosition::new(7, 8, 9);
-            let entry = parser::LogRefEntry::new(
-                code_pos,
-                None,
-                String::from_str("test").unwrap(),
-                LogRefKind::StructuredPreExisting,
+            entries.push(parser::LogRefEntry::new(
+                parser::CodePosition::new(1, 1, 1),
+                reference,
+                String::from_str("test_macro").unwrap(),
+                kind,
                 None,
                 None,
-            );
-            test_entries.push(entry);
+            ));
         }
 
-        {
-            let code_pos = parser::CodePosition::new(10, 11, 12);
-            let entry = parser::LogRefEntry::new(
-                code_pos,
-                None,
-                String::from_str("test").unwrap(),
-                LogRefKind::StructuredNew,
-                None,
-                None,
-            );
-            test_entries.push(entry);
-        }
+        let mut params = Some(ReferenceIds::Sequential(10));
 
-        {
-            let code_pos = parser::CodePosition::new(13, 14, 15);
-            let entry = parser::LogRefEntry::new(
-                code_pos,
-                Some(2),
-                String::from_str("test").unwrap(),
-                LogRefKind::StructuredPreExisting,
-                None,
-                None,
-            );
-            test_entries.push(entry);
-        }
+        assert_eq!(
+            InsertReferencesProcessor::file_params(&mut params, &entries),
+            Some(ReferenceIds::Sequential(10))
+        );
+        assert_eq!(params, Some(ReferenceIds::Sequential(12)));
 
-        let map_result = CountMissingReferenceIdProcessor::map(
-            &String::from(TEST_PATH),
-            &test_contents,
-            &None,
-            &test_entries,
-        )
-        .await;
+        assert_eq!(
+            InsertReferencesProcessor::file_params(&mut params, &[]),
+            Some(ReferenceIds::Sequential(12))
+        );
+        assert_eq!(params, Some(ReferenceIds::Sequential(12)));
 
-        assert_eq!(map_result, Some(2));
+        /*
+         * Running out of IDs doesn't overflow.
+         */
+        let mut params = Some(ReferenceIds::Sequential(u32::MAX - 1));
 
-        logs_assert(|lines: &[&str]| {
-            match lines
-                .iter()
-                .filter(|line| line.contains("missing_reference_5_6"))
-                .count()
-            {
-                1 => Ok(()),
-                n => Err(format!("More than 1 matching event: {}", n)),
-            }
-        });
+        InsertReferencesProcessor::file_params(&mut params, &entries);
+        assert_eq!(params, Some(ReferenceIds::Sequential(u32::MAX)));
 
-        logs_assert(|lines: &[&str]| {
-            match lines
-                .iter()
-                .filter(|line| line.contains("unusable_reference_8_9"))
-                .count()
-            {
-                1 => Ok(()),
-                n => Err(format!("More than 1 matching event: {}", n)),
-            }
-        });
+        /*
+         * Assigned IDs are shared by every file.
+         */
+        let assigned = ReferenceIds::Assigned(Arc::new(HashMap::from([(
+            String::from("test.rs"),
+            vec![7, 3],
+        )])));
+        let mut params = Some(assigned.clone());
+
+        assert_eq!(
+            InsertReferencesProcessor::file_params(&mut params, &entries),
+            Some(assigned.clone())
+        );
+        assert_eq!(params, Some(assigned));
+
+        let mut no_params = None;
+
+        assert_eq!(
+            InsertReferencesProcessor::file_params(&mut no_params, &entries),
+            None
+        );
     }
 
     #[test]
-    fn test_process_no_results()
+    fn test_process_insert_references_concurrent_order()
     {
+        const NUM_FILES: usize = 16;
+        const REFS_PER_FILE: usize = 3;
+
         let temp_dir = TempDir::new("breadlog_test").unwrap();
-        let test_context =
+        let mut test_context =
             create_test_context(&temp_dir.path().to_str().unwrap().to_string(), false, false);
+        test_context.max_jobs = 4;
 
+        for file_index in 0..NUM_FILES
         {
-            let source_file_path = temp_dir
-                .path()
-                .join("test_file1_none.rs")
-                .to_str()
-                .unwrap()
-                .to_string();
-
-            let mut source_file = File::create(&source_file_path).unwrap();
-            source_file.write_all(b"fn test1() {}").unwrap();
-        }
+            let mut contents = String::new();
 
-        {
-            let source_file_path = temp_dir
-                .path()
-                .join("test_file2_none.rs")
-                .to_str()
-                .unwrap()
-                .to_string();
+            for ref_index in 0..REFS_PER_FILE
+            {
+                contents.push_str(&format!(
+                    "fn test{}_{}() {{\n    test_macro!(\"Log test.\");\n}}\n",
+                    file_index, ref_index
+                ));
+            }
 
-            let mut source_file = File::create(&source_file_path).unwrap();
-            source_file.write_all(b"fn test2() {}").unwrap();
+            std::fs::write(
+                temp_dir.path().join(format!("test_file{}.rs", file_index)),
+                contents,
+            )
+            .unwrap();
         }
 
         let test_finder = CodeFinder::new(&test_context).unwrap();
 
+        let insert_result = process_references::<
+            InsertReferencesProcessor,
+            ReferenceIds,
+            InsertReferencesResult,
+            InsertReferencesResult,
+        >(
+            &test_context,
+            Some(ReferenceIds::Sequential(1)),
+            &test_finder,
+        )
+        .unwrap();
+
+        commit_staged(&insert_result, &test_context.journal_path());
+
+        assert!(!insert_result.failure);
         assert_eq!(
-            process_references::<TestRefProcCount, u32, u32, u32>(
-                &test_context,
-                None,
-                &test_finder
-            ),
-            None
+            insert_result.num_inserted_references,
+            NUM_FILES * REFS_PER_FILE
         );
+
+        /*
+         * Each file receives a contiguous range of IDs in canonical file order, regardless of
+         * the order in which concurrent processing completed.
+         */
+        let ref_pattern = Regex::new(r"\[ref: ([0-9]{1,10})\]").unwrap();
+        let mut expected_id: u32 = 1;
+
+        for code_file in test_finder.ordered_code_files()
+        {
+            let contents = std::fs::read_to_string(&code_file.path).unwrap();
+
+            for capture in ref_pattern.captures_iter(&contents)
+            {
+                assert_eq!(capture[1].parse::<u32>().unwrap(), expected_id);
+                expected_id += 1;
+            }
+        }
+
+        assert_eq!(expected_id as usize, NUM_FILES * REFS_PER_FILE + 1);
     }
 
     #[test]
-    fn test_process_results()
+    fn test_process_insert_references_discovery_order_independent()
     {
-        let temp_dir = TempDir::new("breadlog_test").unwrap();
-        let test_context =
-            create_test_context(&temp_dir.path().to_str().unwrap().to_string(), false, false);
+        const NUM_FILES: usize = 8;
 
-        {
-            let source_file_path = temp_dir
-                .path()
-                .join("test_file1.rs")
-                .to_str()
-                .unwrap()
-                .to_string();
+        let run = |discovery_order: &[usize]| -> Vec<String> {
+            let temp_dir = TempDir::new("breadlog_test").unwrap();
+            let test_context =
+                create_test_context(&temp_dir.path().to_str().unwrap().to_string(), false, false);
 
-            let mut source_file = File::create(&source_file_path).unwrap();
-            source_file
-                .write_all(
-                    br#"
-fn test1() {
-    test_macro!("Log test.");
-}
-            "#,
+            for file_index in 0..NUM_FILES
+            {
+                std::fs::write(
+                    temp_dir.path().join(format!("test_file{}.rs", file_index)),
+                    format!(
+                        "fn test{}() {{\n    test_macro!(\"Log 1.\");\n    test_macro!(\"Log 2.\");\n}}\n",
+                        file_index
+                    ),
                 )
                 .unwrap();
-        }
+            }
 
-        {
-            let source_file_path = temp_dir
-                .path()
-                .join("test_file2.rs")
-                .to_str()
-                .unwrap()
-                .to_string();
+            let mut test_finder = CodeFinder::new(&test_context).unwrap();
 
-            let mut source_file = File::create(&source_file_path).unwrap();
-            source_file
-                .write_all(
-                    br#"
-fn test2() {
-    test_macro!("Log test.");
-}
-            "#,
-                )
-                .unwrap();
-        }
+            /*
+             * Simulate a file system that lists directory entries in a different order.
+             */
+            test_finder.code_files.sort_by_key(|f| {
+                discovery_order
+                    .iter()
+                    .position(|i| f.path.ends_with(&format!("test_file{}.rs", i)))
+                    .unwrap()
+            });
 
-        let test_finder = CodeFinder::new(&test_context).unwrap();
+            let insert_result = process_references::<
+                InsertReferencesProcessor,
+                ReferenceIds,
+                InsertReferencesResult,
+                InsertReferencesResult,
+            >(
+                &test_context,
+                Some(ReferenceIds::Sequential(1)),
+                &test_finder,
+            )
+            .unwrap();
+
+            commit_staged(&insert_result, &test_context.journal_path());
+
+            (0..NUM_FILES)
+                .map(|file_index| {
+                    std::fs::read_to_string(
+                        temp_dir.path().join(format!("test_file{}.rs", file_index)),
+                    )
+                    .unwrap()
+                })
+                .collect()
+        };
+
+        let forward_output = run(&[0, 1, 2, 3, 4, 5, 6, 7]);
+        let reverse_output = run(&[7, 6, 5, 4, 3, 2, 1, 0]);
+        let shuffled_output = run(&[3, 7, 0, 5, 1, 6, 4, 2]);
+
+        assert_eq!(forward_output, reverse_output);
+        assert_eq!(forward_output, shuffled_output);
 
         assert_eq!(
-            process_references::<TestRefProcCount, u32, u32, u32>(
-                &test_context,
-                None,
-                &test_finder
-            ),
-            Some(2)
+            forward_output[0],
+            "fn test0() {\n    test_macro!(\"[ref: 1] Log 1.\");\n    test_macro!(\"[ref: 2] Log 2.\");\n}\n"
+        );
+        assert_eq!(
+            forward_output[NUM_FILES - 1],
+            "fn test7() {\n    test_macro!(\"[ref: 15] Log 1.\");\n    test_macro!(\"[ref: 16] Log 2.\");\n}\n"
         );
     }
 
-    #[test]
-    fn test_process_partial_results()
+    #[test_log::test(async_std::test)]
+    async fn test_create_async_temp_file()
     {
+        use AsyncWriteExt;
+
+        let temp_file_path: String;
         let temp_dir = TempDir::new("breadlog_test").unwrap();
-        let test_context =
-            create_test_context(&temp_dir.path().to_str().unwrap().to_string(), false, false);
+        let target_path = temp_dir.path().join("test_file.rs");
 
         {
-            let source_file_path = temp_dir
-                .path()
-                .join("test_file1_none.rs")
-                .to_str()
-                .unwrap()
-                .to_string();
+            let mut temp_file_result =
+                crate::codegen::generate::AsyncTempFile::new(target_path.to_str().unwrap())
+                    .await
+                    .unwrap();
+            assert!(async_std::fs::metadata(temp_file_result.path())
+                .await
+                .is_ok());
 
-            let mut source_file = File::create(&source_file_path).unwrap();
-            source_file
-                .write_all(
-                    br#"
-fn test1() {}
-            "#,
-                )
-                .unwrap();
+            temp_file_path = temp_file_result.path().to_string();
+
+            assert_eq!(
+                std::path::Path::new(&temp_file_path).parent().unwrap(),
+                temp_dir.path()
+            );
+
+            let buffer = String::from_str("test").unwrap();
+            assert_ne!(temp_file_result.path(), "");
+            assert!(temp_file_result
+                .file()
+                .write(buffer.as_bytes())
+                .await
+                .is_ok());
         }
 
+        assert_eq!(async_std::fs::metadata(temp_file_path).await.is_ok(), false);
+    }
+
+    #[test_log::test(async_std::test)]
+    async fn test_stage_async_temp_file()
+    {
+        use std::os::unix::fs::PermissionsExt;
+        use AsyncWriteExt;
+
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let target_path = temp_dir.path().join("test_file.rs");
+
+        std::fs::write(&target_path, "original").unwrap();
+        std::fs::set_permissions(&target_path, std::fs::Permissions::from_mode(0o640)).unwrap();
+
+        let temp_file_path: String;
+
         {
-            let source_file_path = temp_dir
-                .path()
-                .join("test_file2.rs")
-                .to_str()
+            let mut temp_file =
+                crate::codegen::generate::AsyncTempFile::new(target_path.to_str().unwrap())
+                    .await
+                    .unwrap();
+            temp_file_path = temp_file.path().to_string();
+
+            temp_file.file().write_all(b"updated").await.unwrap();
+            let staged_file = temp_file
+                .stage(target_path.to_str().unwrap())
+                .await
+                .unwrap();
+
+            /*
+             * Staging must not modify the target file.
+             */
+            assert_eq!(std::fs::read_to_string(&target_path).unwrap(), "original");
+
+            let mut transaction =
+                Transaction::new(temp_dir.path().join("Breadlog.journal").to_str().unwrap());
+            transaction.add(staged_file);
+            transaction.commit().unwrap();
+        }
+
+        assert_eq!(std::fs::read_to_string(&target_path).unwrap(), "updated");
+        assert_eq!(
+            std::fs::metadata(&target_path)
                 .unwrap()
-                .to_string();
+                .permissions()
+                .mode()
+                & 0o777,
+            0o640
+        );
+        assert!(std::fs::metadata(temp_file_path).is_err());
+        assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+    }
 
-            let mut source_file = File::create(&source_file_path).unwrap();
-            source_file
-                .write_all(
-                    br#"
-fn test2() {
+    #[test_log::test(async_std::test)]
+    async fn test_process_insert_references_symlink()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let real_dir = temp_dir.path().join("real");
+        std::fs::create_dir(&real_dir).unwrap();
+
+        let real_file_path = real_dir.join("test_file.rs");
+        let link_file_path = temp_dir.path().join("test_link.rs");
+
+        let source_file_contents = String::from_str(
+            r#"
+fn test1() {
     test_macro!("Log test.");
 }
-            "#,
-                )
-                .unwrap();
-        }
+"#,
+        )
+        .unwrap();
 
-        let test_finder = CodeFinder::new(&test_context).unwrap();
+        std::fs::write(&real_file_path, &source_file_contents).unwrap();
+        std::os::unix::fs::symlink(&real_file_path, &link_file_path).unwrap();
 
-        assert_eq!(
-            process_references::<TestRefProcCount, u32, u32, u32>(
-                &test_context,
-                None,
-                &test_finder
-            ),
-            Some(1)
+        let test_entries = vec![parser::LogRefEntry::new(
+            parser::CodePosition::new(31, 3, 18),
+            None,
+            String::from_str("test_macro").unwrap(),
+            LogRefKind::String,
+            None,
+            None,
+        )];
+
+        let insert_result = InsertReferencesProcessor::map(
+            link_file_path.to_str().unwrap(),
+            &source_file_contents,
+            &Some(ReferenceIds::Sequential(10)),
+            &test_entries,
+        )
+        .await
+        .unwrap();
+
+        commit_staged(
+            &insert_result,
+            temp_dir.path().join("Breadlog.journal").to_str().unwrap(),
+        );
+
+        assert!(!insert_result.failure);
+        assert_eq!(insert_result.num_inserted_references, 1);
+
+        assert!(std::fs::symlink_metadata(&link_file_path)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(
+            std::fs::read_to_string(&real_file_path).unwrap(),
+            r#"
+fn test1() {
+    test_macro!("[ref: 10] Log test.");
+}
+"#
         );
     }
 
     #[test]
-    fn test_process_stop_command()
+    fn test_generate_no_cache()
     {
-        use std::sync::atomic::Ordering;
-
         let temp_dir = TempDir::new("breadlog_test").unwrap();
         let test_context =
             create_test_context(&temp_dir.path().to_str().unwrap().to_string(), false, false);
 
+        let source_file_path_1: String;
+        let source_file_path_2: String;
+
         {
-            let source_file_path = temp_dir
+            source_file_path_1 = temp_dir
                 .path()
                 .join("test_file1.rs")
                 .to_str()
                 .unwrap()
                 .to_string();
 
-            let mut source_file = File::create(&source_file_path).unwrap();
+            let mut source_file = File::create(&source_file_path_1).unwrap();
             source_file
                 .write_all(
                     br#"
@@ -1456,14 +3774,14 @@ fn test1() {
         }
 
         {
-            let source_file_path = temp_dir
+            source_file_path_2 = temp_dir
                 .path()
                 .join("test_file2.rs")
                 .to_str()
                 .unwrap()
                 .to_string();
 
-            let mut source_file = File::create(&source_file_path).unwrap();
+            let mut source_file = File::create(&source_file_path_2).unwrap();
             source_file
                 .write_all(
                     br#"
@@ -1475,43 +3793,75 @@ fn test2() {
                 .unwrap();
         }
 
-        let test_finder = CodeFinder::new(&test_context).unwrap();
+        assert!(generate_code(&test_context).is_ok());
 
-        test_context
-            .stop_commanded
-            .fetch_or(true, Ordering::Relaxed);
+        let file_1_contents = std::fs::read_to_string(&source_file_path_1).unwrap();
+        let file_2_contents = std::fs::read_to_string(&source_file_path_2).unwrap();
 
-        assert_eq!(
-            process_references::<TestRefProcCount, u32, u32, u32>(
-                &test_context,
-                None,
-                &test_finder
-            ),
-            None
-        );
+        let ref_pattern = Regex::new(r"\[ref: ([0-9]{1,10})\]").unwrap();
+
+        let file_1_match = ref_pattern
+            .captures_iter(file_1_contents.as_str())
+            .next()
+            .unwrap();
+        let file_2_match = ref_pattern
+            .captures_iter(file_2_contents.as_str())
+            .next()
+            .unwrap();
+
+        let file_1_id = file_1_match[1].parse::<u32>().unwrap();
+        let file_2_id = file_2_match[1].parse::<u32>().unwrap();
+
+        assert_eq!(file_1_id, 1);
+        assert_eq!(file_2_id, 2);
     }
 
     #[test]
-    fn test_process_next_reference_id()
+    fn test_generate_uses_cache()
     {
+        use std::collections::BTreeMap;
+
         let temp_dir = TempDir::new("breadlog_test").unwrap();
+
+        let cache_file_path = temp_dir
+            .path()
+            .join("Breadlog.lock")
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        {
+            let mut cache_file = File::create(&cache_file_path).unwrap();
+            cache_file
+                .write_all(
+                    br#"
+---
+next_reference_id: 123
+            "#,
+                )
+                .unwrap();
+        }
+
         let test_context =
             create_test_context(&temp_dir.path().to_str().unwrap().to_string(), false, false);
 
+        let source_file_path_1: String;
+        let source_file_path_2: String;
+
         {
-            let source_file_path = temp_dir
+            source_file_path_1 = temp_dir
                 .path()
                 .join("test_file1.rs")
                 .to_str()
                 .unwrap()
                 .to_string();
 
-            let mut source_file = File::create(&source_file_path).unwrap();
+            let mut source_file = File::create(&source_file_path_1).unwrap();
             source_file
                 .write_all(
                     br#"
 fn test1() {
-    test_macro!("[ref: 9] Log test.");
+    test_macro!("Log test.");
 }
             "#,
                 )
@@ -1519,905 +3869,886 @@ fn test1() {
         }
 
         {
-            let source_file_path = temp_dir
+            source_file_path_2 = temp_dir
                 .path()
                 .join("test_file2.rs")
                 .to_str()
                 .unwrap()
                 .to_string();
 
-            let mut source_file = File::create(&source_file_path).unwrap();
+            let mut source_file = File::create(&source_file_path_2).unwrap();
             source_file
                 .write_all(
                     br#"
 fn test2() {
-    test_macro!("[ref: 10] Log test.");
+    test_macro!("Log test.");
 }
             "#,
                 )
                 .unwrap();
         }
 
-        let test_finder = CodeFinder::new(&test_context).unwrap();
-
-        assert_eq!(
-            process_references::<NextReferenceIdProcessor, u32, (u32, usize), (u32, usize)>(
-                &test_context,
-                None,
-                &test_finder
-            ),
-            Some((11, 0))
-        );
-    }
-
-    #[test]
-    fn test_process_insert_references_no_files()
-    {
-        use std::sync::atomic::AtomicU32;
-        use std::sync::Arc;
-
-        let temp_dir = TempDir::new("breadlog_test").unwrap();
-        let test_context =
-            create_test_context(&temp_dir.path().to_str().unwrap().to_string(), false, false);
-
-        let test_finder = CodeFinder::new(&test_context).unwrap();
-
-        let insert_result = process_references::<
-            InsertReferencesProcessor,
-            Arc<AtomicU32>,
-            InsertReferencesResult,
-            InsertReferencesResult,
-        >(&test_context, None, &test_finder)
-        .unwrap();
-
-        /*
-         * Because there are no files, the map() function should not be called.
-         * As a result, the processor will not report failure despite the lack of the required parameter.
-         */
-
-        assert!(!insert_result.failure);
-        assert_eq!(insert_result.num_inserted_references, 0);
-    }
-
-    #[test_log::test(async_std::test)]
-    #[traced_test]
-    async fn test_process_insert_references_missing_next_id()
-    {
-        let temp_dir = TempDir::new("breadlog_test").unwrap();
-        let source_file_path = temp_dir
-            .path()
-            .join("test_file2.rs")
-            .to_str()
-            .unwrap()
-            .to_string();
-
-        File::create(&source_file_path).unwrap();
-        let file_contents = String::new();
-        let mut test_entries: Vec<parser::LogRefEntry> = Vec::new();
-
-        {
-            let code_pos = parser::CodePosition::new(30, 2, 18);
-            let entry = parser::LogRefEntry::new(
-                code_pos,
-                None,
-                String::from_str("test_macro").unwrap(),
-                LogRefKind::String,
-                None,
-                None,
-            );
-            test_entries.push(entry);
-        }
-
-        let insert_result =
-            InsertReferencesProcessor::map(&source_file_path, &file_contents, &None, &test_entries)
-                .await
-                .unwrap();
-
-        assert!(insert_result.failure);
-        assert_eq!(insert_result.num_inserted_references, 0);
-
-        assert!(logs_contain("unexpected_next_reference_id"));
-    }
-
-    #[test_log::test(async_std::test)]
-    #[traced_test]
-    async fn test_process_insert_references_out_of_order_insert_pos()
-    {
-        use std::sync::atomic::AtomicU32;
-        use std::sync::Arc;
+        assert!(generate_code(&test_context).is_ok());
 
-        let source_file_contents = String::from_str(
-            r#"
-fn test1() {
-    test_macro!("Log test.");
-}
-"#,
-        )
-        .unwrap();
+        let file_1_contents = std::fs::read_to_string(&source_file_path_1).unwrap();
+        let file_2_contents = std::fs::read_to_string(&source_file_path_2).unwrap();
+        let cache_contents = std::fs::read_to_string(&cache_file_path).unwrap();
 
-        let temp_dir = TempDir::new("breadlog_test").unwrap();
-        let source_file_path = temp_dir
-            .path()
-            .join("test_file.rs")
-            .to_str()
-            .unwrap()
-            .to_string();
+        let ref_pattern = Regex::new(r"\[ref: ([0-9]{1,10})\]").unwrap();
 
-        let mut source_file = File::create(&source_file_path).unwrap();
-        source_file
-            .write_all(source_file_contents.as_bytes())
+        let file_1_match = ref_pattern
+            .captures_iter(file_1_contents.as_str())
+            .next()
+            .unwrap();
+        let file_2_match = ref_pattern
+            .captures_iter(file_2_contents.as_str())
+            .next()
             .unwrap();
 
-        let mut test_entries: Vec<parser::LogRefEntry> = Vec::new();
-
-        {
-            let code_pos = parser::CodePosition::new(30, 2, 18);
-            let entry = parser::LogRefEntry::new(
-                code_pos,
-                None,
-                String::from_str("test_macro").unwrap(),
-                LogRefKind::String,
-                None,
-                None,
-            );
-            test_entries.push(entry);
-        }
-
-        {
-            let code_pos = parser::CodePosition::new(29, 2, 17);
-            let entry = parser::LogRefEntry::new(
-                code_pos,
-                None,
-                String::from_str("test_macro").unwrap(),
-                LogRefKind::String,
-                None,
-                None,
-            );
-            test_entries.push(entry);
-        }
-
-        let ref_value = AtomicU32::new(10);
-        let ref_arc = Arc::<AtomicU32>::new(ref_value);
-
-        let insert_result = InsertReferencesProcessor::map(
-            &source_file_path,
-            &source_file_contents,
-            &Some(ref_arc),
-            &test_entries,
-        )
-        .await
-        .unwrap();
-
-        assert!(insert_result.failure);
-        assert_eq!(insert_result.num_inserted_references, 0);
-
-        logs_assert(|lines: &[&str]| {
-            match lines
-                .iter()
-                .filter(|line| line.contains("unexpected_reference_insert_pos"))
-                .count()
-            {
-                1 => Ok(()),
-                n => Err(format!("More than 1 matching event: {}", n)),
-            }
-        });
-    }
-
-    #[test_log::test(async_std::test)]
-    async fn test_process_insert_references_empty_file()
-    {
-        use std::sync::atomic::AtomicU32;
-        use std::sync::Arc;
+        let file_1_id = file_1_match[1].parse::<u32>().unwrap();
+        let file_2_id = file_2_match[1].parse::<u32>().unwrap();
 
-        let source_file_contents = String::new();
+        assert_eq!(file_1_id, 123);
+        assert_eq!(file_2_id, 124);
 
-        let temp_dir = TempDir::new("breadlog_test").unwrap();
-        let source_file_path = temp_dir
-            .path()
-            .join("test_file.rs")
-            .to_str()
-            .unwrap()
-            .to_string();
+        let loaded_cache: BTreeMap<String, u32> =
+            serde_yaml::from_str(cache_contents.as_str()).unwrap();
+        assert_eq!(loaded_cache["next_reference_id"], 125);
+    }
 
-        let _ = File::create(&source_file_path).unwrap();
+    #[test]
+    fn test_generate_selected_files()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let mut test_context =
+            create_test_context(&temp_dir.path().to_str().unwrap().to_string(), false, false);
+        test_context.config.use_cache = false;
 
-        let test_entries: Vec<parser::LogRefEntry> = Vec::new();
+        let selected_file_path = temp_dir.path().join("test_file1.rs");
+        let unselected_file_path = temp_dir.path().join("test_file2.rs");
 
-        let ref_value = AtomicU32::new(1);
-        let ref_arc = Arc::<AtomicU32>::new(ref_value);
+        std::fs::write(
+            &selected_file_path,
+            r#"
+fn test1() {
+    test_macro!("Log test.");
+}
+"#,
+        )
+        .unwrap();
 
-        let insert_result = InsertReferencesProcessor::map(
-            &source_file_path,
-            &source_file_contents,
-            &Some(ref_arc),
-            &test_entries,
+        std::fs::write(
+            &unselected_file_path,
+            r#"
+fn test2() {
+    test_macro!("[ref: 7] Log test.");
+    test_macro!("Log test.");
+}
+"#,
         )
-        .await
         .unwrap();
 
-        assert_eq!(insert_result.failure, false);
-        assert_eq!(insert_result.num_inserted_references, 0);
+        test_context.selected_files = Some(vec![selected_file_path.to_str().unwrap().to_string()]);
+
+        assert!(generate_code(&test_context).is_ok());
+
+        /*
+         * The next reference ID is determined from all files, but only the selected file is
+         * modified.
+         */
+        assert_eq!(
+            std::fs::read_to_string(&selected_file_path).unwrap(),
+            r#"
+fn test1() {
+    test_macro!("[ref: 8] Log test.");
+}
+"#
+        );
+        assert_eq!(
+            std::fs::read_to_string(&unselected_file_path).unwrap(),
+            r#"
+fn test2() {
+    test_macro!("[ref: 7] Log test.");
+    test_macro!("Log test.");
+}
+"#
+        );
     }
 
-    #[test_log::test(async_std::test)]
-    #[traced_test]
-    async fn test_process_insert_references_parent_dir_missing()
+    #[test]
+    fn test_generate_no_selected_files()
     {
-        use std::sync::atomic::AtomicU32;
-        use std::sync::Arc;
-
         let temp_dir = TempDir::new("breadlog_test").unwrap();
-        let source_file_path = temp_dir
-            .path()
-            .join("does_not_exist")
-            .join("test_file.rs")
-            .to_str()
-            .unwrap()
-            .to_string();
+        let mut test_context =
+            create_test_context(&temp_dir.path().to_str().unwrap().to_string(), false, false);
 
-        let source_file_contents = String::from_str(
+        std::fs::write(
+            temp_dir.path().join("test_file1.rs"),
             r#"
 fn test1() {
     test_macro!("Log test.");
 }
-        "#,
+"#,
         )
         .unwrap();
 
-        let mut test_entries: Vec<parser::LogRefEntry> = Vec::new();
+        test_context.selected_files = Some(Vec::new());
 
-        {
-            let code_pos = parser::CodePosition::new(30, 2, 18);
-            let entry = parser::LogRefEntry::new(
-                code_pos,
-                None,
-                String::from_str("test_macro").unwrap(),
-                LogRefKind::String,
-                None,
-                None,
-            );
-            test_entries.push(entry);
-        }
+        assert_eq!(generate_code(&test_context), Ok(0));
+        assert_eq!(check_references(&test_context, &mut Vec::new()), Ok(0));
+    }
 
-        let ref_value = AtomicU32::new(10);
-        let ref_arc = Arc::<AtomicU32>::new(ref_value);
+    #[test]
+    fn test_generate_recovers_interrupted_update()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let test_context =
+            create_test_context(&temp_dir.path().to_str().unwrap().to_string(), false, false);
 
-        let insert_result = InsertReferencesProcessor::map(
+        let source_file_path = temp_dir.path().join("test_file1.rs");
+        let backup_file_path = temp_dir.path().join(".test_file1.rs.bak");
+        let staged_file_path = temp_dir.path().join(".test_file1.rs.tmp");
+
+        std::fs::write(
             &source_file_path,
-            &source_file_contents,
-            &Some(ref_arc),
-            &test_entries,
+            r#"
+fn test1() {
+    test_macro!("Log test.");
+}
+"#,
         )
-        .await
         .unwrap();
 
-        assert!(insert_result.failure);
-        assert_eq!(insert_result.num_inserted_references, 1);
-
-        logs_assert(|lines: &[&str]| {
-            match lines
-                .iter()
-                .filter(|line| line.contains("failed_to_rename_temp_file"))
-                .count()
-            {
-                1 => Ok(()),
-                n => Err(format!("More than 1 matching event: {}", n)),
-            }
-        });
-    }
+        /*
+         * Simulate an update which died after replacing the file but before completing.
+         */
+        std::fs::hard_link(&source_file_path, &backup_file_path).unwrap();
+        std::fs::write(&staged_file_path, "partially updated").unwrap();
+        std::fs::rename(&staged_file_path, &source_file_path).unwrap();
 
-    #[test]
-    fn test_process_insert_references_reduce_success()
-    {
-        let mut test_input: Vec<InsertReferencesResult> = Vec::new();
+        std::fs::write(
+            test_context.journal_path(),
+            format!(
+                "entries:\n  - target: {}\n    staged: {}\n    backup: {}\n",
+                source_file_path.to_str().unwrap(),
+                staged_file_path.to_str().unwrap(),
+                backup_file_path.to_str().unwrap()
+            ),
+        )
+        .unwrap();
 
-        {
-            let result = InsertReferencesResult {
-                failure: false,
-                num_inserted_references: 2,
-            };
-            test_input.push(result);
-        }
+        assert!(check_references(&test_context, &mut Vec::new()).is_err());
+        assert_eq!(
+            std::fs::read_to_string(&source_file_path).unwrap(),
+            "partially updated"
+        );
 
-        {
-            let result = InsertReferencesResult {
-                failure: false,
-                num_inserted_references: 3,
-            };
-            test_input.push(result);
-        }
+        assert!(generate_code(&test_context).is_ok());
 
-        let insert_result = InsertReferencesProcessor::reduce(&test_input).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&source_file_path).unwrap(),
+            r#"
+fn test1() {
+    test_macro!("[ref: 1] Log test.");
+}
+"#
+        );
+        assert!(!std::path::Path::new(&test_context.journal_path()).exists());
+        assert!(!backup_file_path.exists());
 
-        assert_eq!(insert_result.failure, false);
-        assert_eq!(insert_result.num_inserted_references, 5);
+        let reloaded_context =
+            create_test_context(&temp_dir.path().to_str().unwrap().to_string(), false, false);
+        assert_eq!(reloaded_context.cached_next_reference_id, Some(2));
     }
 
     #[test]
-    fn test_process_insert_references_reduce_failure()
+    fn test_generate_run_lock_held()
     {
-        let mut test_input: Vec<InsertReferencesResult> = Vec::new();
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let mut test_context =
+            create_test_context(&temp_dir.path().to_str().unwrap().to_string(), false, false);
+        test_context.config.lock_timeout = 0;
 
-        {
-            let result = InsertReferencesResult {
-                failure: true,
-                num_inserted_references: 2,
-            };
-            test_input.push(result);
-        }
+        let source_file_path = temp_dir.path().join("test_file1.rs");
+        let source_file_contents = r#"
+fn test1() {
+    test_macro!("Log test.");
+}
+"#;
+
+        std::fs::write(&source_file_path, source_file_contents).unwrap();
 
         {
-            let result = InsertReferencesResult {
-                failure: false,
-                num_inserted_references: 1,
-            };
-            test_input.push(result);
-        }
+            let _other_run = RunLock::acquire(
+                &test_context.run_lock_path(),
+                std::time::Duration::ZERO,
+                &test_context.stop_commanded,
+                || {},
+            )
+            .unwrap();
 
-        let insert_result = InsertReferencesProcessor::reduce(&test_input).unwrap();
+            assert_eq!(
+                generate_code(&test_context),
+                Err("Another Breadlog run is in progress")
+            );
+            assert_eq!(
+                std::fs::read_to_string(&source_file_path).unwrap(),
+                source_file_contents
+            );
+        }
 
-        assert!(insert_result.failure);
-        assert_eq!(insert_result.num_inserted_references, 3);
+        assert!(generate_code(&test_context).is_ok());
+        assert!(std::fs::read_to_string(&source_file_path)
+            .unwrap()
+            .contains("[ref: 1]"));
     }
 
     #[test]
-    fn test_process_insert_references_files()
+    fn test_generate_rereads_cache_under_lock()
     {
-        use std::sync::atomic::AtomicU32;
-        use std::sync::Arc;
-
         let temp_dir = TempDir::new("breadlog_test").unwrap();
         let test_context =
             create_test_context(&temp_dir.path().to_str().unwrap().to_string(), false, false);
 
-        let source_file_path_1 = temp_dir
-            .path()
-            .join("test_file1.rs")
-            .to_str()
-            .unwrap()
-            .to_string();
+        std::fs::write(
+            temp_dir.path().join("test_file1.rs"),
+            r#"
+fn test1() {
+    test_macro!("Log test.");
+}
+"#,
+        )
+        .unwrap();
 
-        {
-            let mut source_file = File::create(&source_file_path_1).unwrap();
-            source_file
-                .write_all(
-                    br#"
-    fn test1_1() {
-        test_macro!("Log test 1_1.");
+        /*
+         * Simulate another run updating the lock file after this context was created.
+         */
+        assert!(test_context.cached_next_reference_id.is_none());
+        test_context.cache_next_reference_id(42, &test_context.config.config_dir);
+
+        assert!(generate_code(&test_context).is_ok());
+        assert!(
+            std::fs::read_to_string(temp_dir.path().join("test_file1.rs"))
+                .unwrap()
+                .contains("[ref: 42]")
+        );
     }
 
-    fn test1_2() {
-        test_macro!("Log test 1_2.");
-    }"#,
-                )
-                .unwrap();
-        }
+    #[test]
+    fn test_generate_reserved_block()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let mut test_context =
+            create_test_context(&temp_dir.path().to_str().unwrap().to_string(), false, false);
+        test_context.id_owner = Some(String::from("alice"));
 
-        let source_file_path_2 = temp_dir
-            .path()
-            .join("test_file2.rs")
-            .to_str()
-            .unwrap()
-            .to_string();
+        std::fs::write(
+            temp_dir.path().join("test_file1.rs"),
+            r#"
+fn test1() {
+    test_macro!("Log test 1.");
+    test_macro!("Log test 2.");
+}
+"#,
+        )
+        .unwrap();
 
-        {
-            let mut source_file = File::create(&source_file_path_2).unwrap();
-            source_file
-                .write_all(
-                    br#"
-    fn test2_1() {
-        test_macro!("Log test 2_1.");
-    }
+        /*
+         * There's no reserved block yet.
+         */
+        assert!(generate_code(&test_context).is_err());
+
+        let mut cache = Cache::new(100);
+        cache.reservations.push(Reservation {
+            owner: String::from("alice"),
+            start: 10,
+            size: 3,
+            next: 11,
+        });
+        std::fs::write(
+            Context::cache_path(&test_context.config.config_dir),
+            test_context.cache_contents(&cache).unwrap(),
+        )
+        .unwrap();
 
-    fn test2_2() {
-        test_macro!("Log test 2_2.");
-    }"#,
-                )
-                .unwrap();
-        }
+        assert!(generate_code(&test_context).is_ok());
 
-        let test_finder = CodeFinder::new(&test_context).unwrap();
+        let contents = std::fs::read_to_string(temp_dir.path().join("test_file1.rs")).unwrap();
+        assert!(contents.contains("[ref: 11] Log test 1."));
+        assert!(contents.contains("[ref: 12] Log test 2."));
 
-        let ref_value = AtomicU32::new(2);
-        let ref_arc = Arc::<AtomicU32>::new(ref_value);
+        let cache = test_context.reload_cache().unwrap();
+        assert_eq!(cache.next_reference_id, 100);
+        assert_eq!(cache.reservation("alice").unwrap().next, 13);
+        assert_eq!(cache.reservation("alice").unwrap().remaining(), 0);
 
-        let insert_result = process_references::<
-            InsertReferencesProcessor,
-            Arc<AtomicU32>,
-            InsertReferencesResult,
-            InsertReferencesResult,
-        >(&test_context, Some(ref_arc), &test_finder)
+        /*
+         * The block is used up.
+         */
+        std::fs::write(
+            temp_dir.path().join("test_file2.rs"),
+            "fn test2() {\n    test_macro!(\"Log test 3.\");\n}\n",
+        )
         .unwrap();
 
-        /* Load the source files after they have finished being processed. Note that we can't reuse the file objects from
-         * earlier because the descriptors will no longer be valid due to the rename operation performed by the processor.
-         */
-        let mut source_file_1 = File::open(&source_file_path_1).unwrap();
-        let mut post_file_contents_1 = String::new();
-        source_file_1
-            .read_to_string(&mut post_file_contents_1)
-            .unwrap();
+        assert!(generate_code(&test_context).is_err());
+        assert!(
+            !std::fs::read_to_string(temp_dir.path().join("test_file2.rs"))
+                .unwrap()
+                .contains("[ref:")
+        );
+    }
 
-        let mut source_file_2 = File::open(&source_file_path_2).unwrap();
-        let mut post_file_contents_2 = String::new();
-        source_file_2
-            .read_to_string(&mut post_file_contents_2)
-            .unwrap();
+    #[test]
+    fn test_generate_hash_strategy()
+    {
+        use crate::codegen::hashed_ids::hash_reference_id;
+        use crate::config::context::IdStrategy;
 
-        assert_eq!(insert_result.failure, false);
-        assert_eq!(insert_result.num_inserted_references, 4);
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let mut test_context =
+            create_test_context(&temp_dir.path().to_str().unwrap().to_string(), false, false);
+        test_context.config.id_strategy = IdStrategy::Hash;
+        test_context.config.id_salt = String::from("test-salt");
+
+        let first_choice = hash_reference_id("b/test_file2.rs", "Log test 1.", "test-salt", 0);
 
-        /* The order in which files are processed is not deterministic, so we need to check for both possible results.
+        /*
+         * The first choice of ID for the message in test_file2.rs is already taken, so the next
+         * candidate is used.
          */
+        std::fs::write(
+            temp_dir.path().join("test_file1.rs"),
+            format!(
+                "fn test1() {{\n    test_macro!(\"[ref: {}] Taken.\");\n    test_macro!(\"Log test 1.\");\n}}\n",
+                first_choice
+            ),
+        )
+        .unwrap();
+        std::fs::create_dir(temp_dir.path().join("b")).unwrap();
+        std::fs::write(
+            temp_dir.path().join("b/test_file2.rs"),
+            "fn test2() {\n    test_macro!(\"Log test 1.\");\n    test_macro!(\"Log test 1.\");\n}\n",
+        )
+        .unwrap();
 
-        assert!(
-            (post_file_contents_1
-                == String::from_str(
-                    r#"
-    fn test1_1() {
-        test_macro!("[ref: 4] Log test 1_1.");
-    }
+        assert!(generate_code(&test_context).is_ok());
 
-    fn test1_2() {
-        test_macro!("[ref: 5] Log test 1_2.");
-    }"#
-                )
-                .unwrap())
-                || (post_file_contents_1
-                    == String::from_str(
-                        r#"
-    fn test1_1() {
-        test_macro!("[ref: 2] Log test 1_1.");
-    }
+        let contents1 = std::fs::read_to_string(temp_dir.path().join("test_file1.rs")).unwrap();
+        let contents2 = std::fs::read_to_string(temp_dir.path().join("b/test_file2.rs")).unwrap();
+
+        assert!(contents1.contains(&format!(
+            "[ref: {}] Log test 1.",
+            hash_reference_id("test_file1.rs", "Log test 1.", "test-salt", 0)
+        )));
+        assert!(contents2.contains(&format!(
+            "[ref: {}] Log test 1.",
+            hash_reference_id("b/test_file2.rs", "Log test 1.", "test-salt", 1)
+        )));
+        assert!(contents2.contains(&format!(
+            "[ref: {}] Log test 1.",
+            hash_reference_id("b/test_file2.rs", "Log test 1.", "test-salt", 2)
+        )));
 
-    fn test1_2() {
-        test_macro!("[ref: 3] Log test 1_2.");
-    }"#
-                    )
-                    .unwrap())
+        /*
+         * IDs don't need coordinating, so the lock file isn't written.
+         */
+        assert!(
+            !std::path::Path::new(&Context::cache_path(&test_context.config.config_dir)).exists()
         );
 
-        assert!(
-            (post_file_contents_2
-                == String::from_str(
-                    r#"
-    fn test2_1() {
-        test_macro!("[ref: 4] Log test 2_1.");
+        /*
+         * Running again has nothing to do.
+         */
+        assert_eq!(generate_code(&test_context), Ok(0));
+        assert_eq!(
+            std::fs::read_to_string(temp_dir.path().join("test_file1.rs")).unwrap(),
+            contents1
+        );
     }
 
-    fn test2_2() {
-        test_macro!("[ref: 5] Log test 2_2.");
-    }"#
-                )
-                .unwrap())
-                || (post_file_contents_2
-                    == String::from_str(
-                        r#"
-    fn test2_1() {
-        test_macro!("[ref: 2] Log test 2_1.");
-    }
+    #[test]
+    fn test_generate_hash_strategy_owner()
+    {
+        use crate::config::context::IdStrategy;
 
-    fn test2_2() {
-        test_macro!("[ref: 3] Log test 2_2.");
-    }"#
-                    )
-                    .unwrap())
-        );
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let mut test_context =
+            create_test_context(&temp_dir.path().to_str().unwrap().to_string(), false, false);
+        test_context.config.id_strategy = IdStrategy::Hash;
+        test_context.id_owner = Some(String::from("alice"));
+
+        std::fs::write(
+            temp_dir.path().join("test_file1.rs"),
+            "fn test1() {\n    test_macro!(\"Log test 1.\");\n}\n",
+        )
+        .unwrap();
+
+        assert!(generate_code(&test_context).is_err());
     }
 
     #[test]
-    fn test_process_insert_references_empty_files()
+    fn test_generate_registry_retires_removed_references()
     {
-        use std::sync::atomic::AtomicU32;
-        use std::sync::Arc;
-
         let temp_dir = TempDir::new("breadlog_test").unwrap();
-        let test_context =
+        let mut test_context =
             create_test_context(&temp_dir.path().to_str().unwrap().to_string(), false, false);
+        test_context.config.use_registry = true;
 
-        let source_file_path_1 = temp_dir
-            .path()
-            .join("test_file1.rs")
-            .to_str()
-            .unwrap()
-            .to_string();
+        let source_path = temp_dir.path().join("test_file1.rs");
 
-        {
-            let _ = File::create(&source_file_path_1).unwrap();
-        }
+        std::fs::write(
+            &source_path,
+            "fn test1() {\n    test_macro!(\"[ref: 1] Log test 1.\");\n    test_macro!(\"Log test 2.\");\n}\n",
+        )
+        .unwrap();
 
-        let source_file_path_2 = temp_dir
-            .path()
-            .join("test_file2.rs")
-            .to_str()
-            .unwrap()
-            .to_string();
+        assert!(generate_code(&test_context).is_ok());
 
-        {
-            let _ = File::create(&source_file_path_2).unwrap();
-        }
+        let registry = test_context.read_registry().unwrap().unwrap();
+        assert_eq!(registry.issued_ids(), HashSet::from([1, 2]));
+        assert!(registry.retired_ids().is_empty());
 
-        let test_finder = CodeFinder::new(&test_context).unwrap();
+        /*
+         * Remove the highest numbered reference and delete the lock file, which would
+         * otherwise allow its ID to be issued again.
+         */
+        std::fs::write(
+            &source_path,
+            "fn test1() {\n    test_macro!(\"[ref: 1] Log test 1.\");\n}\n",
+        )
+        .unwrap();
+        std::fs::remove_file(Context::cache_path(&test_context.config.config_dir)).unwrap();
 
-        let ref_value = AtomicU32::new(2);
-        let ref_arc = Arc::<AtomicU32>::new(ref_value);
+        assert!(generate_code(&test_context).is_ok());
 
-        let insert_result = process_references::<
-            InsertReferencesProcessor,
-            Arc<AtomicU32>,
-            InsertReferencesResult,
-            InsertReferencesResult,
-        >(&test_context, Some(ref_arc), &test_finder)
+        let registry = test_context.read_registry().unwrap().unwrap();
+        assert_eq!(registry.retired_ids(), HashSet::from([2]));
+
+        std::fs::write(
+            &source_path,
+            "fn test1() {\n    test_macro!(\"[ref: 1] Log test 1.\");\n    test_macro!(\"Log test 3.\");\n}\n",
+        )
         .unwrap();
 
-        /* Load the source files after they have finished being processed. Note that we can't reuse the file objects from
-         * earlier because the descriptors will no longer be valid due to the rename operation performed by the processor.
+        /*
+         * There was nothing to insert, so the lock file wasn't recreated.
          */
-        let mut source_file_1 = File::open(&source_file_path_1).unwrap();
-        let mut post_file_contents_1 = String::new();
-        source_file_1
-            .read_to_string(&mut post_file_contents_1)
-            .unwrap();
+        assert!(
+            !std::path::Path::new(&Context::cache_path(&test_context.config.config_dir)).exists()
+        );
 
-        let mut source_file_2 = File::open(&source_file_path_2).unwrap();
-        let mut post_file_contents_2 = String::new();
-        source_file_2
-            .read_to_string(&mut post_file_contents_2)
-            .unwrap();
+        assert!(generate_code(&test_context).is_ok());
+        assert!(std::fs::read_to_string(&source_path)
+            .unwrap()
+            .contains("[ref: 3] Log test 3."));
 
-        assert_eq!(insert_result.failure, false);
-        assert_eq!(insert_result.num_inserted_references, 0);
+        let registry = test_context.read_registry().unwrap().unwrap();
+        assert_eq!(registry.issued_ids(), HashSet::from([1, 2, 3]));
+        assert_eq!(registry.retired_ids(), HashSet::from([2]));
     }
 
     #[test]
-    fn test_process_insert_references_structured_files()
+    fn test_generate_registry_records_message_history()
     {
-        use std::sync::atomic::AtomicU32;
-        use std::sync::Arc;
-
         let temp_dir = TempDir::new("breadlog_test").unwrap();
-        let test_context =
-            create_test_context(&temp_dir.path().to_str().unwrap().to_string(), false, true);
+        let mut test_context =
+            create_test_context(&temp_dir.path().to_str().unwrap().to_string(), false, false);
+        test_context.config.use_registry = true;
 
-        let source_file_path_1 = temp_dir
-            .path()
-            .join("test_file1.rs")
-            .to_str()
-            .unwrap()
-            .to_string();
+        let source_path = temp_dir.path().join("test_file1.rs");
 
-        {
-            let mut source_file = File::create(&source_file_path_1).unwrap();
-            source_file
-                .write_all(
-                    br#"
-    fn test1_1() {
-        test_macro!("Log test 1_1.");
-    }
+        std::fs::write(
+            &source_path,
+            "fn test1() {\n    test_macro!(\"Log test 1.\");\n}\n",
+        )
+        .unwrap();
 
-    fn test1_2() {
-        test_macro!("Log test 1_2.");
-    }"#,
-                )
-                .unwrap();
-        }
+        assert!(generate_code(&test_context).is_ok());
 
-        let source_file_path_2 = temp_dir
-            .path()
-            .join("test_file2.rs")
-            .to_str()
-            .unwrap()
-            .to_string();
+        let registry = test_context.read_registry().unwrap().unwrap();
+        let entry = registry.references.get(&1).unwrap();
+        assert_eq!(entry.message, "Log test 1.");
+        assert_eq!(entry.level, "test_macro");
+        assert_eq!(entry.file, "test_file1.rs");
+        assert!(!entry.first_seen.is_empty());
+        assert!(entry.history.is_empty());
 
-        {
-            let mut source_file = File::create(&source_file_path_2).unwrap();
-            source_file
-                .write_all(
-                    br#"
-    fn test2_1() {
-        test_macro!("Log test 2_1.");
-    }
+        assert_eq!(check_references(&test_context, &mut Vec::new()), Ok(0));
 
-    fn test2_2() {
-        test_macro!("Log test 2_2.");
-    }"#,
-                )
-                .unwrap();
-        }
+        /*
+         * Change the message but keep the ID. There's nothing to insert, but the registry is
+         * still updated.
+         */
+        std::fs::write(
+            &source_path,
+            "fn test1() {\n    test_macro!(\"[ref: 1] Log test one.\");\n}\n",
+        )
+        .unwrap();
 
-        let test_finder = CodeFinder::new(&test_context).unwrap();
+        assert_eq!(
+            check_references(&test_context, &mut Vec::new()),
+            Err("Registry out of sync")
+        );
+        assert!(generate_code(&test_context).is_ok());
+        assert_eq!(check_references(&test_context, &mut Vec::new()), Ok(0));
 
-        let ref_value = AtomicU32::new(2);
-        let ref_arc = Arc::<AtomicU32>::new(ref_value);
+        let registry = test_context.read_registry().unwrap().unwrap();
+        let entry = registry.references.get(&1).unwrap();
+        assert_eq!(entry.message, "Log test one.");
+        assert_eq!(entry.history.len(), 1);
+        assert_eq!(entry.history[0].message, "Log test 1.");
+    }
 
-        let insert_result = process_references::<
-            InsertReferencesProcessor,
-            Arc<AtomicU32>,
-            InsertReferencesResult,
-            InsertReferencesResult,
-        >(&test_context, Some(ref_arc), &test_finder)
-        .unwrap();
+    #[test]
+    fn test_check_retired_reference()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let mut test_context =
+            create_test_context(&temp_dir.path().to_str().unwrap().to_string(), true, false);
+        test_context.config.use_registry = true;
+
+        let record = |id: u32| ReferenceRecord {
+            id,
+            message: format!("Log test {}.", id),
+            level: String::from("test_macro"),
+            file: String::from("test_file1.rs"),
+        };
 
-        /* Load the source files after they have finished being processed. Note that we can't reuse the file objects from
-         * earlier because the descriptors will no longer be valid due to the rename operation performed by the processor.
-         */
-        let mut source_file_1 = File::open(&source_file_path_1).unwrap();
-        let mut post_file_contents_1 = String::new();
-        source_file_1
-            .read_to_string(&mut post_file_contents_1)
-            .unwrap();
+        let mut registry = Registry::default();
+        registry.update(&[record(1), record(2)], "2024-01-01");
+        registry.update(&[record(1)], "2024-01-02");
+        std::fs::write(
+            test_context.registry_path(),
+            Context::registry_contents(&registry).unwrap(),
+        )
+        .unwrap();
 
-        let mut source_file_2 = File::open(&source_file_path_2).unwrap();
-        let mut post_file_contents_2 = String::new();
-        source_file_2
-            .read_to_string(&mut post_file_contents_2)
-            .unwrap();
+        std::fs::write(
+            temp_dir.path().join("test_file1.rs"),
+            "fn test1() {\n    test_macro!(\"[ref: 1] Log test 1.\");\n}\n",
+        )
+        .unwrap();
 
-        assert_eq!(insert_result.failure, false);
-        assert_eq!(insert_result.num_inserted_references, 4);
+        assert_eq!(check_references(&test_context, &mut Vec::new()), Ok(0));
 
-        /* The order in which files are processed is not deterministic, so we need to check for both possible results.
-         */
+        std::fs::write(
+            temp_dir.path().join("test_file1.rs"),
+            "fn test1() {\n    test_macro!(\"[ref: 1] Log test 1.\");\n    test_macro!(\"[ref: 2] Log test 2.\");\n}\n",
+        )
+        .unwrap();
 
-        assert!(
-            (post_file_contents_1
-                == String::from_str(
-                    r#"
-    fn test1_1() {
-        test_macro!(ref = 4; "Log test 1_1.");
-    }
+        assert_eq!(
+            check_references(&test_context, &mut Vec::new()),
+            Err("One or more retired references were found")
+        );
 
-    fn test1_2() {
-        test_macro!(ref = 5; "Log test 1_2.");
-    }"#
-                )
-                .unwrap())
-                || (post_file_contents_1
-                    == String::from_str(
-                        r#"
-    fn test1_1() {
-        test_macro!(ref = 2; "Log test 1_1.");
-    }
+        /*
+         * The registry must be updated when a message changes.
+         */
+        std::fs::write(
+            temp_dir.path().join("test_file1.rs"),
+            "fn test1() {\n    test_macro!(\"[ref: 1] Log test one.\");\n}\n",
+        )
+        .unwrap();
 
-    fn test1_2() {
-        test_macro!(ref = 3; "Log test 1_2.");
-    }"#
-                    )
-                    .unwrap())
+        assert_eq!(
+            check_references(&test_context, &mut Vec::new()),
+            Err("Registry out of sync")
         );
 
-        assert!(
-            (post_file_contents_2
-                == String::from_str(
-                    r#"
-    fn test2_1() {
-        test_macro!(ref = 4; "Log test 2_1.");
-    }
-
-    fn test2_2() {
-        test_macro!(ref = 5; "Log test 2_2.");
-    }"#
-                )
-                .unwrap())
-                || (post_file_contents_2
-                    == String::from_str(
-                        r#"
-    fn test2_1() {
-        test_macro!(ref = 2; "Log test 2_1.");
-    }
+        /*
+         * An unreadable registry can't be ignored.
+         */
+        std::fs::write(test_context.registry_path(), "references: [").unwrap();
 
-    fn test2_2() {
-        test_macro!(ref = 3; "Log test 2_2.");
-    }"#
-                    )
-                    .unwrap())
+        assert_eq!(
+            check_references(&test_context, &mut Vec::new()),
+            Err("Failed to read registry")
         );
     }
 
-    #[test_log::test(async_std::test)]
-    async fn test_create_async_temp_file()
+    #[test]
+    fn test_generate_fixes_malformed_references()
     {
-        use AsyncWriteExt;
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let source_path = temp_dir.path().join("test_file1.rs");
 
-        let temp_file_path: String;
+        std::fs::write(
+            &source_path,
+            "fn test1() {\n    test_macro!(\"[ref:1] Log test 1.\");\n    test_macro!(\"Log test 2. [REF 2]\");\n}\n",
+        )
+        .unwrap();
 
-        {
-            let mut temp_file_result = crate::codegen::generate::AsyncTempFile::new()
-                .await
-                .unwrap();
-            assert_eq!(
-                async_std::fs::metadata(temp_file_result.path())
-                    .await
-                    .is_ok(),
-                true
-            );
+        let check_context =
+            create_test_context(&temp_dir.path().to_str().unwrap().to_string(), true, false);
+        assert_eq!(
+            check_references(&check_context, &mut Vec::new()),
+            Err("One or more malformed references were found")
+        );
 
-            temp_file_path = temp_file_result.path().to_string();
+        std::fs::write(
+            &source_path,
+            "fn test1() {\n    test_macro!(\"[ref:1] Log test 1.\");\n    test_macro!(\"Log test 2. [REF 2]\");\n    test_macro!(\"Log test 3.\");\n}\n",
+        )
+        .unwrap();
 
-            let buffer = String::from_str("test").unwrap();
-            assert_ne!(temp_file_result.path(), "");
-            assert!(temp_file_result
-                .file()
-                .write(buffer.as_bytes())
-                .await
-                .is_ok());
-        }
+        /*
+         * Malformed references are normalised in place, keeping their IDs, in the same pass as
+         * new references are inserted.
+         */
+        let test_context =
+            create_test_context(&temp_dir.path().to_str().unwrap().to_string(), false, false);
+        assert!(generate_code(&test_context).is_ok());
 
-        assert_eq!(async_std::fs::metadata(temp_file_path).await.is_ok(), false);
+        assert_eq!(
+            std::fs::read_to_string(&source_path).unwrap(),
+            "fn test1() {\n    test_macro!(\"[ref: 1] Log test 1.\");\n    test_macro!(\"[ref: 2] Log test 2.\");\n    test_macro!(\"[ref: 3] Log test 3.\");\n}\n"
+        );
+
+        assert_eq!(check_references(&check_context, &mut Vec::new()), Ok(0));
     }
 
     #[test]
-    fn test_generate_no_cache()
+    fn test_generate_directory_structured_override()
     {
         let temp_dir = TempDir::new("breadlog_test").unwrap();
-        let test_context =
+        let mut test_context =
             create_test_context(&temp_dir.path().to_str().unwrap().to_string(), false, false);
+        test_context
+            .config
+            .rust
+            .directories
+            .push(RustDirectoryConfig {
+                path: String::from("audit"),
+                structured: Some(true),
+                log_macros: Vec::new(),
+            });
 
-        let source_file_path_1: String;
-        let source_file_path_2: String;
+        let audit_dir = temp_dir.path().join("audit");
+        std::fs::create_dir(&audit_dir).unwrap();
 
-        {
-            source_file_path_1 = temp_dir
-                .path()
-                .join("test_file1.rs")
-                .to_str()
-                .unwrap()
-                .to_string();
+        std::fs::write(
+            temp_dir.path().join("test_file1.rs"),
+            "fn test1() {\n    test_macro!(\"Log test 1.\");\n}\n",
+        )
+        .unwrap();
+        std::fs::write(
+            audit_dir.join("test_file2.rs"),
+            "fn test2() {\n    test_macro!(\"Log test 2.\");\n}\n",
+        )
+        .unwrap();
 
-            let mut source_file = File::create(&source_file_path_1).unwrap();
-            source_file
-                .write_all(
-                    br#"
-fn test1() {
-    test_macro!("Log test.");
-}
-            "#,
-                )
-                .unwrap();
-        }
+        assert!(generate_code(&test_context).is_ok());
 
-        {
-            source_file_path_2 = temp_dir
-                .path()
-                .join("test_file2.rs")
-                .to_str()
-                .unwrap()
-                .to_string();
+        assert_eq!(
+            std::fs::read_to_string(temp_dir.path().join("test_file1.rs")).unwrap(),
+            "fn test1() {\n    test_macro!(\"[ref: 2] Log test 1.\");\n}\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(audit_dir.join("test_file2.rs")).unwrap(),
+            "fn test2() {\n    test_macro!(ref = 1; \"Log test 2.\");\n}\n"
+        );
+    }
 
-            let mut source_file = File::create(&source_file_path_2).unwrap();
-            source_file
-                .write_all(
-                    br#"
-fn test2() {
-    test_macro!("Log test.");
-}
-            "#,
-                )
-                .unwrap();
-        }
+    #[test]
+    fn test_generate_nested_configs()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let source_dir = temp_dir.path().to_str().unwrap().to_string();
+        let test_context = create_test_context(&source_dir, false, false);
+        let services_dir = temp_dir.path().join("services");
+        let audit_dir = temp_dir.path().join("audit");
 
-        assert!(generate_code(&test_context).is_ok());
+        std::fs::create_dir_all(services_dir.join("api")).unwrap();
+        std::fs::create_dir(&audit_dir).unwrap();
 
-        let file_1_contents = std::fs::read_to_string(&source_file_path_1).unwrap();
-        let file_2_contents = std::fs::read_to_string(&source_file_path_2).unwrap();
+        /*
+         * The configuration file in the configuration directory is the code base's own, so it
+         * isn't read as overriding settings.
+         */
+        std::fs::write(
+            temp_dir.path().join("Breadlog.yaml"),
+            format!("source_dir: {}\n", source_dir),
+        )
+        .unwrap();
+        std::fs::write(
+            services_dir.join("Breadlog.yaml"),
+            "rust:\n  structured: true\n",
+        )
+        .unwrap();
+        std::fs::write(
+            audit_dir.join(".breadlog.yaml"),
+            "rust:\n  log_macros:\n    - module: test_module\n      name: audit_macro\n",
+        )
+        .unwrap();
 
-        let ref_pattern = Regex::new(r"\[ref: ([0-9]{1,10})\]").unwrap();
+        std::fs::write(
+            temp_dir.path().join("test_file1.rs"),
+            "fn test1() {\n    test_macro!(\"Log test 1.\");\n    audit_macro!(\"Log test 2.\");\n}\n",
+        )
+        .unwrap();
+        std::fs::write(
+            services_dir.join("api/test_file2.rs"),
+            "fn test2() {\n    test_macro!(\"Log test 3.\");\n}\n",
+        )
+        .unwrap();
+        std::fs::write(
+            audit_dir.join("test_file3.rs"),
+            "fn test3() {\n    test_macro!(\"Log test 4.\");\n    audit_macro!(\"Log test 5.\");\n}\n",
+        )
+        .unwrap();
 
-        let file_1_match = ref_pattern
-            .captures_iter(file_1_contents.as_str())
-            .next()
-            .unwrap();
-        let file_2_match = ref_pattern
-            .captures_iter(file_2_contents.as_str())
-            .next()
-            .unwrap();
+        assert!(generate_code(&test_context).is_ok());
 
-        let file_1_id = file_1_match[1].parse::<u32>().unwrap();
-        let file_2_id = file_2_match[1].parse::<u32>().unwrap();
+        /*
+         * Reference IDs are allocated across the whole code base.
+         */
+        assert_eq!(
+            std::fs::read_to_string(audit_dir.join("test_file3.rs")).unwrap(),
+            "fn test3() {\n    test_macro!(\"[ref: 1] Log test 4.\");\n    audit_macro!(\"[ref: 2] Log test 5.\");\n}\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(services_dir.join("api/test_file2.rs")).unwrap(),
+            "fn test2() {\n    test_macro!(ref = 3; \"Log test 3.\");\n}\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(temp_dir.path().join("test_file1.rs")).unwrap(),
+            "fn test1() {\n    test_macro!(\"[ref: 4] Log test 1.\");\n    audit_macro!(\"Log test 2.\");\n}\n"
+        );
+        assert_eq!(test_context.reload_cached_next_reference_id(), Some(5));
+        assert!(!services_dir.join("Breadlog.lock").exists());
 
-        assert!(file_1_id != file_2_id);
-        assert!(file_1_id == 1 || file_1_id == 2);
-        assert!(file_2_id == 1 || file_2_id == 2);
+        std::fs::write(services_dir.join("Breadlog.yaml"), "source_dir: .\n").unwrap();
+        assert_eq!(
+            check_references(
+                &create_test_context(&source_dir, true, false),
+                &mut Vec::new()
+            ),
+            Err("Code discovery error")
+        );
     }
 
     #[test]
-    fn test_generate_uses_cache()
+    fn test_generate_multiple_source_dirs()
     {
-        use std::collections::BTreeMap;
-
         let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let config_dir = temp_dir.path().to_str().unwrap().to_string();
 
-        let cache_file_path = temp_dir
-            .path()
-            .join("Breadlog.lock")
-            .to_str()
-            .unwrap()
-            .to_string();
-
+        for (source_dir, file_contents) in [
+            ("server/src", "fn test1() {\n    test_macro!(\"[ref: 1] Log test 1.\");\n    test_macro!(\"Log test 2.\");\n}\n"),
+            ("cli/src", "fn test2() {\n    test_macro!(\"Log test 3.\");\n}\n"),
+        ]
         {
-            let mut cache_file = File::create(&cache_file_path).unwrap();
-            cache_file
-                .write_all(
-                    br#"
----
-next_reference_id: 123
-            "#,
-                )
+            std::fs::create_dir_all(temp_dir.path().join(source_dir)).unwrap();
+            std::fs::write(temp_dir.path().join(source_dir).join("main.rs"), file_contents)
                 .unwrap();
         }
 
-        let test_context =
-            create_test_context(&temp_dir.path().to_str().unwrap().to_string(), false, false);
+        let config_yaml = r#"
+source_dirs:
+  - server/src
+  - cli/src
+use_registry: true
+rust:
+  log_macros:
+    - module: test_module
+      name: test_macro
+"#;
+        let test_context = Context::new(config_yaml.to_string(), &config_dir, false).unwrap();
+        let check_context = Context::new(config_yaml.to_string(), &config_dir, true).unwrap();
 
-        let source_file_path_1: String;
-        let source_file_path_2: String;
+        assert_eq!(
+            check_references(&check_context, &mut Vec::new()),
+            Err("One or more missing references were found")
+        );
 
-        {
-            source_file_path_1 = temp_dir
-                .path()
-                .join("test_file1.rs")
-                .to_str()
-                .unwrap()
-                .to_string();
+        /*
+         * The source directories share one set of reference IDs.
+         */
+        assert!(generate_code(&test_context).is_ok());
 
-            let mut source_file = File::create(&source_file_path_1).unwrap();
-            source_file
-                .write_all(
-                    br#"
-fn test1() {
-    test_macro!("Log test.");
-}
-            "#,
-                )
-                .unwrap();
-        }
+        assert_eq!(
+            std::fs::read_to_string(temp_dir.path().join("cli/src/main.rs")).unwrap(),
+            "fn test2() {\n    test_macro!(\"[ref: 2] Log test 3.\");\n}\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(temp_dir.path().join("server/src/main.rs")).unwrap(),
+            "fn test1() {\n    test_macro!(\"[ref: 1] Log test 1.\");\n    test_macro!(\"[ref: 3] Log test 2.\");\n}\n"
+        );
 
-        {
-            source_file_path_2 = temp_dir
-                .path()
-                .join("test_file2.rs")
-                .to_str()
-                .unwrap()
-                .to_string();
+        assert_eq!(check_references(&check_context, &mut Vec::new()), Ok(0));
 
-            let mut source_file = File::create(&source_file_path_2).unwrap();
-            source_file
-                .write_all(
-                    br#"
-fn test2() {
-    test_macro!("Log test.");
-}
-            "#,
-                )
-                .unwrap();
-        }
+        /*
+         * Files are identified by their path relative to the configuration directory.
+         */
+        let registry = test_context.read_registry().unwrap().unwrap();
+        assert_eq!(registry.references.get(&2).unwrap().file, "cli/src/main.rs");
+        assert_eq!(
+            registry.references.get(&3).unwrap().file,
+            "server/src/main.rs"
+        );
 
-        assert!(generate_code(&test_context).is_ok());
+        let overlapping_context =
+            Context::new(config_yaml.replace("cli/src", "server"), &config_dir, true).unwrap();
+        assert_eq!(
+            check_references(&overlapping_context, &mut Vec::new()),
+            Err("Code discovery error")
+        );
+    }
+    #[test]
+    fn test_generate_reference_policies()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let source_dir = temp_dir.path().to_str().unwrap().to_string();
+        let config_yaml = format!(
+            r#"
+source_dir: {}
+rust:
+  log_macros:
+    - module: test_module
+      name: test_macro
+    - module: test_module
+      name: debug_macro
+      policy: optional
+    - module: test_module
+      name: trace_macro
+      policy: forbidden
+"#,
+            source_dir
+        );
+        let test_context = Context::new(config_yaml.clone(), &source_dir, false).unwrap();
+        let check_context = Context::new(config_yaml, &source_dir, true).unwrap();
 
-        let file_1_contents = std::fs::read_to_string(&source_file_path_1).unwrap();
-        let file_2_contents = std::fs::read_to_string(&source_file_path_2).unwrap();
-        let cache_contents = std::fs::read_to_string(&cache_file_path).unwrap();
+        let source_path = temp_dir.path().join("test_file1.rs");
 
-        let ref_pattern = Regex::new(r"\[ref: ([0-9]{1,10})\]").unwrap();
+        std::fs::write(
+            &source_path,
+            "fn test1() {\n    test_macro!(\"Log test 1.\");\n    debug_macro!(\"Log test 2.\");\n    debug_macro!(\"[ref: 5] Log test 3.\");\n    trace_macro!(\"Log test 4.\");\n}\n",
+        )
+        .unwrap();
 
-        let file_1_match = ref_pattern
-            .captures_iter(file_1_contents.as_str())
-            .next()
-            .unwrap();
-        let file_2_match = ref_pattern
-            .captures_iter(file_2_contents.as_str())
-            .next()
-            .unwrap();
+        /*
+         * Only macros that require a reference are given one, but existing references in
+         * optional macros are kept and considered when choosing new IDs.
+         */
+        assert!(generate_code(&test_context).is_ok());
+        assert_eq!(
+            std::fs::read_to_string(&source_path).unwrap(),
+            "fn test1() {\n    test_macro!(\"[ref: 6] Log test 1.\");\n    debug_macro!(\"Log test 2.\");\n    debug_macro!(\"[ref: 5] Log test 3.\");\n    trace_macro!(\"Log test 4.\");\n}\n"
+        );
 
-        let file_1_id = file_1_match[1].parse::<u32>().unwrap();
-        let file_2_id = file_2_match[1].parse::<u32>().unwrap();
+        assert_eq!(check_references(&check_context, &mut Vec::new()), Ok(0));
 
-        assert!(file_1_id != file_2_id);
-        assert!(file_1_id == 123 || file_1_id == 124);
-        assert!(file_2_id == 123 || file_2_id == 124);
+        std::fs::write(
+            &source_path,
+            "fn test1() {\n    test_macro!(\"[ref: 6] Log test 1.\");\n    trace_macro!(\"[ref: 7] Log test 4.\");\n}\n",
+        )
+        .unwrap();
 
-        let loaded_cache: BTreeMap<String, u32> =
-            serde_yaml::from_str(cache_contents.as_str()).unwrap();
-        assert_eq!(loaded_cache["next_reference_id"], 125);
+        assert_eq!(
+            check_references(&check_context, &mut Vec::new()),
+            Err("One or more forbidden references were found")
+        );
     }
 }
@@ -0,0 +1,204 @@
+use super::generate::{MAX_REFERENCE_ID, START_REFERENCE_ID};
+use std::collections::HashSet;
+
+/// The FNV-1a offset basis for 64-bit hashes.
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+
+/// The FNV-1a prime for 64-bit hashes.
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Separates the fields of a hashed key, so that moving characters from one field to the next
+/// changes the hash. This byte never appears in UTF-8 text.
+const FIELD_SEPARATOR: u8 = 0xff;
+
+/// Hash a sequence of fields using 64-bit FNV-1a. FNV-1a is used (rather than the standard
+/// library's hasher) because its output is specified, so IDs never change between Breadlog
+/// versions or platforms.
+///
+/// # Arguments
+///
+/// * `fields` - The fields to hash.
+///
+/// # Returns
+///
+/// The hash of the fields.
+fn fnv1a(fields: &[&[u8]]) -> u64
+{
+    let mut hash = FNV_OFFSET_BASIS;
+
+    for field in fields
+    {
+        for byte in field.iter().chain(std::iter::once(&FIELD_SEPARATOR))
+        {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    hash
+}
+
+/// Derive a candidate reference ID for a log message.
+///
+/// # Arguments
+///
+/// * `path` - The path of the file containing the log message, relative to the source directory.
+/// * `message` - The log message template.
+/// * `salt` - The configured salt.
+/// * `attempt` - The number of candidates already rejected because they collided with another ID.
+///
+/// # Returns
+///
+/// A reference ID between `START_REFERENCE_ID` and `MAX_REFERENCE_ID`, inclusive.
+pub fn hash_reference_id(path: &str, message: &str, salt: &str, attempt: u32) -> u32
+{
+    let hash = fnv1a(&[
+        path.as_bytes(),
+        message.as_bytes(),
+        salt.as_bytes(),
+        &attempt.to_le_bytes(),
+    ]);
+
+    /*
+     * Fold the hash so that every bit contributes to the ID.
+     */
+    let folded = ((hash >> 32) ^ hash) as u32;
+
+    START_REFERENCE_ID + folded % (MAX_REFERENCE_ID - START_REFERENCE_ID + 1)
+}
+
+/// Allocates reference IDs derived from log message content, avoiding every ID already in use.
+///
+/// When a candidate ID is taken, the next candidate is derived by hashing the same message
+/// with an incremented attempt number. Provided messages are allocated IDs in a stable order,
+/// the same code always produces the same IDs.
+pub struct HashedIdAllocator
+{
+    /// The IDs already in use, including those allocated by this allocator.
+    used: HashSet<u32>,
+
+    /// The configured salt.
+    salt: String,
+}
+
+impl HashedIdAllocator
+{
+    /// Create a new allocator.
+    ///
+    /// # Arguments
+    ///
+    /// * `used` - The IDs already in use in the code base.
+    /// * `salt` - The configured salt.
+    pub fn new(used: HashSet<u32>, salt: &str) -> HashedIdAllocator
+    {
+        HashedIdAllocator {
+            used,
+            salt: salt.to_string(),
+        }
+    }
+
+    /// Allocate an ID for a log message.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the file containing the log message, relative to the source
+    ///   directory.
+    /// * `message` - The log message template.
+    ///
+    /// # Returns
+    ///
+    /// The allocated ID, or `None` if every ID is in use.
+    pub fn allocate(&mut self, path: &str, message: &str) -> Option<u32>
+    {
+        if self.used.len() > (MAX_REFERENCE_ID - START_REFERENCE_ID) as usize
+        {
+            return None;
+        }
+
+        let mut attempt: u32 = 0;
+
+        loop
+        {
+            let candidate = hash_reference_id(path, message, &self.salt, attempt);
+
+            if self.used.insert(candidate)
+            {
+                return Some(candidate);
+            }
+
+            attempt = attempt.wrapping_add(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn test_hash_reference_id_stable()
+    {
+        /*
+         * IDs must never change between versions, or references generated on different
+         * branches with different Breadlog versions would differ.
+         */
+        assert_eq!(fnv1a(&[]), FNV_OFFSET_BASIS);
+        assert_eq!(fnv1a(&[b"a"]), 0x089b_c907_b544_c769);
+
+        assert_eq!(
+            hash_reference_id("src/main.rs", "Started", "", 0),
+            2_442_707_582
+        );
+        assert_eq!(
+            hash_reference_id("src/main.rs", "Started", "", 1),
+            668_608_305
+        );
+        assert_eq!(
+            hash_reference_id("src/main.rs", "Started", "team-a", 0),
+            230_048_209
+        );
+    }
+
+    #[test]
+    fn test_hash_reference_id_inputs()
+    {
+        let id = hash_reference_id("src/main.rs", "Started", "", 0);
+
+        assert_ne!(id, hash_reference_id("src/lib.rs", "Started", "", 0));
+        assert_ne!(id, hash_reference_id("src/main.rs", "Stopped", "", 0));
+        assert_ne!(id, hash_reference_id("src/main.rs", "Started", "salt", 0));
+        assert_ne!(id, hash_reference_id("src/main.rs", "Started", "", 1));
+
+        /*
+         * Field boundaries are significant.
+         */
+        assert_ne!(
+            hash_reference_id("ab", "c", "", 0),
+            hash_reference_id("a", "bc", "", 0)
+        );
+    }
+
+    #[test]
+    fn test_allocate_probes_on_collision()
+    {
+        let first_choice = hash_reference_id("src/main.rs", "Started", "", 0);
+        let second_choice = hash_reference_id("src/main.rs", "Started", "", 1);
+
+        let mut allocator = HashedIdAllocator::new(HashSet::from([first_choice]), "");
+
+        assert_eq!(
+            allocator.allocate("src/main.rs", "Started"),
+            Some(second_choice)
+        );
+
+        /*
+         * The same message in the same file is given a different ID each time.
+         */
+        let third_choice = hash_reference_id("src/main.rs", "Started", "", 2);
+        assert_eq!(
+            allocator.allocate("src/main.rs", "Started"),
+            Some(third_choice)
+        );
+    }
+}
@@ -0,0 +1,809 @@
+use super::generate::scan_next_reference_id;
+use crate::config::workspace::{self, MANIFEST_FILENAME};
+use crate::config::{Config, Context};
+use ignore::WalkBuilder;
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::collections::{BTreeSet, HashSet};
+use std::path::{Path, PathBuf};
+
+/// A logging crate whose macros can be searched for.
+struct LoggingCrate
+{
+    /// The name of the crate, as a dependency.
+    name: &'static str,
+
+    /// The module containing the crate's log macros.
+    module: &'static str,
+
+    /// The names of the crate's log macros.
+    macros: &'static [&'static str],
+}
+
+/// The logging crates recognised in a manifest's dependencies. log4rs is a backend for the log
+/// crate, so code using it logs with the log crate's macros.
+const LOGGING_CRATES: [LoggingCrate; 5] = [
+    LoggingCrate {
+        name: "log",
+        module: "log",
+        macros: &["error", "warn", "info", "debug", "trace"],
+    },
+    LoggingCrate {
+        name: "log4rs",
+        module: "log",
+        macros: &["error", "warn", "info", "debug", "trace"],
+    },
+    LoggingCrate {
+        name: "tracing",
+        module: "tracing",
+        macros: &["error", "warn", "info", "debug", "trace"],
+    },
+    LoggingCrate {
+        name: "slog",
+        module: "slog",
+        macros: &["crit", "error", "warn", "info", "debug", "trace"],
+    },
+    LoggingCrate {
+        name: "defmt",
+        module: "defmt",
+        macros: &["error", "warn", "info", "debug", "trace"],
+    },
+];
+
+/// A log macro that could be searched for, and how often the code base uses it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DetectedMacro
+{
+    /// The module containing the macro.
+    pub module: String,
+
+    /// The name of the macro.
+    pub name: String,
+
+    /// The number of times the macro is invoked.
+    pub uses: usize,
+}
+
+/// Where the scaffolded configuration finds the source code.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SourceLayout
+{
+    /// A single source directory, relative to the configuration file.
+    Directory(String),
+
+    /// The crates of a Cargo workspace.
+    CargoWorkspace(usize),
+}
+
+/// A configuration inferred from a code base.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Scaffold
+{
+    /// Where the source code is.
+    pub layout: SourceLayout,
+
+    /// The logging crates found in the manifest's dependencies.
+    pub logging_crates: Vec<String>,
+
+    /// The log macros of the logging crates, in the order they're listed.
+    pub macros: Vec<DetectedMacro>,
+
+    /// The number of log macro invocations using key-value pairs.
+    pub kv_uses: usize,
+
+    /// The number of log macro invocations that don't say which of several logging crates'
+    /// macros they call, and were counted for the first of them.
+    pub ambiguous_uses: usize,
+}
+
+impl Scaffold
+{
+    /// Returns the total number of log macro invocations.
+    pub fn uses(&self) -> usize
+    {
+        self.macros.iter().map(|m| m.uses).sum()
+    }
+
+    /// Whether or not to suggest creating references using structured logging features: only
+    /// if most log statements already use key-value pairs.
+    pub fn structured(&self) -> bool
+    {
+        self.kv_uses * 2 > self.uses()
+    }
+
+    /// Generate a commented configuration file. Macros that aren't used yet are listed, but
+    /// commented out, unless no macros are used yet.
+    ///
+    /// # Returns
+    ///
+    /// The configuration file contents.
+    pub fn to_yaml(&self) -> String
+    {
+        let mut yaml = String::from(
+            "---\n# Generated by breadlog init. All the options are described at\n# https://breadlog.readthedocs.io/en/stable/source/configuration.html\n\n",
+        );
+
+        yaml.push_str(&format!("version: {}\n\n", Config::CURRENT_VERSION));
+
+        match &self.layout
+        {
+            SourceLayout::Directory(dir) =>
+            {
+                yaml.push_str("# The location of the source code to process.\n");
+                yaml.push_str(&format!("source_dir: {}\n", dir));
+            },
+            SourceLayout::CargoWorkspace(crates) =>
+            {
+                yaml.push_str(&format!(
+                    "# The source code is found from the {} crate(s) of the Cargo workspace.\n",
+                    crates
+                ));
+                yaml.push_str("source: cargo_workspace\n");
+            },
+        }
+
+        yaml.push_str("\nrust:\n");
+        yaml.push_str(&format!(
+            "  # {} of {} log statement(s) use key-value pairs. Structured references\n  # need the log crate's \"kv\" feature.\n",
+            self.kv_uses,
+            self.uses()
+        ));
+        yaml.push_str(&format!("  structured: {}\n\n", self.structured()));
+
+        match self.logging_crates.is_empty()
+        {
+            true => yaml.push_str(
+                "  # No logging crates were found in Cargo.toml, so the log crate is assumed.\n",
+            ),
+            false => yaml.push_str(&format!(
+                "  # Logging crates found in Cargo.toml: {}.\n",
+                self.logging_crates.join(", ")
+            )),
+        }
+
+        if self.ambiguous_uses > 0
+        {
+            yaml.push_str(&format!(
+                "  # {} log statement(s) call a macro that more than one of these crates has,\n  # without importing it, so they're counted for the first crate listed below.\n",
+                self.ambiguous_uses
+            ));
+        }
+
+        /*
+         * At least one macro must be configured, so if none are used yet, they're all kept.
+         */
+        let comment_unused = self.uses() > 0;
+
+        if comment_unused
+        {
+            yaml.push_str("  # Macros that aren't used yet are commented out.\n");
+        }
+
+        yaml.push_str("  log_macros:\n");
+
+        for detected_macro in self.macros.iter()
+        {
+            let prefix = match comment_unused && detected_macro.uses == 0
+            {
+                true => "# ",
+                false => "",
+            };
+
+            yaml.push_str(&format!(
+                "    {}- module: {}\n    {}  name: {}\n",
+                prefix, detected_macro.module, prefix, detected_macro.name
+            ));
+        }
+
+        yaml
+    }
+}
+
+/// Find the logging crates in a manifest's dependencies, including renamed dependencies and
+/// those specific to a target. For a workspace, the dependencies of its members are included.
+///
+/// # Arguments
+///
+/// * `manifest_path` - The path of the manifest.
+///
+/// # Returns
+///
+/// The names of the logging crates, otherwise an error message on failure.
+fn logging_dependencies(manifest_path: &Path) -> Result<BTreeSet<String>, String>
+{
+    let dependency_names = |manifest: &toml::Table| -> Vec<String> {
+        let mut tables: Vec<&toml::Value> = ["dependencies", "dev-dependencies"]
+            .iter()
+            .filter_map(|t| manifest.get(*t))
+            .collect();
+
+        tables.extend(
+            manifest
+                .get("workspace")
+                .and_then(|w| w.get("dependencies")),
+        );
+
+        if let Some(toml::Value::Table(targets)) = manifest.get("target")
+        {
+            tables.extend(targets.values().filter_map(|t| t.get("dependencies")));
+        }
+
+        tables
+            .iter()
+            .filter_map(|t| t.as_table())
+            .flat_map(|t| t.iter())
+            .map(|(name, spec)| {
+                spec.get("package")
+                    .and_then(|p| p.as_str())
+                    .unwrap_or(name)
+                    .to_string()
+            })
+            .collect()
+    };
+
+    let mut manifests = vec![workspace::read_manifest(manifest_path)?];
+
+    if let Ok(crates) = workspace::workspace_crates(manifest_path)
+    {
+        for workspace_crate in crates.iter()
+        {
+            manifests.push(workspace::read_manifest(
+                &workspace_crate.path.join(MANIFEST_FILENAME),
+            )?);
+        }
+    }
+
+    Ok(manifests
+        .iter()
+        .flat_map(dependency_names)
+        .filter(|d| LOGGING_CRATES.iter().any(|c| c.name == d))
+        .collect())
+}
+
+/// Expand a `use` declaration's tree into the paths it imports, such as `log::{info, warn}`
+/// into `log::info` and `log::warn`. Renamed imports are left out, as they don't import the
+/// original name.
+///
+/// # Arguments
+///
+/// * `tree` - The use tree, without `use` and the trailing semicolon.
+///
+/// # Returns
+///
+/// The imported paths, split into their segments.
+fn use_paths(tree: &str) -> Vec<Vec<String>>
+{
+    let tree: String = tree.split_whitespace().collect::<Vec<_>>().join(" ");
+
+    let brace = match tree.find('{')
+    {
+        Some(b) => b,
+        None if tree.contains(" as ") => return Vec::new(),
+        None =>
+        {
+            return vec![tree
+                .split("::")
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect()]
+        },
+    };
+
+    let prefix = &tree[..brace];
+    let inner = tree[brace + 1..]
+        .strip_suffix('}')
+        .unwrap_or(&tree[brace + 1..]);
+    let mut items = Vec::new();
+    let mut depth = 0;
+    let mut item_start = 0;
+
+    for (index, c) in inner.char_indices()
+    {
+        match c
+        {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            ',' if depth == 0 =>
+            {
+                items.push(&inner[item_start..index]);
+                item_start = index + 1;
+            },
+            _ => (),
+        }
+    }
+
+    items.push(&inner[item_start..]);
+
+    items
+        .iter()
+        .filter(|i| !i.trim().is_empty())
+        .flat_map(|i| use_paths(&format!("{}{}", prefix, i.trim())))
+        .collect()
+}
+
+/// Find the modules a Rust file imports log macros from, so that an invocation that doesn't
+/// name a module can be attributed to the right logging crate.
+///
+/// # Arguments
+///
+/// * `code` - The contents of the file.
+///
+/// # Returns
+///
+/// The imported macros, as (module, name) pairs, and the modules whose macros are all imported
+/// (with `*` or `#[macro_use]`).
+fn imported_macros(code: &str) -> (HashSet<(String, String)>, HashSet<String>)
+{
+    lazy_static! {
+        static ref USE_PATTERN: Regex =
+            Regex::new(r"(?m)^\s*(?:pub(?:\([^)]*\))?\s+)?use\s+([^;]+);").unwrap();
+        static ref MACRO_USE_PATTERN: Regex =
+            Regex::new(r"#\[macro_use\]\s*extern\s+crate\s+([A-Za-z_][A-Za-z0-9_]*)").unwrap();
+    }
+
+    let mut imported = HashSet::new();
+    let mut glob_modules: HashSet<String> = MACRO_USE_PATTERN
+        .captures_iter(code)
+        .map(|c| c[1].to_string())
+        .collect();
+
+    for path in USE_PATTERN
+        .captures_iter(code)
+        .flat_map(|c| use_paths(&c[1]))
+    {
+        if let Some((name, module)) = path.split_last()
+        {
+            match name.as_str()
+            {
+                "*" => glob_modules.insert(module.join("::")),
+                _ => imported.insert((module.join("::"), name.clone())),
+            };
+        }
+    }
+
+    (imported, glob_modules)
+}
+
+/// Count the log macro invocations in the Rust files in some directories. An invocation that
+/// doesn't name a module is attributed using the file's imports, and if that leaves more than
+/// one macro it could be, it's counted for the first of them.
+///
+/// # Arguments
+///
+/// * `dirs` - The directories to search. Ignore files and hidden files are respected.
+/// * `macros` - The macros to count, whose counts are updated.
+///
+/// # Returns
+///
+/// The number of invocations using key-value pairs, and the number that could be any of several
+/// macros.
+fn count_macro_uses(dirs: &[PathBuf], macros: &mut [DetectedMacro]) -> (usize, usize)
+{
+    lazy_static! {
+        static ref INVOCATION_PATTERN: Regex =
+            Regex::new(r"(?:\b([A-Za-z_][A-Za-z0-9_]*)::)?\b([A-Za-z_][A-Za-z0-9_]*)!\s*\(")
+                .unwrap();
+        static ref KV_PATTERN: Regex =
+            Regex::new(r#"^\s*(?:target:\s*[^;"]*,\s*)?[A-Za-z_][A-Za-z0-9_.]*\s*=[^;"]*;"#)
+                .unwrap();
+    }
+
+    let mut kv_uses = 0;
+    let mut ambiguous_uses = 0;
+
+    let mut walker = match dirs.split_first()
+    {
+        Some((first, rest)) =>
+        {
+            let mut walker = WalkBuilder::new(first);
+            rest.iter().for_each(|d| {
+                walker.add(d);
+            });
+            walker
+        },
+        None => return (0, 0),
+    };
+
+    for entry in walker
+        .require_git(false)
+        .build()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().extension().is_some_and(|x| x == "rs"))
+    {
+        let code = match std::fs::read_to_string(entry.path())
+        {
+            Ok(c) => c,
+            Err(_) => continue,
+        };
+
+        let (imported, glob_modules) = imported_macros(&code);
+
+        for invocation in INVOCATION_PATTERN.captures_iter(&code)
+        {
+            let name = &invocation[2];
+
+            let candidates: Vec<usize> = match invocation.get(1)
+            {
+                Some(module) => macros
+                    .iter()
+                    .position(|m| m.name == name && m.module == module.as_str())
+                    .into_iter()
+                    .collect(),
+                None =>
+                {
+                    let named: Vec<usize> = (0..macros.len())
+                        .filter(|i| macros[*i].name == name)
+                        .collect();
+
+                    let explicit: Vec<usize> = named
+                        .iter()
+                        .copied()
+                        .filter(|i| {
+                            imported.contains(&(macros[*i].module.clone(), name.to_string()))
+                        })
+                        .collect();
+                    let globbed: Vec<usize> = named
+                        .iter()
+                        .copied()
+                        .filter(|i| glob_modules.contains(&macros[*i].module))
+                        .collect();
+
+                    match (explicit.is_empty(), globbed.is_empty())
+                    {
+                        (false, _) => explicit,
+                        (true, false) => globbed,
+                        (true, true) => named,
+                    }
+                },
+            };
+
+            if candidates.len() > 1
+            {
+                ambiguous_uses += 1;
+            }
+
+            let matched = match candidates.first()
+            {
+                Some(index) =>
+                {
+                    macros[*index].uses += 1;
+                    true
+                },
+                None => false,
+            };
+
+            let arguments = &code[invocation.get(0).map_or(0, |m| m.end())..];
+
+            if matched && KV_PATTERN.is_match(arguments)
+            {
+                kv_uses += 1;
+            }
+        }
+    }
+
+    (kv_uses, ambiguous_uses)
+}
+
+/// Infer a configuration for a code base from its Cargo manifest, if it has one, and its use of
+/// log macros.
+///
+/// # Arguments
+///
+/// * `dir` - The code base's root directory.
+///
+/// # Returns
+///
+/// The inferred configuration, otherwise an error message on failure.
+pub fn scaffold_config(dir: &Path) -> Result<Scaffold, String>
+{
+    let manifest_path = dir.join(MANIFEST_FILENAME);
+    let has_manifest = manifest_path.is_file();
+
+    let logging_crates = match has_manifest
+    {
+        true => logging_dependencies(&manifest_path)?,
+        false => BTreeSet::new(),
+    };
+
+    /*
+     * Only a workspace's source is found from its crates, so that a single package can be
+     * configured with its source directory.
+     */
+    let crates = match has_manifest
+        && workspace::read_manifest(&manifest_path)?.contains_key("workspace")
+    {
+        true => workspace::workspace_crates(&manifest_path)?,
+        false => Vec::new(),
+    };
+
+    let (layout, source_dirs) = match crates.len()
+    {
+        0 if dir.join("src").is_dir() => (
+            SourceLayout::Directory(String::from("./src")),
+            vec![dir.join("src")],
+        ),
+        0 => (
+            SourceLayout::Directory(String::from(".")),
+            vec![dir.to_path_buf()],
+        ),
+        count => (
+            SourceLayout::CargoWorkspace(count),
+            crates.iter().flat_map(|c| c.source_dirs(false)).collect(),
+        ),
+    };
+
+    /*
+     * Without a recognised logging crate, assume the log crate.
+     */
+    let mut macros: Vec<DetectedMacro> = Vec::new();
+
+    for logging_crate in LOGGING_CRATES.iter().filter(|c| {
+        logging_crates.contains(c.name) || (logging_crates.is_empty() && c.name == "log")
+    })
+    {
+        for name in logging_crate.macros.iter()
+        {
+            if !macros
+                .iter()
+                .any(|m| m.module == logging_crate.module && m.name == *name)
+            {
+                macros.push(DetectedMacro {
+                    module: logging_crate.module.to_string(),
+                    name: name.to_string(),
+                    uses: 0,
+                });
+            }
+        }
+    }
+
+    let (kv_uses, ambiguous_uses) = count_macro_uses(&source_dirs, &mut macros);
+
+    Ok(Scaffold {
+        layout,
+        logging_crates: logging_crates.into_iter().collect(),
+        macros,
+        kv_uses,
+        ambiguous_uses,
+    })
+}
+
+/// Write a configuration file inferred from a code base, along with an initial lock file
+/// recording the next reference ID after those already in the code.
+///
+/// # Arguments
+///
+/// * `config_path` - The path of the configuration file to write. Its directory is the code
+///   base's root directory.
+/// * `force` - Whether or not to overwrite an existing configuration file.
+///
+/// # Returns
+///
+/// The inferred configuration, otherwise an error message on failure.
+pub fn init_config(config_path: &Path, force: bool) -> Result<Scaffold, String>
+{
+    if config_path.exists() && !force
+    {
+        return Err(format!(
+            "{} already exists - use --force to overwrite it",
+            config_path.to_string_lossy()
+        ));
+    }
+
+    let dir = match config_path.parent()
+    {
+        Some(d) if !d.as_os_str().is_empty() => d.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+
+    let scaffold = scaffold_config(&dir)?;
+    let yaml = scaffold.to_yaml();
+    let dir_str = dir.to_string_lossy().to_string();
+
+    /*
+     * Check the configuration loads before writing it.
+     */
+    let context = Context::new(yaml.clone(), &dir_str, false)?;
+
+    if let Err(e) = std::fs::write(config_path, &yaml)
+    {
+        return Err(format!(
+            "Failed to write {}: {}",
+            config_path.to_string_lossy(),
+            e
+        ));
+    }
+
+    if context.cached_next_reference_id.is_none()
+    {
+        match scan_next_reference_id(&context)
+        {
+            Some(id) => context.cache_next_reference_id(id, &dir_str),
+            None => return Err("Failed to find the next reference ID".to_string()),
+        }
+    }
+
+    Ok(scaffold)
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_scaffold_config()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let root = temp_dir.path();
+
+        std::fs::create_dir(root.join("src")).unwrap();
+        std::fs::write(
+            root.join(MANIFEST_FILENAME),
+            "[package]\nname = \"app\"\n\n[dependencies]\nlogging = { package = \"log\", version = \"0.4\" }\nserde = \"1\"\n\n[target.'cfg(unix)'.dependencies]\ntracing = \"0.1\"\n",
+        )
+        .unwrap();
+        std::fs::write(
+            root.join("src/main.rs"),
+            "fn main() {\n    info!(\"Started\");\n    log::warn!(user = name; \"Unknown user\");\n    tracing::info!(\"Traced\");\n    println!(\"Done\");\n}\n",
+        )
+        .unwrap();
+
+        let scaffold = scaffold_config(root).unwrap();
+
+        assert_eq!(
+            scaffold.layout,
+            SourceLayout::Directory(String::from("./src"))
+        );
+        assert_eq!(scaffold.logging_crates, vec!["log", "tracing"]);
+        assert_eq!(
+            scaffold
+                .macros
+                .iter()
+                .filter(|m| m.uses > 0)
+                .map(|m| (m.module.as_str(), m.name.as_str(), m.uses))
+                .collect::<Vec<_>>(),
+            vec![
+                ("log", "warn", 1),
+                ("log", "info", 1),
+                ("tracing", "info", 1),
+            ]
+        );
+        assert_eq!(scaffold.kv_uses, 1);
+        assert_eq!(scaffold.ambiguous_uses, 1);
+        assert!(!scaffold.structured());
+
+        let yaml = scaffold.to_yaml();
+
+        assert!(yaml.contains("version: 1\n"));
+        assert!(yaml.contains("source_dir: ./src\n"));
+        assert!(yaml.contains("    - module: log\n      name: warn\n"));
+        assert!(yaml.contains("    # - module: log\n    #   name: trace\n"));
+        assert!(yaml.contains("structured: false\n"));
+        assert!(yaml.contains("  # 1 log statement(s) call a macro that more than one"));
+    }
+
+    #[test]
+    fn test_use_paths()
+    {
+        assert_eq!(use_paths("log::info"), vec![vec!["log", "info"]]);
+        assert_eq!(
+            use_paths("::tracing::{info, warn as warning, instrument::{self, *}}"),
+            vec![
+                vec!["tracing", "info"],
+                vec!["tracing", "instrument", "self"],
+                vec!["tracing", "instrument", "*"],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scaffold_config_imported_macros()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let root = temp_dir.path();
+
+        std::fs::create_dir(root.join("src")).unwrap();
+        std::fs::write(
+            root.join(MANIFEST_FILENAME),
+            "[package]\nname = \"app\"\n\n[dependencies]\nlog = \"0.4\"\ntracing = \"0.1\"\nslog = \"2\"\n",
+        )
+        .unwrap();
+        std::fs::write(
+            root.join("src/main.rs"),
+            "use tracing::{\n    info,\n    warn,\n};\n\nfn main() {\n    info!(\"Started\");\n    error!(\"Failed\");\n}\n",
+        )
+        .unwrap();
+        std::fs::write(
+            root.join("src/audit.rs"),
+            "use slog::*;\n\nfn audit() {\n    warn!(\"Audited\");\n    crit!(\"Tampered\");\n}\n",
+        )
+        .unwrap();
+
+        let scaffold = scaffold_config(root).unwrap();
+
+        let mut uses = scaffold
+            .macros
+            .iter()
+            .filter(|m| m.uses > 0)
+            .map(|m| (m.module.as_str(), m.name.as_str(), m.uses))
+            .collect::<Vec<_>>();
+        uses.sort();
+
+        assert_eq!(
+            uses,
+            vec![
+                ("log", "error", 1),
+                ("slog", "crit", 1),
+                ("slog", "warn", 1),
+                ("tracing", "info", 1),
+            ]
+        );
+        assert_eq!(scaffold.ambiguous_uses, 1);
+    }
+
+    #[test]
+    fn test_scaffold_config_without_manifest()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+
+        std::fs::write(
+            temp_dir.path().join("main.rs"),
+            "fn main() {\n    error!(code = 1; \"Failed\");\n}\n",
+        )
+        .unwrap();
+
+        let scaffold = scaffold_config(temp_dir.path()).unwrap();
+
+        assert_eq!(scaffold.layout, SourceLayout::Directory(String::from(".")));
+        assert!(scaffold.logging_crates.is_empty());
+        assert_eq!(scaffold.uses(), 1);
+        assert!(scaffold.structured());
+    }
+
+    #[test]
+    fn test_init_config()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let root = temp_dir.path();
+        let config_path = root.join("Breadlog.yaml");
+
+        std::fs::write(
+            root.join(MANIFEST_FILENAME),
+            "[workspace]\nmembers = [\"crates/*\"]\n\n[workspace.dependencies]\nslog = \"2\"\n",
+        )
+        .unwrap();
+        std::fs::create_dir_all(root.join("crates/net/src")).unwrap();
+        std::fs::write(
+            root.join("crates/net/Cargo.toml"),
+            "[package]\nname = \"net\"\n",
+        )
+        .unwrap();
+        std::fs::write(
+            root.join("crates/net/src/lib.rs"),
+            "fn connect() {\n    crit!(\"[ref: 41] Connection failed\");\n}\n",
+        )
+        .unwrap();
+
+        let scaffold = init_config(&config_path, false).unwrap();
+
+        assert_eq!(scaffold.layout, SourceLayout::CargoWorkspace(1));
+
+        let context = Context::new(
+            std::fs::read_to_string(&config_path).unwrap(),
+            root.to_str().unwrap(),
+            true,
+        )
+        .unwrap();
+
+        assert_eq!(context.config.rust.log_macros.len(), 1);
+        assert_eq!(context.config.rust.log_macros[0].module, "slog");
+        assert_eq!(context.config.rust.log_macros[0].name, "crit");
+        assert_eq!(context.cached_next_reference_id, Some(42));
+
+        assert!(init_config(&config_path, false)
+            .unwrap_err()
+            .contains("already exists"));
+        assert!(init_config(&config_path, true).is_ok());
+    }
+}
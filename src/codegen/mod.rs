@@ -1,3 +1,11 @@
 pub use finder::CodeFinder;
+pub mod conflicts;
+pub mod diff;
 pub mod finder;
 pub mod generate;
+pub mod hashed_ids;
+pub mod init;
+pub mod report;
+pub mod reservations;
+pub mod run_lock;
+pub mod transaction;
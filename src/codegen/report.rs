@@ -0,0 +1,1107 @@
+use super::generate::{process_references, ReferenceProcessor};
+use super::CodeFinder;
+use crate::config::context::ReferencePolicy;
+use crate::config::registry::Registry;
+use crate::config::workspace::{self, WorkspaceCrate};
+use crate::config::Context;
+use crate::parser;
+use async_trait::async_trait;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+/// The kinds of problem reported by check mode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FindingKind
+{
+    /// A log statement without a reference.
+    Missing,
+
+    /// A log statement that can't be given a reference, which is ignored.
+    Unusable,
+
+    /// A reference ID used by an earlier log statement.
+    Duplicate,
+
+    /// A reference ID the registry records as retired.
+    Retired,
+
+    /// A reference that isn't in canonical form at the start of the log message.
+    Malformed,
+
+    /// A reference in a log statement whose macro forbids references.
+    Forbidden,
+}
+
+impl FindingKind
+{
+    const ALL: [FindingKind; 6] = [
+        FindingKind::Missing,
+        FindingKind::Unusable,
+        FindingKind::Duplicate,
+        FindingKind::Retired,
+        FindingKind::Malformed,
+        FindingKind::Forbidden,
+    ];
+
+    /// Returns the identifier of the rule the finding breaks.
+    pub fn rule_id(&self) -> &'static str
+    {
+        match self
+        {
+            FindingKind::Missing => "missing-reference",
+            FindingKind::Unusable => "unusable-reference",
+            FindingKind::Duplicate => "duplicate-reference",
+            FindingKind::Retired => "retired-reference",
+            FindingKind::Malformed => "malformed-reference",
+            FindingKind::Forbidden => "forbidden-reference",
+        }
+    }
+
+    /// Returns a description of the rule the finding breaks.
+    pub fn description(&self) -> &'static str
+    {
+        match self
+        {
+            FindingKind::Missing => "Log statements must have a reference",
+            FindingKind::Unusable => "Log statements that can't be given a reference are ignored",
+            FindingKind::Duplicate => "Reference IDs must be unique",
+            FindingKind::Retired => "Retired reference IDs must not be reused",
+            FindingKind::Malformed =>
+            {
+                "References must be in the form [ref: 1234] at the start of the log message"
+            },
+            FindingKind::Forbidden =>
+            {
+                "Log statements whose macro forbids references must not have one"
+            },
+        }
+    }
+
+    /// Returns whether or not the finding causes check mode to fail, rather than being a
+    /// warning.
+    pub fn is_error(&self) -> bool
+    {
+        matches!(
+            self,
+            FindingKind::Missing
+                | FindingKind::Retired
+                | FindingKind::Malformed
+                | FindingKind::Forbidden
+        )
+    }
+}
+
+/// A problem with a log statement found by check mode.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Finding
+{
+    /// The kind of problem.
+    pub kind: FindingKind,
+
+    /// The path of the file containing the log statement, relative to the working directory if
+    /// it's within it.
+    pub file: String,
+
+    /// The name of the Cargo workspace crate containing the file, if the source directories are
+    /// those of a Cargo workspace.
+    #[serde(rename = "crate", skip_serializing_if = "Option::is_none")]
+    pub crate_name: Option<String>,
+
+    /// The line of the log statement's reference (or where its reference would be inserted).
+    pub line: usize,
+
+    /// The column of the log statement's reference (or where its reference would be inserted).
+    pub column: usize,
+
+    /// The log statement's reference ID, if it has one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reference: Option<u32>,
+
+    /// A description of the problem.
+    pub message: String,
+}
+
+/// The number of errors and warnings found in a Cargo workspace crate.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct CrateSummary
+{
+    /// The name of the crate.
+    pub name: String,
+
+    /// The number of findings that fail check mode.
+    pub errors: usize,
+
+    /// The number of findings that don't fail check mode.
+    pub warnings: usize,
+}
+
+/// The output formats of check mode's report.
+#[derive(Clone, Copy, Debug, PartialEq, clap::ValueEnum)]
+pub enum ReportFormat
+{
+    /// Log lines only.
+    Text,
+
+    /// A JSON document.
+    Json,
+
+    /// A SARIF 2.1.0 log, for code scanning tools.
+    Sarif,
+
+    /// A JUnit XML test report.
+    Junit,
+
+    /// GitHub Actions workflow commands, which appear as annotations on pull requests.
+    Github,
+}
+
+/// The findings in a single file, along with the references it contains for finding
+/// duplicates across files.
+struct FileFindings
+{
+    /// The path to report for the file.
+    file: String,
+
+    /// The name of the Cargo workspace crate containing the file, if any.
+    crate_name: Option<String>,
+
+    /// The findings, other than duplicates.
+    findings: Vec<Finding>,
+
+    /// The reference IDs in the file, and their line and column.
+    references: Vec<(u32, usize, usize)>,
+}
+
+/// A reference processor for collecting check mode's findings. As with all reference
+/// processors, the map and reduce operations are orchestrated by `process_references()`.
+///
+/// The processor's parameters are the set of retired reference IDs and the Cargo workspace
+/// crates to attribute findings to.
+struct FindingsProcessor {}
+
+/// The parameters of `FindingsProcessor`.
+struct FindingsParams
+{
+    /// The retired reference IDs.
+    retired_ids: HashSet<u32>,
+
+    /// The Cargo workspace crates, if the source directories are those of a Cargo workspace.
+    crates: Vec<WorkspaceCrate>,
+}
+
+/// # Type Parameters
+///
+/// * `Params` - The type of the parameters to pass to the processor.
+/// * `MapResult` - The type of the result of the map operation.
+/// * `ReduceResult` - The type of the result of the reduce operation.
+#[async_trait]
+impl ReferenceProcessor<Arc<FindingsParams>, FileFindings, Vec<Finding>> for FindingsProcessor
+{
+    async fn map(
+        path: &str,
+        _file_contents: &str,
+        params: &Option<Arc<FindingsParams>>,
+        entries: &[parser::LogRefEntry],
+    ) -> Option<FileFindings>
+    {
+        let params = params.as_ref()?;
+
+        let mut result = FileFindings {
+            file: report_path(path),
+            crate_name: workspace::crate_of(&params.crates, std::path::Path::new(path))
+                .map(|c| c.name.clone()),
+            findings: Vec::new(),
+            references: Vec::new(),
+        };
+
+        for entry in entries.iter()
+        {
+            let line = entry.position().line();
+            let column = entry.position().column();
+
+            if let Some(id) = entry.reference()
+            {
+                result.references.push((id, line, column));
+            }
+
+            let policy = entry.policy();
+
+            let (kind, message) = match (entry.reference(), entry.malformed_reference())
+            {
+                (Some(_), _) | (None, Some(_)) if policy == ReferencePolicy::Forbidden => (
+                    FindingKind::Forbidden,
+                    format!(
+                        "{} log statements must not have references",
+                        entry.macro_name()
+                    ),
+                ),
+                (Some(id), Some(m)) if m.is_misplaced() => (
+                    FindingKind::Malformed,
+                    format!(
+                        "Misplaced reference - expected [ref: {}] at the start of the log message",
+                        id
+                    ),
+                ),
+                (Some(id), Some(_)) => (
+                    FindingKind::Malformed,
+                    format!("Malformed reference - expected [ref: {}]", id),
+                ),
+                (None, Some(_)) => (
+                    FindingKind::Malformed,
+                    String::from("Malformed reference with an invalid ID - it must be fixed by hand"),
+                ),
+                (Some(id), None) =>
+                {
+                    if !params.retired_ids.contains(&id)
+                    {
+                        continue;
+                    }
+
+                    (
+                        FindingKind::Retired,
+                        format!(
+                            "Reference ID {} has been retired and must not be reused",
+                            id
+                        ),
+                    )
+                },
+                (None, None) if policy != ReferencePolicy::Required => continue,
+                (None, None) if entry.usable_reference_position() =>
+                {
+                    (FindingKind::Missing, String::from("Missing reference"))
+                },
+                (None, None) => (
+                    FindingKind::Unusable,
+                    String::from("A reference can't be inserted in this log statement, so it will be ignored"),
+                ),
+            };
+
+            result.findings.push(Finding {
+                kind,
+                file: result.file.clone(),
+                crate_name: result.crate_name.clone(),
+                line,
+                column,
+                reference: entry.reference(),
+                message,
+            });
+        }
+
+        Some(result)
+    }
+
+    fn reduce(map_results: &[FileFindings]) -> Option<Vec<Finding>>
+    {
+        let mut first_uses: HashMap<u32, (&str, usize, usize)> = HashMap::new();
+        let mut findings: Vec<(usize, Finding)> = Vec::new();
+
+        for (file_index, file_findings) in map_results.iter().enumerate()
+        {
+            findings.extend(
+                file_findings
+                    .findings
+                    .iter()
+                    .map(|f| (file_index, f.clone())),
+            );
+
+            for (id, line, column) in file_findings.references.iter()
+            {
+                match first_uses.get(id)
+                {
+                    Some((first_file, first_line, first_column)) => findings.push((
+                        file_index,
+                        Finding {
+                            kind: FindingKind::Duplicate,
+                            file: file_findings.file.clone(),
+                            crate_name: file_findings.crate_name.clone(),
+                            line: *line,
+                            column: *column,
+                            reference: Some(*id),
+                            message: format!(
+                                "Reference ID {} is also used in {}, line {}, column {}",
+                                id, first_file, first_line, first_column
+                            ),
+                        },
+                    )),
+                    None =>
+                    {
+                        first_uses.insert(*id, (&file_findings.file, *line, *column));
+                    },
+                }
+            }
+        }
+
+        /*
+         * Files are already in canonical order, so only order the findings within each file.
+         */
+        findings.sort_by_key(|(file_index, f)| (*file_index, f.line, f.column));
+
+        Some(findings.into_iter().map(|(_, f)| f).collect())
+    }
+}
+
+/// Determine the path of a file to report. Paths within the working directory are made
+/// relative to it, as expected by CI systems that display findings against a checkout, and
+/// `.` components are removed.
+///
+/// # Arguments
+///
+/// * `path` - The path of the file, as found.
+///
+/// # Returns
+///
+/// The path to report.
+fn report_path(path: &str) -> String
+{
+    use std::path::{Component, Path, PathBuf};
+
+    let normalised_path: PathBuf = Path::new(path)
+        .components()
+        .filter(|c| *c != Component::CurDir)
+        .collect();
+
+    let relative_path = std::env::current_dir()
+        .ok()
+        .and_then(|cwd| normalised_path.strip_prefix(cwd).ok().map(PathBuf::from));
+
+    relative_path
+        .unwrap_or(normalised_path)
+        .to_string_lossy()
+        .to_string()
+}
+
+/// Find the missing, unusable, duplicate, retired, malformed and forbidden references in the code
+/// files found, parsing each file once.
+///
+/// # Arguments
+///
+/// * `context` - Application context.
+/// * `finder` - The code finder for the files to check.
+/// * `registry` - The registry, if it's enabled, recording the retired reference IDs.
+///
+/// # Returns
+///
+/// The findings in canonical file order, then by position within each file, otherwise an error
+/// message on failure.
+pub fn find_findings(
+    context: &Context,
+    finder: &CodeFinder,
+    registry: &Option<Registry>,
+) -> Result<Vec<Finding>, String>
+{
+    let params = FindingsParams {
+        retired_ids: registry
+            .as_ref()
+            .map_or(HashSet::new(), |r| r.retired_ids()),
+        crates: context.config.crates.clone(),
+    };
+
+    match process_references::<FindingsProcessor, Arc<FindingsParams>, FileFindings, Vec<Finding>>(
+        context,
+        Some(Arc::new(params)),
+        finder,
+    )
+    {
+        Some(f) => Ok(f),
+        None => Err("Failed to find references".to_string()),
+    }
+}
+
+/// Count the errors and warnings found in each Cargo workspace crate.
+///
+/// # Arguments
+///
+/// * `findings` - The findings.
+/// * `crates` - The crates, in the order to summarise them.
+///
+/// # Returns
+///
+/// A summary of each crate, including those without findings.
+pub fn summarise_crates(findings: &[Finding], crates: &[WorkspaceCrate]) -> Vec<CrateSummary>
+{
+    crates
+        .iter()
+        .map(|c| {
+            let crate_findings = findings
+                .iter()
+                .filter(|f| f.crate_name.as_deref() == Some(c.name.as_str()));
+
+            let (errors, warnings) =
+                crate_findings.fold((0, 0), |(e, w), f| match f.kind.is_error()
+                {
+                    true => (e + 1, w),
+                    false => (e, w + 1),
+                });
+
+            CrateSummary {
+                name: c.name.clone(),
+                errors,
+                warnings,
+            }
+        })
+        .collect()
+}
+
+/// Format check mode's findings as a report.
+///
+/// # Arguments
+///
+/// * `findings` - The findings.
+/// * `format` - The report format.
+///
+/// # Returns
+///
+/// The report, otherwise an error message on failure.
+pub fn format_report(findings: &[Finding], format: ReportFormat) -> Result<String, String>
+{
+    match format
+    {
+        ReportFormat::Text => Ok(String::new()),
+        ReportFormat::Json => json_report(findings),
+        ReportFormat::Sarif => sarif_report(findings),
+        ReportFormat::Junit => Ok(junit_report(findings)),
+        ReportFormat::Github => Ok(github_report(findings)),
+    }
+}
+
+/// Format findings as a JSON document, with a count of each kind of finding and, for a Cargo
+/// workspace, of the errors and warnings in each crate with findings.
+fn json_report(findings: &[Finding]) -> Result<String, String>
+{
+    let summary: serde_json::Map<String, serde_json::Value> = FindingKind::ALL
+        .iter()
+        .map(|kind| {
+            (
+                serde_json::to_value(kind)
+                    .ok()
+                    .and_then(|v| v.as_str().map(String::from))
+                    .unwrap_or_default(),
+                serde_json::Value::from(findings.iter().filter(|f| f.kind == *kind).count()),
+            )
+        })
+        .collect();
+
+    let mut crates: serde_json::Map<String, serde_json::Value> = serde_json::Map::new();
+
+    for finding in findings.iter()
+    {
+        if let Some(name) = &finding.crate_name
+        {
+            let counts = crates
+                .entry(name.clone())
+                .or_insert_with(|| serde_json::json!({ "errors": 0, "warnings": 0 }));
+            let key = if finding.kind.is_error()
+            {
+                "errors"
+            }
+            else
+            {
+                "warnings"
+            };
+
+            counts[key] = serde_json::Value::from(counts[key].as_u64().unwrap_or_default() + 1);
+        }
+    }
+
+    let mut report = serde_json::json!({
+        "findings": findings,
+        "summary": summary,
+    });
+
+    if !crates.is_empty()
+    {
+        report["crates"] = serde_json::Value::Object(crates);
+    }
+
+    serde_json::to_string_pretty(&report)
+        .map(|mut s| {
+            s.push('\n');
+            s
+        })
+        .map_err(|e| e.to_string())
+}
+
+/// Format findings as a SARIF 2.1.0 log.
+fn sarif_report(findings: &[Finding]) -> Result<String, String>
+{
+    let level = |kind: &FindingKind| if kind.is_error() { "error" } else { "warning" };
+
+    let rules: Vec<serde_json::Value> = FindingKind::ALL
+        .iter()
+        .map(|kind| {
+            serde_json::json!({
+                "id": kind.rule_id(),
+                "shortDescription": { "text": kind.description() },
+                "defaultConfiguration": { "level": level(kind) },
+            })
+        })
+        .collect();
+
+    let results: Vec<serde_json::Value> = findings
+        .iter()
+        .map(|f| {
+            serde_json::json!({
+                "ruleId": f.kind.rule_id(),
+                "level": level(&f.kind),
+                "message": { "text": f.message },
+                "locations": [{
+                    "physicalLocation": {
+                        "artifactLocation": { "uri": f.file },
+                        "region": { "startLine": f.line, "startColumn": f.column },
+                    },
+                }],
+            })
+        })
+        .collect();
+
+    serde_json::to_string_pretty(&serde_json::json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "Breadlog",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "informationUri": "https://breadlog.readthedocs.io",
+                    "rules": rules,
+                },
+            },
+            "results": results,
+        }],
+    }))
+    .map(|mut s| {
+        s.push('\n');
+        s
+    })
+    .map_err(|e| e.to_string())
+}
+
+/// Escape text for use in XML content or attribute values.
+fn xml_escape(text: &str) -> String
+{
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Format findings as a JUnit XML test report. Each finding is a test case: errors are failures
+/// and warnings are skipped. If there are no findings, a single passing test case is reported so
+/// that the report isn't empty.
+fn junit_report(findings: &[Finding]) -> String
+{
+    let failures = findings.iter().filter(|f| f.kind.is_error()).count();
+    let skipped = findings.len() - failures;
+    let tests = findings.len().max(1);
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(&format!(
+        "<testsuites name=\"Breadlog\" tests=\"{}\" failures=\"{}\" skipped=\"{}\">\n",
+        tests, failures, skipped
+    ));
+    xml.push_str(&format!(
+        "  <testsuite name=\"Log references\" tests=\"{}\" failures=\"{}\" skipped=\"{}\">\n",
+        tests, failures, skipped
+    ));
+
+    if findings.is_empty()
+    {
+        xml.push_str("    <testcase classname=\"breadlog\" name=\"Log references\"/>\n");
+    }
+
+    for finding in findings.iter()
+    {
+        let location = format!("{}:{}:{}", finding.file, finding.line, finding.column);
+
+        xml.push_str(&format!(
+            "    <testcase classname=\"{}\" name=\"{} at {}\">\n",
+            xml_escape(&finding.file),
+            finding.kind.rule_id(),
+            xml_escape(&location)
+        ));
+
+        if finding.kind.is_error()
+        {
+            xml.push_str(&format!(
+                "      <failure type=\"{}\" message=\"{}\">{}</failure>\n",
+                finding.kind.rule_id(),
+                xml_escape(&finding.message),
+                xml_escape(&location)
+            ));
+        }
+        else
+        {
+            xml.push_str(&format!(
+                "      <skipped message=\"{}\"/>\n",
+                xml_escape(&finding.message)
+            ));
+        }
+
+        xml.push_str("    </testcase>\n");
+    }
+
+    xml.push_str("  </testsuite>\n</testsuites>\n");
+
+    xml
+}
+
+/// Format findings as GitHub Actions workflow commands.
+fn github_report(findings: &[Finding]) -> String
+{
+    let escape_data = |s: &str| {
+        s.replace('%', "%25")
+            .replace('\r', "%0D")
+            .replace('\n', "%0A")
+    };
+    let escape_property = |s: &str| escape_data(s).replace(':', "%3A").replace(',', "%2C");
+
+    findings
+        .iter()
+        .map(|f| {
+            format!(
+                "::{} file={},line={},col={},title={}::{}\n",
+                if f.kind.is_error()
+                {
+                    "error"
+                }
+                else
+                {
+                    "warning"
+                },
+                escape_property(&f.file),
+                f.line,
+                f.column,
+                escape_property(&format!("Breadlog {}", f.kind.rule_id())),
+                escape_data(&f.message)
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::config::context::RustLogMacro;
+    use tempdir::TempDir;
+
+    fn create_test_context(source_dir: &str) -> Context
+    {
+        Context::new(
+            format!(
+                r#"
+source_dir: {}
+use_registry: true
+rust:
+  structured: true
+  log_macros:
+    - module: test_module
+      name: test_macro
+"#,
+                source_dir
+            ),
+            source_dir,
+            true,
+        )
+        .unwrap()
+    }
+
+    fn find_test_findings(context: &Context) -> Vec<Finding>
+    {
+        let finder = CodeFinder::new(context).unwrap();
+        let registry = context.read_registry().unwrap();
+
+        find_findings(context, &finder, &registry).unwrap()
+    }
+
+    fn finding(kind: FindingKind, line: usize, message: &str) -> Finding
+    {
+        Finding {
+            kind,
+            file: String::from("src/a.rs"),
+            crate_name: None,
+            line,
+            column: 5,
+            reference: None,
+            message: message.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_find_findings()
+    {
+        use crate::config::registry::{ReferenceRecord, Registry};
+
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let test_context = create_test_context(temp_dir.path().to_str().unwrap());
+
+        std::fs::write(
+            temp_dir.path().join("test_file1.rs"),
+            "fn test1() {\n    test_macro!(ref = 1; \"Log test 1.\");\n    test_macro!(\"Log test 2.\");\n}\n",
+        )
+        .unwrap();
+        std::fs::write(
+            temp_dir.path().join("test_file2.rs"),
+            "fn test2() {\n    test_macro!(ref = 2; \"Log test 3.\");\n    test_macro!(ref = 1; \"Log test 4.\");\n    test_macro!(ref = \"abc\"; \"Log test 5.\");\n}\n",
+        )
+        .unwrap();
+
+        let mut registry = Registry::default();
+        registry.update(
+            &[ReferenceRecord {
+                id: 2,
+                message: String::from("Log test 3."),
+                level: String::from("test_macro"),
+                file: String::from("test_file2.rs"),
+            }],
+            "2024-01-01",
+        );
+        registry.update(&[], "2024-01-02");
+        std::fs::write(
+            test_context.registry_path(),
+            Context::registry_contents(&registry).unwrap(),
+        )
+        .unwrap();
+
+        let findings = find_test_findings(&test_context);
+
+        let summary: Vec<(FindingKind, &str, usize, Option<u32>)> = findings
+            .iter()
+            .map(|f| {
+                (
+                    f.kind,
+                    std::path::Path::new(&f.file)
+                        .file_name()
+                        .unwrap()
+                        .to_str()
+                        .unwrap(),
+                    f.line,
+                    f.reference,
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            summary,
+            vec![
+                (FindingKind::Missing, "test_file1.rs", 3, None),
+                (FindingKind::Retired, "test_file2.rs", 2, Some(2)),
+                (FindingKind::Duplicate, "test_file2.rs", 3, Some(1)),
+                (FindingKind::Unusable, "test_file2.rs", 4, None),
+            ]
+        );
+        assert!(findings[2]
+            .message
+            .contains("test_file1.rs, line 2, column"));
+    }
+
+    #[async_std::test]
+    async fn test_findings_map()
+    {
+        use crate::parser::code_parser::LogRefKind;
+
+        let entry = |reference: Option<u32>, line: usize, kind: LogRefKind| {
+            parser::LogRefEntry::new(
+                parser::CodePosition::new(0, line, 5),
+                reference,
+                String::from("test_macro"),
+                kind,
+                None,
+                None,
+            )
+        };
+
+        let test_entries = vec![
+            entry(Some(1), 1, LogRefKind::String),
+            entry(None, 2, LogRefKind::String),
+            entry(None, 3, LogRefKind::StructuredPreExisting),
+            entry(None, 4, LogRefKind::StructuredNew),
+            entry(Some(2), 5, LogRefKind::StructuredPreExisting),
+            entry(Some(3), 6, LogRefKind::String).with_policy(ReferencePolicy::Forbidden),
+            entry(None, 7, LogRefKind::String).with_policy(ReferencePolicy::Forbidden),
+            entry(Some(4), 8, LogRefKind::String).with_policy(ReferencePolicy::Optional),
+            entry(None, 9, LogRefKind::String).with_policy(ReferencePolicy::Optional),
+        ];
+
+        let params = Some(Arc::new(FindingsParams {
+            retired_ids: HashSet::from([2, 3, 5]),
+            crates: Vec::new(),
+        }));
+
+        let file_findings = FindingsProcessor::map("test.rs", "", &params, &test_entries)
+            .await
+            .unwrap();
+
+        let summary: Vec<(FindingKind, usize)> = file_findings
+            .findings
+            .iter()
+            .map(|f| (f.kind, f.line))
+            .collect();
+
+        /*
+         * Forbidden references are reported as forbidden even if they're also retired.
+         */
+        assert_eq!(
+            summary,
+            vec![
+                (FindingKind::Missing, 2),
+                (FindingKind::Unusable, 3),
+                (FindingKind::Missing, 4),
+                (FindingKind::Retired, 5),
+                (FindingKind::Forbidden, 6),
+            ]
+        );
+        assert_eq!(
+            file_findings.references,
+            vec![(1, 1, 5), (2, 5, 5), (3, 6, 5), (4, 8, 5)]
+        );
+    }
+
+    #[test]
+    fn test_find_malformed_findings()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let mut test_context = create_test_context(temp_dir.path().to_str().unwrap());
+        test_context.config.rust.structured = false;
+
+        std::fs::write(
+            temp_dir.path().join("test_file1.rs"),
+            "fn test1() {\n    test_macro!(\"[ref:1] Log test 1.\");\n    test_macro!(\"Log test 2. [ref: 2]\");\n    test_macro!(\"[ref: 1x] Log test 3.\");\n    test_macro!(\"[ref: 1] Log test 4.\");\n}\n",
+        )
+        .unwrap();
+
+        let findings = find_test_findings(&test_context);
+
+        let summary: Vec<(FindingKind, usize, Option<u32>)> = findings
+            .iter()
+            .map(|f| (f.kind, f.line, f.reference))
+            .collect();
+
+        /*
+         * A malformed reference with a valid ID still counts towards duplicate detection.
+         */
+        assert_eq!(
+            summary,
+            vec![
+                (FindingKind::Malformed, 2, Some(1)),
+                (FindingKind::Malformed, 3, Some(2)),
+                (FindingKind::Malformed, 4, None),
+                (FindingKind::Duplicate, 5, Some(1)),
+            ]
+        );
+        assert_eq!(
+            findings[0].message,
+            "Malformed reference - expected [ref: 1]"
+        );
+        assert!(findings[1].message.starts_with("Misplaced reference"));
+    }
+
+    #[test]
+    fn test_find_policy_findings()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let mut test_context = create_test_context(temp_dir.path().to_str().unwrap());
+        test_context.config.rust.structured = false;
+        test_context.config.rust.log_macros[0].policy = ReferencePolicy::Optional;
+        test_context.config.rust.log_macros.push(RustLogMacro {
+            module: String::from("test_module"),
+            name: String::from("trace_macro"),
+            policy: ReferencePolicy::Forbidden,
+            structured: None,
+        });
+
+        std::fs::write(
+            temp_dir.path().join("test_file1.rs"),
+            "fn test1() {\n    test_macro!(\"Log test 1.\");\n    trace_macro!(\"Log test 2.\");\n    trace_macro!(\"[ref: 3] Log test 3.\");\n}\n",
+        )
+        .unwrap();
+
+        let findings = find_test_findings(&test_context);
+
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].kind, FindingKind::Forbidden);
+        assert_eq!(findings[0].line, 4);
+        assert_eq!(findings[0].reference, Some(3));
+        assert_eq!(
+            findings[0].message,
+            "trace_macro log statements must not have references"
+        );
+    }
+
+    #[test]
+    fn test_find_workspace_findings()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+
+        std::fs::write(
+            temp_dir.path().join("Cargo.toml"),
+            "[package]\nname = \"app\"\n\n[workspace]\nmembers = [\"crates/net\"]\n",
+        )
+        .unwrap();
+        std::fs::create_dir_all(temp_dir.path().join("src")).unwrap();
+        std::fs::create_dir_all(temp_dir.path().join("crates/net/src")).unwrap();
+        std::fs::write(
+            temp_dir.path().join("crates/net/Cargo.toml"),
+            "[package]\nname = \"net\"\n",
+        )
+        .unwrap();
+        std::fs::write(
+            temp_dir.path().join("src/main.rs"),
+            "fn test1() {\n    test_macro!(\"[ref: 1] Log test 1.\");\n}\n",
+        )
+        .unwrap();
+        std::fs::write(
+            temp_dir.path().join("crates/net/src/lib.rs"),
+            "fn test2() {\n    test_macro!(\"Log test 2.\");\n    test_macro!(\"[ref: 1] Log test 3.\");\n}\n",
+        )
+        .unwrap();
+
+        let test_context = Context::new(
+            String::from(
+                r#"
+source: cargo_workspace
+rust:
+  log_macros:
+    - module: test_module
+      name: test_macro
+"#,
+            ),
+            temp_dir.path().to_str().unwrap(),
+            true,
+        )
+        .unwrap();
+
+        let findings = find_test_findings(&test_context);
+
+        assert_eq!(
+            findings
+                .iter()
+                .map(|f| (f.kind, f.crate_name.as_deref()))
+                .collect::<Vec<_>>(),
+            vec![
+                (FindingKind::Missing, Some("net")),
+                (FindingKind::Duplicate, Some("app")),
+            ]
+        );
+
+        assert_eq!(
+            summarise_crates(&findings, &test_context.config.crates),
+            vec![
+                CrateSummary {
+                    name: String::from("app"),
+                    errors: 0,
+                    warnings: 1,
+                },
+                CrateSummary {
+                    name: String::from("net"),
+                    errors: 1,
+                    warnings: 0,
+                },
+            ]
+        );
+
+        let report: serde_json::Value =
+            serde_json::from_str(&json_report(&findings).unwrap()).unwrap();
+
+        assert_eq!(report["findings"][0]["crate"], "net");
+        assert_eq!(report["crates"]["net"]["errors"], 1);
+        assert_eq!(report["crates"]["app"]["warnings"], 1);
+    }
+
+    #[test]
+    fn test_report_path()
+    {
+        assert_eq!(report_path("./src/./a.rs"), "src/a.rs");
+        assert_eq!(report_path("/outside/a.rs"), "/outside/a.rs");
+
+        let cwd = std::env::current_dir().unwrap();
+        assert_eq!(
+            report_path(cwd.join("src/a.rs").to_str().unwrap()),
+            "src/a.rs"
+        );
+    }
+
+    #[test]
+    fn test_json_report()
+    {
+        let findings = vec![finding(FindingKind::Missing, 3, "Missing reference")];
+
+        let report: serde_json::Value =
+            serde_json::from_str(&format_report(&findings, ReportFormat::Json).unwrap()).unwrap();
+
+        assert_eq!(report["findings"][0]["kind"], "missing");
+        assert_eq!(report["findings"][0]["file"], "src/a.rs");
+        assert_eq!(report["findings"][0]["line"], 3);
+        assert_eq!(report["findings"][0]["column"], 5);
+        assert!(report["findings"][0].get("reference").is_none());
+        assert_eq!(report["summary"]["missing"], 1);
+        assert_eq!(report["summary"]["duplicate"], 0);
+    }
+
+    #[test]
+    fn test_sarif_report()
+    {
+        let findings = vec![
+            finding(FindingKind::Missing, 3, "Missing reference"),
+            finding(FindingKind::Unusable, 4, "Unusable"),
+        ];
+
+        let report: serde_json::Value =
+            serde_json::from_str(&format_report(&findings, ReportFormat::Sarif).unwrap()).unwrap();
+
+        assert_eq!(report["version"], "2.1.0");
+
+        let results = &report["runs"][0]["results"];
+        assert_eq!(results[0]["ruleId"], "missing-reference");
+        assert_eq!(results[0]["level"], "error");
+        assert_eq!(
+            results[0]["locations"][0]["physicalLocation"]["artifactLocation"]["uri"],
+            "src/a.rs"
+        );
+        assert_eq!(
+            results[0]["locations"][0]["physicalLocation"]["region"]["startLine"],
+            3
+        );
+        assert_eq!(results[1]["level"], "warning");
+    }
+
+    #[test]
+    fn test_junit_report()
+    {
+        let findings = vec![
+            finding(FindingKind::Missing, 3, "Missing <reference>"),
+            finding(FindingKind::Unusable, 4, "Unusable"),
+        ];
+
+        let report = format_report(&findings, ReportFormat::Junit).unwrap();
+
+        assert!(report.contains("tests=\"2\" failures=\"1\" skipped=\"1\""));
+        assert!(report.contains(
+            "<failure type=\"missing-reference\" message=\"Missing &lt;reference&gt;\">src/a.rs:3:5</failure>"
+        ));
+        assert!(report.contains("<skipped message=\"Unusable\"/>"));
+
+        let report = format_report(&[], ReportFormat::Junit).unwrap();
+
+        assert!(report.contains("tests=\"1\" failures=\"0\" skipped=\"0\""));
+        assert!(report.contains("<testcase classname=\"breadlog\" name=\"Log references\"/>"));
+    }
+
+    #[test]
+    fn test_github_report()
+    {
+        let findings = vec![
+            finding(FindingKind::Missing, 3, "Missing reference"),
+            finding(FindingKind::Duplicate, 4, "Also used in a.rs, line 1\n100%"),
+        ];
+
+        assert_eq!(
+            format_report(&findings, ReportFormat::Github).unwrap(),
+            "::error file=src/a.rs,line=3,col=5,title=Breadlog missing-reference::Missing reference\n\
+             ::warning file=src/a.rs,line=4,col=5,title=Breadlog duplicate-reference::Also used in a.rs, line 1%0A100%25\n"
+        );
+    }
+}
@@ -0,0 +1,268 @@
+use super::generate::{begin_update, scan_next_reference_id, stage_cache};
+use super::transaction::Transaction;
+use crate::config::context::{Cache, Reservation};
+use crate::config::Context;
+use log::error;
+
+/// Read the lock file for modification, creating its contents by scanning the code base if it
+/// doesn't exist yet.
+///
+/// # Arguments
+///
+/// * `context` - Application context.
+///
+/// # Returns
+///
+/// The lock file contents, otherwise an error message on failure.
+fn load_cache(context: &Context) -> Result<Cache, &'static str>
+{
+    if !context.config.use_cache
+    {
+        error!("[ref: 71] Reserving reference IDs requires the lock file - set use_cache to true");
+        return Err("Lock file disabled");
+    }
+
+    match context.reload_cache()
+    {
+        Some(c) => Ok(c),
+        None => match scan_next_reference_id(context)
+        {
+            Some(id) => Ok(Cache::new(id)),
+            None => Err("Failed to determine next reference ID"),
+        },
+    }
+}
+
+/// Write the lock file.
+///
+/// # Arguments
+///
+/// * `context` - Application context.
+/// * `cache` - The new contents of the lock file.
+///
+/// # Returns
+///
+/// Nothing, otherwise an error message on failure.
+fn save_cache(context: &Context, cache: &Cache) -> Result<(), &'static str>
+{
+    let mut transaction = Transaction::new(&context.journal_path());
+
+    if let Err(e) = stage_cache(context, cache, &mut transaction).and_then(|_| transaction.commit())
+    {
+        error!("[ref: 72] Failed to write lock file: {}", e);
+        return Err("Failed to write lock file");
+    }
+
+    Ok(())
+}
+
+/// Reserve a block of reference IDs for an owner. The block starts at the next reference ID,
+/// which is moved past the end of the block so that it's never allocated to anyone else.
+///
+/// # Arguments
+///
+/// * `context` - Application context.
+/// * `owner` - The label identifying who the block is reserved for.
+/// * `size` - The number of IDs to reserve.
+///
+/// # Returns
+///
+/// The reserved block, otherwise an error message on failure.
+pub fn reserve(context: &Context, owner: &str, size: u32) -> Result<Reservation, &'static str>
+{
+    let _run_lock = begin_update(context)?;
+
+    let mut cache = load_cache(context)?;
+
+    if let Some(existing) = cache.reservation(owner)
+    {
+        error!(
+            "[ref: 73] A block of reference IDs ({} to {}) is already reserved for {} - release it first",
+            existing.start,
+            existing.end() - 1,
+            owner
+        );
+        return Err("Block already reserved");
+    }
+
+    let start = cache.next_reference_id;
+
+    let end = match start.checked_add(size)
+    {
+        Some(e) if size > 0 => e,
+        _ => return Err("Invalid block size"),
+    };
+
+    let reservation = Reservation {
+        owner: owner.to_string(),
+        start,
+        size,
+        next: start,
+    };
+
+    cache.next_reference_id = end;
+    cache.reservations.push(reservation.clone());
+
+    save_cache(context, &cache)?;
+
+    Ok(reservation)
+}
+
+/// Release the block of reference IDs reserved for an owner. Any unused IDs in the block are
+/// never allocated.
+///
+/// # Arguments
+///
+/// * `context` - Application context.
+/// * `owner` - The label identifying who the block is reserved for.
+///
+/// # Returns
+///
+/// The released block, otherwise an error message on failure.
+pub fn release(context: &Context, owner: &str) -> Result<Reservation, &'static str>
+{
+    let _run_lock = begin_update(context)?;
+
+    let mut cache = load_cache(context)?;
+
+    let index = match cache.reservations.iter().position(|r| r.owner == owner)
+    {
+        Some(i) => i,
+        None =>
+        {
+            error!(
+                "[ref: 74] No block of reference IDs is reserved for {}",
+                owner
+            );
+            return Err("No reserved block of reference IDs");
+        },
+    };
+
+    let reservation = cache.reservations.remove(index);
+
+    save_cache(context, &cache)?;
+
+    Ok(reservation)
+}
+
+/// List the reserved blocks of reference IDs.
+///
+/// # Arguments
+///
+/// * `context` - Application context.
+///
+/// # Returns
+///
+/// The reserved blocks, in order of their first ID.
+pub fn list(context: &Context) -> Vec<Reservation>
+{
+    let mut reservations = context
+        .reload_cache()
+        .map_or(Vec::new(), |c| c.reservations);
+
+    reservations.sort_by_key(|r| r.start);
+
+    reservations
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use tempdir::TempDir;
+
+    fn create_test_context(source_dir: &str) -> Context
+    {
+        Context::new(
+            format!(
+                r#"
+source_dir: {}
+rust:
+  log_macros:
+    - module: test_module
+      name: test_macro
+"#,
+                source_dir
+            ),
+            source_dir,
+            false,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_reserve_release_list()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let test_context = create_test_context(temp_dir.path().to_str().unwrap());
+
+        std::fs::write(
+            temp_dir.path().join("test_file1.rs"),
+            "fn test1() {\n    test_macro!(\"[ref: 7] Log test.\");\n}\n",
+        )
+        .unwrap();
+
+        /*
+         * Without a lock file, the first block starts after the highest reference in the code.
+         */
+        let alice = reserve(&test_context, "alice", 10).unwrap();
+        assert_eq!(alice.start, 8);
+        assert_eq!(alice.end(), 18);
+
+        let bob = reserve(&test_context, "bob", 5).unwrap();
+        assert_eq!(bob.start, 18);
+
+        assert_eq!(
+            reserve(&test_context, "alice", 1),
+            Err("Block already reserved")
+        );
+
+        let cache = test_context.reload_cache().unwrap();
+        assert_eq!(cache.next_reference_id, 23);
+
+        let reserved = list(&test_context);
+        assert_eq!(reserved.len(), 2);
+        assert_eq!(reserved[0].owner, "alice");
+        assert_eq!(reserved[1].owner, "bob");
+
+        assert_eq!(release(&test_context, "alice").unwrap().start, 8);
+        assert_eq!(
+            release(&test_context, "alice"),
+            Err("No reserved block of reference IDs")
+        );
+
+        /*
+         * Released IDs are never reused.
+         */
+        let cache = test_context.reload_cache().unwrap();
+        assert_eq!(cache.next_reference_id, 23);
+        assert_eq!(list(&test_context).len(), 1);
+    }
+
+    #[test]
+    fn test_reserve_invalid_size()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let test_context = create_test_context(temp_dir.path().to_str().unwrap());
+
+        assert_eq!(
+            reserve(&test_context, "alice", 0),
+            Err("Invalid block size")
+        );
+        assert!(list(&test_context).is_empty());
+    }
+
+    #[test]
+    fn test_reserve_cache_disabled()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let mut test_context = create_test_context(temp_dir.path().to_str().unwrap());
+        test_context.config.use_cache = false;
+
+        assert_eq!(
+            reserve(&test_context, "alice", 10),
+            Err("Lock file disabled")
+        );
+        assert_eq!(release(&test_context, "alice"), Err("Lock file disabled"));
+    }
+}
@@ -0,0 +1,247 @@
+use fs2::FileExt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use std::time::Instant;
+
+/// How often to retry taking the lock while another run holds it.
+const RETRY_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Represents an exclusive, advisory lock preventing concurrent Breadlog runs from modifying the
+/// same code base. The lock is released when dropped, or if the process dies.
+///
+/// The lock file itself is left in place when the lock is released: deleting it would allow two
+/// runs to each lock a different file at the same path.
+pub struct RunLock
+{
+    /// The locked file.
+    file: std::fs::File,
+}
+
+/// The ways in which taking the run lock can fail.
+#[derive(Debug, PartialEq)]
+pub enum RunLockError
+{
+    /// Another run held the lock for longer than the timeout.
+    Timeout,
+
+    /// A stop was commanded while waiting for another run to release the lock.
+    Stopped,
+
+    /// The lock file could not be opened or locked.
+    Io(String),
+}
+
+impl RunLock
+{
+    /// Take the run lock, waiting for another run to release it if necessary.
+    ///
+    /// # Arguments
+    ///
+    /// * `lock_path` - The path to the lock file, which is created if it doesn't exist.
+    /// * `timeout` - How long to wait for another run to release the lock. If zero, don't wait.
+    /// * `stop_commanded` - Set when the process has been asked to stop, which ends the wait.
+    /// * `on_wait` - Called once if the lock is held by another run, before waiting.
+    ///
+    /// # Returns
+    ///
+    /// The held lock, otherwise the reason it could not be taken.
+    pub fn acquire<F>(
+        lock_path: &str,
+        timeout: Duration,
+        stop_commanded: &AtomicBool,
+        on_wait: F,
+    ) -> Result<RunLock, RunLockError>
+    where
+        F: FnOnce(),
+    {
+        let file = match std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(lock_path)
+        {
+            Ok(f) => f,
+            Err(e) =>
+            {
+                return Err(RunLockError::Io(format!(
+                    "Failed to open {}: {}",
+                    lock_path, e
+                )))
+            },
+        };
+
+        let start = Instant::now();
+        let mut on_wait = Some(on_wait);
+
+        loop
+        {
+            match file.try_lock_exclusive()
+            {
+                Ok(_) => return Ok(RunLock { file }),
+                Err(e) if e.kind() == fs2::lock_contended_error().kind() =>
+                {
+                    if stop_commanded.load(Ordering::Relaxed)
+                    {
+                        return Err(RunLockError::Stopped);
+                    }
+
+                    if start.elapsed() >= timeout
+                    {
+                        return Err(RunLockError::Timeout);
+                    }
+
+                    if let Some(f) = on_wait.take()
+                    {
+                        f();
+                    }
+
+                    std::thread::sleep(RETRY_INTERVAL.min(timeout.saturating_sub(start.elapsed())));
+                },
+                Err(e) =>
+                {
+                    return Err(RunLockError::Io(format!(
+                        "Failed to lock {}: {}",
+                        lock_path, e
+                    )))
+                },
+            }
+        }
+    }
+}
+
+/// Release the lock when dropped.
+impl Drop for RunLock
+{
+    /// Release the lock.
+    fn drop(&mut self)
+    {
+        /* Closing the file releases the lock anyway, so don't worry about errors.
+         */
+        let _ = self.file.unlock();
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_acquire_and_release()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let lock_path = temp_dir.path().join("Breadlog.run.lock");
+        let lock_path = lock_path.to_str().unwrap();
+
+        {
+            let _lock = RunLock::acquire(lock_path, Duration::ZERO, &AtomicBool::new(false), || {})
+                .unwrap();
+        }
+
+        /*
+         * Once released, the lock can be taken again.
+         */
+        assert!(
+            RunLock::acquire(lock_path, Duration::ZERO, &AtomicBool::new(false), || {}).is_ok()
+        );
+        assert!(std::path::Path::new(lock_path).exists());
+    }
+
+    #[test]
+    fn test_acquire_contended_timeout()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let lock_path = temp_dir.path().join("Breadlog.run.lock");
+        let lock_path = lock_path.to_str().unwrap();
+
+        let _lock =
+            RunLock::acquire(lock_path, Duration::ZERO, &AtomicBool::new(false), || {}).unwrap();
+
+        let mut waited = false;
+        let start = Instant::now();
+
+        let result = RunLock::acquire(
+            lock_path,
+            Duration::from_millis(300),
+            &AtomicBool::new(false),
+            || waited = true,
+        );
+
+        assert_eq!(result.err(), Some(RunLockError::Timeout));
+        assert!(waited);
+        assert!(start.elapsed() >= Duration::from_millis(300));
+    }
+
+    #[test]
+    fn test_acquire_waits_for_release()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let lock_path = temp_dir.path().join("Breadlog.run.lock");
+        let lock_path = lock_path.to_str().unwrap().to_string();
+
+        let lock =
+            RunLock::acquire(&lock_path, Duration::ZERO, &AtomicBool::new(false), || {}).unwrap();
+
+        let releaser = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(200));
+            drop(lock);
+        });
+
+        assert!(RunLock::acquire(
+            &lock_path,
+            Duration::from_secs(10),
+            &AtomicBool::new(false),
+            || {}
+        )
+        .is_ok());
+
+        releaser.join().unwrap();
+    }
+
+    #[test]
+    fn test_acquire_stopped_while_waiting()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let lock_path = temp_dir.path().join("Breadlog.run.lock");
+        let lock_path = lock_path.to_str().unwrap();
+
+        let _lock =
+            RunLock::acquire(lock_path, Duration::ZERO, &AtomicBool::new(false), || {}).unwrap();
+
+        let stop_commanded = std::sync::Arc::new(AtomicBool::new(false));
+        let stopper_flag = std::sync::Arc::clone(&stop_commanded);
+
+        let stopper = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(200));
+            stopper_flag.store(true, Ordering::Relaxed);
+        });
+
+        let start = Instant::now();
+
+        let result = RunLock::acquire(lock_path, Duration::from_secs(10), &stop_commanded, || {});
+
+        assert_eq!(result.err(), Some(RunLockError::Stopped));
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        stopper.join().unwrap();
+    }
+
+    #[test]
+    fn test_acquire_bad_path()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let lock_path = temp_dir.path().join("missing").join("Breadlog.run.lock");
+
+        assert!(matches!(
+            RunLock::acquire(
+                lock_path.to_str().unwrap(),
+                Duration::ZERO,
+                &AtomicBool::new(false),
+                || {}
+            ),
+            Err(RunLockError::Io(_))
+        ));
+    }
+}
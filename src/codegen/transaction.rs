@@ -0,0 +1,829 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Arc;
+
+/// The path to a staged file, which is deleted when dropped. Once a staged file has been
+/// committed it no longer exists at this path, so the deletion is a no-op.
+struct StagedPath
+{
+    /// The path to the staged file.
+    path: String,
+}
+
+/// Delete the staged file when dropped.
+impl Drop for StagedPath
+{
+    /// Delete the staged file.
+    fn drop(&mut self)
+    {
+        /* The file may already have been committed or removed, so don't worry about errors.
+         */
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Represents new contents for a target file, written to disk but not yet committed. The staged
+/// file is in the same directory as the target so that committing it is an atomic rename.
+#[derive(Clone)]
+pub struct StagedFile
+{
+    /// The staged file, shared between clones and deleted when the last clone is dropped.
+    staged: Arc<StagedPath>,
+
+    /// The path to the file that the staged file will replace.
+    target_path: String,
+}
+
+impl StagedFile
+{
+    /// Create a new `StagedFile` from a file that has already been written and synced to disk.
+    ///
+    /// # Arguments
+    ///
+    /// * `staged_path` - The path to the staged file. It will be deleted if it isn't committed.
+    /// * `target_path` - The path to the file that the staged file will replace.
+    pub fn new(staged_path: String, target_path: String) -> StagedFile
+    {
+        Self {
+            staged: Arc::new(StagedPath { path: staged_path }),
+            target_path,
+        }
+    }
+
+    /// Stage new contents for a target file. If the target file already exists, the staged file
+    /// is given its permissions.
+    ///
+    /// # Arguments
+    ///
+    /// * `target_path` - The path to the file that the staged file will replace.
+    /// * `contents` - The new contents of the target file.
+    ///
+    /// # Returns
+    ///
+    /// The staged file, or an error message if it could not be written.
+    pub fn write(target_path: &str, contents: &[u8]) -> Result<StagedFile, String>
+    {
+        use std::io::Write;
+
+        let staged_path = sibling_path(target_path, "tmp")?;
+
+        /* Wrap the path first so it's cleaned up if any of the following steps fail.
+         */
+        let result = StagedFile::new(staged_path.clone(), target_path.to_string());
+
+        let mut file = match std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&staged_path)
+        {
+            Ok(f) => f,
+            Err(e) => return Err(format!("Failed to create staged file: {}", e)),
+        };
+
+        if let Err(e) = file.write_all(contents).and_then(|_| file.sync_all())
+        {
+            return Err(format!("Failed to write staged file: {}", e));
+        }
+
+        if let Ok(target_metadata) = std::fs::metadata(target_path)
+        {
+            if let Err(e) = std::fs::set_permissions(&staged_path, target_metadata.permissions())
+            {
+                return Err(format!("Failed to set permissions of staged file: {}", e));
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Returns the path to the staged file.
+    #[allow(dead_code)]
+    pub fn staged_path(&self) -> &str
+    {
+        &self.staged.path
+    }
+
+    /// Returns the path to the file that the staged file will replace.
+    #[allow(dead_code)]
+    pub fn target_path(&self) -> &str
+    {
+        &self.target_path
+    }
+}
+
+/// A single file replacement recorded in the journal.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct JournalEntry
+{
+    /// The path to the file being replaced.
+    target: String,
+
+    /// The path to the staged file containing the new contents.
+    staged: String,
+
+    /// The path to a link to the original file, if the target file already existed.
+    backup: Option<String>,
+}
+
+/// The recovery journal, written before any target file is replaced and deleted once all of
+/// them have been. If the journal exists when Breadlog starts, a previous commit was
+/// interrupted and is rolled back.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct Journal
+{
+    entries: Vec<JournalEntry>,
+}
+
+/// Represents a set of staged files that are committed together: either all target files are
+/// replaced, or none of them are.
+pub struct Transaction
+{
+    /// The path to the recovery journal.
+    journal_path: String,
+
+    /// The files to commit.
+    staged_files: Vec<StagedFile>,
+}
+
+impl Transaction
+{
+    /// Create a new, empty `Transaction`.
+    ///
+    /// # Arguments
+    ///
+    /// * `journal_path` - The path to write the recovery journal to while committing.
+    pub fn new(journal_path: &str) -> Transaction
+    {
+        Self {
+            journal_path: journal_path.to_string(),
+            staged_files: Vec::new(),
+        }
+    }
+
+    /// Add a staged file to the transaction.
+    ///
+    /// # Arguments
+    ///
+    /// * `staged_file` - The staged file to add.
+    pub fn add(&mut self, staged_file: StagedFile)
+    {
+        self.staged_files.push(staged_file);
+    }
+
+    /// Returns the staged files in the transaction.
+    pub fn staged_files(&self) -> &[StagedFile]
+    {
+        &self.staged_files
+    }
+
+    /// Returns the number of staged files in the transaction.
+    #[allow(dead_code)]
+    pub fn len(&self) -> usize
+    {
+        self.staged_files.len()
+    }
+
+    /// Returns whether or not the transaction has any staged files.
+    #[allow(dead_code)]
+    pub fn is_empty(&self) -> bool
+    {
+        self.staged_files.is_empty()
+    }
+
+    /// Commit the transaction, replacing every target file with its staged file. If any
+    /// replacement fails, the target files that have already been replaced are restored.
+    ///
+    /// The original files are kept (as hard links) until the journal has been deleted, so that
+    /// a commit interrupted by the process dying can be rolled back by `recover()`.
+    ///
+    /// # Returns
+    ///
+    /// Nothing, or an error message if the transaction could not be committed.
+    pub fn commit(self) -> Result<(), String>
+    {
+        if self.staged_files.is_empty()
+        {
+            return Ok(());
+        }
+
+        let journal = self.journal()?;
+
+        write_journal(&self.journal_path, &journal)?;
+
+        for entry in journal.entries.iter()
+        {
+            let replace_result = match &entry.backup
+            {
+                Some(backup) => std::fs::hard_link(&entry.target, backup)
+                    .and_then(|_| std::fs::rename(&entry.staged, &entry.target)),
+                None => std::fs::rename(&entry.staged, &entry.target),
+            };
+
+            if let Err(e) = replace_result
+            {
+                let error = format!("Failed to replace {}: {}", entry.target, e);
+
+                return match rollback(&journal).and_then(|_| remove_and_sync(&self.journal_path))
+                {
+                    Ok(_) => Err(error),
+                    Err(rollback_error) => Err(format!(
+                        "{} (rollback also failed: {})",
+                        error, rollback_error
+                    )),
+                };
+            }
+        }
+
+        for entry in journal.entries.iter()
+        {
+            sync_parent_dir(&entry.target);
+        }
+
+        /*
+         * Deleting the journal is the point at which the transaction is committed.
+         */
+        remove_and_sync(&self.journal_path)?;
+
+        for backup in journal.entries.iter().filter_map(|e| e.backup.as_ref())
+        {
+            let _ = std::fs::remove_file(backup);
+        }
+
+        Ok(())
+    }
+
+    /// Build the journal for the transaction, choosing a backup path for each target that
+    /// already exists.
+    ///
+    /// # Returns
+    ///
+    /// The journal, or an error message if a path can't be resolved.
+    fn journal(&self) -> Result<Journal, String>
+    {
+        let mut journal = Journal {
+            entries: Vec::new(),
+        };
+
+        for staged_file in self.staged_files.iter()
+        {
+            /*
+             * The journal may be recovered by a run from another working directory, so it
+             * mustn't contain relative paths.
+             */
+            let target = absolute_path(&staged_file.target_path)?;
+            let staged = absolute_path(&staged_file.staged.path)?;
+
+            let backup = if Path::new(&target).exists()
+            {
+                Some(sibling_path(&target, "bak")?)
+            }
+            else
+            {
+                None
+            };
+
+            journal.entries.push(JournalEntry {
+                target,
+                staged,
+                backup,
+            });
+        }
+
+        Ok(journal)
+    }
+}
+
+/// Roll back a commit that was interrupted by the process dying, if there is one.
+///
+/// # Arguments
+///
+/// * `journal_path` - The path to the recovery journal.
+///
+/// # Returns
+///
+/// The number of files that were part of the rolled back commit (0 if there wasn't one), or an
+/// error message if the rollback failed or the journal refers to a path that can't be resolved,
+/// in which case the journal is kept.
+pub fn recover(journal_path: &str) -> Result<usize, String>
+{
+    if !Path::new(journal_path).exists()
+    {
+        return Ok(0);
+    }
+
+    let journal_yaml = match std::fs::read_to_string(journal_path)
+    {
+        Ok(y) => y,
+        Err(e) => return Err(format!("Failed to read journal {}: {}", journal_path, e)),
+    };
+
+    let journal = match serde_yaml::from_str::<Journal>(&journal_yaml)
+    {
+        Ok(j) => j,
+        Err(e) => return Err(format!("Failed to parse journal {}: {}", journal_path, e)),
+    };
+
+    for entry in journal.entries.iter()
+    {
+        check_resolvable(entry, journal_path)?;
+    }
+
+    rollback(&journal)?;
+    remove_and_sync(journal_path)?;
+
+    Ok(journal.entries.len())
+}
+
+/// Determine whether an interrupted commit is waiting to be rolled back.
+///
+/// # Arguments
+///
+/// * `journal_path` - The path to the recovery journal.
+pub fn recovery_pending(journal_path: &str) -> bool
+{
+    Path::new(journal_path).exists()
+}
+
+/// Restore the original target files recorded in a journal. This is safe to call at any point
+/// during a commit, and more than once.
+///
+/// # Arguments
+///
+/// * `journal` - The journal describing the commit to roll back.
+///
+/// # Returns
+///
+/// Nothing, or an error message if a file could not be restored.
+fn rollback(journal: &Journal) -> Result<(), String>
+{
+    for entry in journal.entries.iter().rev()
+    {
+        match &entry.backup
+        {
+            Some(backup) =>
+            {
+                /*
+                 * If the backup exists, it's the original file - whether or not the target has
+                 * been replaced yet.
+                 */
+                if is_same_file(backup, &entry.target)
+                {
+                    /*
+                     * The target hasn't been replaced yet. Renaming a hard link over another
+                     * link to the same file does nothing, so the backup must be removed instead.
+                     */
+                    if let Err(e) = std::fs::remove_file(backup)
+                    {
+                        return Err(format!("Failed to remove {}: {}", backup, e));
+                    }
+                }
+                else if Path::new(backup).exists()
+                {
+                    if let Err(e) = std::fs::rename(backup, &entry.target)
+                    {
+                        return Err(format!("Failed to restore {}: {}", entry.target, e));
+                    }
+                }
+            },
+            None =>
+            {
+                /*
+                 * The target didn't exist before the commit, so if the staged file has gone it
+                 * must have been renamed to the target.
+                 */
+                if !Path::new(&entry.staged).exists() && Path::new(&entry.target).exists()
+                {
+                    if let Err(e) = std::fs::remove_file(&entry.target)
+                    {
+                        return Err(format!("Failed to remove {}: {}", entry.target, e));
+                    }
+                }
+            },
+        }
+
+        let _ = std::fs::remove_file(&entry.staged);
+
+        sync_parent_dir(&entry.target);
+    }
+
+    Ok(())
+}
+
+/// Determine whether two paths refer to the same file.
+///
+/// # Arguments
+///
+/// * `first_path` - The first path.
+/// * `second_path` - The second path.
+///
+/// # Returns
+///
+/// True if both paths exist and refer to the same file, otherwise false.
+fn is_same_file(first_path: &str, second_path: &str) -> bool
+{
+    match (
+        std::fs::metadata(first_path),
+        std::fs::metadata(second_path),
+    )
+    {
+        #[cfg(unix)]
+        (Ok(first), Ok(second)) =>
+        {
+            use std::os::unix::fs::MetadataExt;
+
+            first.dev() == second.dev() && first.ino() == second.ino()
+        },
+        _ => false,
+    }
+}
+
+/// Atomically write the journal to disk.
+///
+/// # Arguments
+///
+/// * `journal_path` - The path to write the journal to.
+/// * `journal` - The journal to write.
+///
+/// # Returns
+///
+/// Nothing, or an error message if the journal could not be written.
+fn write_journal(journal_path: &str, journal: &Journal) -> Result<(), String>
+{
+    let journal_yaml = match serde_yaml::to_string(journal)
+    {
+        Ok(y) => y,
+        Err(e) => return Err(format!("Failed to serialize journal: {}", e)),
+    };
+
+    let staged_journal = StagedFile::write(journal_path, journal_yaml.as_bytes())?;
+
+    if let Err(e) = std::fs::rename(staged_journal.staged_path(), journal_path)
+    {
+        return Err(format!("Failed to write journal {}: {}", journal_path, e));
+    }
+
+    sync_parent_dir(journal_path);
+
+    Ok(())
+}
+
+/// Remove a file and make the removal durable.
+///
+/// # Arguments
+///
+/// * `path` - The path to the file to remove.
+///
+/// # Returns
+///
+/// Nothing, or an error message if the file could not be removed.
+fn remove_and_sync(path: &str) -> Result<(), String>
+{
+    if let Err(e) = std::fs::remove_file(path)
+    {
+        return Err(format!("Failed to remove {}: {}", path, e));
+    }
+
+    sync_parent_dir(path);
+
+    Ok(())
+}
+
+/// Make changes to the entries of a file's parent directory durable. Not all platforms support
+/// syncing a directory, so this is best effort.
+///
+/// # Arguments
+///
+/// * `path` - The path to a file in the directory to sync.
+fn sync_parent_dir(path: &str)
+{
+    if let Some(parent) = Path::new(path).parent()
+    {
+        if let Ok(dir) = std::fs::File::open(parent)
+        {
+            let _ = dir.sync_all();
+        }
+    }
+}
+
+/// Make a path absolute, without requiring it to exist.
+///
+/// # Arguments
+///
+/// * `path` - The path, which may be relative to the current working directory.
+///
+/// # Returns
+///
+/// The absolute path, or an error message if the current working directory can't be determined.
+fn absolute_path(path: &str) -> Result<String, String>
+{
+    match std::path::absolute(path)
+    {
+        Ok(p) => Ok(p.to_string_lossy().to_string()),
+        Err(e) => Err(format!("Failed to resolve path {}: {}", path, e)),
+    }
+}
+
+/// Check that every path in a journal entry can be resolved, so that a rollback doesn't mistake
+/// a file it can't find for one that was never created.
+///
+/// # Arguments
+///
+/// * `entry` - The journal entry.
+/// * `journal_path` - The path to the recovery journal, for error messages.
+///
+/// # Returns
+///
+/// An error message if a path is relative or its directory doesn't exist.
+fn check_resolvable(entry: &JournalEntry, journal_path: &str) -> Result<(), String>
+{
+    for path in [
+        Some(&entry.target),
+        Some(&entry.staged),
+        entry.backup.as_ref(),
+    ]
+    .into_iter()
+    .flatten()
+    {
+        let resolvable = Path::new(path).is_absolute()
+            && Path::new(path).parent().is_some_and(|dir| dir.is_dir());
+
+        if !resolvable
+        {
+            return Err(format!(
+                "Failed to resolve {} in journal {}: restore the file by hand, then delete the journal",
+                path, journal_path
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Generate a unique, hidden path in the same directory as a target file.
+///
+/// # Arguments
+///
+/// * `target_path` - The path to the target file.
+/// * `extension` - The extension to give the generated path.
+///
+/// # Returns
+///
+/// The generated path, or an error message if the target path has no file name.
+pub fn sibling_path(target_path: &str, extension: &str) -> Result<String, String>
+{
+    use uuid::Uuid;
+
+    let target = Path::new(target_path);
+
+    let (target_dir, target_name) = match (target.parent(), target.file_name())
+    {
+        (Some(dir), Some(name)) => (dir, name.to_string_lossy()),
+        _ =>
+        {
+            return Err(format!(
+                "Failed to determine directory of file {}",
+                target_path
+            ))
+        },
+    };
+
+    match target_dir
+        .join(format!(
+            ".{}.breadlog-{}.{}",
+            target_name,
+            Uuid::new_v4(),
+            extension
+        ))
+        .to_str()
+    {
+        Some(p) => Ok(p.to_string()),
+        None => Err("Failed to convert path to string".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use tempdir::TempDir;
+
+    fn path_string(dir: &TempDir, name: &str) -> String
+    {
+        dir.path().join(name).to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_staged_file_dropped()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let target_path = path_string(&temp_dir, "test_file.rs");
+        let staged_path: String;
+
+        {
+            let staged_file = StagedFile::write(&target_path, b"new").unwrap();
+            staged_path = staged_file.staged_path().to_string();
+
+            assert!(Path::new(&staged_path).exists());
+            assert_eq!(
+                Path::new(&staged_path).parent().unwrap(),
+                Path::new(&target_path).parent().unwrap()
+            );
+        }
+
+        assert!(!Path::new(&staged_path).exists());
+        assert!(!Path::new(&target_path).exists());
+    }
+
+    #[test]
+    fn test_commit()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let journal_path = path_string(&temp_dir, "Breadlog.journal");
+        let existing_path = path_string(&temp_dir, "existing.rs");
+        let new_path = path_string(&temp_dir, "new.rs");
+
+        std::fs::write(&existing_path, "old").unwrap();
+
+        let mut transaction = Transaction::new(&journal_path);
+        transaction.add(StagedFile::write(&existing_path, b"updated").unwrap());
+        transaction.add(StagedFile::write(&new_path, b"created").unwrap());
+
+        assert_eq!(transaction.len(), 2);
+        assert!(transaction.commit().is_ok());
+
+        assert_eq!(std::fs::read_to_string(&existing_path).unwrap(), "updated");
+        assert_eq!(std::fs::read_to_string(&new_path).unwrap(), "created");
+        assert!(!Path::new(&journal_path).exists());
+
+        /*
+         * No staged files, backups or journal should be left behind.
+         */
+        assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn test_commit_failure_rolls_back()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let journal_path = path_string(&temp_dir, "Breadlog.journal");
+        let first_path = path_string(&temp_dir, "first.rs");
+        let second_path = path_string(&temp_dir, "second.rs");
+        let new_path = path_string(&temp_dir, "new.rs");
+
+        std::fs::write(&first_path, "first").unwrap();
+        std::fs::write(&second_path, "second").unwrap();
+
+        let mut transaction = Transaction::new(&journal_path);
+        transaction.add(StagedFile::write(&first_path, b"first updated").unwrap());
+        transaction.add(StagedFile::write(&new_path, b"created").unwrap());
+
+        let second_staged = StagedFile::write(&second_path, b"second updated").unwrap();
+
+        /*
+         * Simulate a failure part way through the commit.
+         */
+        std::fs::remove_file(second_staged.staged_path()).unwrap();
+        transaction.add(second_staged);
+
+        assert!(transaction.commit().is_err());
+
+        assert_eq!(std::fs::read_to_string(&first_path).unwrap(), "first");
+        assert_eq!(std::fs::read_to_string(&second_path).unwrap(), "second");
+        assert!(!Path::new(&new_path).exists());
+        assert!(!Path::new(&journal_path).exists());
+        assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn test_recover_interrupted_commit()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let journal_path = path_string(&temp_dir, "Breadlog.journal");
+        let replaced_path = path_string(&temp_dir, "replaced.rs");
+        let pending_path = path_string(&temp_dir, "pending.rs");
+        let created_path = path_string(&temp_dir, "created.rs");
+
+        std::fs::write(&replaced_path, "replaced original").unwrap();
+        std::fs::write(&pending_path, "pending original").unwrap();
+
+        let replaced_staged = path_string(&temp_dir, ".replaced.rs.staged");
+        let replaced_backup = path_string(&temp_dir, ".replaced.rs.bak");
+        let pending_staged = path_string(&temp_dir, ".pending.rs.staged");
+        let pending_backup = path_string(&temp_dir, ".pending.rs.bak");
+        let created_staged = path_string(&temp_dir, ".created.rs.staged");
+
+        /*
+         * Reproduce the state of a commit that died after replacing one existing file and
+         * creating a new one, but before replacing the last file (whose backup link was
+         * already made).
+         */
+        std::fs::write(&pending_staged, "pending updated").unwrap();
+        std::fs::hard_link(&pending_path, &pending_backup).unwrap();
+        std::fs::hard_link(&replaced_path, &replaced_backup).unwrap();
+        std::fs::write(&replaced_staged, "replaced updated").unwrap();
+        std::fs::rename(&replaced_staged, &replaced_path).unwrap();
+        std::fs::write(&created_path, "created").unwrap();
+
+        write_journal(
+            &journal_path,
+            &Journal {
+                entries: vec![
+                    JournalEntry {
+                        target: replaced_path.clone(),
+                        staged: replaced_staged,
+                        backup: Some(replaced_backup),
+                    },
+                    JournalEntry {
+                        target: created_path.clone(),
+                        staged: created_staged,
+                        backup: None,
+                    },
+                    JournalEntry {
+                        target: pending_path.clone(),
+                        staged: pending_staged,
+                        backup: Some(pending_backup),
+                    },
+                ],
+            },
+        )
+        .unwrap();
+
+        assert!(recovery_pending(&journal_path));
+        assert_eq!(recover(&journal_path), Ok(3));
+        assert!(!recovery_pending(&journal_path));
+
+        assert_eq!(
+            std::fs::read_to_string(&replaced_path).unwrap(),
+            "replaced original"
+        );
+        assert_eq!(
+            std::fs::read_to_string(&pending_path).unwrap(),
+            "pending original"
+        );
+        assert!(!Path::new(&created_path).exists());
+        assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn test_commit_relative_paths()
+    {
+        let temp_dir = TempDir::new_in(".", "breadlog_test").unwrap();
+        let relative_dir = temp_dir.path().file_name().unwrap().to_str().unwrap();
+        let journal_path = path_string(&temp_dir, "Breadlog.journal");
+        let target_path = format!("{}/target.rs", relative_dir);
+
+        let mut transaction = Transaction::new(&journal_path);
+
+        transaction.add(StagedFile::write(&target_path, b"created").unwrap());
+
+        let journal = transaction.journal().unwrap();
+
+        assert!(Path::new(&journal.entries[0].target).is_absolute());
+        assert!(Path::new(&journal.entries[0].staged).is_absolute());
+        assert_eq!(transaction.commit(), Ok(()));
+        assert_eq!(std::fs::read_to_string(&target_path).unwrap(), "created");
+        assert!(!Path::new(&journal_path).exists());
+    }
+
+    #[test]
+    fn test_recover_unresolvable_entry()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let journal_path = path_string(&temp_dir, "Breadlog.journal");
+        let missing_dir_path = path_string(&temp_dir, "missing/created.rs");
+
+        for (target, staged) in [
+            ("created.rs".to_string(), ".created.rs.staged".to_string()),
+            (
+                missing_dir_path.clone(),
+                path_string(&temp_dir, "missing/.created.rs.staged"),
+            ),
+        ]
+        {
+            write_journal(
+                &journal_path,
+                &Journal {
+                    entries: vec![JournalEntry {
+                        target,
+                        staged,
+                        backup: None,
+                    }],
+                },
+            )
+            .unwrap();
+
+            assert!(recover(&journal_path).is_err());
+            assert!(recovery_pending(&journal_path));
+        }
+    }
+
+    #[test]
+    fn test_recover_nothing_to_do()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let journal_path = path_string(&temp_dir, "Breadlog.journal");
+
+        assert!(!recovery_pending(&journal_path));
+        assert_eq!(recover(&journal_path), Ok(0));
+    }
+}
@@ -1,18 +1,76 @@
+use super::registry::Registry;
+use super::validation;
+use super::workspace::{self, WorkspaceCrate};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::path::{Component, Path, PathBuf};
 use std::str::FromStr;
 use std::sync::atomic;
 use std::sync::Arc;
 
+/// Whether or not log statements using a macro must have references.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ReferencePolicy
+{
+    /// References are inserted where they're missing, and check mode fails if any are missing.
+    #[default]
+    Required,
+
+    /// Existing references are kept, but new ones aren't inserted.
+    Optional,
+
+    /// Check mode fails if any references are present.
+    Forbidden,
+}
+
 /// A Rust log macro to search for.
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
 pub struct RustLogMacro
 {
+    /// The module containing the macro, which must be a Rust path.
+    #[serde(deserialize_with = "validation::deserialize_module_path")]
+    #[schemars(regex(pattern = r"^[A-Za-z_][A-Za-z0-9_]*(::[A-Za-z_][A-Za-z0-9_]*)*$"))]
     pub module: String,
+
+    /// The name of the macro, which must be a Rust identifier.
+    #[serde(deserialize_with = "validation::deserialize_identifier")]
+    #[schemars(regex(pattern = r"^[A-Za-z_][A-Za-z0-9_]*$"))]
     pub name: String,
+
+    /// Whether or not log statements using the macro must have references.
+    #[serde(default)]
+    pub policy: ReferencePolicy,
+
+    /// Whether or not references are to be created using structured logging features in log
+    /// statements using the macro, overriding the directory and language settings.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub structured: Option<bool>,
+}
+
+/// Rust settings overridden for a directory.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct RustDirectoryConfig
+{
+    /// The directory, relative to the source directory.
+    pub path: String,
+
+    /// Whether or not references are to be created using structured logging features in files
+    /// in the directory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub structured: Option<bool>,
+
+    /// Log macros to search for in files in the directory, in addition to those searched for in
+    /// its parent. A macro with the same module and name as an inherited one replaces it.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub log_macros: Vec<RustLogMacro>,
 }
 
 /// The configuration for the Rust language.
-#[derive(Clone, Default, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Default, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
 pub struct RustConfig
 {
     /// Whether or not references are to be created using structured logging features.
@@ -20,38 +78,419 @@ pub struct RustConfig
     pub structured: bool,
 
     /// The log macros to search for.
+    #[serde(deserialize_with = "validation::deserialize_log_macros")]
+    #[schemars(length(min = 1))]
     pub log_macros: Vec<RustLogMacro>,
 
     /// The extensions of files to search for log macros in.
-    #[serde(default = "default_rust_extensions")]
+    #[serde(
+        default = "default_rust_extensions",
+        deserialize_with = "validation::deserialize_extensions"
+    )]
+    #[schemars(length(min = 1))]
     pub extensions: Vec<String>,
+
+    /// Settings overridden for directories in the source directory.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub directories: Vec<RustDirectoryConfig>,
+}
+
+impl RustConfig
+{
+    /// Determine the Rust settings for a file, taking into account the settings overridden for
+    /// the directories containing it. Overrides are applied from the least to the most specific
+    /// directory, so the most specific one takes precedence.
+    ///
+    /// # Arguments
+    ///
+    /// * `relative_path` - The path of the file, relative to the source directory.
+    ///
+    /// # Returns
+    ///
+    /// The settings for the file, or `None` if no directory containing it overrides any.
+    #[allow(dead_code)]
+    pub fn for_path(&self, relative_path: &Path) -> Option<RustConfig>
+    {
+        let normalise = |p: &str| -> PathBuf {
+            Path::new(p)
+                .components()
+                .filter(|c| *c != Component::CurDir)
+                .collect()
+        };
+
+        let mut directories: Vec<(usize, &RustDirectoryConfig)> = self
+            .directories
+            .iter()
+            .map(|d| (normalise(&d.path), d))
+            .filter(|(path, _)| relative_path.starts_with(path))
+            .map(|(path, d)| (path.components().count(), d))
+            .collect();
+
+        if directories.is_empty()
+        {
+            return None;
+        }
+
+        /*
+         * The sort is stable, so later directories take precedence over earlier ones with the
+         * same path.
+         */
+        directories.sort_by_key(|(depth, _)| *depth);
+
+        let mut result = self.clone();
+
+        for (_, directory) in directories.iter()
+        {
+            if let Some(structured) = directory.structured
+            {
+                result.structured = structured;
+            }
+
+            for log_macro in directory.log_macros.iter()
+            {
+                match result
+                    .log_macros
+                    .iter_mut()
+                    .find(|m| m.module == log_macro.module && m.name == log_macro.name)
+                {
+                    Some(inherited) => *inherited = log_macro.clone(),
+                    None => result.log_macros.push(log_macro.clone()),
+                }
+            }
+        }
+
+        Some(result)
+    }
+
+    /// Determine whether or not references are to be created using structured logging features
+    /// in a file. If more than one directory containing the file has overridden the setting,
+    /// the most specific one is used.
+    ///
+    /// # Arguments
+    ///
+    /// * `relative_path` - The path of the file, relative to the source directory.
+    ///
+    /// # Returns
+    ///
+    /// Whether or not references are to be created using structured logging features.
+    #[allow(dead_code)]
+    pub fn structured_for_path(&self, relative_path: &Path) -> bool
+    {
+        self.for_path(relative_path)
+            .map_or(self.structured, |r| r.structured)
+    }
+}
+
+/// How the directories containing the source code to be processed are found.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SourceMode
+{
+    /// The directories are listed in `source_dir` or `source_dirs`.
+    #[default]
+    Directories,
+
+    /// The directories are those of the member crates of a Cargo workspace.
+    CargoWorkspace,
+}
+
+/// The configuration for finding the source directories of a Cargo workspace.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct CargoWorkspaceConfig
+{
+    /// The path of the workspace's manifest, relative to the configuration file.
+    #[serde(default = "default_cargo_manifest")]
+    pub manifest: String,
+
+    /// Whether or not to process each crate's `tests` directory, as well as its `src`,
+    /// `examples` and `benches` directories.
+    #[serde(default)]
+    pub include_tests: bool,
+}
+
+impl Default for CargoWorkspaceConfig
+{
+    fn default() -> Self
+    {
+        CargoWorkspaceConfig {
+            manifest: default_cargo_manifest(),
+            include_tests: false,
+        }
+    }
+}
+
+/// How new reference IDs are chosen.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum IdStrategy
+{
+    /// Allocate contiguous IDs, starting from the next reference ID.
+    #[default]
+    Sequential,
+
+    /// Derive each ID from a hash of the file path, log message and salt, so that IDs can be
+    /// generated on independent branches without coordinating through the lock file.
+    Hash,
 }
 
 /// The configuration for Breadlog.
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
 pub struct Config
 {
     /// The directory containing the Breadlog configuration file. This is of course not read from the configuration file itself.
     #[serde(skip)]
     pub config_dir: String,
 
-    /// The directory containing the source code to be processed.
+    /// The version of the configuration format. Configuration files without a version are in
+    /// the first version of the format.
+    #[serde(default = "default_config_version")]
+    #[schemars(range(min = 1, max = 1))]
+    pub version: u32,
+
+    /// How the directories containing the source code to be processed are found.
+    #[serde(default)]
+    pub source: SourceMode,
+
+    /// The configuration for finding the source directories of a Cargo workspace, if `source`
+    /// is `cargo_workspace`.
+    #[serde(default)]
+    pub cargo_workspace: CargoWorkspaceConfig,
+
+    /// The crates of the Cargo workspace, if `source` is `cargo_workspace`. These are found
+    /// rather than read from the configuration file.
+    #[serde(skip)]
+    pub crates: Vec<WorkspaceCrate>,
+
+    /// The directory containing the source code to be processed. Unless `source` is
+    /// `cargo_workspace`, either this or `source_dirs` must be set.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub source_dir: String,
 
+    /// The directories containing the source code to be processed, if there's more than one.
+    /// They're scanned as one code base, sharing reference IDs.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub source_dirs: Vec<String>,
+
     /// Whether or not to use a lock file to cache the next reference ID.
     #[serde(default = "default_use_cache")]
     pub use_cache: bool,
 
+    /// Whether or not to keep a registry of every reference ID ever issued, so that IDs removed
+    /// from the code base are never issued again.
+    #[serde(default)]
+    pub use_registry: bool,
+
+    /// How long, in seconds, to wait for another Breadlog run to release the run lock before
+    /// giving up.
+    #[serde(default = "default_lock_timeout")]
+    pub lock_timeout: u64,
+
+    /// How new reference IDs are chosen.
+    #[serde(default)]
+    pub id_strategy: IdStrategy,
+
+    /// Mixed into the hash of each reference when using the hash ID strategy, so that different
+    /// code bases derive different IDs from the same messages.
+    #[serde(default)]
+    pub id_salt: String,
+
+    /// Glob patterns, relative to the source directory, of the files to search. If empty, every
+    /// file is searched.
+    #[serde(default)]
+    pub include: Vec<String>,
+
+    /// Glob patterns, relative to the source directory, of the files and directories not to
+    /// search. Takes precedence over `include`.
+    #[serde(default)]
+    pub exclude: Vec<String>,
+
+    /// Whether or not to skip the files ignored by `.gitignore` and `.ignore` files.
+    #[serde(default)]
+    pub respect_gitignore: bool,
+
+    /// The size, in bytes, above which files aren't searched.
+    #[serde(default)]
+    pub max_file_size: Option<u64>,
+
+    /// Whether or not to follow symbolic links when searching the source directory.
+    #[serde(default)]
+    pub follow_symlinks: bool,
+
     /// The configuration for the Rust language.
     #[serde(default)]
     pub rust: RustConfig,
 }
 
+impl Config
+{
+    /// The version of the configuration format written by this version of Breadlog.
+    pub const CURRENT_VERSION: u32 = 1;
+
+    /// Returns the absolute paths of the directories containing the source code to be processed.
+    pub fn source_dirs(&self) -> Vec<String>
+    {
+        match self.source_dirs.is_empty()
+        {
+            true => vec![self.source_dir.clone()],
+            false => self.source_dirs.clone(),
+        }
+    }
+}
+
+/// A block of reference IDs reserved for a developer or branch, so that references inserted on
+/// different branches never collide.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Reservation
+{
+    /// The label identifying who the block is reserved for.
+    pub owner: String,
+
+    /// The first ID in the block.
+    pub start: u32,
+
+    /// The number of IDs in the block.
+    pub size: u32,
+
+    /// The next unused ID in the block.
+    pub next: u32,
+}
+
+impl Reservation
+{
+    /// Check that the block is consistent. The lock file can be edited by hand, so this is done
+    /// when it's loaded, before any arithmetic is done on the block's IDs.
+    ///
+    /// # Returns
+    ///
+    /// Nothing, or an error message if the block is empty, ends beyond the last reference ID,
+    /// or its next ID is outside it.
+    pub fn validate(&self) -> Result<(), String>
+    {
+        match self.start.checked_add(self.size)
+        {
+            Some(end) if self.size > 0 && self.start <= self.next && self.next <= end => Ok(()),
+            _ => Err(format!(
+                "Invalid reservation for {}: start {}, size {}, next {}",
+                self.owner, self.start, self.size, self.next
+            )),
+        }
+    }
+
+    /// Returns the ID after the last ID in the block.
+    #[allow(dead_code)]
+    pub fn end(&self) -> u32
+    {
+        self.start + self.size
+    }
+
+    /// Returns the number of unused IDs in the block.
+    #[allow(dead_code)]
+    pub fn remaining(&self) -> u32
+    {
+        self.end() - self.next
+    }
+}
+
 /// The Breadlog lock file structure.
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Cache
 {
     pub next_reference_id: u32,
+
+    /// Blocks of reference IDs reserved for developers or branches. Every reserved ID is below
+    /// `next_reference_id`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reservations: Vec<Reservation>,
+}
+
+impl Cache
+{
+    /// Create a new lock file structure with no reservations.
+    ///
+    /// # Arguments
+    ///
+    /// * `next_reference_id` - The next reference ID.
+    #[allow(dead_code)]
+    pub fn new(next_reference_id: u32) -> Cache
+    {
+        Cache {
+            next_reference_id,
+            reservations: Vec::new(),
+        }
+    }
+
+    /// Parse and validate the contents of a lock file.
+    ///
+    /// # Arguments
+    ///
+    /// * `yaml` - The lock file contents.
+    ///
+    /// # Returns
+    ///
+    /// The lock file structure, or an error message if it can't be parsed or has an invalid
+    /// reservation.
+    pub fn from_yaml(yaml: &str) -> Result<Cache, String>
+    {
+        let cache = serde_yaml::from_str::<Cache>(yaml).map_err(|e| e.to_string())?;
+
+        for reservation in cache.reservations.iter()
+        {
+            reservation.validate()?;
+        }
+
+        Ok(cache)
+    }
+
+    /// Returns the reservation belonging to an owner, if there is one.
+    ///
+    /// # Arguments
+    ///
+    /// * `owner` - The owner label.
+    #[allow(dead_code)]
+    pub fn reservation(&self, owner: &str) -> Option<&Reservation>
+    {
+        self.reservations.iter().find(|r| r.owner == owner)
+    }
+
+    /// Returns the reservation belonging to an owner for modification, if there is one.
+    ///
+    /// # Arguments
+    ///
+    /// * `owner` - The owner label.
+    #[allow(dead_code)]
+    pub fn reservation_mut(&mut self, owner: &str) -> Option<&mut Reservation>
+    {
+        self.reservations.iter_mut().find(|r| r.owner == owner)
+    }
+
+    /// Merge another version of the lock file into this one. The larger next reference ID is
+    /// kept, as are the reservations from both versions; where both versions have the same
+    /// reservation, the one with more IDs used is kept.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - The other version of the lock file.
+    #[allow(dead_code)]
+    pub fn merge(&mut self, other: &Cache)
+    {
+        self.next_reference_id = self.next_reference_id.max(other.next_reference_id);
+
+        for other_reservation in other.reservations.iter()
+        {
+            match self
+                .reservations
+                .iter_mut()
+                .find(|r| r.owner == other_reservation.owner && r.start == other_reservation.start)
+            {
+                Some(r) => r.next = r.next.max(other_reservation.next),
+                None => self.reservations.push(other_reservation.clone()),
+            }
+        }
+
+        self.reservations.sort_by_key(|r| r.start);
+    }
 }
 
 /// Application context.
@@ -71,12 +510,37 @@ pub struct Context
     /// Whether or not there's a pending exit request (e.g. from a signal).
     #[allow(dead_code)]
     pub stop_commanded: Arc<atomic::AtomicBool>,
+
+    /// The absolute paths of the files to restrict processing to, if only a subset of the source
+    /// directory is to be checked or modified.
+    #[allow(dead_code)]
+    pub selected_files: Option<Vec<String>>,
+
+    /// The maximum number of files to process concurrently.
+    #[allow(dead_code)]
+    pub max_jobs: usize,
+
+    /// The owner of the reserved block of IDs to draw new references from, if any.
+    #[allow(dead_code)]
+    pub id_owner: Option<String>,
 }
 
 impl Context
 {
     const CACHE_FILENAME: &'static str = "Breadlog.lock";
 
+    #[allow(dead_code)]
+    const JOURNAL_FILENAME: &'static str = "Breadlog.journal";
+
+    #[allow(dead_code)]
+    const RUN_LOCK_FILENAME: &'static str = "Breadlog.run.lock";
+
+    /// The name of the registry file, kept alongside the lock file.
+    const REGISTRY_FILENAME: &'static str = "Breadlog.registry";
+
+    /// The header written at the top of the registry file.
+    const REGISTRY_EDIT_WARNING: &'static str = "# AUTO-GENERATED FILE - DON'T EDIT\n# This file records every reference ID ever issued, so that IDs are never reused. Commit it\n# along with your code.\n\n";
+
     #[allow(dead_code)]
     const CACHE_EDIT_WARNING: &'static str = "# AUTO-GENERATED FILE - DON'T EDIT\n# If you would like to recalculate the next reference from your code, delete this file and\n# run Breadlog.\n\n";
 
@@ -107,6 +571,9 @@ impl Context
                     cached_next_reference_id: next_reference_id,
                     check_mode,
                     stop_commanded: Arc::new(atomic::AtomicBool::new(false)),
+                    selected_files: None,
+                    max_jobs: Context::default_max_jobs(),
+                    id_owner: None,
                 };
 
                 match String::from_str(config_dir)
@@ -115,21 +582,90 @@ impl Context
                     Err(e) => return Err(e.to_string()),
                 }
 
-                if !loaded_context
-                    .config
-                    .source_dir
-                    .starts_with(std::path::MAIN_SEPARATOR)
+                let config = &mut loaded_context.config;
+
+                if config.version == 0 || config.version > Config::CURRENT_VERSION
+                {
+                    return Err(format!(
+                        "Unsupported configuration version {}: this version of Breadlog supports versions 1 to {}",
+                        config.version,
+                        Config::CURRENT_VERSION
+                    ));
+                }
+
+                match (
+                    config.source,
+                    config.source_dir.is_empty(),
+                    config.source_dirs.is_empty(),
+                )
                 {
-                    // It's a relative path so prepend the config dir
-                    match path::Path::new(&loaded_context.config.config_dir)
-                        .join(&loaded_context.config.source_dir)
-                        .to_str()
+                    (SourceMode::Directories, true, true) =>
                     {
-                        None =>
+                        return Err("One of source_dir or source_dirs must be set".to_string())
+                    },
+                    (SourceMode::Directories, false, false) =>
+                    {
+                        return Err("Only one of source_dir or source_dirs can be set".to_string())
+                    },
+                    (SourceMode::CargoWorkspace, false, _)
+                    | (SourceMode::CargoWorkspace, _, false) => return Err(
+                        "source_dir and source_dirs can't be set when source is cargo_workspace"
+                            .to_string(),
+                    ),
+                    _ => (),
+                }
+
+                for source_dir in std::iter::once(&mut config.source_dir)
+                    .chain(config.source_dirs.iter_mut())
+                    .filter(|d| !d.is_empty())
+                {
+                    if !source_dir.starts_with(std::path::MAIN_SEPARATOR)
+                    {
+                        // It's a relative path so prepend the config dir
+                        match path::Path::new(&config.config_dir)
+                            .join(&source_dir)
+                            .to_str()
                         {
-                            return Err("Failed to make configuration path absolute".to_string())
-                        },
-                        Some(p) => loaded_context.config.source_dir = p.to_string(),
+                            None =>
+                            {
+                                return Err("Failed to make configuration path absolute".to_string())
+                            },
+                            Some(p) => *source_dir = p.to_string(),
+                        }
+                    }
+                }
+
+                if config.source == SourceMode::CargoWorkspace
+                {
+                    let manifest_path =
+                        path::Path::new(&config.config_dir).join(&config.cargo_workspace.manifest);
+
+                    config.crates = workspace::workspace_crates(&manifest_path)?;
+
+                    for source_dir in config
+                        .crates
+                        .iter()
+                        .flat_map(|c| c.source_dirs(config.cargo_workspace.include_tests))
+                    {
+                        match source_dir.to_str()
+                        {
+                            None =>
+                            {
+                                return Err(format!(
+                                    "Invalid source directory {}",
+                                    source_dir.to_string_lossy()
+                                ))
+                            },
+                            Some(d) => config.source_dirs.push(d.to_string()),
+                        }
+                    }
+
+                    if config.source_dirs.is_empty()
+                    {
+                        return Err(format!(
+                            "No source directories found in the crates of {}",
+                            manifest_path.to_string_lossy()
+                        ));
                     }
                 }
 
@@ -140,6 +676,44 @@ impl Context
         }
     }
 
+    /// Create a context for reading a different source directory with the same configuration,
+    /// for example to compare two versions of a code base. The new context is always in check
+    /// mode.
+    ///
+    /// # Arguments
+    ///
+    /// * `source_dir` - The source directory.
+    ///
+    /// # Returns
+    ///
+    /// The new application context.
+    #[allow(dead_code)]
+    pub fn for_source_dir(&self, source_dir: &str) -> Context
+    {
+        let mut config = self.config.clone();
+        config.source_dir = source_dir.to_string();
+        config.source_dirs.clear();
+        config.source = SourceMode::Directories;
+        config.crates.clear();
+
+        Context {
+            config,
+            cached_next_reference_id: None,
+            check_mode: true,
+            stop_commanded: Arc::clone(&self.stop_commanded),
+            selected_files: None,
+            max_jobs: self.max_jobs,
+            id_owner: None,
+        }
+    }
+
+    /// The default maximum number of files to process concurrently, which is the number of CPUs
+    /// available to the process.
+    pub fn default_max_jobs() -> usize
+    {
+        std::thread::available_parallelism().map_or(1, |n| n.get())
+    }
+
     /// Read the next reference ID from the lock file.
     ///
     /// # Arguments
@@ -151,6 +725,21 @@ impl Context
     ///
     /// The cached next reference ID, if one exists.
     fn read_cached_next_reference_id(config: &Config, directory_path: &str) -> Option<u32>
+    {
+        Context::read_cache(config, directory_path).map(|c| c.next_reference_id)
+    }
+
+    /// Read the lock file.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The Breadlog configuration.
+    /// * `directory_path` - The directory containing the lock file.
+    ///
+    /// # Returns
+    ///
+    /// The lock file contents, if caching is enabled and the lock file exists and is valid.
+    fn read_cache(config: &Config, directory_path: &str) -> Option<Cache>
     {
         let cache_path = std::path::Path::new(directory_path).join(Context::CACHE_FILENAME);
 
@@ -161,9 +750,9 @@ impl Context
 
         if let Ok(cache_yaml) = std::fs::read_to_string(cache_path)
         {
-            match serde_yaml::from_str::<Cache>(cache_yaml.as_str())
+            match Cache::from_yaml(cache_yaml.as_str())
             {
-                Ok(loaded_cache) => Some(loaded_cache.next_reference_id),
+                Ok(loaded_cache) => Some(loaded_cache),
                 Err(e) =>
                 {
                     log::warn!(
@@ -194,39 +783,253 @@ impl Context
     #[allow(dead_code)]
     pub fn cache_next_reference_id(&self, id: u32, directory_path: &str)
     {
-        if !self.config.use_cache
+        let mut cache = Context::read_cache(&self.config, directory_path).unwrap_or(Cache::new(id));
+        cache.next_reference_id = id;
+
+        if let Some(yaml) = self.cache_contents(&cache)
         {
-            return;
+            if let Err(e) = std::fs::write(Context::cache_path(directory_path), yaml)
+            {
+                log::warn!(
+                    "[ref: 33] Failed to write lock file {}: {}",
+                    Context::CACHE_FILENAME,
+                    e
+                );
+            }
         }
+    }
 
-        let cache_path = std::path::Path::new(directory_path).join(Context::CACHE_FILENAME);
+    /// Generate the contents of the lock file.
+    ///
+    /// # Arguments
+    ///
+    /// * `cache` - The lock file structure to serialize.
+    ///
+    /// # Returns
+    ///
+    /// The lock file contents, or `None` if caching is disabled or serialization fails.
+    #[allow(dead_code)]
+    pub fn cache_contents(&self, cache: &Cache) -> Option<String>
+    {
+        if !self.config.use_cache
+        {
+            return None;
+        }
 
-        let cache = Cache {
-            next_reference_id: id,
-        };
+        match Context::serialize_cache(cache)
+        {
+            Ok(yaml) => Some(yaml),
+            Err(e) =>
+            {
+                log::warn!(
+                    "[ref: 34] Failed to serialize lock file {}: {}",
+                    Context::CACHE_FILENAME,
+                    e
+                );
+                None
+            },
+        }
+    }
 
-        match serde_yaml::to_string(&cache)
+    /// Serialize a lock file structure in the lock file format.
+    ///
+    /// # Arguments
+    ///
+    /// * `cache` - The lock file structure.
+    ///
+    /// # Returns
+    ///
+    /// The lock file contents, or an error message if serialization fails.
+    #[allow(dead_code)]
+    fn serialize_cache(cache: &Cache) -> Result<String, String>
+    {
+        match serde_yaml::to_string(cache)
         {
             Ok(mut yaml) =>
             {
                 yaml.insert_str(0, Context::CACHE_EDIT_WARNING);
+                Ok(yaml)
+            },
+            Err(e) => Err(e.to_string()),
+        }
+    }
 
-                if let Err(e) = std::fs::write(cache_path, yaml)
-                {
-                    log::warn!(
-                        "[ref: 33] Failed to write lock file {}: {}",
-                        Context::CACHE_FILENAME,
-                        e
-                    );
-                }
+    /// Merge two versions of a lock file, for use as a git merge driver. The merged lock file
+    /// holds the larger of the two next reference IDs and the reservations from both versions
+    /// (see `Cache::merge()`), and is written over our version.
+    ///
+    /// # Arguments
+    ///
+    /// * `ours_path` - Our version of the lock file, which receives the merged result.
+    /// * `theirs_path` - Their version of the lock file.
+    ///
+    /// # Returns
+    ///
+    /// The merged next reference ID, or an error message if either version couldn't be read.
+    #[allow(dead_code)]
+    pub fn merge_cache_files(ours_path: &str, theirs_path: &str) -> Result<u32, String>
+    {
+        let read_cache = |path: &str| -> Result<Cache, String> {
+            let cache_yaml = match std::fs::read_to_string(path)
+            {
+                Ok(y) => y,
+                Err(e) => return Err(format!("Failed to read {}: {}", path, e)),
+            };
+
+            match Cache::from_yaml(&cache_yaml)
+            {
+                Ok(c) => Ok(c),
+                Err(e) => Err(format!("Failed to parse {}: {}", path, e)),
+            }
+        };
+
+        let mut merged = read_cache(ours_path)?;
+        merged.merge(&read_cache(theirs_path)?);
+
+        if let Err(e) = std::fs::write(ours_path, Context::serialize_cache(&merged)?)
+        {
+            return Err(format!("Failed to write {}: {}", ours_path, e));
+        }
+
+        Ok(merged.next_reference_id)
+    }
+
+    /// Returns the path to the lock file in a directory.
+    ///
+    /// # Arguments
+    ///
+    /// * `directory_path` - The directory containing the lock file.
+    #[allow(dead_code)]
+    pub fn cache_path(directory_path: &str) -> String
+    {
+        absolute_path(std::path::Path::new(directory_path).join(Context::CACHE_FILENAME))
+    }
+
+    /// Read the next reference ID from the lock file again, in case another run has updated it
+    /// since the context was created.
+    ///
+    /// # Returns
+    ///
+    /// The cached next reference ID, if one exists.
+    #[allow(dead_code)]
+    pub fn reload_cached_next_reference_id(&self) -> Option<u32>
+    {
+        Context::read_cached_next_reference_id(&self.config, &self.config.config_dir)
+    }
+
+    /// Read the lock file again, in case another run has updated it since the context was
+    /// created.
+    ///
+    /// # Returns
+    ///
+    /// The lock file contents, if caching is enabled and the lock file exists and is valid.
+    #[allow(dead_code)]
+    pub fn reload_cache(&self) -> Option<Cache>
+    {
+        Context::read_cache(&self.config, &self.config.config_dir)
+    }
+
+    /// Returns the path to the registry file, which is kept alongside the lock file.
+    #[allow(dead_code)]
+    pub fn registry_path(&self) -> String
+    {
+        absolute_path(
+            std::path::Path::new(&self.config.config_dir).join(Context::REGISTRY_FILENAME),
+        )
+    }
+
+    /// Read the registry file. Unlike the lock file, the registry can't be recreated from the
+    /// code base, so an unreadable registry is an error rather than being ignored.
+    ///
+    /// # Returns
+    ///
+    /// The registry if it's enabled (empty if the file doesn't exist yet), `None` if it's
+    /// disabled, otherwise an error message if the file can't be read.
+    #[allow(dead_code)]
+    pub fn read_registry(&self) -> Result<Option<Registry>, String>
+    {
+        if !self.config.use_registry
+        {
+            return Ok(None);
+        }
+
+        let registry_path = self.registry_path();
+
+        let registry_yaml = match std::fs::read_to_string(&registry_path)
+        {
+            Ok(y) => y,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound =>
+            {
+                return Ok(Some(Registry::default()))
             },
-            Err(e) => log::warn!(
-                "[ref: 34] Failed to serialize lock file {}: {}",
-                Context::CACHE_FILENAME,
-                e
-            ),
+            Err(e) => return Err(format!("Failed to read {}: {}", registry_path, e)),
+        };
+
+        match serde_yaml::from_str::<Registry>(&registry_yaml)
+        {
+            Ok(r) => Ok(Some(r)),
+            Err(e) => Err(format!("Failed to parse {}: {}", registry_path, e)),
         }
     }
+
+    /// Serialize a registry structure in the registry file format.
+    ///
+    /// # Arguments
+    ///
+    /// * `registry` - The registry structure.
+    ///
+    /// # Returns
+    ///
+    /// The registry file contents, or an error message if serialization fails.
+    #[allow(dead_code)]
+    pub fn registry_contents(registry: &Registry) -> Result<String, String>
+    {
+        match serde_yaml::to_string(registry)
+        {
+            Ok(mut yaml) =>
+            {
+                yaml.insert_str(0, Context::REGISTRY_EDIT_WARNING);
+                Ok(yaml)
+            },
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    /// Returns the path to the file used to stop concurrent runs, which is kept alongside the
+    /// lock file.
+    #[allow(dead_code)]
+    pub fn run_lock_path(&self) -> String
+    {
+        absolute_path(
+            std::path::Path::new(&self.config.config_dir).join(Context::RUN_LOCK_FILENAME),
+        )
+    }
+
+    /// Returns the path to the recovery journal, which is kept alongside the lock file.
+    #[allow(dead_code)]
+    pub fn journal_path(&self) -> String
+    {
+        absolute_path(std::path::Path::new(&self.config.config_dir).join(Context::JOURNAL_FILENAME))
+    }
+}
+
+/// Make a path to a file kept alongside the lock file absolute, so that it still refers to the
+/// same file if it's recorded and read back from another working directory.
+///
+/// # Arguments
+///
+/// * `path` - The path, which may be relative to the current working directory.
+///
+/// # Returns
+///
+/// The absolute path, or the path unchanged if the current working directory can't be
+/// determined.
+fn absolute_path(path: std::path::PathBuf) -> String
+{
+    std::path::absolute(&path)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .to_string()
 }
 
 /// Default extensions for Rust files.
@@ -235,6 +1038,18 @@ fn default_rust_extensions() -> Vec<String>
     vec!["rs".to_string()]
 }
 
+/// Default configuration format version, for configuration files written before versioning.
+fn default_config_version() -> u32
+{
+    1
+}
+
+/// Default path of a Cargo workspace's manifest.
+fn default_cargo_manifest() -> String
+{
+    "Cargo.toml".to_string()
+}
+
 /// Default structured logging behaviour.
 fn default_rust_structured() -> bool
 {
@@ -247,10 +1062,19 @@ fn default_use_cache() -> bool
     true
 }
 
+/// Default run lock timeout, in seconds.
+fn default_lock_timeout() -> u64
+{
+    30
+}
+
 #[cfg(test)]
 mod tests
 {
+    use super::Cache;
     use super::Context;
+    use super::Reservation;
+    use std::path::Path;
 
     use tempdir::TempDir;
 
@@ -277,6 +1101,195 @@ mod tests
         assert!(subject.is_err());
     }
 
+    #[test]
+    fn test_structured_for_path()
+    {
+        let test_input = r#"
+        source_dir: /tmp
+        rust:
+          structured: false
+          log_macros:
+            - module: test_module
+              name: test_macro
+              structured: true
+          directories:
+            - path: ./audit
+              structured: true
+            - path: audit/legacy
+              structured: false
+        "#;
+
+        let subject = Context::new(test_input.to_string(), "/tmp", true).unwrap();
+        let rust_config = &subject.config.rust;
+
+        assert_eq!(rust_config.log_macros[0].structured, Some(true));
+        assert!(!rust_config.structured_for_path(Path::new("main.rs")));
+        assert!(rust_config.structured_for_path(Path::new("audit/main.rs")));
+        assert!(!rust_config.structured_for_path(Path::new("audit/legacy/main.rs")));
+        assert!(rust_config.structured_for_path(Path::new("audit/legacy2/main.rs")));
+        assert!(!rust_config.structured_for_path(Path::new("auditing/main.rs")));
+    }
+
+    #[test]
+    fn test_rust_config_for_path()
+    {
+        let test_input = r#"
+        source_dir: /tmp
+        rust:
+          log_macros:
+            - module: test_module
+              name: test_macro
+          directories:
+            - path: audit
+              log_macros:
+                - module: test_module
+                  name: test_macro
+                  policy: optional
+                - module: audit
+                  name: record
+            - path: audit/legacy
+              structured: true
+              log_macros:
+                - module: legacy
+                  name: record
+        "#;
+
+        let subject = Context::new(test_input.to_string(), "/tmp", true).unwrap();
+        let rust_config = &subject.config.rust;
+
+        assert_eq!(rust_config.for_path(Path::new("main.rs")), None);
+
+        let audit_config = rust_config.for_path(Path::new("audit/main.rs")).unwrap();
+        assert!(!audit_config.structured);
+        assert_eq!(audit_config.log_macros.len(), 2);
+        assert_eq!(
+            audit_config.log_macros[0].policy,
+            super::ReferencePolicy::Optional
+        );
+
+        let legacy_config = rust_config
+            .for_path(Path::new("audit/legacy/main.rs"))
+            .unwrap();
+        assert!(legacy_config.structured);
+        assert_eq!(
+            legacy_config
+                .log_macros
+                .iter()
+                .map(|m| format!("{}::{}", m.module, m.name))
+                .collect::<Vec<_>>(),
+            vec!["test_module::test_macro", "audit::record", "legacy::record"]
+        );
+    }
+
+    #[test]
+    fn test_source_dirs_config()
+    {
+        let test_input = r#"
+        source_dirs:
+          - server/src
+          - /abs/src
+        rust:
+          log_macros:
+            - module: test_module
+              name: test_macro
+        "#;
+
+        let subject = Context::new(test_input.to_string(), "/tmp/test", true).unwrap();
+
+        assert!(subject.config.source_dir.is_empty());
+        assert_eq!(
+            subject.config.source_dirs(),
+            vec![
+                String::from("/tmp/test/server/src"),
+                String::from("/abs/src")
+            ]
+        );
+
+        let both_input =
+            test_input.replace("source_dirs:", "source_dir: src\n        source_dirs:");
+
+        assert!(Context::new(both_input, "/tmp/test", true).is_err());
+    }
+    #[test]
+    fn test_cargo_workspace_config()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let config_dir = temp_dir.path().to_str().unwrap();
+
+        std::fs::write(
+            temp_dir.path().join("Cargo.toml"),
+            "[workspace]\nmembers = [\"crates/*\"]\n",
+        )
+        .unwrap();
+
+        for (name, dirs) in [
+            ("net", vec!["src", "tests"]),
+            ("db", vec!["src", "examples"]),
+        ]
+        {
+            let crate_dir = temp_dir.path().join("crates").join(name);
+            std::fs::create_dir_all(&crate_dir).unwrap();
+            std::fs::write(
+                crate_dir.join("Cargo.toml"),
+                format!("[package]\nname = \"{}\"\n", name),
+            )
+            .unwrap();
+
+            for dir in dirs
+            {
+                std::fs::create_dir_all(crate_dir.join(dir)).unwrap();
+            }
+        }
+
+        let test_input = r#"
+        source: cargo_workspace
+        rust:
+          log_macros:
+            - module: test_module
+              name: test_macro
+        "#;
+
+        let subject = Context::new(test_input.to_string(), config_dir, true).unwrap();
+
+        assert_eq!(
+            subject
+                .config
+                .crates
+                .iter()
+                .map(|c| c.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["db", "net"]
+        );
+        assert_eq!(
+            subject.config.source_dirs(),
+            vec![
+                format!("{}/crates/db/src", config_dir),
+                format!("{}/crates/db/examples", config_dir),
+                format!("{}/crates/net/src", config_dir),
+            ]
+        );
+
+        let with_tests_input = format!(
+            "{}cargo_workspace:\n          include_tests: true\n",
+            test_input
+        );
+        let subject = Context::new(with_tests_input, config_dir, true).unwrap();
+
+        assert_eq!(
+            subject.config.source_dirs().last(),
+            Some(&format!("{}/crates/net/tests", config_dir))
+        );
+
+        let with_source_dir_input =
+            test_input.replace("source:", "source_dir: src\n        source:");
+        assert!(Context::new(with_source_dir_input, config_dir, true).is_err());
+
+        let missing_manifest_input = format!(
+            "{}cargo_workspace:\n          manifest: missing/Cargo.toml\n",
+            test_input
+        );
+        assert!(Context::new(missing_manifest_input, config_dir, true).is_err());
+    }
     #[test]
     fn test_absolute_config_path()
     {
@@ -469,4 +1482,137 @@ mod tests
         let subject = Context::new(test_input.to_string(), temp_dir_str, true).unwrap();
         assert_eq!(subject.cached_next_reference_id, None);
     }
+
+    #[test]
+    fn test_merge_cache_files()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let ours_path = temp_dir.path().join("ours");
+        let theirs_path = temp_dir.path().join("theirs");
+
+        std::fs::write(
+            &ours_path,
+            Context::serialize_cache(&Cache::new(15)).unwrap(),
+        )
+        .unwrap();
+        std::fs::write(
+            &theirs_path,
+            Context::serialize_cache(&Cache::new(23)).unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            Context::merge_cache_files(ours_path.to_str().unwrap(), theirs_path.to_str().unwrap()),
+            Ok(23)
+        );
+
+        let merged = std::fs::read_to_string(&ours_path).unwrap();
+        assert!(merged.starts_with(Context::CACHE_EDIT_WARNING));
+        assert!(merged.ends_with("next_reference_id: 23\n"));
+
+        /*
+         * Our version is kept if it's the larger.
+         */
+        std::fs::write(
+            &theirs_path,
+            Context::serialize_cache(&Cache::new(7)).unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            Context::merge_cache_files(ours_path.to_str().unwrap(), theirs_path.to_str().unwrap()),
+            Ok(23)
+        );
+    }
+
+    #[test]
+    fn test_cache_merge_reservations()
+    {
+        let reservation = |owner: &str, start: u32, next: u32| Reservation {
+            owner: owner.to_string(),
+            start,
+            size: 10,
+            next,
+        };
+
+        let mut ours = Cache::new(30);
+        ours.reservations.push(reservation("alice", 10, 12));
+        ours.reservations.push(reservation("carol", 20, 20));
+
+        let mut theirs = Cache::new(40);
+        theirs.reservations.push(reservation("bob", 30, 31));
+        theirs.reservations.push(reservation("alice", 10, 15));
+
+        /*
+         * Carol's block was released on their side, but the merge can't tell that from her
+         * reserving it on ours, so it's kept.
+         */
+        ours.merge(&theirs);
+
+        assert_eq!(ours.next_reference_id, 40);
+        assert_eq!(
+            ours.reservations,
+            vec![
+                reservation("alice", 10, 15),
+                reservation("carol", 20, 20),
+                reservation("bob", 30, 31)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cache_invalid_reservations()
+    {
+        let lock_file = |start: u32, size: u32, next: u32| {
+            format!(
+                "next_reference_id: 10\nreservations:\n- owner: alice\n  start: {}\n  size: {}\n  next: {}\n",
+                start, size, next
+            )
+        };
+
+        assert!(Cache::from_yaml(&lock_file(1, 9, 1)).is_ok());
+        assert!(Cache::from_yaml(&lock_file(1, 9, 10)).is_ok());
+        assert!(Cache::from_yaml(&lock_file(u32::MAX - 1, 1, u32::MAX)).is_ok());
+
+        for (start, size, next) in [
+            (u32::MAX - 1, 2, u32::MAX - 1),
+            (1, 0, 1),
+            (5, 5, 4),
+            (5, 5, 11),
+        ]
+        {
+            assert_eq!(
+                Cache::from_yaml(&lock_file(start, size, next)),
+                Err(format!(
+                    "Invalid reservation for alice: start {}, size {}, next {}",
+                    start, size, next
+                ))
+            );
+        }
+    }
+
+    #[test]
+    fn test_merge_cache_files_invalid()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let ours_path = temp_dir.path().join("ours");
+        let theirs_path = temp_dir.path().join("theirs");
+        let ours_contents = Context::serialize_cache(&Cache::new(15)).unwrap();
+
+        std::fs::write(&ours_path, &ours_contents).unwrap();
+        std::fs::write(&theirs_path, "<<<<<<< conflict").unwrap();
+
+        assert!(Context::merge_cache_files(
+            ours_path.to_str().unwrap(),
+            theirs_path.to_str().unwrap()
+        )
+        .is_err());
+
+        assert_eq!(std::fs::read_to_string(&ours_path).unwrap(), ours_contents);
+        assert!(Context::merge_cache_files(
+            ours_path.to_str().unwrap(),
+            temp_dir.path().join("missing").to_str().unwrap()
+        )
+        .is_err());
+    }
 }
@@ -0,0 +1,264 @@
+use super::context::{RustDirectoryConfig, RustLogMacro};
+use super::workspace::{self, MANIFEST_FILENAME};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+/// The names of the YAML configuration files searched for, in order of preference.
+pub const CONFIG_FILENAMES: [&str; 2] = ["Breadlog.yaml", ".breadlog.yaml"];
+
+/// A configuration file in a subdirectory of the code base, which can only override Rust
+/// settings for the subdirectory.
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DirectoryConfigFile
+{
+    #[serde(default)]
+    rust: DirectoryRustConfig,
+}
+
+/// The Rust settings that can be overridden by a configuration file in a subdirectory.
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DirectoryRustConfig
+{
+    #[serde(default)]
+    structured: Option<bool>,
+
+    #[serde(default)]
+    log_macros: Vec<RustLogMacro>,
+}
+
+/// Find the YAML configuration file in a directory.
+///
+/// # Arguments
+///
+/// * `dir` - The directory.
+///
+/// # Returns
+///
+/// The path of the configuration file, or `None` if there isn't one.
+pub fn config_file_in(dir: &Path) -> Option<PathBuf>
+{
+    CONFIG_FILENAMES
+        .iter()
+        .map(|f| dir.join(f))
+        .find(|f| f.is_file())
+}
+
+/// Determine whether a YAML configuration file only overrides settings for its directory, rather
+/// than configuring a whole code base. Only the latter say where the source code is.
+///
+/// # Arguments
+///
+/// * `path` - The path of the configuration file.
+///
+/// # Returns
+///
+/// `true` if the file only overrides settings for its directory, `false` otherwise, including
+/// if it can't be read.
+pub fn is_directory_config(path: &Path) -> bool
+{
+    let yaml = match std::fs::read_to_string(path)
+    {
+        Ok(y) => y,
+        Err(_) => return false,
+    };
+
+    match serde_yaml::from_str::<serde_yaml::Value>(&yaml)
+    {
+        Ok(serde_yaml::Value::Mapping(m)) => !["source", "source_dir", "source_dirs"]
+            .iter()
+            .any(|key| m.contains_key(*key)),
+        Ok(serde_yaml::Value::Null) => true,
+        _ => false,
+    }
+}
+
+/// Read a configuration file in a subdirectory of the code base, which overrides Rust settings
+/// for the subdirectory, inheriting the rest from the configuration of the code base.
+///
+/// # Arguments
+///
+/// * `path` - The path of the configuration file.
+/// * `relative_dir` - The path of the subdirectory, relative to the source directory.
+///
+/// # Returns
+///
+/// The settings overridden for the subdirectory, otherwise an error message on failure.
+#[allow(dead_code)]
+pub fn read_directory_config(path: &Path, relative_dir: &str)
+    -> Result<RustDirectoryConfig, String>
+{
+    let yaml = match std::fs::read_to_string(path)
+    {
+        Ok(y) => y,
+        Err(e) => return Err(e.to_string()),
+    };
+
+    /*
+     * An empty file overrides nothing.
+     */
+    let config_file = match yaml.trim().is_empty()
+    {
+        true => DirectoryConfigFile::default(),
+        false => match serde_yaml::from_str::<DirectoryConfigFile>(&yaml)
+        {
+            Ok(c) => c,
+            Err(e) => return Err(e.to_string()),
+        },
+    };
+
+    Ok(RustDirectoryConfig {
+        path: relative_dir.to_string(),
+        structured: config_file.rust.structured,
+        log_macros: config_file.rust.log_macros,
+    })
+}
+
+/// Find the configuration for a directory: the nearest `Breadlog.yaml`, `.breadlog.yaml` or
+/// `Cargo.toml` with Breadlog metadata in the directory or one of its ancestors. Within a
+/// directory, YAML configuration files are preferred to a Cargo manifest. YAML configuration
+/// files that only override settings for their directory are skipped.
+///
+/// # Arguments
+///
+/// * `dir` - The directory to start searching from.
+///
+/// # Returns
+///
+/// The path of the configuration file, or `None` if there isn't one.
+#[allow(dead_code)]
+pub fn find_config_file(dir: &Path) -> Option<PathBuf>
+{
+    dir.ancestors().find_map(|d| {
+        let manifest = d.join(MANIFEST_FILENAME);
+
+        match config_file_in(d).filter(|f| !is_directory_config(f))
+        {
+            Some(f) => Some(f),
+            None if manifest.is_file()
+                && matches!(workspace::metadata_config(&manifest), Ok(Some(_))) =>
+            {
+                Some(manifest)
+            },
+            None => None,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_read_directory_config()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let config_path = temp_dir.path().join("Breadlog.yaml");
+
+        std::fs::write(
+            &config_path,
+            "rust:\n  structured: true\n  log_macros:\n    - module: audit\n      name: record\n",
+        )
+        .unwrap();
+
+        let directory_config = read_directory_config(&config_path, "audit").unwrap();
+
+        assert_eq!(directory_config.path, "audit");
+        assert_eq!(directory_config.structured, Some(true));
+        assert_eq!(directory_config.log_macros[0].name, "record");
+
+        std::fs::write(&config_path, "").unwrap();
+        assert_eq!(
+            read_directory_config(&config_path, "audit"),
+            Ok(RustDirectoryConfig {
+                path: String::from("audit"),
+                structured: None,
+                log_macros: Vec::new(),
+            })
+        );
+
+        /*
+         * Only Rust settings can be overridden.
+         */
+        for invalid_yaml in ["source_dir: ./src\n", "rust:\n  extensions:\n    - rs\n"]
+        {
+            std::fs::write(&config_path, invalid_yaml).unwrap();
+            assert!(read_directory_config(&config_path, "audit").is_err());
+        }
+    }
+
+    #[test]
+    fn test_find_config_file()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let root = temp_dir.path();
+        let source_dir = root.join("crates/net/src");
+
+        std::fs::create_dir_all(&source_dir).unwrap();
+        std::fs::write(
+            root.join(MANIFEST_FILENAME),
+            "[workspace]\nmembers = [\"crates/*\"]\n\n[workspace.metadata.breadlog]\n",
+        )
+        .unwrap();
+        std::fs::write(
+            root.join("crates/net").join(MANIFEST_FILENAME),
+            "[package]\nname = \"net\"\n",
+        )
+        .unwrap();
+
+        /*
+         * A manifest without Breadlog metadata is skipped.
+         */
+        assert_eq!(
+            find_config_file(&source_dir),
+            Some(root.join(MANIFEST_FILENAME))
+        );
+
+        std::fs::write(root.join(".breadlog.yaml"), "source_dir: .\n").unwrap();
+        assert_eq!(
+            find_config_file(&source_dir),
+            Some(root.join(".breadlog.yaml"))
+        );
+
+        std::fs::write(root.join("Breadlog.yaml"), "source_dir: .\n").unwrap();
+        assert_eq!(
+            find_config_file(&source_dir),
+            Some(root.join("Breadlog.yaml"))
+        );
+
+        /*
+         * The nearest configuration is used.
+         */
+        std::fs::write(
+            root.join("crates/net/.breadlog.yaml"),
+            "source: cargo_workspace\n",
+        )
+        .unwrap();
+        assert_eq!(
+            find_config_file(&source_dir),
+            Some(root.join("crates/net/.breadlog.yaml"))
+        );
+
+        /*
+         * A configuration file overriding settings for its directory is skipped.
+         */
+        std::fs::write(
+            root.join("crates/net/.breadlog.yaml"),
+            "rust:\n  structured: true\n",
+        )
+        .unwrap();
+        assert_eq!(
+            find_config_file(&source_dir),
+            Some(root.join("Breadlog.yaml"))
+        );
+
+        std::fs::write(root.join(MANIFEST_FILENAME), "[workspace]\n").unwrap();
+        std::fs::remove_file(root.join("crates/net/.breadlog.yaml")).unwrap();
+        std::fs::remove_file(root.join("Breadlog.yaml")).unwrap();
+        std::fs::remove_file(root.join(".breadlog.yaml")).unwrap();
+        assert_eq!(find_config_file(&source_dir), None);
+    }
+}
@@ -0,0 +1,306 @@
+use super::context::Config;
+
+/// A change to the configuration format, upgrading a configuration file from one version to the
+/// next. Migrations edit the text of the configuration file rather than its parsed form, so that
+/// comments and formatting are preserved.
+#[allow(dead_code)]
+pub struct Migration
+{
+    /// The version the migration upgrades from.
+    pub from: u32,
+
+    /// Upgrade the contents of a configuration file, returning an error message on failure.
+    pub apply: fn(&str) -> Result<String, String>,
+}
+
+/// The migrations between versions of the configuration format, in order. There are none yet,
+/// as the format hasn't changed since it was first versioned.
+#[allow(dead_code)]
+const MIGRATIONS: [Migration; 0] = [];
+
+/// A configuration file upgraded to the current version of the format.
+#[allow(dead_code)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct MigratedConfig
+{
+    /// The version upgraded from.
+    pub from: u32,
+
+    /// The upgraded contents of the configuration file.
+    pub yaml: String,
+}
+
+/// Read the version of a configuration file's format.
+///
+/// # Arguments
+///
+/// * `yaml` - The YAML configuration file contents.
+///
+/// # Returns
+///
+/// The version, and whether or not it's given explicitly, otherwise an error message on failure.
+#[allow(dead_code)]
+fn read_version(yaml: &str) -> Result<(u32, bool), String>
+{
+    let document = match serde_yaml::from_str::<serde_yaml::Value>(yaml)
+    {
+        Ok(serde_yaml::Value::Mapping(m)) => m,
+        Ok(_) => return Err("The configuration must be a mapping".to_string()),
+        Err(e) => return Err(e.to_string()),
+    };
+
+    match document.get("version")
+    {
+        None => Ok((1, false)),
+        Some(v) => match v.as_u64().and_then(|v| u32::try_from(v).ok())
+        {
+            Some(version) => Ok((version, true)),
+            None => Err("version must be a positive integer".to_string()),
+        },
+    }
+}
+
+/// Set the version of a configuration file's format, preserving its comments. An existing
+/// top-level `version` key is updated; otherwise the key is added before the first top-level key,
+/// and any comments directly above it.
+///
+/// # Arguments
+///
+/// * `yaml` - The YAML configuration file contents.
+/// * `version` - The version.
+///
+/// # Returns
+///
+/// The updated configuration file contents.
+#[allow(dead_code)]
+fn set_version(yaml: &str, version: u32) -> String
+{
+    let mut lines: Vec<String> = yaml.lines().map(String::from).collect();
+    let version_line = format!("version: {}", version);
+
+    let is_comment = |line: &str| line.trim_start().starts_with('#');
+
+    match lines.iter().position(|l| l.starts_with("version:"))
+    {
+        Some(index) =>
+        {
+            let comment = lines[index]
+                .find(" #")
+                .map(|c| lines[index][c..].to_string());
+            lines[index] = version_line + &comment.unwrap_or_default();
+        },
+        None =>
+        {
+            let first_key = lines
+                .iter()
+                .position(|l| !l.trim().is_empty() && !is_comment(l) && l.trim() != "---");
+
+            match first_key
+            {
+                Some(index) =>
+                {
+                    let mut insert_at = index;
+
+                    while insert_at > 0 && is_comment(&lines[insert_at - 1])
+                    {
+                        insert_at -= 1;
+                    }
+
+                    if insert_at < index
+                    {
+                        lines.insert(insert_at, String::new());
+                    }
+
+                    lines.insert(insert_at, version_line);
+                },
+                None => lines.push(version_line),
+            }
+        },
+    }
+
+    lines.join("\n") + "\n"
+}
+
+/// Upgrade a configuration file to a version of the format, applying migrations in order.
+///
+/// # Arguments
+///
+/// * `yaml` - The YAML configuration file contents.
+/// * `current_version` - The version to upgrade to.
+/// * `migrations` - The migrations between versions, in order.
+///
+/// # Returns
+///
+/// The upgraded configuration, or `None` if it's already explicitly at the version to upgrade
+/// to, otherwise an error message on failure.
+#[allow(dead_code)]
+fn apply_migrations(
+    yaml: &str,
+    current_version: u32,
+    migrations: &[Migration],
+) -> Result<Option<MigratedConfig>, String>
+{
+    let (version, explicit) = read_version(yaml)?;
+
+    if version == 0 || version > current_version
+    {
+        return Err(format!(
+            "Unsupported configuration version {}: this version of Breadlog supports versions 1 to {}",
+            version, current_version
+        ));
+    }
+
+    if version == current_version && explicit
+    {
+        return Ok(None);
+    }
+
+    let mut migrated = yaml.to_string();
+
+    for migration in migrations.iter().filter(|m| m.from >= version)
+    {
+        migrated = (migration.apply)(&migrated).map_err(|e| {
+            format!(
+                "Failed to upgrade from version {} to {}: {}",
+                migration.from,
+                migration.from + 1,
+                e
+            )
+        })?;
+    }
+
+    migrated = set_version(&migrated, current_version);
+
+    /*
+     * Make sure the result still parses, in case a migration or the version key couldn't be
+     * applied to the file's layout.
+     */
+    if let Err(e) = serde_yaml::from_str::<Config>(&migrated)
+    {
+        return Err(format!("The upgraded configuration is invalid: {}", e));
+    }
+
+    Ok(Some(MigratedConfig {
+        from: version,
+        yaml: migrated,
+    }))
+}
+
+/// Upgrade a configuration file to the current version of the format, preserving its comments
+/// where possible. Configuration files without a version are given one explicitly.
+///
+/// # Arguments
+///
+/// * `yaml` - The YAML configuration file contents.
+///
+/// # Returns
+///
+/// The upgraded configuration, or `None` if it's already explicitly at the current version,
+/// otherwise an error message on failure.
+#[allow(dead_code)]
+pub fn migrate_config(yaml: &str) -> Result<Option<MigratedConfig>, String>
+{
+    apply_migrations(yaml, Config::CURRENT_VERSION, &MIGRATIONS)
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    const CONFIG_BODY: &str = "rust:\n  # Only the log crate is used.\n  log_macros:\n    - module: log\n      name: info\n";
+
+    #[test]
+    fn test_set_version()
+    {
+        assert_eq!(
+            set_version(
+                "---\n# Breadlog configuration\n\n# The code.\nsource_dir: .\n",
+                1
+            ),
+            "---\n# Breadlog configuration\n\nversion: 1\n\n# The code.\nsource_dir: .\n"
+        );
+        assert_eq!(
+            set_version("---\nsource_dir: .\n", 1),
+            "---\nversion: 1\nsource_dir: .\n"
+        );
+        assert_eq!(
+            set_version("source_dir: .\nversion: 1 # The format version.\n", 2),
+            "source_dir: .\nversion: 2 # The format version.\n"
+        );
+        assert_eq!(set_version("# Empty\n", 1), "# Empty\nversion: 1\n");
+    }
+
+    #[test]
+    fn test_migrate_config()
+    {
+        let yaml = format!("---\n# The code.\nsource_dir: .\n{}", CONFIG_BODY);
+        let migrated = migrate_config(&yaml).unwrap().unwrap();
+
+        assert_eq!(migrated.from, 1);
+        assert_eq!(
+            migrated.yaml,
+            format!(
+                "---\nversion: 1\n\n# The code.\nsource_dir: .\n{}",
+                CONFIG_BODY
+            )
+        );
+
+        /*
+         * An explicitly versioned configuration at the current version doesn't need migrating.
+         */
+        assert_eq!(migrate_config(&migrated.yaml), Ok(None));
+
+        assert!(
+            migrate_config(&format!("version: 2\nsource_dir: .\n{}", CONFIG_BODY))
+                .unwrap_err()
+                .contains("Unsupported configuration version 2")
+        );
+        assert!(migrate_config("version: latest\n").is_err());
+        assert!(migrate_config("- source_dir\n").is_err());
+    }
+
+    #[test]
+    fn test_apply_migrations()
+    {
+        /*
+         * A hypothetical version 2, renaming source_directory to source_dir.
+         */
+        let migrations = [Migration {
+            from: 1,
+            apply: |yaml| Ok(yaml.replace("\nsource_directory:", "\nsource_dir:")),
+        }];
+
+        let yaml = format!("---\nsource_directory: . # The code.\n{}", CONFIG_BODY);
+        let migrated = apply_migrations(&yaml, 2, &migrations).unwrap().unwrap();
+
+        assert_eq!(migrated.from, 1);
+        assert_eq!(
+            migrated.yaml,
+            format!(
+                "---\nversion: 2\nsource_dir: . # The code.\n{}",
+                CONFIG_BODY
+            )
+        );
+
+        /*
+         * A configuration already at the version to upgrade to is left alone.
+         */
+        let yaml = format!("version: 2\nsource_directory: .\n{}", CONFIG_BODY);
+        assert_eq!(apply_migrations(&yaml, 2, &migrations), Ok(None));
+
+        let failing_migrations = [Migration {
+            from: 1,
+            apply: |_| Err("unexpected layout".to_string()),
+        }];
+
+        assert_eq!(
+            apply_migrations(
+                &yaml.replace("version: 2", "version: 1"),
+                2,
+                &failing_migrations
+            ),
+            Err("Failed to upgrade from version 1 to 2: unexpected layout".to_string())
+        );
+    }
+}
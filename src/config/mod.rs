@@ -1,3 +1,10 @@
 pub use context::Config;
 pub use context::Context;
 pub mod context;
+pub mod discovery;
+pub mod migration;
+pub mod registry;
+pub mod schema;
+pub mod sources;
+pub mod validation;
+pub mod workspace;
@@ -0,0 +1,476 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::collections::HashSet;
+
+/// A previous version of a log message.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MessageRevision
+{
+    /// The message template.
+    pub message: String,
+
+    /// The date (`YYYY-MM-DD`, UTC) on which the message was replaced.
+    pub replaced: String,
+}
+
+/// The registry's record of a reference ID.
+#[derive(Clone, Default, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RegistryEntry
+{
+    /// Whether the reference has been removed from the code base. Retired IDs are never issued
+    /// again.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub retired: bool,
+
+    /// The current message template.
+    #[serde(default)]
+    pub message: String,
+
+    /// The log level, which is the name of the macro used to log the message.
+    #[serde(default)]
+    pub level: String,
+
+    /// The file containing the log statement, relative to the source directory.
+    #[serde(default)]
+    pub file: String,
+
+    /// The date (`YYYY-MM-DD`, UTC) on which the reference was first recorded.
+    #[serde(default)]
+    pub first_seen: String,
+
+    /// The date (`YYYY-MM-DD`, UTC) on which the reference's message, level or file last
+    /// changed, or on which it was retired.
+    #[serde(default)]
+    pub last_changed: String,
+
+    /// The previous versions of the message template, oldest first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub history: Vec<MessageRevision>,
+}
+
+/// A reference found in the code base.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ReferenceRecord
+{
+    /// The reference ID.
+    pub id: u32,
+
+    /// The message template.
+    pub message: String,
+
+    /// The log level, which is the name of the macro used to log the message.
+    pub level: String,
+
+    /// The file containing the log statement, relative to the source directory.
+    pub file: String,
+}
+
+/// The reference IDs affected by updating the registry.
+#[derive(Clone, Default, Debug, PartialEq)]
+pub struct RegistryChanges
+{
+    /// IDs recorded for the first time.
+    pub added: Vec<u32>,
+
+    /// IDs whose message, level or file changed.
+    pub changed: Vec<u32>,
+
+    /// IDs retired because they're no longer in the code base.
+    pub retired: Vec<u32>,
+}
+
+impl RegistryChanges
+{
+    /// Returns whether or not the registry was changed.
+    #[allow(dead_code)]
+    pub fn is_empty(&self) -> bool
+    {
+        self.added.is_empty() && self.changed.is_empty() && self.retired.is_empty()
+    }
+}
+
+/// The Breadlog registry structure: a record of every reference ID ever issued, including those
+/// since removed from the code base, and how each reference's message has read over time.
+#[derive(Clone, Default, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Registry
+{
+    /// The issued reference IDs.
+    #[serde(default)]
+    pub references: BTreeMap<u32, RegistryEntry>,
+}
+
+impl Registry
+{
+    /// Returns the ID after the highest issued ID, or `None` if no IDs have been issued.
+    #[allow(dead_code)]
+    pub fn next_reference_id(&self) -> Option<u32>
+    {
+        self.references
+            .last_key_value()
+            .map(|(id, _)| id.saturating_add(1))
+    }
+
+    /// Returns every issued ID, including retired ones.
+    #[allow(dead_code)]
+    pub fn issued_ids(&self) -> HashSet<u32>
+    {
+        self.references.keys().copied().collect()
+    }
+
+    /// Returns the retired IDs.
+    #[allow(dead_code)]
+    pub fn retired_ids(&self) -> HashSet<u32>
+    {
+        self.references
+            .iter()
+            .filter(|(_, entry)| entry.retired)
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
+    /// Record the references now present in the code base. IDs seen for the first time are
+    /// added, changes to the message, level or file of existing IDs are recorded (keeping the
+    /// previous message in the history), and issued IDs that are no longer present are retired.
+    /// A retired ID stays retired, and its record unchanged, even if it reappears.
+    ///
+    /// If an ID appears more than once, only its first occurrence is recorded.
+    ///
+    /// # Arguments
+    ///
+    /// * `present` - Every reference in the code base.
+    /// * `date` - Today's date (`YYYY-MM-DD`, UTC).
+    ///
+    /// # Returns
+    ///
+    /// The IDs affected by the update.
+    #[allow(dead_code)]
+    pub fn update(&mut self, present: &[ReferenceRecord], date: &str) -> RegistryChanges
+    {
+        let mut changes = RegistryChanges::default();
+        let mut present_ids: HashSet<u32> = HashSet::new();
+
+        for record in present.iter()
+        {
+            if !present_ids.insert(record.id)
+            {
+                continue;
+            }
+
+            let entry = match self.references.get_mut(&record.id)
+            {
+                Some(e) => e,
+                None =>
+                {
+                    self.references.insert(
+                        record.id,
+                        RegistryEntry {
+                            retired: false,
+                            message: record.message.clone(),
+                            level: record.level.clone(),
+                            file: record.file.clone(),
+                            first_seen: date.to_string(),
+                            last_changed: date.to_string(),
+                            history: Vec::new(),
+                        },
+                    );
+
+                    changes.added.push(record.id);
+                    continue;
+                },
+            };
+
+            if entry.retired
+                || (entry.message == record.message
+                    && entry.level == record.level
+                    && entry.file == record.file)
+            {
+                continue;
+            }
+
+            /*
+             * An entry recorded before messages were tracked has no message to keep.
+             */
+            if entry.first_seen.is_empty()
+            {
+                entry.first_seen = date.to_string();
+            }
+            else if entry.message != record.message
+            {
+                entry.history.push(MessageRevision {
+                    message: entry.message.clone(),
+                    replaced: date.to_string(),
+                });
+            }
+
+            entry.message = record.message.clone();
+            entry.level = record.level.clone();
+            entry.file = record.file.clone();
+            entry.last_changed = date.to_string();
+
+            changes.changed.push(record.id);
+        }
+
+        for (id, entry) in self.references.iter_mut()
+        {
+            if !entry.retired && !present_ids.contains(id)
+            {
+                entry.retired = true;
+                entry.last_changed = date.to_string();
+                changes.retired.push(*id);
+            }
+        }
+
+        changes.changed.sort();
+
+        changes
+    }
+}
+
+/// Returns today's date (`YYYY-MM-DD`, UTC), as recorded in the registry.
+#[allow(dead_code)]
+pub fn today() -> String
+{
+    let days = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() / 86400) as i64;
+
+    civil_date(days)
+}
+
+/// Convert a number of days since 1970-01-01 to a date in the proleptic Gregorian calendar.
+///
+/// # Arguments
+///
+/// * `days` - The number of days since 1970-01-01.
+///
+/// # Returns
+///
+/// The date, formatted as `YYYY-MM-DD`.
+fn civil_date(days: i64) -> String
+{
+    /*
+     * Count from 0000-03-01, so that the leap day falls at the end of each 400 year era.
+     */
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10
+    {
+        month_index + 3
+    }
+    else
+    {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn record(id: u32, message: &str, file: &str) -> ReferenceRecord
+    {
+        ReferenceRecord {
+            id,
+            message: message.to_string(),
+            level: String::from("info"),
+            file: file.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_update()
+    {
+        let mut registry = Registry::default();
+
+        assert_eq!(registry.next_reference_id(), None);
+
+        let changes = registry.update(
+            &[
+                record(1, "One", "a.rs"),
+                record(2, "Two", "a.rs"),
+                record(5, "Five", "b.rs"),
+            ],
+            "2024-01-01",
+        );
+
+        assert_eq!(changes.added, vec![1, 2, 5]);
+        assert!(changes.changed.is_empty() && changes.retired.is_empty());
+        assert_eq!(registry.issued_ids(), HashSet::from([1, 2, 5]));
+        assert!(registry.retired_ids().is_empty());
+        assert_eq!(registry.next_reference_id(), Some(6));
+        assert_eq!(registry.references[&5].first_seen, "2024-01-01");
+
+        /*
+         * Removing the highest ID doesn't allow it to be issued again.
+         */
+        let changes = registry.update(
+            &[
+                record(1, "One", "a.rs"),
+                record(2, "Two", "a.rs"),
+                record(3, "Three", "a.rs"),
+            ],
+            "2024-01-02",
+        );
+
+        assert_eq!(changes.added, vec![3]);
+        assert_eq!(changes.retired, vec![5]);
+        assert_eq!(registry.issued_ids(), HashSet::from([1, 2, 3, 5]));
+        assert_eq!(registry.retired_ids(), HashSet::from([5]));
+        assert_eq!(registry.next_reference_id(), Some(6));
+        assert_eq!(registry.references[&5].last_changed, "2024-01-02");
+
+        /*
+         * A retired ID that reappears stays retired.
+         */
+        let changes = registry.update(
+            &[
+                record(1, "One", "a.rs"),
+                record(2, "Two", "a.rs"),
+                record(3, "Three", "a.rs"),
+                record(5, "Five again", "a.rs"),
+            ],
+            "2024-01-03",
+        );
+
+        assert!(changes.is_empty());
+        assert_eq!(registry.retired_ids(), HashSet::from([5]));
+        assert_eq!(registry.references[&5].message, "Five");
+    }
+
+    #[test]
+    fn test_update_history()
+    {
+        let mut registry = Registry::default();
+
+        registry.update(&[record(1, "Started", "a.rs")], "2024-01-01");
+
+        /*
+         * Moving the log statement changes its file without adding to the message history.
+         */
+        let changes = registry.update(&[record(1, "Started", "b.rs")], "2024-02-01");
+
+        assert_eq!(changes.changed, vec![1]);
+        assert!(registry.references[&1].history.is_empty());
+
+        registry.update(&[record(1, "Started {}", "b.rs")], "2024-03-01");
+        registry.update(&[record(1, "Started {} in {}", "b.rs")], "2024-04-01");
+
+        let entry = &registry.references[&1];
+
+        assert_eq!(entry.message, "Started {} in {}");
+        assert_eq!(entry.file, "b.rs");
+        assert_eq!(entry.first_seen, "2024-01-01");
+        assert_eq!(entry.last_changed, "2024-04-01");
+        assert_eq!(
+            entry.history,
+            vec![
+                MessageRevision {
+                    message: String::from("Started"),
+                    replaced: String::from("2024-03-01"),
+                },
+                MessageRevision {
+                    message: String::from("Started {}"),
+                    replaced: String::from("2024-04-01"),
+                },
+            ]
+        );
+
+        /*
+         * Nothing changes if the code doesn't.
+         */
+        assert!(registry
+            .update(&[record(1, "Started {} in {}", "b.rs")], "2024-05-01")
+            .is_empty());
+        assert_eq!(registry.references[&1].last_changed, "2024-04-01");
+    }
+
+    #[test]
+    fn test_update_duplicate()
+    {
+        let mut registry = Registry::default();
+
+        registry.update(
+            &[record(1, "First", "a.rs"), record(1, "Second", "b.rs")],
+            "2024-01-01",
+        );
+
+        assert_eq!(registry.references[&1].message, "First");
+    }
+
+    #[test]
+    fn test_update_untracked_entry()
+    {
+        let mut registry: Registry = serde_yaml::from_str("references:\n  1: {}\n").unwrap();
+
+        let changes = registry.update(&[record(1, "One", "a.rs")], "2024-01-01");
+
+        assert_eq!(changes.changed, vec![1]);
+
+        let entry = &registry.references[&1];
+
+        assert_eq!(entry.message, "One");
+        assert_eq!(entry.first_seen, "2024-01-01");
+        assert!(entry.history.is_empty());
+    }
+
+    #[test]
+    fn test_serialization()
+    {
+        let mut registry = Registry::default();
+        registry.update(
+            &[record(1, "One", "a.rs"), record(2, "Two", "a.rs")],
+            "2024-01-01",
+        );
+        registry.update(&[record(2, "Two!", "a.rs")], "2024-01-02");
+
+        let yaml = serde_yaml::to_string(&registry).unwrap();
+
+        assert_eq!(
+            yaml,
+            r#"references:
+  1:
+    retired: true
+    message: One
+    level: info
+    file: a.rs
+    first_seen: 2024-01-01
+    last_changed: 2024-01-02
+  2:
+    message: Two!
+    level: info
+    file: a.rs
+    first_seen: 2024-01-01
+    last_changed: 2024-01-02
+    history:
+    - message: Two
+      replaced: 2024-01-02
+"#
+        );
+        assert_eq!(serde_yaml::from_str::<Registry>(&yaml).unwrap(), registry);
+        assert_eq!(
+            serde_yaml::from_str::<Registry>("{}").unwrap(),
+            Registry::default()
+        );
+    }
+
+    #[test]
+    fn test_civil_date()
+    {
+        assert_eq!(civil_date(0), "1970-01-01");
+        assert_eq!(civil_date(59), "1970-03-01");
+        assert_eq!(civil_date(11016), "2000-02-29");
+        assert_eq!(civil_date(19797), "2024-03-15");
+        assert_eq!(civil_date(-1), "1969-12-31");
+        assert_eq!(today().len(), 10);
+    }
+}
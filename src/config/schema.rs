@@ -0,0 +1,50 @@
+use super::context::Config;
+use schemars::schema_for;
+
+/// Generate a JSON Schema describing the format of configuration files, which editors can use
+/// to validate and complete them.
+///
+/// # Returns
+///
+/// The JSON Schema document.
+#[allow(dead_code)]
+pub fn config_schema() -> String
+{
+    let mut schema = schema_for!(Config);
+    schema.schema.metadata().title = Some("Breadlog configuration".to_string());
+
+    serde_json::to_string_pretty(&schema).unwrap()
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn test_config_schema()
+    {
+        let schema: serde_json::Value = serde_json::from_str(&config_schema()).unwrap();
+
+        assert_eq!(schema["title"], "Breadlog configuration");
+        assert_eq!(schema["additionalProperties"], false);
+        assert_eq!(
+            schema["properties"]["version"]["maximum"].as_f64(),
+            Some(Config::CURRENT_VERSION as f64)
+        );
+        assert!(schema["properties"].get("config_dir").is_none());
+        assert!(schema["properties"].get("crates").is_none());
+        assert_eq!(
+            schema["definitions"]["RustConfig"]["properties"]["log_macros"]["minItems"],
+            1
+        );
+        assert_eq!(
+            schema["definitions"]["RustConfig"]["required"],
+            serde_json::json!(["log_macros"])
+        );
+        assert_eq!(
+            schema["definitions"]["SourceMode"]["oneOf"][1]["enum"],
+            serde_json::json!(["cargo_workspace"])
+        );
+    }
+}
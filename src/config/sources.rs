@@ -0,0 +1,258 @@
+use super::Config;
+use std::path::{Component, Path, PathBuf};
+
+/// A directory containing source code to be processed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SourceRoot
+{
+    /// The absolute path of the directory, as configured.
+    pub path: PathBuf,
+
+    /// The canonical path of the directory.
+    pub canonical_path: PathBuf,
+
+    /// Prepended to the path of each file relative to the directory, so that files are
+    /// identified uniquely across all the source directories. Empty if there's only one source
+    /// directory.
+    prefix: String,
+}
+
+/// The directories containing the source code to be processed, which are scanned as one code
+/// base.
+#[derive(Clone, Default, Debug, PartialEq)]
+pub struct SourceRoots
+{
+    roots: Vec<SourceRoot>,
+}
+
+/// Join the components of a path with `/`, leaving out `.` components, so that the result
+/// doesn't depend on the platform.
+///
+/// # Arguments
+///
+/// * `path` - The path.
+fn portable_path(path: &Path) -> String
+{
+    path.components()
+        .filter(|c| *c != Component::CurDir)
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+impl SourceRoots
+{
+    /// Resolve the configured source directories.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The configuration.
+    ///
+    /// # Returns
+    ///
+    /// The source directories, otherwise an error message if a directory can't be resolved or
+    /// two directories overlap.
+    #[allow(dead_code)]
+    pub fn new(config: &Config) -> Result<SourceRoots, String>
+    {
+        let source_dirs = config.source_dirs();
+        let mut roots: Vec<SourceRoot> = Vec::new();
+
+        for source_dir in source_dirs.iter()
+        {
+            let path = PathBuf::from(source_dir);
+
+            let canonical_path = match path.canonicalize()
+            {
+                Ok(p) => p,
+                Err(e) => return Err(format!("Failed to resolve {}: {}", source_dir, e)),
+            };
+
+            /*
+             * With more than one source directory, files are identified by their path relative
+             * to the configuration directory.
+             */
+            let prefix = match source_dirs.len()
+            {
+                1 => String::new(),
+                _ => portable_path(
+                    path.strip_prefix(&config.config_dir)
+                        .unwrap_or(path.as_path()),
+                ),
+            };
+
+            if let Some(other) = roots.iter().find(|r| {
+                r.canonical_path.starts_with(&canonical_path)
+                    || canonical_path.starts_with(&r.canonical_path)
+            })
+            {
+                return Err(format!(
+                    "Source directories {} and {} overlap",
+                    other.path.to_string_lossy(),
+                    source_dir
+                ));
+            }
+
+            roots.push(SourceRoot {
+                path,
+                canonical_path,
+                prefix,
+            });
+        }
+
+        Ok(SourceRoots { roots })
+    }
+
+    /// Returns the source directories, in configured order.
+    #[allow(dead_code)]
+    pub fn roots(&self) -> &[SourceRoot]
+    {
+        &self.roots
+    }
+
+    /// Find the source directory containing a path.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path, either canonical or beginning with a source directory's configured
+    ///   path.
+    ///
+    /// # Returns
+    ///
+    /// The source directory, or `None` if the path isn't in any of them.
+    #[allow(dead_code)]
+    pub fn root_of(&self, path: &Path) -> Option<&SourceRoot>
+    {
+        self.roots
+            .iter()
+            .find(|r| path.starts_with(&r.canonical_path) || path.starts_with(&r.path))
+    }
+
+    /// Determine the path that identifies a file in the code base, independently of where the
+    /// code base is checked out and of the platform. This is the path relative to the file's
+    /// source directory, with components separated by `/`. If there's more than one source
+    /// directory, it's prefixed with the source directory's path relative to the configuration
+    /// directory.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the file, either canonical or beginning with a source directory's
+    ///   configured path.
+    ///
+    /// # Returns
+    ///
+    /// The relative path, or `None` if the file isn't in any of the source directories.
+    #[allow(dead_code)]
+    pub fn relative_path(&self, path: &Path) -> Option<String>
+    {
+        let root = self.root_of(path)?;
+
+        let relative = path
+            .strip_prefix(&root.canonical_path)
+            .or_else(|_| path.strip_prefix(&root.path))
+            .ok()?;
+
+        let relative = portable_path(relative);
+
+        match (root.prefix.is_empty(), relative.is_empty())
+        {
+            (true, _) => Some(relative),
+            (false, true) => Some(root.prefix.clone()),
+            (false, false) => Some(format!("{}/{}", root.prefix, relative)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::config::Context;
+    use tempdir::TempDir;
+
+    fn create_test_context(config_dir: &Path, source_dirs: &str) -> Context
+    {
+        Context::new(
+            format!(
+                r#"
+{}
+rust:
+  log_macros:
+    - module: test_module
+      name: test_macro
+"#,
+                source_dirs
+            ),
+            config_dir.to_str().unwrap(),
+            true,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_single_source_dir()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        std::fs::create_dir_all(temp_dir.path().join("src/net")).unwrap();
+
+        let context = create_test_context(temp_dir.path(), "source_dir: ./src");
+        let roots = SourceRoots::new(&context.config).unwrap();
+
+        assert_eq!(roots.roots().len(), 1);
+        assert_eq!(
+            roots.relative_path(&temp_dir.path().join("./src/net/client.rs")),
+            Some(String::from("net/client.rs"))
+        );
+        assert_eq!(
+            roots.relative_path(&temp_dir.path().canonicalize().unwrap().join("src/main.rs")),
+            Some(String::from("main.rs"))
+        );
+        assert_eq!(roots.relative_path(&temp_dir.path().join("main.rs")), None);
+    }
+
+    #[test]
+    fn test_multiple_source_dirs()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        std::fs::create_dir_all(temp_dir.path().join("server/src")).unwrap();
+        std::fs::create_dir_all(temp_dir.path().join("cli/src")).unwrap();
+
+        let context = create_test_context(
+            temp_dir.path(),
+            "source_dirs:\n  - ./server/src\n  - cli/src",
+        );
+        let roots = SourceRoots::new(&context.config).unwrap();
+
+        assert_eq!(roots.roots().len(), 2);
+        assert_eq!(
+            roots.relative_path(&temp_dir.path().join("server/src/main.rs")),
+            Some(String::from("server/src/main.rs"))
+        );
+        assert_eq!(
+            roots.relative_path(&temp_dir.path().join("cli/src/main.rs")),
+            Some(String::from("cli/src/main.rs"))
+        );
+        assert_eq!(
+            roots.root_of(&temp_dir.path().join("cli/src/main.rs")),
+            Some(&roots.roots()[1])
+        );
+    }
+
+    #[test]
+    fn test_overlapping_source_dirs()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        std::fs::create_dir_all(temp_dir.path().join("server/src")).unwrap();
+
+        let context =
+            create_test_context(temp_dir.path(), "source_dirs:\n  - server\n  - server/src");
+
+        assert!(SourceRoots::new(&context.config)
+            .unwrap_err()
+            .contains("overlap"));
+
+        let context = create_test_context(temp_dir.path(), "source_dirs:\n  - server\n  - missing");
+
+        assert!(SourceRoots::new(&context.config).is_err());
+    }
+}
@@ -0,0 +1,412 @@
+use super::context::{Config, RustLogMacro, SourceMode};
+use serde::{Deserialize, Deserializer};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+
+/// How serious a configuration problem is.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Severity
+{
+    /// The configuration can't be used.
+    Error,
+
+    /// The configuration can be used, but probably isn't what was intended.
+    Warning,
+}
+
+/// A problem found in a configuration file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConfigProblem
+{
+    /// How serious the problem is.
+    pub severity: Severity,
+
+    /// A description of the problem.
+    pub message: String,
+
+    /// The line and column of the problem in the configuration file, starting from 1, if known.
+    pub location: Option<(usize, usize)>,
+}
+
+impl fmt::Display for ConfigProblem
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match self.location
+        {
+            Some((line, column)) => write!(f, "line {}, column {}: {}", line, column, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// Determine whether a string is a Rust identifier. Raw identifiers and keywords aren't
+/// considered.
+///
+/// # Arguments
+///
+/// * `s` - The string.
+///
+/// # Returns
+///
+/// `true` if the string is an identifier, `false` otherwise.
+pub fn is_identifier(s: &str) -> bool
+{
+    let mut chars = s.chars();
+
+    match chars.next()
+    {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' =>
+        {
+            s != "_" && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        },
+        _ => false,
+    }
+}
+
+/// Deserialize the name of a log macro, which must be a Rust identifier.
+pub fn deserialize_identifier<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let name = String::deserialize(deserializer)?;
+
+    match is_identifier(&name)
+    {
+        true => Ok(name),
+        false => Err(serde::de::Error::custom(format!(
+            "invalid macro name \"{}\": must be a Rust identifier",
+            name
+        ))),
+    }
+}
+
+/// Deserialize the module of a log macro, which must be a Rust path such as `log` or
+/// `my_crate::logging`.
+pub fn deserialize_module_path<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let module = String::deserialize(deserializer)?;
+
+    match module.split("::").all(is_identifier)
+    {
+        true => Ok(module),
+        false => Err(serde::de::Error::custom(format!(
+            "invalid module \"{}\": must be a Rust path, such as log or my_crate::logging",
+            module
+        ))),
+    }
+}
+
+/// Deserialize a list that mustn't be empty.
+///
+/// # Arguments
+///
+/// * `deserializer` - The deserializer.
+/// * `key` - The key of the list, used to describe the error.
+///
+/// # Returns
+///
+/// The list, otherwise an error if it's empty.
+fn deserialize_non_empty<'de, D, T>(deserializer: D, key: &str) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    let items = Vec::<T>::deserialize(deserializer)?;
+
+    match items.is_empty()
+    {
+        true => Err(serde::de::Error::custom(format!(
+            "{} must list at least one item",
+            key
+        ))),
+        false => Ok(items),
+    }
+}
+
+/// Deserialize the log macros to search for, of which there must be at least one.
+pub fn deserialize_log_macros<'de, D>(deserializer: D) -> Result<Vec<RustLogMacro>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserialize_non_empty(deserializer, "log_macros")
+}
+
+/// Deserialize the extensions of files to search, of which there must be at least one.
+pub fn deserialize_extensions<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserialize_non_empty(deserializer, "extensions")
+}
+
+/// Find the line and column of a top-level key's value in a YAML document.
+///
+/// # Arguments
+///
+/// * `yaml` - The YAML document.
+/// * `key` - The key.
+///
+/// # Returns
+///
+/// The line and column, starting from 1, or `None` if the key isn't found.
+fn key_location(yaml: &str, key: &str) -> Option<(usize, usize)>
+{
+    let prefix = format!("{}:", key);
+
+    yaml.lines().enumerate().find_map(|(index, line)| {
+        line.strip_prefix(&prefix).map(|value| {
+            (
+                index + 1,
+                prefix.len() + value.len() - value.trim_start().len() + 1,
+            )
+        })
+    })
+}
+
+/// Find the log macros listed more than once.
+///
+/// # Arguments
+///
+/// * `log_macros` - The log macros.
+/// * `path` - The path of the list in the configuration, used to describe duplicates.
+///
+/// # Returns
+///
+/// A warning for each duplicate.
+fn duplicate_macros(log_macros: &[RustLogMacro], path: &str) -> Vec<ConfigProblem>
+{
+    let mut first_indices: HashMap<(&str, &str), usize> = HashMap::new();
+    let mut problems = Vec::new();
+
+    for (index, log_macro) in log_macros.iter().enumerate()
+    {
+        let key = (log_macro.module.as_str(), log_macro.name.as_str());
+
+        match first_indices.get(&key)
+        {
+            Some(first_index) => problems.push(ConfigProblem {
+                severity: Severity::Warning,
+                message: format!(
+                    "{}[{}]: duplicate macro {}::{}, already listed at {}[{}]",
+                    path, index, log_macro.module, log_macro.name, path, first_index
+                ),
+                location: None,
+            }),
+            None =>
+            {
+                first_indices.insert(key, index);
+            },
+        }
+    }
+
+    problems
+}
+
+/// Validate a configuration file strictly: besides the checks made when loading it, the log
+/// macros must be configured, and the source directories must exist. Duplicate log macros are
+/// reported as warnings, without a location.
+///
+/// The file is deserialized strictly first, so an error that stops it being read (such as an
+/// unknown field) is the only problem reported. Every other problem is reported together.
+///
+/// # Arguments
+///
+/// * `yaml` - The YAML configuration file contents.
+/// * `config_dir` - The directory containing the configuration file.
+///
+/// # Returns
+///
+/// The problems found, if any.
+#[allow(dead_code)]
+pub fn validate_config(yaml: &str, config_dir: &str) -> Vec<ConfigProblem>
+{
+    let config = match serde_yaml::from_str::<Config>(yaml)
+    {
+        Ok(c) => c,
+        Err(e) =>
+        {
+            /*
+             * The location is reported separately, so remove it from the message.
+             */
+            let message = e.to_string();
+            let message = match (e.location(), message.rsplit_once(" at line "))
+            {
+                (Some(_), Some((m, _))) => m.to_string(),
+                _ => message,
+            };
+
+            return vec![ConfigProblem {
+                severity: Severity::Error,
+                message,
+                location: e.location().map(|l| (l.line(), l.column())),
+            }];
+        },
+    };
+
+    let mut problems = Vec::new();
+
+    if config.version == 0 || config.version > Config::CURRENT_VERSION
+    {
+        problems.push(ConfigProblem {
+            severity: Severity::Error,
+            message: format!(
+                "version: unsupported configuration version {}, this version of Breadlog supports versions 1 to {}",
+                config.version,
+                Config::CURRENT_VERSION
+            ),
+            location: key_location(yaml, "version"),
+        });
+    }
+
+    if config.rust.log_macros.is_empty()
+    {
+        problems.push(ConfigProblem {
+            severity: Severity::Error,
+            message: "rust: log_macros must list at least one item".to_string(),
+            location: key_location(yaml, "rust"),
+        });
+    }
+
+    if config.source == SourceMode::Directories
+    {
+        let source_dirs = std::iter::once(("source_dir", &config.source_dir))
+            .filter(|(_, d)| !d.is_empty())
+            .chain(config.source_dirs.iter().map(|d| ("source_dirs", d)));
+
+        for (key, source_dir) in source_dirs
+        {
+            if !Path::new(config_dir).join(source_dir).is_dir()
+            {
+                problems.push(ConfigProblem {
+                    severity: Severity::Error,
+                    message: format!("{}: directory {} doesn't exist", key, source_dir),
+                    location: key_location(yaml, key),
+                });
+            }
+        }
+    }
+
+    problems.extend(duplicate_macros(&config.rust.log_macros, "rust.log_macros"));
+
+    for (index, directory) in config.rust.directories.iter().enumerate()
+    {
+        problems.extend(duplicate_macros(
+            &directory.log_macros,
+            &format!("rust.directories[{}].log_macros", index),
+        ));
+    }
+
+    problems
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_is_identifier()
+    {
+        for identifier in ["info", "_private", "log2", "Info_Macro"]
+        {
+            assert!(is_identifier(identifier));
+        }
+
+        for not_identifier in ["", "_", "2log", "info!", "log::info", "my-macro", "ïnfo"]
+        {
+            assert!(!is_identifier(not_identifier));
+        }
+    }
+
+    #[test]
+    fn test_validate_config()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let config_dir = temp_dir.path().to_str().unwrap();
+
+        std::fs::create_dir(temp_dir.path().join("src")).unwrap();
+
+        let valid_yaml = "source_dir: ./src\nrust:\n  log_macros:\n    - module: my_crate::logging\n      name: info\n";
+        assert!(validate_config(valid_yaml, config_dir).is_empty());
+
+        let check = |yaml: &str| -> ConfigProblem {
+            let problems = validate_config(yaml, config_dir);
+            assert_eq!(problems.len(), 1, "{:?}", problems);
+            problems[0].clone()
+        };
+
+        let problem =
+            check("source_dir: ./src\nrust:\n  log_macros:\n    - module: log\n      nme: info\n");
+        assert_eq!(problem.severity, Severity::Error);
+        assert!(problem.message.contains("unknown field `nme`"));
+        assert_eq!(problem.location, Some((5, 7)));
+
+        let problem = check("source_dir: ./src\nsource_dri: ./src\n");
+        assert!(problem.message.contains("unknown field `source_dri`"));
+        assert_eq!(problem.location.map(|l| l.0), Some(2));
+
+        let problem = check(
+            "source_dir: ./src\nrust:\n  log_macros:\n    - module: log\n      name: info!\n",
+        );
+        assert!(problem.message.contains("invalid macro name \"info!\""));
+        assert_eq!(problem.location.map(|l| l.0), Some(4));
+
+        let problem = check(
+            "source_dir: ./src\nrust:\n  log_macros:\n    - module: log:info\n      name: info\n",
+        );
+        assert!(problem.message.contains("invalid module"));
+
+        let problem = check("source_dir: ./src\nrust:\n  log_macros: []\n");
+        assert!(problem
+            .message
+            .contains("rust: log_macros must list at least one item"));
+        assert_eq!(problem.location.map(|l| l.0), Some(3));
+
+        let problem = check(
+            "version: 9\nsource_dir: ./src\nrust:\n  log_macros:\n    - module: log\n      name: info\n",
+        );
+        assert!(problem
+            .message
+            .contains("unsupported configuration version 9"));
+        assert_eq!(problem.location, Some((1, 10)));
+
+        let problem = check("source_dir: ./src\n");
+        assert_eq!(
+            problem.to_string(),
+            "rust: log_macros must list at least one item"
+        );
+
+        let problem = check(
+            "source_dir: ./src\nrust:\n  extensions: []\n  log_macros:\n    - module: log\n      name: info\n",
+        );
+        assert!(problem
+            .message
+            .contains("rust: extensions must list at least one item"));
+
+        let problem = check(
+            "source_dir:  ./missing\nrust:\n  log_macros:\n    - module: log\n      name: info\n",
+        );
+        assert_eq!(
+            problem.to_string(),
+            "line 1, column 14: source_dir: directory ./missing doesn't exist"
+        );
+
+        let problems = validate_config(
+            "source_dir: ./src\nrust:\n  log_macros:\n    - module: log\n      name: info\n    - module: log\n      name: warn\n    - module: log\n      name: info\n",
+            config_dir,
+        );
+        assert_eq!(
+            problems,
+            vec![ConfigProblem {
+                severity: Severity::Warning,
+                message: "rust.log_macros[2]: duplicate macro log::info, already listed at rust.log_macros[0]".to_string(),
+                location: None,
+            }]
+        );
+    }
+}
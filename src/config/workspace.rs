@@ -0,0 +1,470 @@
+use std::path::{Path, PathBuf};
+
+/// The directories of a crate that contain code to be processed, other than `tests`.
+const CRATE_SOURCE_DIRS: [&str; 3] = ["src", "examples", "benches"];
+
+/// The name of a crate's manifest file.
+pub const MANIFEST_FILENAME: &str = "Cargo.toml";
+
+/// A crate in a Cargo workspace.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WorkspaceCrate
+{
+    /// The name of the crate's package.
+    pub name: String,
+
+    /// The path of the directory containing the crate's manifest.
+    pub path: PathBuf,
+
+    /// The canonical path of the directory containing the crate's manifest.
+    pub canonical_path: PathBuf,
+}
+
+impl WorkspaceCrate
+{
+    /// Determine which of the crate's source directories exist.
+    ///
+    /// # Arguments
+    ///
+    /// * `include_tests` - Whether or not to include the crate's `tests` directory.
+    ///
+    /// # Returns
+    ///
+    /// The paths of the source directories.
+    pub fn source_dirs(&self, include_tests: bool) -> Vec<PathBuf>
+    {
+        CRATE_SOURCE_DIRS
+            .iter()
+            .chain(include_tests.then_some(&"tests"))
+            .map(|d| self.path.join(d))
+            .filter(|d| d.is_dir())
+            .collect()
+    }
+}
+
+/// Find the crate containing a file. If crates are nested, the innermost one is used.
+///
+/// # Arguments
+///
+/// * `crates` - The crates in the workspace.
+/// * `path` - The path of the file, either canonical or beginning with a crate's path.
+///
+/// # Returns
+///
+/// The crate, or `None` if the file isn't in any of them.
+#[allow(dead_code)]
+pub fn crate_of<'a>(crates: &'a [WorkspaceCrate], path: &Path) -> Option<&'a WorkspaceCrate>
+{
+    crates
+        .iter()
+        .filter(|c| path.starts_with(&c.canonical_path) || path.starts_with(&c.path))
+        .max_by_key(|c| c.canonical_path.components().count())
+}
+
+/// Read and parse a Cargo manifest.
+///
+/// # Arguments
+///
+/// * `path` - The path of the manifest.
+///
+/// # Returns
+///
+/// The manifest's top-level table, otherwise an error message on failure.
+pub fn read_manifest(path: &Path) -> Result<toml::Table, String>
+{
+    let contents = match std::fs::read_to_string(path)
+    {
+        Ok(c) => c,
+        Err(e) => return Err(format!("Failed to read {}: {}", path.to_string_lossy(), e)),
+    };
+
+    contents
+        .parse::<toml::Table>()
+        .map_err(|e| format!("Failed to parse {}: {}", path.to_string_lossy(), e))
+}
+
+/// Read an optional array of strings from a table in a manifest.
+///
+/// # Arguments
+///
+/// * `table` - The table.
+/// * `key` - The key of the array.
+///
+/// # Returns
+///
+/// The strings, which are empty if the key isn't present, otherwise an error message if the
+/// value isn't an array of strings.
+fn string_array(table: &toml::Table, key: &str) -> Result<Vec<String>, String>
+{
+    match table.get(key)
+    {
+        None => Ok(Vec::new()),
+        Some(toml::Value::Array(values)) => values
+            .iter()
+            .map(|v| match v.as_str()
+            {
+                Some(s) => Ok(s.to_string()),
+                None => Err(format!("workspace.{} must be an array of strings", key)),
+            })
+            .collect(),
+        Some(_) => Err(format!("workspace.{} must be an array of strings", key)),
+    }
+}
+
+/// Create the crate whose manifest is in a directory.
+///
+/// # Arguments
+///
+/// * `path` - The crate's directory.
+/// * `manifest` - The crate's manifest.
+///
+/// # Returns
+///
+/// The crate, otherwise an error message if the manifest has no package name or the directory
+/// can't be resolved.
+fn workspace_crate(path: PathBuf, manifest: &toml::Table) -> Result<WorkspaceCrate, String>
+{
+    let name = match manifest
+        .get("package")
+        .and_then(|p| p.get("name"))
+        .and_then(|n| n.as_str())
+    {
+        Some(n) => n.to_string(),
+        None =>
+        {
+            return Err(format!(
+                "{} has no package name",
+                path.join(MANIFEST_FILENAME).to_string_lossy()
+            ))
+        },
+    };
+
+    let canonical_path = match path.canonicalize()
+    {
+        Ok(p) => p,
+        Err(e) =>
+        {
+            return Err(format!(
+                "Failed to resolve {}: {}",
+                path.to_string_lossy(),
+                e
+            ))
+        },
+    };
+
+    Ok(WorkspaceCrate {
+        name,
+        path,
+        canonical_path,
+    })
+}
+
+/// Enumerate the crates in a Cargo workspace: the root package, if the workspace manifest has
+/// one, followed by the members matching the `members` patterns and not matching the `exclude`
+/// patterns. A manifest without a `[workspace]` table is treated as a workspace containing only
+/// its own package.
+///
+/// # Arguments
+///
+/// * `manifest_path` - The path of the workspace's manifest.
+///
+/// # Returns
+///
+/// The crates, otherwise an error message on failure.
+pub fn workspace_crates(manifest_path: &Path) -> Result<Vec<WorkspaceCrate>, String>
+{
+    let manifest = read_manifest(manifest_path)?;
+
+    let workspace_dir = match manifest_path.parent()
+    {
+        Some(d) if !d.as_os_str().is_empty() => d.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+
+    let mut crates: Vec<WorkspaceCrate> = Vec::new();
+
+    if manifest.contains_key("package")
+    {
+        crates.push(workspace_crate(workspace_dir.clone(), &manifest)?);
+    }
+
+    let workspace = match manifest.get("workspace")
+    {
+        Some(toml::Value::Table(w)) => w,
+        Some(_) => return Err("workspace must be a table".to_string()),
+        None if crates.is_empty() =>
+        {
+            return Err(format!(
+                "{} has neither a [workspace] nor a [package] table",
+                manifest_path.to_string_lossy()
+            ))
+        },
+        None => return Ok(crates),
+    };
+
+    let exclude = string_array(workspace, "exclude")?
+        .iter()
+        .map(|e| glob::Pattern::new(e).map(|p| (PathBuf::from(e), p)))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Invalid workspace.exclude pattern: {}", e))?;
+
+    let escaped_workspace_dir = glob::Pattern::escape(&workspace_dir.to_string_lossy());
+
+    for member in string_array(workspace, "members")?.iter()
+    {
+        let pattern = Path::new(&escaped_workspace_dir).join(member);
+
+        let member_dirs = match glob::glob(&pattern.to_string_lossy())
+        {
+            Ok(paths) => paths
+                .filter_map(Result::ok)
+                .filter(|p| p.is_dir())
+                .collect::<Vec<PathBuf>>(),
+            Err(e) => return Err(format!("Invalid workspace member {}: {}", member, e)),
+        };
+
+        for member_dir in member_dirs.into_iter()
+        {
+            let relative_dir = member_dir
+                .strip_prefix(&workspace_dir)
+                .unwrap_or(&member_dir)
+                .to_path_buf();
+
+            /*
+             * As with Cargo, excluding a directory excludes everything in it.
+             */
+            if exclude.iter().any(|(path, pattern)| {
+                relative_dir.starts_with(path) || pattern.matches_path(&relative_dir)
+            })
+            {
+                continue;
+            }
+
+            let member_manifest = read_manifest(&member_dir.join(MANIFEST_FILENAME))?;
+            let member_crate = workspace_crate(member_dir, &member_manifest)?;
+
+            if !crates
+                .iter()
+                .any(|c| c.canonical_path == member_crate.canonical_path)
+            {
+                crates.push(member_crate);
+            }
+        }
+    }
+
+    Ok(crates)
+}
+
+/// Read the Breadlog configuration from a Cargo manifest's `[package.metadata.breadlog]` table,
+/// or failing that its `[workspace.metadata.breadlog]` table. If the configuration doesn't say
+/// where the source code is, it's found from the Cargo workspace of the manifest, which is
+/// expected to be named `Cargo.toml`.
+///
+/// # Arguments
+///
+/// * `manifest_path` - The path of the manifest.
+///
+/// # Returns
+///
+/// The configuration as YAML, `None` if the manifest has no Breadlog metadata, otherwise an
+/// error message on failure.
+#[allow(dead_code)]
+pub fn metadata_config(manifest_path: &Path) -> Result<Option<String>, String>
+{
+    let manifest = read_manifest(manifest_path)?;
+
+    let metadata = ["package", "workspace"].iter().find_map(|table| {
+        manifest
+            .get(*table)
+            .and_then(|t| t.get("metadata"))
+            .and_then(|m| m.get("breadlog"))
+    });
+
+    let mut metadata = match metadata
+    {
+        Some(toml::Value::Table(m)) => m.clone(),
+        Some(_) => return Err("metadata.breadlog must be a table".to_string()),
+        None => return Ok(None),
+    };
+
+    if !["source", "source_dir", "source_dirs"]
+        .iter()
+        .any(|key| metadata.contains_key(*key))
+    {
+        metadata.insert(
+            "source".to_string(),
+            toml::Value::String("cargo_workspace".to_string()),
+        );
+    }
+
+    serde_yaml::to_string(&metadata)
+        .map(Some)
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use tempdir::TempDir;
+
+    fn write_manifest(dir: &Path, contents: &str)
+    {
+        std::fs::create_dir_all(dir).unwrap();
+        std::fs::write(dir.join(MANIFEST_FILENAME), contents).unwrap();
+    }
+
+    fn crate_names(crates: &[WorkspaceCrate]) -> Vec<&str>
+    {
+        crates.iter().map(|c| c.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_workspace_crates()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let root = temp_dir.path();
+
+        write_manifest(
+            root,
+            r#"
+[package]
+name = "app"
+
+[workspace]
+members = [".", "crates/*", "tools/cli"]
+exclude = ["crates/experimental"]
+"#,
+        );
+        write_manifest(&root.join("crates/net"), "[package]\nname = \"net\"\n");
+        write_manifest(&root.join("crates/db"), "[package]\nname = \"db\"\n");
+        write_manifest(
+            &root.join("crates/experimental"),
+            "[package]\nname = \"experimental\"\n",
+        );
+        write_manifest(&root.join("tools/cli"), "[package]\nname = \"cli\"\n");
+        std::fs::write(root.join("crates/README.md"), "").unwrap();
+
+        let crates = workspace_crates(&root.join(MANIFEST_FILENAME)).unwrap();
+
+        assert_eq!(crate_names(&crates), vec!["app", "db", "net", "cli"]);
+        assert_eq!(crates[1].path, root.join("crates/db"));
+
+        assert_eq!(
+            crate_of(&crates, &root.join("crates/net/src/lib.rs")).map(|c| c.name.as_str()),
+            Some("net")
+        );
+        assert_eq!(
+            crate_of(&crates, &root.join("src/main.rs")).map(|c| c.name.as_str()),
+            Some("app")
+        );
+    }
+
+    #[test]
+    fn test_crate_source_dirs()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let root = temp_dir.path();
+
+        write_manifest(root, "[package]\nname = \"app\"\n");
+
+        for dir in ["src", "benches", "tests"]
+        {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+
+        let crates = workspace_crates(&root.join(MANIFEST_FILENAME)).unwrap();
+
+        assert_eq!(crate_names(&crates), vec!["app"]);
+        assert_eq!(
+            crates[0].source_dirs(false),
+            vec![root.join("src"), root.join("benches")]
+        );
+        assert_eq!(
+            crates[0].source_dirs(true),
+            vec![root.join("src"), root.join("benches"), root.join("tests")]
+        );
+    }
+
+    #[test]
+    fn test_invalid_workspaces()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let root = temp_dir.path();
+
+        assert!(workspace_crates(&root.join(MANIFEST_FILENAME))
+            .unwrap_err()
+            .contains("Failed to read"));
+
+        write_manifest(root, "[dependencies]\n");
+        assert!(workspace_crates(&root.join(MANIFEST_FILENAME))
+            .unwrap_err()
+            .contains("neither"));
+
+        write_manifest(root, "[workspace]\nmembers = [\"crates/*\"]\n");
+        std::fs::create_dir_all(root.join("crates/empty")).unwrap();
+        assert!(workspace_crates(&root.join(MANIFEST_FILENAME))
+            .unwrap_err()
+            .contains("Failed to read"));
+
+        write_manifest(root, "[workspace]\nmembers = \"crates/*\"\n");
+        assert!(workspace_crates(&root.join(MANIFEST_FILENAME))
+            .unwrap_err()
+            .contains("array of strings"));
+
+        write_manifest(root, "[workspace\n");
+        assert!(workspace_crates(&root.join(MANIFEST_FILENAME))
+            .unwrap_err()
+            .contains("Failed to parse"));
+    }
+
+    #[test]
+    fn test_metadata_config()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let root = temp_dir.path();
+        let manifest_path = root.join(MANIFEST_FILENAME);
+
+        write_manifest(root, "[package]\nname = \"app\"\n");
+        assert_eq!(metadata_config(&manifest_path), Ok(None));
+
+        write_manifest(
+            root,
+            r#"
+[package]
+name = "app"
+
+[package.metadata.breadlog]
+source_dir = "src"
+
+[[package.metadata.breadlog.rust.log_macros]]
+module = "log"
+name = "info"
+
+[workspace.metadata.breadlog]
+source_dir = "ignored"
+"#,
+        );
+
+        let config: serde_yaml::Value =
+            serde_yaml::from_str(&metadata_config(&manifest_path).unwrap().unwrap()).unwrap();
+
+        assert_eq!(config["source_dir"], "src");
+        assert_eq!(config["rust"]["log_macros"][0]["name"], "info");
+        assert!(config.get("source").is_none());
+
+        write_manifest(
+            root,
+            "[workspace]\nmembers = []\n\n[workspace.metadata.breadlog.rust]\nstructured = true\n",
+        );
+
+        let config: serde_yaml::Value =
+            serde_yaml::from_str(&metadata_config(&manifest_path).unwrap().unwrap()).unwrap();
+
+        assert_eq!(config["source"], "cargo_workspace");
+        assert_eq!(config["rust"]["structured"], true);
+
+        write_manifest(root, "[workspace.metadata]\nbreadlog = 1\n");
+        assert!(metadata_config(&manifest_path).is_err());
+    }
+}
@@ -5,62 +5,387 @@ extern crate pest_derive;
 mod codegen;
 mod config;
 mod parser;
+mod vcs;
 
-use clap::Parser;
-use log::{error, info, LevelFilter};
+use clap::{CommandFactory, FromArgMatches, Parser};
+use log::{error, info, warn, LevelFilter};
 use simple_logger::SimpleLogger;
 use std::fs;
 
-const ERR_CODE_CONFIG_READ: u32 = 1;
-const ERR_CODE_CONFIG_LOAD: u32 = 2;
+/*
+ * Exit codes. Each kind of failure has its own code, and 2 is left for command-line errors,
+ * which clap reports. Keep these in step with docs/source/running-breadlog.rst.
+ */
+const ERR_CODE_FAILED: u8 = 1;
+const ERR_CODE_INIT: u8 = 3;
+const ERR_CODE_CONFIG_READ: u8 = 4;
+const ERR_CODE_CONFIG_LOAD: u8 = 5;
+const ERR_CODE_FILE_SELECTION: u8 = 6;
+const ERR_CODE_RESERVATIONS: u8 = 7;
+const ERR_CODE_DIFF: u8 = 8;
+const ERR_CODE_REPORT: u8 = 9;
 
 /// Command-line arguments for the program.
 #[derive(Parser, Debug)]
 #[clap(name = "Breadlog")]
 #[clap(author = "James Mistry")]
 #[clap(about = "Maintain unique references to log messages in source code.", version, long_about = None)]
+#[clap(subcommand_negates_reqs = true)]
 struct ProgArgs
 {
-    #[clap(short, long, value_parser)]
-    /// YAML configuration file. Its format is described in detail at https://breadlog.readthedocs.io/en/stable/source/configuration.html
-    config: String,
+    #[clap(short, long, value_parser, global = true)]
+    /// YAML configuration file, or a Cargo.toml containing the configuration as [package.metadata.breadlog] or [workspace.metadata.breadlog]. Defaults to the nearest Breadlog.yaml, .breadlog.yaml or Cargo.toml with Breadlog metadata, searching upward from the directory of the first file given, or from the current directory. Its format is described in detail at https://breadlog.readthedocs.io/en/stable/source/configuration.html
+    config: Option<String>,
 
     #[clap(long, action)]
     /// Check all log messages have valid references, but don't modify any code. If the check fails, exits with a non-zero code.
     check: bool,
+
+    #[clap(long, value_enum, default_value_t = codegen::report::ReportFormat::Text)]
+    /// The format of check mode's report of missing, unusable, duplicate, retired, malformed and forbidden references. Other than text (log lines only), the report is written to stdout, or to the file given by --output. If it's written to stdout, nothing is logged.
+    format: codegen::report::ReportFormat,
+
+    #[clap(long, value_parser)]
+    /// Write check mode's report to this file rather than stdout.
+    output: Option<String>,
+
+    #[clap(short, long, value_parser = clap::value_parser!(u16).range(1..))]
+    /// The maximum number of files to process concurrently. Defaults to the number of available CPUs.
+    jobs: Option<u16>,
+
+    #[clap(long, value_parser)]
+    /// How long, in seconds, to wait for another Breadlog run in the same code base to finish before giving up. Overrides the lock_timeout configuration option.
+    lock_timeout: Option<u64>,
+
+    #[clap(long, value_parser, conflicts_with = "files")]
+    /// Only check or modify files that have changed in the source directory's git working tree since this revision (e.g. HEAD or main). Reference IDs are still allocated safely for the whole code base.
+    changed_since: Option<String>,
+
+    #[clap(value_parser)]
+    /// Only check or modify these files. Files outside the configured source directory are ignored. Reference IDs are still allocated safely for the whole code base.
+    files: Vec<String>,
+
+    #[clap(long, value_parser, global = true)]
+    /// The owner of a reserved block of reference IDs (see the reserve command). New references are drawn from the owner's block. Defaults to the BREADLOG_OWNER environment variable.
+    owner: Option<String>,
+
+    #[clap(subcommand)]
+    command: Option<Command>,
+}
+
+/// Commands other than checking or inserting references.
+#[derive(clap::Subcommand, Debug)]
+enum Command
+{
+    /// Merge two versions of Breadlog.lock, for use as a git merge driver. The merged lock file is written over OURS.
+    MergeLock
+    {
+        /// The common ancestor's version of the lock file (unused).
+        base: String,
+
+        /// Our version of the lock file, which receives the merged result.
+        ours: String,
+
+        /// Their version of the lock file.
+        theirs: String,
+    },
+
+    /// Create a configuration for the code base in the working directory, detecting the log macros it uses, along with an initial Breadlog.lock. The configuration is written to --config if given, otherwise to Breadlog.yaml.
+    Init
+    {
+        #[clap(long, default_value_t = false)]
+        /// Overwrite an existing configuration file.
+        force: bool,
+    },
+
+    /// Print a JSON Schema describing the format of configuration files, for editors to validate them with.
+    ConfigSchema,
+
+    /// Upgrade the configuration file to the current version of the configuration format, preserving its comments where possible. Requires a YAML configuration (see --config).
+    MigrateConfig,
+
+    /// Check the configuration strictly. An error that stops the configuration being read (such as an unknown option) is reported on its own, otherwise every problem is reported, with its location in the configuration file where it's known. Requires a configuration (see --config).
+    ValidateConfig,
+
+    /// Reserve a block of reference IDs for the owner given by --owner. Requires a configuration (see --config).
+    Reserve
+    {
+        #[clap(long, value_parser = clap::value_parser!(u32).range(1..), default_value_t = 1000)]
+        /// The number of reference IDs to reserve.
+        size: u32,
+    },
+
+    /// Release the block of reference IDs reserved for the owner given by --owner. Requires a configuration (see --config).
+    Release,
+
+    /// List the reserved blocks of reference IDs. Requires a configuration (see --config).
+    Reservations,
+
+    /// Compare the references in two versions of a code base, each given as a source directory or a catalog file (a Breadlog.registry file). Scanning a source directory requires a configuration (see --config).
+    Diff
+    {
+        /// The old version's source directory or catalog file.
+        old: String,
+
+        /// The new version's source directory or catalog file.
+        new: String,
+
+        #[clap(long, value_enum, default_value_t = codegen::diff::DiffFormat::Text)]
+        /// The output format.
+        format: codegen::diff::DiffFormat,
+    },
+
+    /// After a merge, renumber reference IDs allocated on both sides of the merge. Requires a configuration (see --config).
+    ResolveConflicts
+    {
+        #[clap(long, value_parser)]
+        /// The revision on our side of the merge. Defaults to HEAD during a merge, otherwise HEAD^1.
+        ours: Option<String>,
+    },
 }
 
-/// Set up and return the application context. This includes reading the configuration file and parsing it.
+/// Determine the configuration file to use. Unless one is given with --config, the nearest
+/// `Breadlog.yaml`, `.breadlog.yaml` or `Cargo.toml` with Breadlog metadata is used, searching
+/// upward from the directory of the first file given on the command line, or otherwise from the
+/// working directory.
+///
+/// # Arguments
+///
+/// * `args` - The command-line arguments.
+///
+/// # Returns
+///
+/// The path of the configuration file, or `None` if there isn't one.
+fn config_filename(args: &ProgArgs) -> Option<String>
+{
+    if let Some(c) = &args.config
+    {
+        return Some(c.clone());
+    }
+
+    let working_dir = std::env::current_dir().ok()?;
+
+    let search_dir = match args.files.first()
+    {
+        Some(f) => working_dir
+            .join(f)
+            .parent()
+            .map_or(working_dir.clone(), std::path::Path::to_path_buf),
+        None => working_dir,
+    };
+
+    config::discovery::find_config_file(&search_dir).map(|f| f.to_string_lossy().to_string())
+}
+
+/// Parse the command-line arguments, exiting with a usage error if a subcommand is combined with
+/// arguments that only apply to checking or modifying code. A subcommand given after files is
+/// otherwise taken to be another file, so that's rejected too.
+///
+/// # Returns
+///
+/// The command-line arguments.
+fn parse_args() -> ProgArgs
+{
+    const CODE_ONLY_ARGS: [&str; 5] = ["check", "changed_since", "files", "format", "output"];
+
+    let mut command = ProgArgs::command();
+    let matches = command.get_matches_mut();
+
+    let args = match ProgArgs::from_arg_matches(&matches)
+    {
+        Ok(a) => a,
+        Err(e) => e.format(&mut command).exit(),
+    };
+
+    let subcommand = matches.subcommand_name().map(String::from).or_else(|| {
+        args.files
+            .iter()
+            .find(|f| {
+                command.find_subcommand(f.as_str()).is_some() && !std::path::Path::new(f).exists()
+            })
+            .cloned()
+    });
+
+    let conflicting_arg = CODE_ONLY_ARGS
+        .iter()
+        .find(|id| matches.value_source(id) == Some(clap::parser::ValueSource::CommandLine));
+
+    if let (Some(subcommand), Some(_)) = (subcommand, conflicting_arg)
+    {
+        command
+            .error(
+                clap::error::ErrorKind::ArgumentConflict,
+                format!(
+                    "the subcommand '{}' can't be used with files, --check, --changed-since, --format or --output",
+                    subcommand
+                ),
+            )
+            .exit();
+    }
+
+    args
+}
+
+/// Read a configuration file.
+///
+/// # Arguments
+///
+/// * `config_filename` - The path to the configuration file. If it's a Cargo manifest
+///   (`Cargo.toml`), the configuration is read from its Breadlog metadata.
+///
+/// # Returns
+///
+/// The YAML configuration and the directory containing the configuration file, otherwise an
+/// error message on failure.
+fn read_config_file(config_filename: &String) -> Result<(String, String), String>
+{
+    let config_path = std::path::Path::new(config_filename);
+
+    let yaml = match config_path.file_name().and_then(|f| f.to_str())
+    {
+        Some(config::workspace::MANIFEST_FILENAME) =>
+        {
+            config::workspace::metadata_config(config_path).and_then(|c| {
+                c.ok_or(format!(
+                    "No [package.metadata.breadlog] or [workspace.metadata.breadlog] table in {}",
+                    config_filename
+                ))
+            })
+        },
+        _ => fs::read_to_string(config_filename).map_err(|e| e.to_string()),
+    }?;
+
+    let config_dir = match config_path.parent()
+    {
+        None => String::from(""),
+        Some(p) => String::from(p.to_str().unwrap()),
+    };
+
+    Ok((yaml, config_dir))
+}
+
+/// Upgrade a configuration written in an older version of the configuration format, without
+/// modifying the configuration file.
 ///
 /// # Arguments
 ///
 /// * `config_filename` - The path to the configuration file.
-/// * `check_mode` - Whether to run in check mode or not.
+/// * `yaml` - The YAML configuration.
+///
+/// # Returns
+///
+/// The configuration, upgraded if necessary, and whether or not it was upgraded.
+fn upgrade_config(config_filename: &str, yaml: String) -> (String, bool)
+{
+    match config::migration::migrate_config(&yaml)
+    {
+        Ok(Some(migrated)) if migrated.from < config::Config::CURRENT_VERSION =>
+        {
+            warn!(
+                "[ref: 130] {} uses configuration version {} - run breadlog migrate-config to upgrade it to version {}",
+                config_filename,
+                migrated.from,
+                config::Config::CURRENT_VERSION
+            );
+            (migrated.yaml, true)
+        },
+        _ => (yaml, false),
+    }
+}
+
+/// Validate a configuration strictly.
+///
+/// # Arguments
+///
+/// * `config_filename` - The path to the configuration file.
+/// * `yaml` - The YAML configuration.
+/// * `config_dir` - The directory containing the configuration file.
+/// * `upgraded` - Whether or not the configuration was upgraded from an older version of the
+///   configuration format.
+///
+/// # Returns
 ///
-fn setup_context(config_filename: &String, check_mode: bool) -> Result<config::Context, u32>
+/// The problems found, if any.
+fn config_problems(
+    config_filename: &str,
+    yaml: &str,
+    config_dir: &str,
+    upgraded: bool,
+) -> Vec<config::validation::ConfigProblem>
 {
-    let config_contents = fs::read_to_string(config_filename);
+    let mut problems = config::validation::validate_config(yaml, config_dir);
 
+    /*
+     * Configuration read from a Cargo manifest is converted to YAML, and upgraded configuration
+     * is rewritten, so locations in either don't correspond to the configuration file.
+     */
+    if upgraded || config_filename.ends_with(config::workspace::MANIFEST_FILENAME)
+    {
+        problems.iter_mut().for_each(|p| p.location = None);
+    }
+
+    problems
+}
+
+/// Log the problems found validating a configuration file.
+///
+/// # Arguments
+///
+/// * `problems` - The problems.
+///
+/// # Returns
+///
+/// Whether or not any of the problems are errors.
+fn log_config_problems(problems: &[config::validation::ConfigProblem]) -> bool
+{
+    use config::validation::Severity;
+
+    for problem in problems.iter()
+    {
+        match problem.severity
+        {
+            Severity::Error => error!("[ref: 126] Invalid configuration: {}", problem),
+            Severity::Warning => warn!("[ref: 127] Configuration warning: {}", problem),
+        }
+    }
+
+    problems.iter().any(|p| p.severity == Severity::Error)
+}
+
+/// Set up and return the application context. This includes reading the configuration file,
+/// validating it strictly and parsing it.
+///
+/// # Arguments
+///
+/// * `config_filename` - The path to the configuration file. If it's a Cargo manifest
+///   (`Cargo.toml`), the configuration is read from its Breadlog metadata.
+/// * `check_mode` - Whether to run in check mode or not.
+///
+fn setup_context(config_filename: &String, check_mode: bool) -> Result<config::Context, u8>
+{
     info!("[ref: 22] Reading configuration file: {}", config_filename);
 
-    let app_ctx = match config_contents
+    let app_ctx = match read_config_file(config_filename)
     {
         Err(e) =>
         {
-            error!(
-                "[ref: 23] Failed to read configuration file: {}",
-                e.to_string()
-            );
+            error!("[ref: 23] Failed to read configuration file: {}", e);
             return Err(ERR_CODE_CONFIG_READ);
         },
 
-        Ok(yaml) =>
+        Ok((yaml, config_dir)) =>
         {
-            let config_dir = match std::path::Path::new(config_filename).parent()
+            let (yaml, upgraded) = upgrade_config(config_filename, yaml);
+
+            if log_config_problems(&config_problems(
+                config_filename,
+                &yaml,
+                &config_dir,
+                upgraded,
+            ))
             {
-                None => String::from(""),
-                Some(p) => String::from(p.to_str().unwrap()),
-            };
+                return Err(ERR_CODE_CONFIG_LOAD);
+            }
 
             config::Context::new(yaml, &config_dir, check_mode)
         },
@@ -82,28 +407,499 @@ fn setup_context(config_filename: &String, check_mode: bool) -> Result<config::C
     }
 }
 
-fn main() -> Result<(), u32>
+/// Restrict processing to the files specified on the command line, if any.
+///
+/// # Arguments
+///
+/// * `args` - The command-line arguments.
+/// * `app_context` - The application context to update.
+///
+fn select_files(args: &ProgArgs, app_context: &mut config::Context) -> Result<(), u8>
 {
-    use std::sync::Arc;
+    if let Some(revision) = &args.changed_since
+    {
+        let changed_files = app_context
+            .config
+            .source_dirs()
+            .iter()
+            .map(|d| vcs::changed_files(d, revision))
+            .collect::<Result<Vec<_>, String>>()
+            .map(|files| {
+                let mut files: Vec<String> = files.into_iter().flatten().collect();
+                files.sort();
+                files.dedup();
+                files
+            });
 
-    const INIT_ERR_CODE: u32 = 1;
-    const CODE_GEN_ERR_CODE: u32 = 2;
+        match changed_files
+        {
+            Ok(files) =>
+            {
+                info!(
+                    "[ref: 41] Found {} file(s) changed since {}",
+                    files.len(),
+                    revision
+                );
+                app_context.selected_files = Some(files);
+            },
+            Err(e) =>
+            {
+                error!("[ref: 42] Failed to find changed files: {}", e);
+                return Err(ERR_CODE_FILE_SELECTION);
+            },
+        }
+    }
+    else if !args.files.is_empty()
+    {
+        app_context.selected_files = Some(args.files.clone());
+    }
 
-    SimpleLogger::new()
-        .with_level(LevelFilter::Info)
-        .init()
-        .unwrap();
+    Ok(())
+}
+
+/// Run a command to reserve, release or list blocks of reference IDs.
+///
+/// # Arguments
+///
+/// * `command` - The command to run.
+/// * `app_context` - The application context.
+///
+fn manage_reservations(command: &Command, app_context: &config::Context) -> Result<(), u8>
+{
+    if let Command::Reservations = command
+    {
+        let reservations = codegen::reservations::list(app_context);
+
+        if reservations.is_empty()
+        {
+            info!("[ref: 75] No blocks of reference IDs are reserved");
+        }
+
+        for reservation in reservations.iter()
+        {
+            info!(
+                "[ref: 76] {}: {} to {} ({} unused)",
+                reservation.owner,
+                reservation.start,
+                reservation.end() - 1,
+                reservation.remaining()
+            );
+        }
+
+        return Ok(());
+    }
+
+    let owner = match &app_context.id_owner
+    {
+        Some(o) => o,
+        None =>
+        {
+            error!("[ref: 77] An owner must be specified with --owner or BREADLOG_OWNER");
+            return Err(ERR_CODE_RESERVATIONS);
+        },
+    };
+
+    let result =
+        match command
+        {
+            Command::Reserve { size } => codegen::reservations::reserve(app_context, owner, *size)
+                .map(|r| {
+                    info!(
+                        "[ref: 78] Reserved reference IDs {} to {} for {}",
+                        r.start,
+                        r.end() - 1,
+                        owner
+                    )
+                }),
+            _ => codegen::reservations::release(app_context, owner).map(|r| {
+                info!(
+                    "[ref: 79] Released reference IDs {} to {} for {} ({} unused)",
+                    r.start,
+                    r.end() - 1,
+                    owner,
+                    r.remaining()
+                )
+            }),
+        };
+
+    if let Err(err) = result
+    {
+        error!("[ref: 80] Failed: {}", err);
+        return Err(ERR_CODE_RESERVATIONS);
+    }
+
+    Ok(())
+}
+
+/// Write check mode's report of the problems found in the code base.
+///
+/// # Arguments
+///
+/// * `args` - The command-line arguments.
+/// * `findings` - Check mode's findings.
+///
+fn write_report(args: &ProgArgs, findings: &[codegen::report::Finding]) -> Result<(), u8>
+{
+    let report = match codegen::report::format_report(findings, args.format)
+    {
+        Ok(r) => r,
+        Err(e) =>
+        {
+            error!("[ref: 108] Failed to produce report: {}", e);
+            return Err(ERR_CODE_REPORT);
+        },
+    };
+
+    match &args.output
+    {
+        Some(path) =>
+        {
+            if let Err(e) = fs::write(path, report)
+            {
+                error!("[ref: 109] Failed to write report to {}: {}", path, e);
+                return Err(ERR_CODE_REPORT);
+            }
+
+            info!("[ref: 110] Report written to {}", path);
+        },
+        None => print!("{}", report),
+    }
 
-    let args = ProgArgs::parse();
+    Ok(())
+}
 
-    let app_context_parsed = setup_context(&args.config, args.check);
+/// Log the number of errors and warnings found in each crate of a Cargo workspace.
+///
+/// # Arguments
+///
+/// * `app_context` - The application context.
+/// * `findings` - Check mode's findings.
+///
+fn log_crate_summaries(app_context: &config::Context, findings: &[codegen::report::Finding])
+{
+    for summary in codegen::report::summarise_crates(findings, &app_context.config.crates).iter()
+    {
+        info!(
+            "[ref: 120] Crate {}: {} error(s), {} warning(s)",
+            summary.name, summary.errors, summary.warnings
+        );
+    }
+}
 
-    let app_context = match app_context_parsed
+/// Validate a configuration file strictly, logging the problems found (see
+/// `config::validation::validate_config()`).
+///
+/// # Arguments
+///
+/// * `config_filename` - The path to the configuration file.
+///
+/// # Returns
+///
+/// `Ok(())` if the configuration is valid, even with warnings, otherwise an error code.
+fn validate_config(config_filename: &String) -> Result<(), u8>
+{
+    let (yaml, config_dir) = match read_config_file(config_filename)
     {
-        Err(_e) => return Err(INIT_ERR_CODE),
         Ok(c) => c,
+        Err(e) =>
+        {
+            error!("[ref: 128] Failed to read configuration file: {}", e);
+            return Err(ERR_CODE_CONFIG_READ);
+        },
+    };
+
+    let (yaml, upgraded) = upgrade_config(config_filename, yaml);
+    let mut problems = config_problems(config_filename, &yaml, &config_dir, upgraded);
+
+    /*
+     * Loading the configuration catches the remaining problems, such as an invalid Cargo
+     * workspace.
+     */
+    if problems
+        .iter()
+        .all(|p| p.severity == config::validation::Severity::Warning)
+    {
+        if let Err(e) = config::Context::new(yaml, &config_dir, true)
+        {
+            problems.push(config::validation::ConfigProblem {
+                severity: config::validation::Severity::Error,
+                message: e,
+                location: None,
+            });
+        }
+    }
+
+    match log_config_problems(&problems)
+    {
+        true => Err(ERR_CODE_CONFIG_LOAD),
+        false =>
+        {
+            info!(
+                "[ref: 129] {} is valid, with {} warning(s)",
+                config_filename,
+                problems.len()
+            );
+            Ok(())
+        },
+    }
+}
+
+/// Upgrade a configuration file to the current version of the configuration format, rewriting
+/// it in place.
+///
+/// # Arguments
+///
+/// * `config_filename` - The path to the configuration file.
+///
+/// # Returns
+///
+/// `Ok(())` if the configuration file was upgraded or is already up to date, otherwise an error
+/// code.
+fn migrate_config(config_filename: &String) -> Result<(), u8>
+{
+    if config_filename.ends_with(config::workspace::MANIFEST_FILENAME)
+    {
+        error!(
+            "[ref: 131] Configuration in {} can't be migrated automatically - see the configuration documentation for the changes between versions",
+            config_filename
+        );
+        return Err(ERR_CODE_CONFIG_READ);
+    }
+
+    let yaml = match fs::read_to_string(config_filename)
+    {
+        Ok(y) => y,
+        Err(e) =>
+        {
+            error!("[ref: 132] Failed to read configuration file: {}", e);
+            return Err(ERR_CODE_CONFIG_READ);
+        },
+    };
+
+    let migrated = match config::migration::migrate_config(&yaml)
+    {
+        Ok(Some(m)) => m,
+        Ok(None) =>
+        {
+            info!(
+                "[ref: 133] {} is already at configuration version {}",
+                config_filename,
+                config::Config::CURRENT_VERSION
+            );
+            return Ok(());
+        },
+        Err(e) =>
+        {
+            error!("[ref: 134] Failed to migrate {}: {}", config_filename, e);
+            return Err(ERR_CODE_CONFIG_LOAD);
+        },
+    };
+
+    if let Err(e) = fs::write(config_filename, &migrated.yaml)
+    {
+        error!("[ref: 135] Failed to write {}: {}", config_filename, e);
+        return Err(ERR_CODE_CONFIG_LOAD);
+    }
+
+    /*
+     * A configuration file without a version is in the current format if it's in the first
+     * version of the format, in which case only the version is added.
+     */
+    match migrated.from == config::Config::CURRENT_VERSION
+    {
+        true => info!(
+            "[ref: 136] Recorded configuration version {} in {}",
+            migrated.from, config_filename
+        ),
+        false => info!(
+            "[ref: 137] Migrated {} from configuration version {} to {}",
+            config_filename,
+            migrated.from,
+            config::Config::CURRENT_VERSION
+        ),
+    }
+
+    Ok(())
+}
+
+/// Compare the references in two versions of a code base and print the differences.
+///
+/// # Arguments
+///
+/// * `args` - The command-line arguments.
+/// * `old` - The old version's source directory or catalog file.
+/// * `new` - The new version's source directory or catalog file.
+/// * `format` - The output format.
+///
+fn diff_catalogs(
+    args: &ProgArgs,
+    old: &str,
+    new: &str,
+    format: codegen::diff::DiffFormat,
+) -> Result<(), u8>
+{
+    let app_context = match config_filename(args)
+    {
+        Some(c) => Some(setup_context(&c, true)?),
+        None => None,
+    };
+
+    let mut catalogs = Vec::new();
+
+    for path in [old, new]
+    {
+        match codegen::diff::load_catalog(app_context.as_ref(), path)
+        {
+            Ok(c) => catalogs.push(c),
+            Err(e) =>
+            {
+                error!("[ref: 106] Failed to load references: {}", e);
+                return Err(ERR_CODE_DIFF);
+            },
+        }
+    }
+
+    match codegen::diff::CatalogDiff::new(&catalogs[0], &catalogs[1]).format(format)
+    {
+        Ok(output) =>
+        {
+            print!("{}", output);
+            Ok(())
+        },
+        Err(e) =>
+        {
+            error!("[ref: 107] Failed to format differences: {}", e);
+            Err(ERR_CODE_DIFF)
+        },
+    }
+}
+
+/// Run the program.
+///
+/// # Returns
+///
+/// `Ok(())` if the run succeeded, otherwise the code to exit with.
+fn run() -> Result<(), u8>
+{
+    use std::sync::Arc;
+
+    let args = parse_args();
+
+    /*
+     * The differences and reports are written to stdout, so only warnings and errors are logged
+     * alongside differences, and nothing is logged alongside reports.
+     */
+    let writes_report =
+        args.command.is_none() && args.check && args.format != codegen::report::ReportFormat::Text;
+
+    let log_level = match &args.command
+    {
+        Some(Command::Diff { .. }) | Some(Command::ConfigSchema) => LevelFilter::Warn,
+        None if writes_report && args.output.is_none() => LevelFilter::Off,
+        _ => LevelFilter::Info,
+    };
+
+    SimpleLogger::new().with_level(log_level).init().unwrap();
+
+    if let Some(Command::Diff { old, new, format }) = &args.command
+    {
+        return diff_catalogs(&args, old, new, *format);
+    }
+
+    if let Some(Command::MergeLock {
+        base: _,
+        ours,
+        theirs,
+    }) = &args.command
+    {
+        return match config::Context::merge_cache_files(ours, theirs)
+        {
+            Ok(id) =>
+            {
+                info!("[ref: 63] Merged lock file, next reference ID: {}", id);
+                Ok(())
+            },
+            Err(e) =>
+            {
+                error!("[ref: 64] Failed to merge lock file: {}", e);
+                Err(ERR_CODE_FAILED)
+            },
+        };
+    }
+
+    if let Some(Command::ConfigSchema) = &args.command
+    {
+        println!("{}", config::schema::config_schema());
+        return Ok(());
+    }
+
+    if let Some(Command::Init { force }) = &args.command
+    {
+        let config_path = std::path::PathBuf::from(
+            args.config
+                .clone()
+                .unwrap_or(config::discovery::CONFIG_FILENAMES[0].to_string()),
+        );
+
+        return match codegen::init::init_config(&config_path, *force)
+        {
+            Ok(scaffold) =>
+            {
+                info!(
+                    "[ref: 124] Wrote {}: {} log macro invocation(s) found",
+                    config_path.to_string_lossy(),
+                    scaffold.uses()
+                );
+                Ok(())
+            },
+            Err(e) =>
+            {
+                error!("[ref: 125] Failed to create configuration: {}", e);
+                Err(ERR_CODE_INIT)
+            },
+        };
+    }
+
+    let config_filename = match config_filename(&args)
+    {
+        Some(c) => c,
+        None =>
+        {
+            error!("[ref: 65] No configuration file found - specify one with --config, or add a Breadlog.yaml to the code base");
+            return Err(ERR_CODE_INIT);
+        },
     };
 
+    if let Some(Command::ValidateConfig) = &args.command
+    {
+        return validate_config(&config_filename);
+    }
+
+    if let Some(Command::MigrateConfig) = &args.command
+    {
+        return migrate_config(&config_filename);
+    }
+
+    let mut app_context = setup_context(&config_filename, args.check)?;
+
+    select_files(&args, &mut app_context)?;
+
+    app_context.id_owner = args
+        .owner
+        .clone()
+        .or_else(|| std::env::var("BREADLOG_OWNER").ok())
+        .filter(|o| !o.is_empty());
+
+    if let Some(jobs) = args.jobs
+    {
+        app_context.max_jobs = jobs as usize;
+    }
+
+    if let Some(lock_timeout) = args.lock_timeout
+    {
+        app_context.config.lock_timeout = lock_timeout;
+    }
+
     /*
      * Set up the signal handler.
      */
@@ -114,17 +910,44 @@ fn main() -> Result<(), u32>
     .is_err()
     {
         error!("[ref: 26] Failed to register signal handler");
-        return Err(INIT_ERR_CODE);
+        return Err(ERR_CODE_INIT);
+    }
+
+    if let Some(Command::Reserve { .. } | Command::Release | Command::Reservations) = &args.command
+    {
+        return manage_reservations(args.command.as_ref().unwrap(), &app_context);
     }
 
-    if app_context.check_mode
+    if let Some(Command::ResolveConflicts { ours }) = &args.command
+    {
+        info!("[ref: 66] Resolving duplicate references after a merge");
+
+        if let Err(err) = codegen::conflicts::resolve_conflicts(&app_context, ours.as_deref())
+        {
+            error!("[ref: 67] Failed: {}", err);
+            return Err(ERR_CODE_FAILED);
+        }
+    }
+    else if app_context.check_mode
     {
         info!("[ref: 27] Running in check mode");
 
-        if let Err(err) = codegen::generate::check_references(&app_context)
+        let mut findings = Vec::new();
+        let result = codegen::generate::check_references(&app_context, &mut findings);
+
+        if writes_report
+        {
+            write_report(&args, &findings)?;
+        }
+        else if !app_context.config.crates.is_empty()
+        {
+            log_crate_summaries(&app_context, &findings);
+        }
+
+        if let Err(err) = result
         {
             error!("[ref: 28] Failed: {}", err);
-            return Err(CODE_GEN_ERR_CODE);
+            return Err(ERR_CODE_FAILED);
         }
     }
     else
@@ -134,7 +957,7 @@ fn main() -> Result<(), u32>
         if let Err(err) = codegen::generate::generate_code(&app_context)
         {
             error!("[ref: 30] Failed: {}", err);
-            return Err(CODE_GEN_ERR_CODE);
+            return Err(ERR_CODE_FAILED);
         }
     }
 
@@ -183,4 +1006,44 @@ mod tests
         assert_eq!(config_file_path.exists(), true);
         assert!(setup_context(&config_file_path_string, false).is_err());
     }
+
+    #[test]
+    fn test_context_load_manifest()
+    {
+        let scratch_dir = TempDir::new().unwrap();
+        let manifest_path = scratch_dir
+            .path()
+            .join(config::workspace::MANIFEST_FILENAME);
+        let manifest_path_string = String::from(manifest_path.to_str().unwrap());
+
+        fs::create_dir_all(scratch_dir.path().join("src")).unwrap();
+        fs::write(&manifest_path, "[package]\nname = \"app\"\n").unwrap();
+
+        assert!(setup_context(&manifest_path_string, false).is_err());
+
+        fs::write(
+            &manifest_path,
+            "[package]\nname = \"app\"\n\n[package.metadata.breadlog.rust]\nstructured = true\nlog_macros = [{ module = \"log\", name = \"info\" }]\n",
+        )
+        .unwrap();
+
+        let app_context = setup_context(&manifest_path_string, false).unwrap();
+
+        assert!(app_context.config.rust.structured);
+        assert_eq!(app_context.config.rust.log_macros[0].name, "info");
+        assert_eq!(app_context.config.crates[0].name, "app");
+        assert_eq!(
+            app_context.config.source_dirs(),
+            vec![scratch_dir.path().join("src").to_str().unwrap().to_string()]
+        );
+    }
+}
+
+fn main() -> std::process::ExitCode
+{
+    match run()
+    {
+        Ok(()) => std::process::ExitCode::SUCCESS,
+        Err(code) => std::process::ExitCode::from(code),
+    }
 }
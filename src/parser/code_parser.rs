@@ -2,6 +2,7 @@ use lazy_static::lazy_static;
 use regex::Regex;
 
 use super::rust_parser::rust_log_ref_finder;
+use crate::config::context::ReferencePolicy;
 use crate::config::Config;
 
 #[derive(Copy, Clone, PartialEq, Debug)]
@@ -38,7 +39,7 @@ pub struct LogRefEntry
     reference: Option<u32>,
 
     /// The name of the macro used to log the message.
-    _macro_name: String,
+    macro_name: String,
 
     /// The kind of log reference.
     kind: LogRefKind,
@@ -48,6 +49,36 @@ pub struct LogRefEntry
 
     /// Characters to insert after the reference ID.
     insertion_suffix: Option<String>,
+
+    /// The contents of the log message string literal, excluding any reference, if the log
+    /// message has one.
+    message: Option<String>,
+
+    /// A reference in the log message that isn't in canonical form, if there is one.
+    malformed_reference: Option<MalformedReference>,
+
+    /// Whether or not the log message must have a reference, according to the configuration of
+    /// the macro used to log it.
+    policy: ReferencePolicy,
+}
+
+/// A reference in a log message string literal that isn't in the canonical `[ref: 1234]` form at
+/// the start of the message, such as `[ref:12]`, `[REF: 12]` or `Failed [ref: 12] to connect`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MalformedReference
+{
+    /// The range of characters in the source code occupied by the reference.
+    span: std::ops::Range<usize>,
+
+    /// The range of characters to remove when moving the reference to the start of the log
+    /// message: the reference and one adjacent space, if there is one.
+    removal_span: std::ops::Range<usize>,
+
+    /// The 0-based character offset in the source code of the start of the log message.
+    message_start: usize,
+
+    /// The reference ID, if it's a valid number.
+    reference: Option<u32>,
 }
 
 #[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug)]
@@ -66,13 +97,17 @@ pub enum CodeLanguage
 ///
 /// # Returns
 ///
-/// A vector of log references found in the source code.
+/// A vector of log references found in the source code, in order of position.
 pub fn find_references(language: CodeLanguage, code: &str, config: &Config) -> Vec<LogRefEntry>
 {
-    match language
+    let mut result = match language
     {
         CodeLanguage::Rust => rust_log_ref_finder::find(code, config),
-    }
+    };
+
+    result.sort_by_key(|entry| entry.position().character());
+
+    result
 }
 
 /// Determines if a log message present at the specified position is preceded
@@ -262,11 +297,11 @@ impl LogRefEntry
     ///
     /// * `position` - The position of the log reference in the source code.
     /// * `reference` - The numeric reference associated with the log message, if one exists.
-    /// * `_macro_name` - The name of the macro used to log the message.
+    /// * `macro_name` - The name of the macro used to log the message.
     pub fn new(
         position: CodePosition,
         reference: Option<u32>,
-        _macro_name: String,
+        macro_name: String,
         kind: LogRefKind,
         insertion_prefix: Option<String>,
         insertion_suffix: Option<String>,
@@ -275,13 +310,81 @@ impl LogRefEntry
         LogRefEntry {
             position,
             reference,
-            _macro_name,
+            macro_name,
             kind,
             insertion_prefix,
             insertion_suffix,
+            message: None,
+            malformed_reference: None,
+            policy: ReferencePolicy::Required,
         }
     }
 
+    /// Sets whether or not the log message must have a reference.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - The reference policy of the macro used to log the message.
+    pub fn with_policy(mut self, policy: ReferencePolicy) -> LogRefEntry
+    {
+        self.policy = policy;
+
+        self
+    }
+
+    /// Sets the malformed reference found in the log message, if any. If the malformed
+    /// reference's ID is valid, it becomes the log message's reference, so that the log message
+    /// isn't given a second one.
+    ///
+    /// # Arguments
+    ///
+    /// * `malformed_reference` - The malformed reference, if there is one.
+    pub fn with_malformed_reference(
+        mut self,
+        malformed_reference: Option<MalformedReference>,
+    ) -> LogRefEntry
+    {
+        if self.reference.is_none()
+        {
+            self.reference = malformed_reference.as_ref().and_then(|m| m.reference());
+        }
+
+        self.malformed_reference = malformed_reference;
+
+        self
+    }
+
+    /// Sets the log message associated with the reference.
+    ///
+    /// # Arguments
+    ///
+    /// * `log_literal` - The contents of the log message string literal, if there is one. Any
+    ///   reference at the start of the literal is removed.
+    pub fn with_message(mut self, log_literal: Option<&str>) -> LogRefEntry
+    {
+        lazy_static! {
+            static ref LOG_REF_PREFIX_PATTERN: Regex =
+                Regex::new(r"^\[ref: [0-9]{1,10}\] ?").unwrap();
+        }
+
+        /*
+         * The message of a log statement with a malformed reference is as it'll be once the
+         * reference is fixed.
+         */
+        let fixed_literal = match (&self.malformed_reference, log_literal)
+        {
+            (Some(m), Some(l)) => m.fix_literal(l),
+            _ => None,
+        };
+
+        self.message = fixed_literal
+            .as_deref()
+            .or(log_literal)
+            .map(|l| LOG_REF_PREFIX_PATTERN.replace(l, "").to_string());
+
+        self
+    }
+
     /// Returns the numeric reference associated with the log message, if one exists.
     ///
     /// Numeric references take the following form: `[ref: 1234]`
@@ -315,6 +418,15 @@ impl LogRefEntry
         self.reference.is_some()
     }
 
+    /// Does the log message need a reference inserting? Returns true if the log message has no
+    /// reference, one can be inserted and the macro's policy requires one, false otherwise.
+    pub fn needs_reference(&self) -> bool
+    {
+        !self.exists()
+            && self.usable_reference_position()
+            && self.policy == ReferencePolicy::Required
+    }
+
     /// Returns the position of the log reference in the source code.
     pub fn position(&self) -> &CodePosition
     {
@@ -327,6 +439,28 @@ impl LogRefEntry
         self.reference
     }
 
+    /// Returns the range of characters in the source code occupied by the numeric reference, if
+    /// one exists. Replacing this range renumbers the reference.
+    #[allow(dead_code)]
+    pub fn reference_span(&self) -> Option<std::ops::Range<usize>>
+    {
+        if self.malformed_reference.is_some()
+        {
+            return None;
+        }
+
+        let reference_len = self.reference?.to_string().len();
+
+        let start = match self.kind
+        {
+            LogRefKind::String => self.position.character + "[ref: ".len(),
+            LogRefKind::StructuredPreExisting => self.position.character,
+            _ => return None,
+        };
+
+        Some(start..start + reference_len)
+    }
+
     /// Returns the kind of log reference.
     #[allow(dead_code)]
     pub fn kind(&self) -> LogRefKind
@@ -345,6 +479,15 @@ impl LogRefEntry
             return false;
         }
 
+        /*
+         * A malformed reference without a valid ID can't be fixed, but inserting another
+         * reference in front of it would make matters worse.
+         */
+        if self.malformed_reference.is_some() && !self.exists()
+        {
+            return false;
+        }
+
         true
     }
 
@@ -387,9 +530,154 @@ impl LogRefEntry
     }
 
     /// Returns the name of the macro used to log the message.
-    pub fn _macro_name(&self) -> &str
+    pub fn macro_name(&self) -> &str
+    {
+        self.macro_name.as_str()
+    }
+
+    /// Returns the contents of the log message string literal, excluding any reference, if the
+    /// log message has one.
+    #[allow(dead_code)]
+    pub fn message(&self) -> Option<&str>
     {
-        self._macro_name.as_str()
+        self.message.as_deref()
+    }
+
+    /// Returns the reference in the log message that isn't in canonical form, if there is one.
+    #[allow(dead_code)]
+    pub fn malformed_reference(&self) -> Option<&MalformedReference>
+    {
+        self.malformed_reference.as_ref()
+    }
+
+    /// Returns whether or not the log message must have a reference.
+    pub fn policy(&self) -> ReferencePolicy
+    {
+        self.policy
+    }
+}
+
+impl MalformedReference
+{
+    /// Find a reference in a log message string literal that isn't in canonical form. Only call
+    /// this if the literal doesn't start with a canonical reference (see
+    /// `LogRefEntry::extract_reference()`).
+    ///
+    /// Near misses such as `[ref:12]`, `[REF: 12]`, `[ref 12]` and `[ref: 00x]` are found
+    /// anywhere in the literal, as are canonical references after the start of it.
+    ///
+    /// # Arguments
+    ///
+    /// * `log_literal` - The contents of the log message string literal.
+    /// * `message_start` - The 0-based character offset of the literal in the source code.
+    ///
+    /// # Returns
+    ///
+    /// The first malformed reference in the literal, if there is one.
+    pub fn find(log_literal: &str, message_start: usize) -> Option<MalformedReference>
+    {
+        lazy_static! {
+            static ref NEAR_MISS_PATTERN: Regex =
+                Regex::new(r"(?i)\[\s*ref\s*:?\s*([0-9][0-9a-z]*)\s*\]").unwrap();
+        }
+
+        let capture = NEAR_MISS_PATTERN.captures(log_literal)?;
+        let span = capture.get(0)?.range();
+
+        let removal_span = if log_literal[span.end..].starts_with(' ')
+        {
+            span.start..span.end + 1
+        }
+        else if log_literal[..span.start].ends_with(' ')
+        {
+            span.start - 1..span.end
+        }
+        else
+        {
+            span.clone()
+        };
+
+        Some(MalformedReference {
+            span: message_start + span.start..message_start + span.end,
+            removal_span: message_start + removal_span.start..message_start + removal_span.end,
+            message_start,
+            reference: capture[1].parse::<u32>().ok(),
+        })
+    }
+
+    /// Returns the reference ID, if it's a valid number.
+    pub fn reference(&self) -> Option<u32>
+    {
+        self.reference
+    }
+
+    /// Returns the range of characters in the source code occupied by the reference.
+    #[allow(dead_code)]
+    pub fn span(&self) -> std::ops::Range<usize>
+    {
+        self.span.clone()
+    }
+
+    /// Whether or not the reference is somewhere other than the start of the log message.
+    pub fn is_misplaced(&self) -> bool
+    {
+        self.span.start != self.message_start
+    }
+
+    /// Returns the edits to the source code that put the reference in canonical form at the
+    /// start of the log message, in order of position. Each edit replaces a range of characters
+    /// with new text.
+    ///
+    /// # Returns
+    ///
+    /// The edits, or `None` if the reference ID isn't valid, in which case the reference can't
+    /// be fixed automatically.
+    pub fn fix(&self) -> Option<Vec<(std::ops::Range<usize>, String)>>
+    {
+        let reference = self.reference?;
+
+        if self.is_misplaced()
+        {
+            Some(vec![
+                (
+                    self.message_start..self.message_start,
+                    format!("[ref: {}] ", reference),
+                ),
+                (self.removal_span.clone(), String::new()),
+            ])
+        }
+        else
+        {
+            Some(vec![(self.span.clone(), format!("[ref: {}]", reference))])
+        }
+    }
+
+    /// Apply the fix to the log message string literal containing the reference.
+    ///
+    /// # Arguments
+    ///
+    /// * `log_literal` - The contents of the log message string literal.
+    ///
+    /// # Returns
+    ///
+    /// The fixed literal, or `None` if the reference can't be fixed automatically.
+    fn fix_literal(&self, log_literal: &str) -> Option<String>
+    {
+        let mut fixed_literal = String::new();
+        let mut unwritten_start: usize = 0;
+
+        for (range, text) in self.fix()?
+        {
+            let start = range.start - self.message_start;
+
+            fixed_literal.push_str(log_literal.get(unwritten_start..start)?);
+            fixed_literal.push_str(&text);
+            unwritten_start = range.end - self.message_start;
+        }
+
+        fixed_literal.push_str(log_literal.get(unwritten_start..)?);
+
+        Some(fixed_literal)
     }
 }
 
@@ -432,7 +720,7 @@ mod tests
         assert_eq!(subject.position().character(), 10);
         assert_eq!(subject.position().line(), 5);
         assert_eq!(subject.position().column(), 2);
-        assert_eq!(subject._macro_name(), "test_macro");
+        assert_eq!(subject.macro_name(), "test_macro");
     }
 
     #[test]
@@ -458,7 +746,7 @@ mod tests
         assert_eq!(subject.position().character(), 10);
         assert_eq!(subject.position().line(), 5);
         assert_eq!(subject.position().column(), 2);
-        assert_eq!(subject._macro_name(), "test_macro");
+        assert_eq!(subject.macro_name(), "test_macro");
     }
 
     #[test]
@@ -917,4 +1205,137 @@ mod tests
             "test-prefix: 123 :test-suffix"
         );
     }
+
+    #[test]
+    fn test_malformed_reference_find()
+    {
+        use crate::parser::code_parser::MalformedReference;
+
+        let cases = [
+            ("[ref:12] Connected", Some(12), false, "[ref: 12] Connected"),
+            (
+                "[REF: 12] Connected",
+                Some(12),
+                false,
+                "[ref: 12] Connected",
+            ),
+            (
+                "[ ref 12 ] Connected",
+                Some(12),
+                false,
+                "[ref: 12] Connected",
+            ),
+            (
+                "Failed [ref: 12] to connect",
+                Some(12),
+                true,
+                "[ref: 12] Failed to connect",
+            ),
+            (
+                "Failed to connect [ref: 12]",
+                Some(12),
+                true,
+                "[ref: 12] Failed to connect",
+            ),
+            ("Failed[ref: 12]", Some(12), true, "[ref: 12] Failed"),
+        ];
+
+        for (literal, reference, misplaced, fixed) in cases
+        {
+            let malformed = MalformedReference::find(literal, 100).unwrap();
+
+            assert_eq!(malformed.reference(), reference, "{}", literal);
+            assert_eq!(malformed.is_misplaced(), misplaced, "{}", literal);
+            assert_eq!(
+                malformed.fix_literal(literal).unwrap(),
+                fixed,
+                "{}",
+                literal
+            );
+        }
+
+        let malformed = MalformedReference::find("Failed [ref: 12] to connect", 100).unwrap();
+        assert_eq!(malformed.span(), 107..116);
+        assert_eq!(
+            malformed.fix().unwrap(),
+            vec![
+                (100..100, String::from("[ref: 12] ")),
+                (107..117, String::new())
+            ]
+        );
+
+        /*
+         * A reference without a valid ID can't be fixed.
+         */
+        let malformed = MalformedReference::find("[ref: 00x] Connected", 0).unwrap();
+        assert_eq!(malformed.reference(), None);
+        assert_eq!(malformed.fix(), None);
+
+        assert!(MalformedReference::find("Connected", 0).is_none());
+        assert!(MalformedReference::find("See [reference] for details", 0).is_none());
+        assert!(MalformedReference::find("Index [12] out of range", 0).is_none());
+    }
+
+    #[test]
+    fn test_logref_malformed_reference()
+    {
+        use crate::parser::code_parser::MalformedReference;
+        use std::str::FromStr;
+
+        let entry = |literal: &str| {
+            LogRefEntry::new(
+                CodePosition {
+                    character: 10,
+                    line: 5,
+                    column: 2,
+                },
+                None,
+                String::from_str("test_macro").unwrap(),
+                LogRefKind::String,
+                None,
+                None,
+            )
+            .with_malformed_reference(MalformedReference::find(literal, 10))
+            .with_message(Some(literal))
+        };
+
+        let subject = entry("Failed [ref:12] to connect");
+        assert!(subject.exists());
+        assert_eq!(subject.reference(), Some(12));
+        assert!(subject.usable_reference_position());
+        assert_eq!(subject.reference_span(), None);
+        assert_eq!(subject.message(), Some("Failed to connect"));
+
+        let subject = entry("[ref: 00x] Failed to connect");
+        assert!(!subject.exists());
+        assert!(!subject.usable_reference_position());
+        assert_eq!(subject.message(), Some("[ref: 00x] Failed to connect"));
+    }
+
+    #[test]
+    fn test_with_message()
+    {
+        use std::str::FromStr;
+
+        let subject = LogRefEntry::new(
+            CodePosition {
+                character: 10,
+                line: 5,
+                column: 2,
+            },
+            Some(12),
+            String::from_str("test_macro").unwrap(),
+            LogRefKind::String,
+            None,
+            None,
+        );
+
+        assert_eq!(subject.message(), None);
+
+        let subject = subject.with_message(Some("[ref: 12] Failed to open {}"));
+        assert_eq!(subject.message(), Some("Failed to open {}"));
+
+        let subject = subject.with_message(Some("Failed to open {} [ref: 12]"));
+        assert_eq!(subject.message(), Some("Failed to open {} [ref: 12]"));
+    }
 }
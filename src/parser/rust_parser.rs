@@ -1,9 +1,11 @@
 use super::check_for_ignore_directive;
 use super::check_for_no_kvp_directive;
+use super::code_parser::MalformedReference;
 use super::get_name_for_ref_kvp_key;
 use super::CodePosition;
 use super::LogRefEntry;
 use super::LogRefKind;
+use crate::config::context::RustLogMacro;
 use crate::config::Config;
 use lazy_static::lazy_static;
 use pest::Parser;
@@ -19,13 +21,13 @@ pub mod rust_log_ref_finder
 {
     use super::*;
 
-    fn macro_of_interest(macro_name: &String, config: &Config) -> bool
+    fn macro_of_interest<'a>(macro_name: &String, config: &'a Config) -> Option<&'a RustLogMacro>
     {
         for config_macro in &config.rust.log_macros
         {
             if macro_name == config_macro.name.as_str()
             {
-                return true;
+                return Some(config_macro);
             }
             else
             {
@@ -34,12 +36,12 @@ pub mod rust_log_ref_finder
 
                 if macro_name == qualified_macro_name.as_str()
                 {
-                    return true;
+                    return Some(config_macro);
                 }
             }
         }
 
-        false
+        None
     }
 
     /// Finds all log references in the given code.
@@ -113,13 +115,17 @@ pub mod rust_log_ref_finder
                         Ok(name) => name,
                     };
 
-                    if !macro_of_interest(&macro_name_str, config)
+                    let config_macro = match macro_of_interest(&macro_name_str, config)
                     {
-                        /*
-                         * This isn't a macro specified in config.
-                         */
-                        continue;
-                    }
+                        Some(m) => m,
+                        None =>
+                        {
+                            /*
+                             * This isn't a macro specified in config.
+                             */
+                            continue;
+                        },
+                    };
 
                     // Macro arguments
                     let rule_l1 = inner_rules.next();
@@ -189,8 +195,11 @@ pub mod rust_log_ref_finder
                     let mut code_pos: Option<CodePosition> = None;
                     let mut insertion_prefix: Option<String> = None;
                     let mut insertion_suffix: Option<String> = None;
+                    let mut malformed_reference: Option<MalformedReference> = None;
+
+                    let structured = config_macro.structured.unwrap_or(config.rust.structured);
 
-                    if config.rust.structured
+                    if structured
                         && !check_for_no_kvp_directive(
                             code,
                             rule_ref_container_span.start(),
@@ -293,6 +302,14 @@ pub mod rust_log_ref_finder
                                 ref_kind = LogRefKind::String;
                                 reference =
                                     LogRefEntry::extract_reference(&code[span.start()..span.end()]);
+
+                                if reference.is_none()
+                                {
+                                    malformed_reference = MalformedReference::find(
+                                        &code[span.start()..span.end()],
+                                        span.start(),
+                                    );
+                                }
                             },
                         }
                     }
@@ -311,7 +328,10 @@ pub mod rust_log_ref_finder
                             ref_kind,
                             insertion_prefix,
                             insertion_suffix,
-                        ),
+                        )
+                        .with_malformed_reference(malformed_reference)
+                        .with_policy(config_macro.policy)
+                        .with_message(log_message_span.map(|s| s.as_str())),
                     };
 
                     result.push(ref_entry);
@@ -330,6 +350,7 @@ mod tests
 {
     use super::rust_log_ref_finder;
     use super::LogRefEntry;
+    use crate::config::context::ReferencePolicy;
     use crate::config::Context;
     use std::str::FromStr;
     use test_log::test;
@@ -378,7 +399,7 @@ rust:
         let found_macros = apply_grammar_to_string(test_data, false);
 
         assert_eq!(found_macros.len(), 1);
-        assert_eq!(found_macros[0]._macro_name(), "test_macro");
+        assert_eq!(found_macros[0].macro_name(), "test_macro");
         assert_eq!(found_macros[0].position().character(), 13);
         assert_eq!(found_macros[0].position().line(), 1);
         assert_eq!(found_macros[0].position().column(), 14);
@@ -394,7 +415,7 @@ rust:
         let found_macros = apply_grammar_to_string(test_data, false);
 
         assert_eq!(found_macros.len(), 1);
-        assert_eq!(found_macros[0]._macro_name(), "test_macro");
+        assert_eq!(found_macros[0].macro_name(), "test_macro");
         assert_eq!(found_macros[0].position().character(), 13);
         assert_eq!(found_macros[0].position().line(), 1);
         assert_eq!(found_macros[0].position().column(), 14);
@@ -410,13 +431,13 @@ rust:
         let found_macros = apply_grammar_to_string(test_data, false);
 
         assert_eq!(found_macros.len(), 2);
-        assert_eq!(found_macros[0]._macro_name(), "test_macro1");
+        assert_eq!(found_macros[0].macro_name(), "test_macro1");
         assert_eq!(found_macros[0].position().character(), 14);
         assert_eq!(found_macros[0].position().line(), 1);
         assert_eq!(found_macros[0].position().column(), 15);
         assert_eq!(found_macros[0].reference(), None);
         assert_eq!(found_macros[0].kind(), super::LogRefKind::String);
-        assert_eq!(found_macros[1]._macro_name(), "test_macro2");
+        assert_eq!(found_macros[1].macro_name(), "test_macro2");
         assert_eq!(found_macros[1].position().character(), 46);
         assert_eq!(found_macros[1].position().line(), 2);
         assert_eq!(found_macros[1].position().column(), 15);
@@ -433,13 +454,13 @@ rust:
         let found_macros = apply_grammar_to_string(test_data, false);
 
         assert_eq!(found_macros.len(), 2);
-        assert_eq!(found_macros[0]._macro_name(), "test_macro1");
+        assert_eq!(found_macros[0].macro_name(), "test_macro1");
         assert_eq!(found_macros[0].position().character(), 14);
         assert_eq!(found_macros[0].position().line(), 1);
         assert_eq!(found_macros[0].position().column(), 15);
         assert_eq!(found_macros[0].reference(), None);
         assert_eq!(found_macros[0].kind(), super::LogRefKind::String);
-        assert_eq!(found_macros[1]._macro_name(), "test_macro2");
+        assert_eq!(found_macros[1].macro_name(), "test_macro2");
         assert_eq!(found_macros[1].position().character(), 48);
         assert_eq!(found_macros[1].position().line(), 2);
         assert_eq!(found_macros[1].position().column(), 15);
@@ -455,7 +476,7 @@ rust:
         let found_macros = apply_grammar_to_string(test_data, false);
 
         assert_eq!(found_macros.len(), 1);
-        assert_eq!(found_macros[0]._macro_name(), "test_macro2");
+        assert_eq!(found_macros[0].macro_name(), "test_macro2");
         assert_eq!(found_macros[0].position().character(), 47);
         assert_eq!(found_macros[0].position().line(), 2);
         assert_eq!(found_macros[0].position().column(), 15);
@@ -491,7 +512,7 @@ rust:
         let found_macros = apply_grammar_to_string(test_data, false);
 
         assert_eq!(found_macros.len(), 1);
-        assert_eq!(found_macros[0]._macro_name(), "test_macro");
+        assert_eq!(found_macros[0].macro_name(), "test_macro");
         assert_eq!(found_macros[0].position().character(), 33);
         assert_eq!(found_macros[0].position().line(), 1);
         assert_eq!(found_macros[0].position().column(), 34);
@@ -537,7 +558,7 @@ rust:
         let found_macros = apply_grammar_to_string(test_data, false);
 
         assert_eq!(found_macros.len(), 1);
-        assert_eq!(found_macros[0]._macro_name(), "test_macro");
+        assert_eq!(found_macros[0].macro_name(), "test_macro");
         assert_eq!(found_macros[0].position().character(), 17);
         assert_eq!(found_macros[0].position().line(), 1);
         assert_eq!(found_macros[0].position().column(), 18);
@@ -553,7 +574,7 @@ rust:
         let found_macros = apply_grammar_to_string(test_data, false);
 
         assert_eq!(found_macros.len(), 1);
-        assert_eq!(found_macros[0]._macro_name(), "test_macro");
+        assert_eq!(found_macros[0].macro_name(), "test_macro");
         assert_eq!(found_macros[0].position().character(), 32);
         assert_eq!(found_macros[0].position().line(), 1);
         assert_eq!(found_macros[0].position().column(), 33);
@@ -612,7 +633,7 @@ rust:
 
         assert_eq!(found_macros.len(), 1);
         assert_eq!(found_macros[0].reference(), Some(1234));
-        assert_eq!(found_macros[0]._macro_name(), "test_macro");
+        assert_eq!(found_macros[0].macro_name(), "test_macro");
         assert_eq!(found_macros[0].kind(), super::LogRefKind::String);
     }
 
@@ -625,7 +646,7 @@ rust:
 
         assert_eq!(found_macros.len(), 1);
         assert_eq!(found_macros[0].reference(), Some(1234));
-        assert_eq!(found_macros[0]._macro_name(), "test_macro3");
+        assert_eq!(found_macros[0].macro_name(), "test_macro3");
         assert_eq!(found_macros[0].kind(), super::LogRefKind::String);
     }
 
@@ -637,7 +658,7 @@ rust:
         let found_macros = apply_grammar_to_string(test_data, false);
 
         assert_eq!(found_macros.len(), 1);
-        assert_eq!(found_macros[0]._macro_name(), "test_macro");
+        assert_eq!(found_macros[0].macro_name(), "test_macro");
         assert_eq!(found_macros[0].position().character(), 36);
         assert_eq!(found_macros[0].position().line(), 1);
         assert_eq!(found_macros[0].position().column(), 37);
@@ -653,13 +674,13 @@ rust:
         let found_macros = apply_grammar_to_string(test_data, false);
 
         assert_eq!(found_macros.len(), 2);
-        assert_eq!(found_macros[0]._macro_name(), "test_macro1");
+        assert_eq!(found_macros[0].macro_name(), "test_macro1");
         assert_eq!(found_macros[0].position().character(), 38);
         assert_eq!(found_macros[0].position().line(), 1);
         assert_eq!(found_macros[0].position().column(), 39);
         assert_eq!(found_macros[0].reference(), None);
         assert_eq!(found_macros[0].kind(), super::LogRefKind::String);
-        assert_eq!(found_macros[1]._macro_name(), "test_macro2");
+        assert_eq!(found_macros[1].macro_name(), "test_macro2");
         assert_eq!(found_macros[1].position().character(), 94);
         assert_eq!(found_macros[1].position().line(), 2);
         assert_eq!(found_macros[1].position().column(), 39);
@@ -709,7 +730,7 @@ rust:
         let found_macros = apply_grammar_to_string(test_data, false);
 
         assert_eq!(found_macros.len(), 1);
-        assert_eq!(found_macros[0]._macro_name(), "test_macro");
+        assert_eq!(found_macros[0].macro_name(), "test_macro");
         assert_eq!(found_macros[0].position().character(), 20);
         assert_eq!(found_macros[0].position().line(), 1);
         assert_eq!(found_macros[0].position().column(), 21);
@@ -725,7 +746,7 @@ rust:
         let found_macros = apply_grammar_to_string(test_data, false);
 
         assert_eq!(found_macros.len(), 1);
-        assert_eq!(found_macros[0]._macro_name(), "test_macro");
+        assert_eq!(found_macros[0].macro_name(), "test_macro");
         assert_eq!(found_macros[0].position().character(), 43);
         assert_eq!(found_macros[0].position().line(), 1);
         assert_eq!(found_macros[0].position().column(), 44);
@@ -741,7 +762,7 @@ rust:
         let found_macros = apply_grammar_to_string(test_data, false);
 
         assert_eq!(found_macros.len(), 1);
-        assert_eq!(found_macros[0]._macro_name(), "test_macro");
+        assert_eq!(found_macros[0].macro_name(), "test_macro");
         assert_eq!(found_macros[0].position().character(), 27);
         assert_eq!(found_macros[0].position().line(), 1);
         assert_eq!(found_macros[0].position().column(), 28);
@@ -757,7 +778,7 @@ rust:
         let found_macros = apply_grammar_to_string(test_data, false);
 
         assert_eq!(found_macros.len(), 1);
-        assert_eq!(found_macros[0]._macro_name(), "test_macro");
+        assert_eq!(found_macros[0].macro_name(), "test_macro");
         assert_eq!(found_macros[0].position().character(), 50);
         assert_eq!(found_macros[0].position().line(), 1);
         assert_eq!(found_macros[0].position().column(), 51);
@@ -773,7 +794,7 @@ rust:
         let found_macros = apply_grammar_to_string(test_data, false);
 
         assert_eq!(found_macros.len(), 1);
-        assert_eq!(found_macros[0]._macro_name(), "test_macro");
+        assert_eq!(found_macros[0].macro_name(), "test_macro");
         assert_eq!(found_macros[0].position().character(), 22);
         assert_eq!(found_macros[0].position().line(), 1);
         assert_eq!(found_macros[0].position().column(), 23);
@@ -789,7 +810,7 @@ rust:
         let found_macros = apply_grammar_to_string(test_data, false);
 
         assert_eq!(found_macros.len(), 1);
-        assert_eq!(found_macros[0]._macro_name(), "test_macro");
+        assert_eq!(found_macros[0].macro_name(), "test_macro");
         assert_eq!(found_macros[0].position().character(), 26);
         assert_eq!(found_macros[0].position().line(), 1);
         assert_eq!(found_macros[0].position().column(), 27);
@@ -805,7 +826,7 @@ rust:
         let found_macros = apply_grammar_to_string(test_data, false);
 
         assert_eq!(found_macros.len(), 1);
-        assert_eq!(found_macros[0]._macro_name(), "test_macro");
+        assert_eq!(found_macros[0].macro_name(), "test_macro");
         assert_eq!(found_macros[0].position().character(), 35);
         assert_eq!(found_macros[0].position().line(), 1);
         assert_eq!(found_macros[0].position().column(), 36);
@@ -822,7 +843,7 @@ rust:
         let found_macros = apply_grammar_to_string(test_data, false);
 
         assert_eq!(found_macros.len(), 1);
-        assert_eq!(found_macros[0]._macro_name(), "test_macro");
+        assert_eq!(found_macros[0].macro_name(), "test_macro");
         assert_eq!(found_macros[0].position().character(), 57);
         assert_eq!(found_macros[0].position().line(), 1);
         assert_eq!(found_macros[0].position().column(), 58);
@@ -839,7 +860,7 @@ rust:
         let found_macros = apply_grammar_to_string(test_data, false);
 
         assert_eq!(found_macros.len(), 1);
-        assert_eq!(found_macros[0]._macro_name(), "test_macro");
+        assert_eq!(found_macros[0].macro_name(), "test_macro");
         assert_eq!(found_macros[0].position().character(), 55);
         assert_eq!(found_macros[0].position().line(), 1);
         assert_eq!(found_macros[0].position().column(), 56);
@@ -856,7 +877,7 @@ rust:
         let found_macros = apply_grammar_to_string(test_data, false);
 
         assert_eq!(found_macros.len(), 1);
-        assert_eq!(found_macros[0]._macro_name(), "test_macro");
+        assert_eq!(found_macros[0].macro_name(), "test_macro");
         assert_eq!(found_macros[0].position().character(), 56);
         assert_eq!(found_macros[0].position().line(), 1);
         assert_eq!(found_macros[0].position().column(), 57);
@@ -872,7 +893,7 @@ rust:
         let found_macros = apply_grammar_to_string(test_data, false);
 
         assert_eq!(found_macros.len(), 1);
-        assert_eq!(found_macros[0]._macro_name(), "test_macro");
+        assert_eq!(found_macros[0].macro_name(), "test_macro");
         assert_eq!(found_macros[0].position().character(), 16);
         assert_eq!(found_macros[0].position().line(), 1);
         assert_eq!(found_macros[0].position().column(), 17);
@@ -888,7 +909,7 @@ rust:
         let found_macros = apply_grammar_to_string(test_data, false);
 
         assert_eq!(found_macros.len(), 1);
-        assert_eq!(found_macros[0]._macro_name(), "test_macro");
+        assert_eq!(found_macros[0].macro_name(), "test_macro");
         assert_eq!(found_macros[0].position().character(), 19);
         assert_eq!(found_macros[0].position().line(), 1);
         assert_eq!(found_macros[0].position().column(), 20);
@@ -904,7 +925,7 @@ rust:
         let found_macros = apply_grammar_to_string(test_data, false);
 
         assert_eq!(found_macros.len(), 1);
-        assert_eq!(found_macros[0]._macro_name(), "test_macro");
+        assert_eq!(found_macros[0].macro_name(), "test_macro");
         assert_eq!(found_macros[0].position().character(), 23);
         assert_eq!(found_macros[0].position().line(), 1);
         assert_eq!(found_macros[0].position().column(), 24);
@@ -920,7 +941,7 @@ rust:
         let found_macros = apply_grammar_to_string(test_data, false);
 
         assert_eq!(found_macros.len(), 1);
-        assert_eq!(found_macros[0]._macro_name(), "test_macro2");
+        assert_eq!(found_macros[0].macro_name(), "test_macro2");
         assert_eq!(found_macros[0].position().character(), 65);
         assert_eq!(found_macros[0].position().line(), 3);
         assert_eq!(found_macros[0].position().column(), 15);
@@ -936,7 +957,7 @@ rust:
         let found_macros = apply_grammar_to_string(test_data, false);
 
         assert_eq!(found_macros.len(), 1);
-        assert_eq!(found_macros[0]._macro_name(), "test_macro2");
+        assert_eq!(found_macros[0].macro_name(), "test_macro2");
         assert_eq!(found_macros[0].position().character(), 68);
         assert_eq!(found_macros[0].position().line(), 3);
         assert_eq!(found_macros[0].position().column(), 15);
@@ -952,7 +973,7 @@ rust:
         let found_macros = apply_grammar_to_string(test_data, true);
 
         assert_eq!(found_macros.len(), 1);
-        assert_eq!(found_macros[0]._macro_name(), "test_macro");
+        assert_eq!(found_macros[0].macro_name(), "test_macro");
         assert_eq!(found_macros[0].position().character(), 18);
         assert_eq!(found_macros[0].position().line(), 1);
         assert_eq!(found_macros[0].position().column(), 19);
@@ -971,7 +992,7 @@ rust:
         let found_macros = apply_grammar_to_string(test_data, true);
 
         assert_eq!(found_macros.len(), 1);
-        assert_eq!(found_macros[0]._macro_name(), "test_macro");
+        assert_eq!(found_macros[0].macro_name(), "test_macro");
         assert_eq!(found_macros[0].position().character(), 18);
         assert_eq!(found_macros[0].position().line(), 1);
         assert_eq!(found_macros[0].position().column(), 19);
@@ -990,7 +1011,7 @@ rust:
         let found_macros = apply_grammar_to_string(test_data, true);
 
         assert_eq!(found_macros.len(), 1);
-        assert_eq!(found_macros[0]._macro_name(), "test_macro");
+        assert_eq!(found_macros[0].macro_name(), "test_macro");
         assert_eq!(found_macros[0].position().character(), 12);
         assert_eq!(found_macros[0].position().line(), 1);
         assert_eq!(found_macros[0].position().column(), 13);
@@ -1010,7 +1031,7 @@ rust:
         let found_macros = apply_grammar_to_string(test_data, true);
 
         assert_eq!(found_macros.len(), 1);
-        assert_eq!(found_macros[0]._macro_name(), "test_macro");
+        assert_eq!(found_macros[0].macro_name(), "test_macro");
         assert_eq!(found_macros[0].position().character(), 12);
         assert_eq!(found_macros[0].position().line(), 1);
         assert_eq!(found_macros[0].position().column(), 13);
@@ -1031,7 +1052,7 @@ rust:
         let found_macros = apply_grammar_to_string(test_data, true);
 
         assert_eq!(found_macros.len(), 1);
-        assert_eq!(found_macros[0]._macro_name(), "test_macro");
+        assert_eq!(found_macros[0].macro_name(), "test_macro");
         assert_eq!(found_macros[0].position().character(), 18);
         assert_eq!(found_macros[0].position().line(), 1);
         assert_eq!(found_macros[0].position().column(), 19);
@@ -1051,7 +1072,7 @@ rust:
         let found_macros = apply_grammar_to_string(test_data, true);
 
         assert_eq!(found_macros.len(), 1);
-        assert_eq!(found_macros[0]._macro_name(), "test_macro");
+        assert_eq!(found_macros[0].macro_name(), "test_macro");
         assert_eq!(found_macros[0].position().character(), 35);
         assert_eq!(found_macros[0].position().line(), 1);
         assert_eq!(found_macros[0].position().column(), 36);
@@ -1071,7 +1092,7 @@ rust:
         let found_macros = apply_grammar_to_string(test_data, true);
 
         assert_eq!(found_macros.len(), 1);
-        assert_eq!(found_macros[0]._macro_name(), "test_macro");
+        assert_eq!(found_macros[0].macro_name(), "test_macro");
         assert_eq!(found_macros[0].position().character(), 50);
         assert_eq!(found_macros[0].position().line(), 1);
         assert_eq!(found_macros[0].position().column(), 51);
@@ -1090,7 +1111,7 @@ rust:
         let found_macros = apply_grammar_to_string(test_data, true);
 
         assert_eq!(found_macros.len(), 1);
-        assert_eq!(found_macros[0]._macro_name(), "test_macro");
+        assert_eq!(found_macros[0].macro_name(), "test_macro");
         assert_eq!(found_macros[0].position().character(), 43);
         assert_eq!(found_macros[0].position().line(), 2);
         assert_eq!(found_macros[0].position().column(), 25);
@@ -1106,7 +1127,7 @@ rust:
         let found_macros = apply_grammar_to_string(test_data, true);
 
         assert_eq!(found_macros.len(), 1);
-        assert_eq!(found_macros[0]._macro_name(), "test_macro");
+        assert_eq!(found_macros[0].macro_name(), "test_macro");
         assert_eq!(found_macros[0].position().character(), 43);
         assert_eq!(found_macros[0].position().line(), 2);
         assert_eq!(found_macros[0].position().column(), 25);
@@ -1122,11 +1143,138 @@ rust:
         let found_macros = apply_grammar_to_string(test_data, true);
 
         assert_eq!(found_macros.len(), 1);
-        assert_eq!(found_macros[0]._macro_name(), "test_macro");
+        assert_eq!(found_macros[0].macro_name(), "test_macro");
         assert_eq!(found_macros[0].position().character(), 32);
         assert_eq!(found_macros[0].position().line(), 2);
         assert_eq!(found_macros[0].position().column(), 14);
         assert_eq!(found_macros[0].reference(), None);
         assert_eq!(found_macros[0].kind(), super::LogRefKind::String);
     }
+
+    #[test]
+    fn test_reference_span_string()
+    {
+        let test_data = "test_macro!(\"[ref: 123] Test string.\")\n";
+
+        let found_macros = apply_grammar_to_string(test_data, false);
+
+        assert_eq!(found_macros.len(), 1);
+        assert_eq!(&test_data[found_macros[0].reference_span().unwrap()], "123");
+    }
+
+    #[test]
+    fn test_reference_span_structured()
+    {
+        let test_data = "test_macro!(key = 1, ref = 4567; \"Test string.\")\n";
+
+        let found_macros = apply_grammar_to_string(test_data, true);
+
+        assert_eq!(found_macros.len(), 1);
+        assert_eq!(
+            &test_data[found_macros[0].reference_span().unwrap()],
+            "4567"
+        );
+    }
+
+    #[test]
+    fn test_reference_span_no_reference()
+    {
+        let found_macros = apply_grammar_to_string("test_macro!(\"Test string.\")\n", false);
+
+        assert_eq!(found_macros.len(), 1);
+        assert!(found_macros[0].reference_span().is_none());
+    }
+
+    #[test]
+    fn test_message()
+    {
+        let found_macros =
+            apply_grammar_to_string("test_macro!(\"[ref: 123] Test string {}.\", x)\n", false);
+
+        assert_eq!(found_macros.len(), 1);
+        assert_eq!(found_macros[0].message(), Some("Test string {}."));
+
+        let found_macros =
+            apply_grammar_to_string("test_macro!(key = 1; \"Test string.\")\n", true);
+
+        assert_eq!(found_macros.len(), 1);
+        assert_eq!(found_macros[0].message(), Some("Test string."));
+    }
+
+    #[test]
+    fn test_policy()
+    {
+        let ctx = Context::new(
+            r#"
+source_dir: /tmp/test
+rust:
+  log_macros:
+    - module: test_module
+      name: test_macro1
+    - module: test_module
+      name: test_macro2
+      policy: optional
+    - module: test_module
+      name: test_macro3
+      policy: forbidden
+"#
+            .to_string(),
+            "/tmp",
+            false,
+        )
+        .unwrap();
+
+        let found_macros = rust_log_ref_finder::find(
+            "test_macro1!(\"Test 1.\");\ntest_module::test_macro2!(\"Test 2.\");\ntest_macro3!(\"Test 3.\");\n",
+            &ctx.config,
+        );
+
+        assert_eq!(found_macros.len(), 3);
+        assert_eq!(found_macros[0].policy(), ReferencePolicy::Required);
+        assert!(found_macros[0].needs_reference());
+        assert_eq!(found_macros[1].policy(), ReferencePolicy::Optional);
+        assert!(!found_macros[1].needs_reference());
+        assert_eq!(found_macros[2].policy(), ReferencePolicy::Forbidden);
+        assert!(!found_macros[2].needs_reference());
+    }
+
+    #[test]
+    fn test_macro_structured_override()
+    {
+        let ctx = Context::new(
+            r#"
+source_dir: /tmp/test
+rust:
+  structured: true
+  log_macros:
+    - module: test_module
+      name: test_macro1
+    - module: test_module
+      name: log_compat
+      structured: false
+"#
+            .to_string(),
+            "/tmp",
+            false,
+        )
+        .unwrap();
+
+        let found_macros = rust_log_ref_finder::find(
+            "test_macro1!(\"Test 1.\");\nlog_compat!(\"Test 2.\");\n",
+            &ctx.config,
+        );
+
+        assert_eq!(found_macros.len(), 2);
+        assert_eq!(found_macros[0].kind(), super::LogRefKind::StructuredNew);
+        assert_eq!(found_macros[1].kind(), super::LogRefKind::String);
+
+        let mut config = ctx.config.clone();
+        config.rust.structured = false;
+        config.rust.log_macros[1].structured = Some(true);
+
+        let found_macros = rust_log_ref_finder::find("log_compat!(\"Test 2.\");\n", &config);
+
+        assert_eq!(found_macros.len(), 1);
+        assert_eq!(found_macros[0].kind(), super::LogRefKind::StructuredNew);
+    }
 }
@@ -0,0 +1,309 @@
+use std::path::Path;
+use std::process::Command;
+
+/// Run a git command in the given directory and return its standard output.
+///
+/// # Arguments
+///
+/// * `repo_dir` - A directory within the git working tree.
+/// * `args` - The arguments to pass to git.
+///
+/// # Returns
+///
+/// The standard output of the command, or an error message if git couldn't be run or failed.
+fn run_git(repo_dir: &str, args: &[&str]) -> Result<String, String>
+{
+    let output = match Command::new("git")
+        .arg("-C")
+        .arg(repo_dir)
+        .args(args)
+        .output()
+    {
+        Ok(o) => o,
+        Err(e) => return Err(format!("Failed to run git: {}", e)),
+    };
+
+    if !output.status.success()
+    {
+        return Err(format!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    match String::from_utf8(output.stdout)
+    {
+        Ok(s) => Ok(s),
+        Err(e) => Err(format!("Failed to decode git output: {}", e)),
+    }
+}
+
+/// Find the files in a git working tree that have changed since the given revision. This includes
+/// committed changes since the revision, uncommitted changes (staged or not) and untracked files
+/// that aren't ignored. Deleted files are not included.
+///
+/// # Arguments
+///
+/// * `repo_dir` - A directory within the git working tree.
+/// * `revision` - The revision to compare the working tree against.
+///
+/// # Returns
+///
+/// The absolute paths of the changed files, or an error message on failure.
+pub fn changed_files(repo_dir: &str, revision: &str) -> Result<Vec<String>, String>
+{
+    let top_level = run_git(repo_dir, &["rev-parse", "--show-toplevel"])?;
+    let top_level = top_level.trim_end_matches('\n');
+
+    let changed = run_git(
+        repo_dir,
+        &[
+            "diff",
+            "--name-only",
+            "-z",
+            "--diff-filter=d",
+            revision,
+            "--",
+        ],
+    )?;
+
+    let untracked = run_git(
+        repo_dir,
+        &[
+            "ls-files",
+            "--others",
+            "--exclude-standard",
+            "-z",
+            "--full-name",
+        ],
+    )?;
+
+    let mut result = Vec::new();
+
+    for relative_path in changed.split('\0').chain(untracked.split('\0'))
+    {
+        if relative_path.is_empty()
+        {
+            continue;
+        }
+
+        match Path::new(top_level).join(relative_path).to_str()
+        {
+            Some(p) => result.push(p.to_string()),
+            None =>
+            {
+                return Err(format!(
+                    "Failed to convert path to string: {}",
+                    relative_path
+                ))
+            },
+        }
+    }
+
+    result.sort();
+    result.dedup();
+
+    Ok(result)
+}
+
+/// Determine whether a merge is in progress in a git working tree.
+///
+/// # Arguments
+///
+/// * `repo_dir` - A directory within the git working tree.
+pub fn merge_in_progress(repo_dir: &str) -> bool
+{
+    run_git(repo_dir, &["rev-parse", "-q", "--verify", "MERGE_HEAD"]).is_ok()
+}
+
+/// Check that a revision names a commit.
+///
+/// # Arguments
+///
+/// * `repo_dir` - A directory within the git working tree.
+/// * `revision` - The revision to check.
+///
+/// # Returns
+///
+/// Nothing, or an error message if the revision doesn't name a commit.
+pub fn verify_revision(repo_dir: &str, revision: &str) -> Result<(), String>
+{
+    run_git(
+        repo_dir,
+        &["rev-parse", "--verify", &format!("{}^{{commit}}", revision)],
+    )
+    .map(|_| ())
+}
+
+/// Read the contents of a file as it was at the given revision.
+///
+/// # Arguments
+///
+/// * `path` - The path to the file in the git working tree.
+/// * `revision` - The revision to read the file from.
+///
+/// # Returns
+///
+/// The contents of the file, `None` if the file didn't exist at the revision, or an error message
+/// on failure.
+pub fn file_at_revision(path: &str, revision: &str) -> Result<Option<String>, String>
+{
+    let file_path = Path::new(path);
+
+    let (dir, name) = match (file_path.parent(), file_path.file_name())
+    {
+        (Some(d), Some(n)) => (d.to_string_lossy(), n.to_string_lossy()),
+        _ => return Err(format!("Failed to determine directory of file {}", path)),
+    };
+
+    /*
+     * A "./" prefix makes the path relative to the directory git is run in, rather than the
+     * root of the working tree.
+     */
+    let object = format!("{}:./{}", revision, name);
+
+    if run_git(&dir, &["cat-file", "-e", &object]).is_err()
+    {
+        return Ok(None);
+    }
+
+    run_git(&dir, &["show", &object]).map(Some)
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::changed_files;
+    use super::file_at_revision;
+    use super::merge_in_progress;
+    use super::verify_revision;
+    use std::process::Command;
+    use tempdir::TempDir;
+
+    fn git(repo_dir: &str, args: &[&str])
+    {
+        let status = Command::new("git")
+            .arg("-C")
+            .arg(repo_dir)
+            .args(["-c", "user.name=Test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .output()
+            .unwrap()
+            .status;
+
+        assert!(status.success());
+    }
+
+    #[test]
+    fn test_changed_files()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let repo_dir = temp_dir.path().to_str().unwrap();
+
+        git(repo_dir, &["init", "-q"]);
+
+        std::fs::write(temp_dir.path().join("unchanged.rs"), "fn a() {}").unwrap();
+        std::fs::write(temp_dir.path().join("modified.rs"), "fn b() {}").unwrap();
+        std::fs::write(temp_dir.path().join("deleted.rs"), "fn c() {}").unwrap();
+        std::fs::write(temp_dir.path().join(".gitignore"), "ignored.rs\n").unwrap();
+
+        git(repo_dir, &["add", "-A"]);
+        git(repo_dir, &["commit", "-q", "-m", "Initial"]);
+
+        std::fs::write(temp_dir.path().join("modified.rs"), "fn b2() {}").unwrap();
+        std::fs::remove_file(temp_dir.path().join("deleted.rs")).unwrap();
+        std::fs::write(temp_dir.path().join("untracked.rs"), "fn d() {}").unwrap();
+        std::fs::write(temp_dir.path().join("ignored.rs"), "fn e() {}").unwrap();
+
+        let top_level = temp_dir.path().canonicalize().unwrap();
+
+        let subject = changed_files(repo_dir, "HEAD").unwrap();
+
+        assert_eq!(
+            subject,
+            vec![
+                top_level.join("modified.rs").to_str().unwrap().to_string(),
+                top_level.join("untracked.rs").to_str().unwrap().to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_changed_files_invalid_revision()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let repo_dir = temp_dir.path().to_str().unwrap();
+
+        git(repo_dir, &["init", "-q"]);
+
+        assert!(changed_files(repo_dir, "does-not-exist").is_err());
+    }
+
+    #[test]
+    fn test_changed_files_not_a_repo()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+
+        assert!(changed_files(temp_dir.path().to_str().unwrap(), "HEAD").is_err());
+    }
+
+    #[test]
+    fn test_file_at_revision()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let repo_dir = temp_dir.path().to_str().unwrap();
+        let sub_dir = temp_dir.path().join("sub");
+        let file_path = sub_dir.join("file.rs");
+
+        git(repo_dir, &["init", "-q"]);
+
+        std::fs::create_dir(&sub_dir).unwrap();
+        std::fs::write(&file_path, "fn a() {}").unwrap();
+
+        git(repo_dir, &["add", "-A"]);
+        git(repo_dir, &["commit", "-q", "-m", "Initial"]);
+
+        std::fs::write(&file_path, "fn b() {}").unwrap();
+
+        assert!(verify_revision(repo_dir, "HEAD").is_ok());
+        assert!(verify_revision(repo_dir, "does-not-exist").is_err());
+
+        assert_eq!(
+            file_at_revision(file_path.to_str().unwrap(), "HEAD"),
+            Ok(Some("fn a() {}".to_string()))
+        );
+        assert_eq!(
+            file_at_revision(sub_dir.join("new.rs").to_str().unwrap(), "HEAD"),
+            Ok(None)
+        );
+    }
+
+    #[test]
+    fn test_merge_in_progress()
+    {
+        let temp_dir = TempDir::new("breadlog_test").unwrap();
+        let repo_dir = temp_dir.path().to_str().unwrap();
+        let file_path = temp_dir.path().join("file.rs");
+
+        git(repo_dir, &["init", "-q", "-b", "main"]);
+        std::fs::write(&file_path, "1\n").unwrap();
+        git(repo_dir, &["add", "-A"]);
+        git(repo_dir, &["commit", "-q", "-m", "Initial"]);
+
+        git(repo_dir, &["checkout", "-q", "-b", "feature"]);
+        std::fs::write(temp_dir.path().join("other.rs"), "2\n").unwrap();
+        git(repo_dir, &["add", "-A"]);
+        git(repo_dir, &["commit", "-q", "-m", "Feature"]);
+        git(repo_dir, &["checkout", "-q", "main"]);
+
+        assert!(!merge_in_progress(repo_dir));
+
+        git(
+            repo_dir,
+            &["merge", "-q", "--no-ff", "--no-commit", "feature"],
+        );
+
+        assert!(merge_in_progress(repo_dir));
+    }
+}
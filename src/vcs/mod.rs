@@ -0,0 +1,5 @@
+pub use git::changed_files;
+pub use git::file_at_revision;
+pub use git::merge_in_progress;
+pub use git::verify_revision;
+pub mod git;
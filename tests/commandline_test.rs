@@ -241,7 +241,14 @@ fn check_ids_contiguous_and_no_duplicates(all_ids: &Vec<usize>)
 #[test]
 fn test_no_args()
 {
+    /*
+     * Without --config, a configuration is searched for from the working directory, so run
+     * somewhere without one.
+     */
+    let temp_dir = TempDir::new("breadlog_test").unwrap();
+
     let output = test_bin::get_test_bin("breadlog")
+        .current_dir(temp_dir.path())
         .status()
         .unwrap()
         .success();
@@ -360,6 +367,447 @@ fn test_check()
     }
 }
 
+#[test]
+fn test_check_selected_files()
+{
+    let temp_dir = TempDir::new("breadlog_test").unwrap();
+
+    copy_dir::copy_dir(
+        Path::new("tests/rust_data"),
+        Path::new(temp_dir.path()).join("rust_data"),
+    )
+    .unwrap();
+
+    let config_filename = temp_dir
+        .path()
+        .join("rust_data/rocket/breadlog.yaml")
+        .to_str()
+        .unwrap()
+        .to_string();
+
+    let selected_filename = temp_dir
+        .path()
+        .join("rust_data/rocket/core/http/src/listener.rs")
+        .to_str()
+        .unwrap()
+        .to_string();
+
+    let output = test_bin::get_test_bin("breadlog")
+        .args(["--config", &config_filename, "--check", &selected_filename])
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+
+    let command_stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert!(command_stdout.contains("Found 1 file(s)"));
+    assert!(command_stdout.contains("Total missing references (all files): 3"));
+}
+
+#[test]
+fn test_check_report_formats()
+{
+    let temp_dir = TempDir::new("breadlog_test").unwrap();
+    let config_filename = temp_dir.path().join("Breadlog.yaml");
+    let source_dir = temp_dir.path().join("src");
+
+    std::fs::create_dir(&source_dir).unwrap();
+    std::fs::write(
+        source_dir.join("main.rs"),
+        "fn main() {\n    info!(\"[ref: 1] Started\");\n    info!(\"Stopped\");\n}\n",
+    )
+    .unwrap();
+    std::fs::write(
+        &config_filename,
+        "source_dir: ./src\nrust:\n  log_macros:\n    - module: log\n      name: info\n",
+    )
+    .unwrap();
+
+    let config_filename = config_filename.to_str().unwrap();
+
+    let output = test_bin::get_test_bin("breadlog")
+        .args(["-c", config_filename, "--check", "--format", "json"])
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+
+    /*
+     * Nothing else is written to stdout alongside the report.
+     */
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["findings"][0]["kind"], "missing");
+    assert_eq!(report["findings"][0]["line"], 3);
+    assert!(report["findings"][0]["file"]
+        .as_str()
+        .unwrap()
+        .ends_with("main.rs"));
+
+    let report_filename = temp_dir.path().join("report.txt");
+
+    let output = test_bin::get_test_bin("breadlog")
+        .args([
+            "-c",
+            config_filename,
+            "--check",
+            "--format",
+            "github",
+            "--output",
+            report_filename.to_str().unwrap(),
+        ])
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stdout)
+        .unwrap()
+        .contains("Failed: One or more missing references were found"));
+
+    let report = std::fs::read_to_string(&report_filename).unwrap();
+    assert!(report.starts_with("::error file="));
+    assert!(report
+        .ends_with("main.rs,line=3,col=12,title=Breadlog missing-reference::Missing reference\n"));
+}
+
+#[test]
+fn test_cargo_metadata_config()
+{
+    let temp_dir = TempDir::new("breadlog_test").unwrap();
+    let crate_dir = temp_dir.path().join("crates/net");
+
+    std::fs::create_dir_all(crate_dir.join("src")).unwrap();
+    std::fs::write(
+        temp_dir.path().join("Cargo.toml"),
+        "[workspace]\nmembers = [\"crates/*\"]\n\n[[workspace.metadata.breadlog.rust.log_macros]]\nmodule = \"log\"\nname = \"info\"\n",
+    )
+    .unwrap();
+    std::fs::write(crate_dir.join("Cargo.toml"), "[package]\nname = \"net\"\n").unwrap();
+    std::fs::write(
+        crate_dir.join("src/lib.rs"),
+        "fn connect() {\n    info!(\"Connected\");\n}\n",
+    )
+    .unwrap();
+
+    /*
+     * Without --config, the workspace's manifest is found from a crate's directory.
+     */
+    let output = test_bin::get_test_bin("breadlog")
+        .arg("--check")
+        .current_dir(crate_dir.join("src"))
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+
+    let command_stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert!(command_stdout.contains("Crate net: 1 error(s), 0 warning(s)"));
+
+    let output = test_bin::get_test_bin("breadlog")
+        .args(["-c", temp_dir.path().join("Cargo.toml").to_str().unwrap()])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        std::fs::read_to_string(crate_dir.join("src/lib.rs")).unwrap(),
+        "fn connect() {\n    info!(\"[ref: 1] Connected\");\n}\n"
+    );
+}
+
+#[test]
+fn test_config_discovery()
+{
+    let temp_dir = TempDir::new("breadlog_test").unwrap();
+    let source_dir = temp_dir.path().join("src/net");
+
+    std::fs::create_dir_all(&source_dir).unwrap();
+    std::fs::write(
+        temp_dir.path().join(".breadlog.yaml"),
+        "source_dir: ./src\nrust:\n  log_macros:\n    - module: log\n      name: info\n",
+    )
+    .unwrap();
+    std::fs::write(
+        source_dir.join("client.rs"),
+        "fn connect() {\n    info!(\"Connected\");\n}\n",
+    )
+    .unwrap();
+
+    /*
+     * The configuration is found from a subdirectory of the code base.
+     */
+    let output = test_bin::get_test_bin("breadlog")
+        .arg("--check")
+        .current_dir(&source_dir)
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stdout)
+        .unwrap()
+        .contains("Total missing references (all files): 1"));
+
+    /*
+     * Given a file, as an editor would, the configuration is found from the file's directory
+     * rather than the working directory.
+     */
+    let other_dir = TempDir::new("breadlog_test").unwrap();
+    let client_filename = source_dir.join("client.rs");
+
+    let output = test_bin::get_test_bin("breadlog")
+        .arg(client_filename.to_str().unwrap())
+        .current_dir(other_dir.path())
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        std::fs::read_to_string(&client_filename).unwrap(),
+        "fn connect() {\n    info!(\"[ref: 1] Connected\");\n}\n"
+    );
+}
+
+#[test]
+fn test_merge_lock()
+{
+    let temp_dir = TempDir::new("breadlog_test").unwrap();
+    let base_filename = temp_dir.path().join("base");
+    let ours_filename = temp_dir.path().join("ours");
+    let theirs_filename = temp_dir.path().join("theirs");
+
+    std::fs::write(&base_filename, "next_reference_id: 10\n").unwrap();
+    std::fs::write(&ours_filename, "next_reference_id: 15\n").unwrap();
+    std::fs::write(&theirs_filename, "next_reference_id: 12\n").unwrap();
+
+    let output = test_bin::get_test_bin("breadlog")
+        .args([
+            "merge-lock",
+            base_filename.to_str().unwrap(),
+            ours_filename.to_str().unwrap(),
+            theirs_filename.to_str().unwrap(),
+        ])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert!(std::fs::read_to_string(&ours_filename)
+        .unwrap()
+        .ends_with("next_reference_id: 15\n"));
+}
+
+#[test]
+fn test_reservations()
+{
+    let temp_dir = TempDir::new("breadlog_test").unwrap();
+    let config_filename = temp_dir.path().join("Breadlog.yaml");
+    let source_dir = temp_dir.path().join("src");
+
+    std::fs::create_dir(&source_dir).unwrap();
+    std::fs::write(
+        source_dir.join("main.rs"),
+        "fn main() {\n    info!(\"[ref: 4] Started\");\n    info!(\"Stopped\");\n}\n",
+    )
+    .unwrap();
+    std::fs::write(
+        &config_filename,
+        "source_dir: ./src\nrust:\n  log_macros:\n    - module: log\n      name: info\n",
+    )
+    .unwrap();
+
+    let config_filename = config_filename.to_str().unwrap();
+
+    let output = test_bin::get_test_bin("breadlog")
+        .args([
+            "-c",
+            config_filename,
+            "reserve",
+            "--owner",
+            "alice",
+            "--size",
+            "10",
+        ])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stdout)
+        .unwrap()
+        .contains("Reserved reference IDs 5 to 14 for alice"));
+
+    let output = test_bin::get_test_bin("breadlog")
+        .args(["-c", config_filename])
+        .env("BREADLOG_OWNER", "alice")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert!(std::fs::read_to_string(source_dir.join("main.rs"))
+        .unwrap()
+        .contains("[ref: 5] Stopped"));
+
+    let output = test_bin::get_test_bin("breadlog")
+        .args(["-c", config_filename, "reservations"])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stdout)
+        .unwrap()
+        .contains("alice: 5 to 14 (9 unused)"));
+
+    let output = test_bin::get_test_bin("breadlog")
+        .args(["-c", config_filename, "release", "--owner", "alice"])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+
+    let lock_contents = std::fs::read_to_string(temp_dir.path().join("Breadlog.lock")).unwrap();
+    assert!(lock_contents.contains("next_reference_id: 15"));
+    assert!(!lock_contents.contains("alice"));
+}
+
+#[test]
+fn test_subcommand_conflicts()
+{
+    let temp_dir = TempDir::new("breadlog_test").unwrap();
+
+    std::fs::create_dir(temp_dir.path().join("src")).unwrap();
+    std::fs::write(
+        temp_dir.path().join("src").join("main.rs"),
+        "fn main() {\n    info!(\"Started\");\n}\n",
+    )
+    .unwrap();
+    std::fs::write(
+        temp_dir.path().join("Breadlog.yaml"),
+        "source_dir: ./src\nrust:\n  log_macros:\n    - module: log\n      name: info\n",
+    )
+    .unwrap();
+
+    for args in [
+        vec!["--check", "reserve", "--owner", "alice"],
+        vec!["--format", "json", "reservations"],
+        vec!["src/main.rs", "reserve", "--owner", "alice"],
+        vec!["--check", "src/main.rs", "release", "--owner", "alice"],
+    ]
+    {
+        let output = test_bin::get_test_bin("breadlog")
+            .args(&args)
+            .current_dir(temp_dir.path())
+            .output()
+            .unwrap();
+
+        assert!(!output.status.success(), "{:?}", args);
+        assert!(
+            String::from_utf8(output.stderr)
+                .unwrap()
+                .contains("can't be used with files"),
+            "{:?}",
+            args
+        );
+    }
+
+    /*
+     * Nothing was reserved or modified.
+     */
+    assert!(!temp_dir.path().join("Breadlog.lock").exists());
+    assert!(
+        !std::fs::read_to_string(temp_dir.path().join("src").join("main.rs"))
+            .unwrap()
+            .contains("[ref:")
+    );
+
+    /*
+     * Options that apply to subcommands can still be given before them.
+     */
+    let output = test_bin::get_test_bin("breadlog")
+        .args(["--owner", "alice", "reservations"])
+        .current_dir(temp_dir.path())
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+}
+
+#[test]
+fn test_diff()
+{
+    let temp_dir = TempDir::new("breadlog_test").unwrap();
+    let config_filename = temp_dir.path().join("Breadlog.yaml");
+    let old_dir = temp_dir.path().join("old");
+    let new_dir = temp_dir.path().join("new");
+
+    std::fs::create_dir(&old_dir).unwrap();
+    std::fs::create_dir(&new_dir).unwrap();
+    std::fs::write(
+        old_dir.join("main.rs"),
+        "fn main() {\n    info!(\"[ref: 1] Started\");\n    info!(\"[ref: 2] Stopped\");\n}\n",
+    )
+    .unwrap();
+    std::fs::write(
+        new_dir.join("main.rs"),
+        "fn main() {\n    warn!(\"[ref: 1] Started\");\n    info!(\"[ref: 3] Finished\");\n}\n",
+    )
+    .unwrap();
+    std::fs::write(
+        &config_filename,
+        "source_dir: ./old\nrust:\n  log_macros:\n    - module: log\n      name: info\n    - module: log\n      name: warn\n",
+    )
+    .unwrap();
+
+    let output = test_bin::get_test_bin("breadlog")
+        .args([
+            "-c",
+            config_filename.to_str().unwrap(),
+            "diff",
+            old_dir.to_str().unwrap(),
+            new_dir.to_str().unwrap(),
+        ])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "Added (1):\n  3: info in main.rs: \"Finished\"\nRemoved (1):\n  2: info in main.rs: \"Stopped\"\nLevel changed (1):\n  1: \"info\" -> \"warn\"\n"
+    );
+
+    let output = test_bin::get_test_bin("breadlog")
+        .args([
+            "-c",
+            config_filename.to_str().unwrap(),
+            "diff",
+            "--format",
+            "json",
+            old_dir.to_str().unwrap(),
+            new_dir.to_str().unwrap(),
+        ])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+
+    let diff: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(diff["added"][0]["id"], 3);
+    assert_eq!(diff["removed"][0]["message"], "Stopped");
+    assert_eq!(diff["level_changed"][0]["new"], "warn");
+
+    /*
+     * Source directories can't be scanned without a configuration, and none is found from a
+     * working directory outside the code base.
+     */
+    let other_dir = TempDir::new("breadlog_test").unwrap();
+
+    let output = test_bin::get_test_bin("breadlog")
+        .args(["diff", old_dir.to_str().unwrap(), new_dir.to_str().unwrap()])
+        .current_dir(other_dir.path())
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+}
+
 #[test]
 fn test_integration_rust_non_structured_ref_ids()
 {
@@ -371,3 +819,258 @@ fn test_integration_rust_structured_ref_ids()
 {
     run_integration_test_case("fib-rs", 5, true);
 }
+
+#[test]
+fn test_init()
+{
+    let temp_dir = TempDir::new("breadlog_test").unwrap();
+    let source_dir = temp_dir.path().join("src");
+
+    std::fs::create_dir_all(&source_dir).unwrap();
+    std::fs::write(
+        temp_dir.path().join("Cargo.toml"),
+        "[package]\nname = \"app\"\n\n[dependencies]\nlog = \"0.4\"\n",
+    )
+    .unwrap();
+    std::fs::write(
+        source_dir.join("main.rs"),
+        "fn main() {\n    log::info!(\"[ref: 7] Started\");\n    log::error!(\"Failed\");\n}\n",
+    )
+    .unwrap();
+
+    let output = test_bin::get_test_bin("breadlog")
+        .arg("init")
+        .current_dir(temp_dir.path())
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert!(temp_dir.path().join("Breadlog.lock").is_file());
+
+    /*
+     * The generated configuration finds the log statement without a reference.
+     */
+    let output = test_bin::get_test_bin("breadlog")
+        .arg("--check")
+        .current_dir(temp_dir.path())
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stdout)
+        .unwrap()
+        .contains("Total missing references (all files): 1"));
+
+    /*
+     * An existing configuration isn't overwritten without --force.
+     */
+    let output = test_bin::get_test_bin("breadlog")
+        .arg("init")
+        .current_dir(temp_dir.path())
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+
+    let output = test_bin::get_test_bin("breadlog")
+        .args(["init", "--force"])
+        .current_dir(temp_dir.path())
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+
+    let output = test_bin::get_test_bin("breadlog")
+        .current_dir(temp_dir.path())
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert!(std::fs::read_to_string(source_dir.join("main.rs"))
+        .unwrap()
+        .contains("log::error!(\"[ref: 8] Failed\")"));
+}
+
+#[test]
+fn test_validate_config()
+{
+    let temp_dir = TempDir::new("breadlog_test").unwrap();
+    let config_path = temp_dir.path().join("Breadlog.yaml");
+
+    std::fs::create_dir(temp_dir.path().join("src")).unwrap();
+    std::fs::write(
+        &config_path,
+        "source_dir: ./src\nrust:\n  log_macros:\n    - module: log\n      name: info\n    - module: log\n      name: info\n",
+    )
+    .unwrap();
+
+    /*
+     * Duplicate macros are only warned about.
+     */
+    let output = test_bin::get_test_bin("breadlog")
+        .arg("validate-config")
+        .current_dir(temp_dir.path())
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stdout)
+        .unwrap()
+        .contains("rust.log_macros[1]: duplicate macro log::info"));
+
+    std::fs::write(
+        &config_path,
+        "source_dir: ./src\nrust:\n  log_macros:\n    - module: log\n      name: info\n  structure: true\n",
+    )
+    .unwrap();
+
+    let output = test_bin::get_test_bin("breadlog")
+        .arg("validate-config")
+        .current_dir(temp_dir.path())
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stdout)
+        .unwrap()
+        .contains("line 6, column 3: rust: unknown field `structure`"));
+
+    /*
+     * Other commands load the configuration just as strictly.
+     */
+    std::fs::write(
+        &config_path,
+        "source_dir: ./missing\nrust:\n  log_macros:\n    - module: log\n      name: info\n",
+    )
+    .unwrap();
+
+    let output = test_bin::get_test_bin("breadlog")
+        .arg("--check")
+        .current_dir(temp_dir.path())
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stdout)
+        .unwrap()
+        .contains("line 1, column 13: source_dir: directory ./missing doesn't exist"));
+}
+
+#[test]
+fn test_migrate_config()
+{
+    let temp_dir = TempDir::new("breadlog_test").unwrap();
+    let config_path = temp_dir.path().join("Breadlog.yaml");
+
+    std::fs::create_dir(temp_dir.path().join("src")).unwrap();
+    std::fs::write(
+        &config_path,
+        "---\n# The code to process.\nsource_dir: ./src\nrust:\n  log_macros:\n    - module: log\n      name: info # Only info for now.\n",
+    )
+    .unwrap();
+
+    let output = test_bin::get_test_bin("breadlog")
+        .arg("migrate-config")
+        .current_dir(temp_dir.path())
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        std::fs::read_to_string(&config_path).unwrap(),
+        "---\nversion: 1\n\n# The code to process.\nsource_dir: ./src\nrust:\n  log_macros:\n    - module: log\n      name: info # Only info for now.\n"
+    );
+
+    let output = test_bin::get_test_bin("breadlog")
+        .arg("migrate-config")
+        .current_dir(temp_dir.path())
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stdout)
+        .unwrap()
+        .contains("is already at configuration version 1"));
+
+    /*
+     * Configuration from a newer version of Breadlog isn't loaded.
+     */
+    let migrated = std::fs::read_to_string(&config_path).unwrap();
+    std::fs::write(&config_path, migrated.replace("version: 1", "version: 2")).unwrap();
+
+    for command in ["migrate-config", "validate-config"]
+    {
+        let output = test_bin::get_test_bin("breadlog")
+            .arg(command)
+            .current_dir(temp_dir.path())
+            .output()
+            .unwrap();
+
+        assert!(!output.status.success());
+        assert!(String::from_utf8(output.stdout)
+            .unwrap()
+            .contains("nsupported configuration version 2"));
+    }
+}
+
+#[test]
+fn test_config_schema()
+{
+    let temp_dir = TempDir::new("breadlog_test").unwrap();
+
+    let output = test_bin::get_test_bin("breadlog")
+        .arg("config-schema")
+        .current_dir(temp_dir.path())
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+
+    let schema: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+
+    assert_eq!(schema["title"], "Breadlog configuration");
+    assert!(schema["properties"]["source_dir"].is_object());
+}
+
+#[test]
+fn test_exit_codes()
+{
+    let temp_dir = TempDir::new("breadlog_test").unwrap();
+    let source_dir = temp_dir.path().join("src");
+
+    std::fs::create_dir(&source_dir).unwrap();
+    std::fs::write(
+        source_dir.join("main.rs"),
+        "fn main() {\n    info!(\"Started\");\n}\n",
+    )
+    .unwrap();
+
+    let exit_code = |args: &[&str]| {
+        test_bin::get_test_bin("breadlog")
+            .args(args)
+            .current_dir(temp_dir.path())
+            .output()
+            .unwrap()
+            .status
+            .code()
+    };
+
+    assert_eq!(exit_code(&["--check"]), Some(3));
+    assert_eq!(exit_code(&["--config", "Missing.yaml", "--check"]), Some(4));
+    assert_eq!(exit_code(&["--check", "--format", "xml"]), Some(2));
+
+    std::fs::write(temp_dir.path().join("Breadlog.yaml"), "source_dir: [").unwrap();
+
+    assert_eq!(exit_code(&["--check"]), Some(5));
+
+    std::fs::write(
+        temp_dir.path().join("Breadlog.yaml"),
+        "source_dir: ./src\nrust:\n  log_macros:\n    - module: log\n      name: info\n",
+    )
+    .unwrap();
+
+    assert_eq!(exit_code(&["--check"]), Some(1));
+    assert_eq!(exit_code(&["--check", "--changed-since", "HEAD"]), Some(6));
+    assert_eq!(exit_code(&[]), Some(0));
+    assert_eq!(exit_code(&["--check"]), Some(0));
+}